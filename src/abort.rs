@@ -0,0 +1,105 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    OnceLock,
+};
+
+/// Requests made before the error rate may abort a crawl, so that a few early failures don't
+/// abort it before the error rate means anything
+pub const MIN_REQUESTS: usize = 20;
+
+/// Stops a crawl once too many of its requests fail - connection errors, 5xx and 429 responses -
+/// i.e. when the site went down or started rejecting the crawler, rather than crawling on
+#[derive(Debug)]
+pub struct AbortThreshold {
+    max_error_rate: f64,
+    requests: AtomicUsize,
+    errors: AtomicUsize,
+    reason: OnceLock<String>,
+}
+
+impl AbortThreshold {
+    pub fn new(max_error_rate: f64) -> Self {
+        AbortThreshold {
+            max_error_rate,
+            requests: AtomicUsize::new(0),
+            errors: AtomicUsize::new(0),
+            reason: OnceLock::new(),
+        }
+    }
+
+    /// Records the outcome of a request, returning whether it aborted the crawl
+    pub fn record(&self, is_error: bool) -> bool {
+        let requests = self.requests.fetch_add(1, Ordering::Relaxed) + 1;
+        let errors =
+            self.errors.fetch_add(is_error as usize, Ordering::Relaxed) + is_error as usize;
+        let error_rate = errors as f64 / requests as f64;
+        if requests < MIN_REQUESTS || error_rate <= self.max_error_rate {
+            return false;
+        }
+
+        self.reason
+            .set(format!(
+                "{} of {} requests failed, over the {:.0}% abort threshold",
+                errors,
+                requests,
+                self.max_error_rate * 100.0
+            ))
+            .is_ok()
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.reason.get().is_some()
+    }
+
+    /// Why the crawl was aborted, if it was
+    pub fn reason(&self) -> Option<String> {
+        self.reason.get().cloned()
+    }
+}
+
+/// Parses an abort error rate, a fraction of the requests from 0 and below 1, which they could
+/// never be over
+pub fn error_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if (0.0..1.0).contains(&rate) => Ok(rate),
+        _ => Err(format!("`{}` is not a fraction from 0 and below 1", s)),
+    }
+}
+
+#[cfg(test)]
+mod abort_tests {
+    use super::{error_rate, AbortThreshold, MIN_REQUESTS};
+
+    #[test]
+    fn abort_threshold_aborts_once_over_the_error_rate() {
+        let threshold = AbortThreshold::new(0.5);
+
+        // every request failing, but too few of them to tell
+        for _ in 0..MIN_REQUESTS - 1 {
+            assert!(!threshold.record(true));
+        }
+        assert!(threshold.record(true));
+        assert!(threshold.is_aborted());
+        // only the request that crossed the threshold aborts the crawl
+        assert!(!threshold.record(true));
+        assert_eq!(
+            threshold.reason().as_deref(),
+            Some("20 of 20 requests failed, over the 50% abort threshold")
+        );
+
+        let threshold = AbortThreshold::new(0.5);
+        for i in 0..100 {
+            assert!(!threshold.record(i % 2 == 1));
+        }
+        assert!(threshold.reason().is_none());
+    }
+
+    #[test]
+    fn error_rate_accepts_fractions_below_1() {
+        assert_eq!(error_rate("0"), Ok(0.0));
+        assert_eq!(error_rate("0.5"), Ok(0.5));
+        assert!(error_rate("1").is_err());
+        assert!(error_rate("-0.1").is_err());
+        assert!(error_rate("half").is_err());
+    }
+}
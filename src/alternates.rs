@@ -0,0 +1,329 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+use url::Url;
+
+use crate::{data_store::DataStore, link_header::HeaderLink};
+
+/// Where a page declares its alternates
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AlternateSource {
+    /// `<link rel="alternate" hreflang>` in the page
+    Html,
+    /// `Link: <...>; rel="alternate"; hreflang` response header
+    LinkHeader,
+    /// `<xhtml:link rel="alternate" hreflang>` in the page's sitemap entry
+    Sitemap,
+}
+
+impl fmt::Display for AlternateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AlternateSource::Html => "HTML",
+            AlternateSource::LinkHeader => "Link header",
+            AlternateSource::Sitemap => "sitemap",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// A language or region version of a page, i.e. `hreflang="fr-CA"`
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Alternate {
+    /// Lowercase language code, or `x-default`
+    pub hreflang: String,
+    pub url: String,
+    pub source: AlternateSource,
+}
+
+impl Alternate {
+    /// An alternate of `page_url`, resolving a relative `href` against it
+    pub fn new(
+        page_url: &str,
+        hreflang: &str,
+        href: &str,
+        source: AlternateSource,
+    ) -> Option<Self> {
+        let url = Url::parse(page_url).ok()?.join(href.trim()).ok()?;
+
+        Some(Alternate {
+            hreflang: hreflang.trim().to_lowercase(),
+            url: url.to_string(),
+            source,
+        })
+    }
+}
+
+/// The alternates declared by the `Link` headers of a response
+pub fn from_header_links(page_url: &str, links: &[HeaderLink]) -> Vec<Alternate> {
+    links
+        .iter()
+        .filter(|link| link.has_rel("alternate"))
+        .filter_map(|link| {
+            Alternate::new(
+                page_url,
+                link.param("hreflang")?,
+                &link.target,
+                AlternateSource::LinkHeader,
+            )
+        })
+        .collect()
+}
+
+/// Something wrong with the alternates of a page
+#[derive(Debug, PartialEq, Clone)]
+pub enum AlternateIssue {
+    /// The sources declaring alternates disagree on the URL of a language, none when a source
+    /// leaves it out
+    Inconsistent {
+        hreflang: String,
+        urls: BTreeMap<AlternateSource, Option<String>>,
+    },
+    /// The alternate was crawled but doesn't declare the page as one of its own alternates
+    NoReturnLink { hreflang: String, url: String },
+    /// The alternate was crawled and responded with an error, or not at all
+    Broken {
+        hreflang: String,
+        url: String,
+        status: Option<u16>,
+    },
+}
+
+impl fmt::Display for AlternateIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlternateIssue::Inconsistent { hreflang, urls } => {
+                let urls = urls
+                    .iter()
+                    .map(|(source, url)| match url {
+                        Some(url) => format!("{} {}", source, url),
+                        None => format!("{} missing", source),
+                    })
+                    .collect::<Vec<_>>();
+                write!(f, "{}: sources disagree, {}", hreflang, urls.join(", "))
+            }
+            AlternateIssue::NoReturnLink { hreflang, url } => {
+                write!(f, "{}: {} doesn't link back", hreflang, url)
+            }
+            AlternateIssue::Broken {
+                hreflang,
+                url,
+                status: Some(status),
+            } => write!(f, "{}: {} responded with {}", hreflang, url, status),
+            AlternateIssue::Broken { hreflang, url, .. } => {
+                write!(f, "{}: {} could not be fetched", hreflang, url)
+            }
+        }
+    }
+}
+
+/// Cross-checks the alternates each crawled page declares in its HTML, its `Link` headers and the
+/// sitemaps: the sources must agree, and every alternate must be reachable and declare the page back
+#[derive(Debug, PartialEq, Default)]
+pub struct AlternatesAudit {
+    /// Issues by page, of the pages declaring alternates
+    pub issues: BTreeMap<String, Vec<AlternateIssue>>,
+    /// Pages declaring alternates
+    pub pages: usize,
+}
+
+impl AlternatesAudit {
+    pub fn from_store<T: DataStore>(store: &T) -> Self {
+        let mut audit = AlternatesAudit::default();
+
+        for (page, entry) in store.entries() {
+            if entry.alternates.is_empty() {
+                continue;
+            }
+            audit.pages += 1;
+            let mut issues = vec![];
+
+            let mut by_source = BTreeMap::<AlternateSource, BTreeMap<&str, &str>>::new();
+            for alternate in &entry.alternates {
+                by_source
+                    .entry(alternate.source)
+                    .or_default()
+                    .entry(&alternate.hreflang)
+                    .or_insert(&alternate.url);
+            }
+            let hreflangs = by_source
+                .values()
+                .flat_map(|alternates| alternates.keys().copied())
+                .collect::<BTreeSet<_>>();
+            for hreflang in &hreflangs {
+                let urls = by_source
+                    .iter()
+                    .map(|(source, alternates)| {
+                        (*source, alternates.get(hreflang).map(|url| url.to_string()))
+                    })
+                    .collect::<BTreeMap<_, _>>();
+                if urls.values().collect::<BTreeSet<_>>().len() > 1 {
+                    issues.push(AlternateIssue::Inconsistent {
+                        hreflang: hreflang.to_string(),
+                        urls,
+                    });
+                }
+            }
+
+            let alternates = by_source
+                .values()
+                .flat_map(|alternates| alternates.iter().map(|(hreflang, url)| (*hreflang, *url)))
+                .filter(|(_, url)| *url != page.as_str())
+                .collect::<BTreeSet<_>>();
+            for (hreflang, url) in alternates {
+                let Some(alternate) = store.get(url).filter(|alternate| alternate.visited) else {
+                    continue;
+                };
+                match alternate.status {
+                    Some(status) if status < 400 => {
+                        if !alternate.alternates.iter().any(|back| back.url == *page) {
+                            issues.push(AlternateIssue::NoReturnLink {
+                                hreflang: hreflang.to_string(),
+                                url: url.to_string(),
+                            });
+                        }
+                    }
+                    status => issues.push(AlternateIssue::Broken {
+                        hreflang: hreflang.to_string(),
+                        url: url.to_string(),
+                        status,
+                    }),
+                }
+            }
+
+            if !issues.is_empty() {
+                audit.issues.insert(page.clone(), issues);
+            }
+        }
+
+        audit
+    }
+}
+
+impl fmt::Display for AlternatesAudit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Alternates of {} pages checked, {} with issues:",
+            self.pages,
+            self.issues.len()
+        )?;
+        for (page, issues) in &self.issues {
+            writeln!(f, "  {}", page)?;
+            for issue in issues {
+                writeln!(f, "    {}", issue)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod alternates_tests {
+    use std::collections::BTreeMap;
+
+    use super::{Alternate, AlternateIssue, AlternateSource, AlternatesAudit};
+    use crate::data_store::{DataStore, Store};
+
+    fn alternates(page: &str, declared: &[(&str, &str, AlternateSource)]) -> Vec<Alternate> {
+        declared
+            .iter()
+            .map(|(hreflang, href, source)| Alternate::new(page, hreflang, href, *source).unwrap())
+            .collect()
+    }
+
+    fn page(s: &mut Store, url: &str, status: u16, declared: &[(&str, &str, AlternateSource)]) {
+        s.add(url.to_owned(), None);
+        s.visited(url);
+        s.set_status(url, status);
+        s.set_alternates(url, alternates(url, declared));
+    }
+
+    #[test]
+    fn alternate_resolves_relative_urls_and_lowercases_the_language() {
+        assert_eq!(
+            Alternate::new(
+                "https://a.com/en/",
+                "fr-CA",
+                "../fr/",
+                AlternateSource::Html
+            ),
+            Some(Alternate {
+                hreflang: "fr-ca".to_owned(),
+                url: "https://a.com/fr/".to_owned(),
+                source: AlternateSource::Html,
+            })
+        );
+    }
+
+    #[test]
+    fn alternates_audit_finds_disagreements_missing_return_links_and_broken_alternates() {
+        use AlternateSource::{Html, LinkHeader, Sitemap};
+        let mut s = Store::new();
+        page(
+            &mut s,
+            "https://a.com/en",
+            200,
+            &[
+                ("en", "/en", Html),
+                ("fr", "/fr", Html),
+                ("de", "/de", Html),
+                ("es", "/es", Html),
+                ("en", "/en", Sitemap),
+                ("fr", "/fr-fr", Sitemap),
+                ("de", "/de", Sitemap),
+            ],
+        );
+        page(
+            &mut s,
+            "https://a.com/fr",
+            200,
+            &[("en", "/en", LinkHeader), ("fr", "/fr", LinkHeader)],
+        );
+        page(&mut s, "https://a.com/de", 200, &[("de", "/de", Html)]);
+        page(&mut s, "https://a.com/es", 404, &[]);
+
+        let audit = AlternatesAudit::from_store(&s);
+
+        assert_eq!(audit.pages, 3);
+        assert_eq!(
+            audit.issues,
+            BTreeMap::from([(
+                "https://a.com/en".to_owned(),
+                vec![
+                    AlternateIssue::Inconsistent {
+                        hreflang: "es".to_owned(),
+                        urls: BTreeMap::from([
+                            (Html, Some("https://a.com/es".to_owned())),
+                            (Sitemap, None)
+                        ]),
+                    },
+                    AlternateIssue::Inconsistent {
+                        hreflang: "fr".to_owned(),
+                        urls: BTreeMap::from([
+                            (Html, Some("https://a.com/fr".to_owned())),
+                            (Sitemap, Some("https://a.com/fr-fr".to_owned()))
+                        ]),
+                    },
+                    AlternateIssue::NoReturnLink {
+                        hreflang: "de".to_owned(),
+                        url: "https://a.com/de".to_owned(),
+                    },
+                    AlternateIssue::Broken {
+                        hreflang: "es".to_owned(),
+                        url: "https://a.com/es".to_owned(),
+                        status: Some(404),
+                    },
+                ]
+            ),])
+        );
+        assert!(audit.to_string().contains(
+            "    fr: sources disagree, HTML https://a.com/fr, sitemap https://a.com/fr-fr\n"
+        ));
+    }
+}
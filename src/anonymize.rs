@@ -0,0 +1,224 @@
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{
+    alternates::Alternate,
+    data_store::{DataStore, DataStoreEntry, LocaleResult, Store},
+    fetch::Redirect,
+    parked::ParkedSign,
+    parser::Form,
+};
+
+const BLOCK_SIZE: usize = 64;
+/// Hex characters kept of each hash, enough to tell apart the URLs of any crawl
+const HASH_LEN: usize = 16;
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| block.iter().map(|b| b ^ byte).collect::<Vec<_>>();
+
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+/// Replaces URLs, and anything else that could reveal the crawled site, with keyed hashes, so that
+/// the structure and statistics of a crawl can be shared without its paths. Hosts and paths are
+/// hashed separately, so links between hosts are kept. Without the key, hashes can't be matched
+/// against guessed URLs.
+pub struct Anonymizer {
+    key: Vec<u8>,
+}
+
+impl Anonymizer {
+    pub fn new(key: &str) -> Self {
+        Anonymizer {
+            key: key.as_bytes().to_vec(),
+        }
+    }
+
+    fn hash(&self, value: &str) -> String {
+        let hash = hmac_sha256(&self.key, value.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+
+        hash[..HASH_LEN].to_owned()
+    }
+
+    /// The URL with its host and its path and query hashed, i.e. `https://<host hash>.invalid/<path hash>`
+    pub fn url(&self, url: &str) -> String {
+        let Ok(parsed) = Url::parse(url) else {
+            return self.hash(url);
+        };
+        let Some(host) = parsed.host_str() else {
+            return self.hash(url);
+        };
+        let host = match parsed.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_owned(),
+        };
+        let path = match parsed.query() {
+            Some(query) => format!("{}?{}", parsed.path(), query),
+            None => parsed.path().to_owned(),
+        };
+
+        format!(
+            "{}://{}.invalid/{}",
+            parsed.scheme(),
+            self.hash(&host),
+            self.hash(&path)
+        )
+    }
+
+    /// The page with its URLs hashed and its text (title, description and body sample) left out
+    pub fn entry(&self, entry: &DataStoreEntry) -> DataStoreEntry {
+        DataStoreEntry {
+            visited: entry.visited,
+            urls_found: entry.urls_found.iter().map(|url| self.url(url)).collect(),
+            link_counts: entry
+                .link_counts
+                .iter()
+                .map(|(url, count)| (self.url(url), *count))
+                .collect(),
+            status: entry.status,
+            canonical: entry.canonical.as_deref().map(|url| self.url(url)),
+            redirects: entry
+                .redirects
+                .iter()
+                .map(|redirect| Redirect {
+                    from: self.url(&redirect.from),
+                    to: self.url(&redirect.to),
+                    status: redirect.status,
+                })
+                .collect(),
+            // the hash of a public page's content would give the page away
+            content_hash: entry.content_hash.as_deref().map(|hash| self.hash(hash)),
+            type_mismatch: entry.type_mismatch.clone(),
+            title: None,
+            description: None,
+            body_sample: None,
+            forms: entry
+                .forms
+                .iter()
+                .map(|form| Form {
+                    action: self.url(&form.action),
+                    method: form.method.clone(),
+                    fields: form.fields.iter().map(|field| self.hash(field)).collect(),
+                })
+                .collect(),
+            link_decisions: entry
+                .link_decisions
+                .iter()
+                .map(|(url, decision)| (self.url(url), *decision))
+                .collect(),
+            search_page: entry.search_page,
+            locales: entry
+                .locales
+                .iter()
+                .map(|(locale, result)| {
+                    let result = LocaleResult {
+                        status: result.status,
+                        url: self.url(&result.url),
+                        content_hash: self.hash(&result.content_hash),
+                    };
+                    (locale.clone(), result)
+                })
+                .collect(),
+            // parking services are public, the domain that took over a page would give it away
+            parked: entry.parked.as_ref().map(|parked| match parked {
+                ParkedSign::HomepageRedirect(url) => ParkedSign::HomepageRedirect(self.url(url)),
+                parked => parked.clone(),
+            }),
+            alternates: entry
+                .alternates
+                .iter()
+                .map(|alternate| Alternate {
+                    url: self.url(&alternate.url),
+                    ..alternate.clone()
+                })
+                .collect(),
+            resources: entry.resources.iter().map(|url| self.url(url)).collect(),
+            streaming: entry.streaming,
+            partial_content: entry.partial_content.clone(),
+            mobile: entry.mobile.clone(),
+            link_sources: entry
+                .link_sources
+                .iter()
+                .map(|(url, source)| (self.url(url), source.clone()))
+                .collect(),
+        }
+    }
+
+    /// An anonymized copy of the store, to be exported
+    pub fn store<T: DataStore>(&self, store: &T) -> Store {
+        let mut anonymized = Store::new();
+        for (url, entry) in store.entries() {
+            anonymized.insert(self.url(url), self.entry(entry));
+        }
+
+        anonymized
+    }
+}
+
+#[cfg(test)]
+mod anonymize_tests {
+    use super::{hmac_sha256, Anonymizer};
+    use crate::data_store::{DataStore, Store};
+
+    #[test]
+    fn hmac_sha256_matches_rfc_4231() {
+        let hmac = hmac_sha256(b"Jefe", b"what do ya want for nothing?")
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+
+        assert_eq!(
+            hmac,
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn anonymizer_hashes_urls_with_the_key_keeping_hosts_apart() {
+        let anonymizer = Anonymizer::new("secret");
+        let page = anonymizer.url("https://a.com/private/page?id=1");
+        let other_page = anonymizer.url("https://a.com/other");
+        let host = |url: &str| url.split('/').nth(2).unwrap().to_owned();
+
+        assert!(page.starts_with("https://"));
+        assert_eq!(page.rsplit('/').next().unwrap().len(), 16);
+        assert!(!page.contains("private") && !page.contains("a.com"));
+        assert_eq!(host(&page), host(&other_page));
+        assert_ne!(page, other_page);
+        assert_ne!(
+            Anonymizer::new("other secret").url("https://a.com/other"),
+            other_page
+        );
+        assert_eq!(anonymizer.url("mailto:me@a.com").len(), 16);
+
+        let mut s = Store::new();
+        s.add(
+            "https://a.com/private/page?id=1".to_owned(),
+            Some("https://a.com/other".to_owned()),
+        );
+        s.set_title("https://a.com/private/page?id=1", "Private page".to_owned());
+        let anonymized = anonymizer.store(&s);
+        let entry = anonymized.get(&page).unwrap();
+
+        assert_eq!(entry.urls_found, vec![other_page]);
+        assert_eq!(entry.title, None);
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Global allocator wrapper that counts allocations, used to report allocation stats
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    // forwarded so that growing a `Vec` or `String` can still be done in place
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct AllocationStats {
+    pub allocations: usize,
+    pub allocated_bytes: usize,
+}
+
+impl AllocationStats {
+    pub fn now() -> Self {
+        AllocationStats {
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            allocated_bytes: ALLOCATED_BYTES.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn since(&self, earlier: &AllocationStats) -> Self {
+        AllocationStats {
+            allocations: self.allocations - earlier.allocations,
+            allocated_bytes: self.allocated_bytes - earlier.allocated_bytes,
+        }
+    }
+}
+
+/// A generated site where every page links to the next `links_per_page` pages
+#[derive(Debug, Clone, Copy)]
+pub struct BenchSite {
+    pub pages: usize,
+    pub page_size: usize,
+    pub links_per_page: usize,
+}
+
+impl BenchSite {
+    pub fn page(&self, n: usize) -> Option<String> {
+        if n >= self.pages {
+            return None;
+        }
+
+        let first_link = n * self.links_per_page + 1;
+        let mut body = (first_link..first_link + self.links_per_page)
+            .filter(|link| *link < self.pages)
+            .map(|link| format!("<a href=\"/page/{}\">Page {}</a>", link, link))
+            .collect::<String>();
+
+        let padding = self.page_size.saturating_sub(body.len() + 7);
+        body.push_str(&format!("<p>{}</p>", "x".repeat(padding)));
+
+        Some(body)
+    }
+
+    /// Starts serving the site on a random local port and returns its base URL
+    pub async fn serve(self) -> Result<String, std::io::Error> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let base_url = format!("http://{}", listener.local_addr()?);
+        let site = Arc::new(self);
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(respond(stream, site.clone()));
+            }
+        });
+
+        Ok(base_url)
+    }
+}
+
+async fn respond(mut stream: TcpStream, site: Arc<BenchSite>) {
+    let mut request = vec![];
+    let mut buf = [0; 1024];
+
+    while !request.ends_with(b"\r\n\r\n") {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let page = request
+        .split_whitespace()
+        .nth(1)
+        .and_then(|path| path.strip_prefix("/page/"))
+        .and_then(|n| n.parse::<usize>().ok())
+        .and_then(|n| site.page(n));
+
+    let (status, body) = match page {
+        Some(body) => ("200 OK", body),
+        None => ("404 Not Found", "".to_owned()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+
+    let _ = stream.write_all(response.as_bytes()).await;
+}
+
+pub struct BenchReport {
+    pub pages: usize,
+    pub elapsed: Duration,
+    pub allocations: AllocationStats,
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.elapsed.as_secs_f64();
+
+        writeln!(f, "Pages crawled: {}", self.pages)?;
+        writeln!(f, "Elapsed: {:.3}s", seconds)?;
+        writeln!(f, "Pages/sec: {:.1}", self.pages as f64 / seconds)?;
+        writeln!(f, "Allocations: {}", self.allocations.allocations)?;
+        writeln!(f, "Allocated bytes: {}", self.allocations.allocated_bytes)?;
+        if let Some(per_page) = self.allocations.allocated_bytes.checked_div(self.pages) {
+            writeln!(f, "Allocated bytes/page: {}", per_page)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod bench_tests {
+    use super::BenchSite;
+    use crate::{fetch::Fetch, fetch::HttpFetch, parser::Parser};
+
+    const SITE: BenchSite = BenchSite {
+        pages: 5,
+        page_size: 100,
+        links_per_page: 2,
+    };
+
+    #[test]
+    fn page_links_to_following_pages_within_site() {
+        let links = Parser::new(SITE.page(1).unwrap()).all_links();
+
+        assert_eq!(links, vec!["/page/3", "/page/4"]);
+        assert!(SITE.page(2).unwrap().len() >= SITE.page_size);
+        assert_eq!(SITE.page(5), None);
+    }
+
+    #[tokio::test]
+    async fn serve_responds_with_generated_pages() {
+        let base_url = SITE.serve().await.unwrap();
+        let f: HttpFetch = Fetch::new();
+
+        let page = f.fetch(&format!("{}/page/0", base_url)).await.unwrap();
+        let missing = f.fetch(&format!("{}/page/9", base_url)).await.unwrap();
+
+        assert_eq!(page.status, 200);
+        assert_eq!(page.body, SITE.page(0).unwrap());
+        assert_eq!(missing.status, 404);
+    }
+}
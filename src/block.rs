@@ -0,0 +1,264 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::Mutex,
+    time::Duration,
+};
+
+use crate::{link::host_with_port, scheduler::MAX_PAUSE};
+
+/// Consecutive `403`/`429` responses, from a host that served pages before, taken as a block
+const DENIED_THRESHOLD: usize = 5;
+/// Challenge or CAPTCHA pages in a row taken as a block, a single one may be a page like any other
+const CHALLENGED_THRESHOLD: usize = 3;
+/// Pauses of a host after which it is crawled regardless, recording its responses as errors
+const MAX_PAUSES: u32 = 3;
+/// Start of the body searched for challenge markers, they sit near the top of interstitials
+const MARKERS_SEARCH_BYTES: usize = 32 * 1024;
+/// Lowercase markers of bot challenge interstitials and CAPTCHA pages served instead of content
+const CHALLENGE_MARKERS: [&str; 6] = [
+    "<title>just a moment...</title>",
+    "cf-browser-verification",
+    "<title>attention required! | cloudflare</title>",
+    "captcha-delivery.com",
+    "px-captcha",
+    "<title>access denied</title>",
+];
+/// Lowercase markers of CAPTCHA widgets, only a block sign on error responses as forms use them too
+const CAPTCHA_MARKERS: [&str; 3] = ["g-recaptcha", "h-captcha", "captcha"];
+
+/// What gave away that a host is blocking the crawler
+#[derive(Debug, PartialEq, Clone)]
+pub enum BlockSign {
+    /// A challenge or CAPTCHA page, with the marker found in it
+    Challenge(&'static str),
+    /// Consecutive `403`/`429` responses from a host that served pages before
+    Denied(usize),
+}
+
+impl fmt::Display for BlockSign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockSign::Challenge(marker) => write!(f, "challenge page (`{}`)", marker),
+            BlockSign::Denied(count) => write!(f, "{} requests in a row denied", count),
+        }
+    }
+}
+
+/// A host that looks like it is blocking the crawler, and how long to pause requests to it
+#[derive(Debug, PartialEq, Clone)]
+pub struct Block {
+    pub host: String,
+    pub sign: BlockSign,
+    pub pause: Duration,
+}
+
+/// The challenge marker found in a response, if any
+pub fn challenge_marker(status: u16, body: &str) -> Option<&'static str> {
+    let mut end = body.len().min(MARKERS_SEARCH_BYTES);
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    let body = body[..end].to_lowercase();
+    let captcha_markers: &[&'static str] = match status {
+        400.. => &CAPTCHA_MARKERS,
+        _ => &[],
+    };
+
+    CHALLENGE_MARKERS
+        .iter()
+        .chain(captcha_markers)
+        .find(|marker| body.contains(*marker))
+        .copied()
+}
+
+#[derive(Debug, Default)]
+struct HostState {
+    served: bool,
+    denied: usize,
+    challenged: usize,
+    pauses: u32,
+    last_sign: Option<BlockSign>,
+}
+
+/// Tells when hosts start blocking the crawler - uniform `403`s, CAPTCHA pages or challenge
+/// interstitials - so that requests to them are paused rather than their pages recorded as errors
+pub struct BlockDetector {
+    pause: Duration,
+    hosts: Mutex<HashMap<String, HostState>>,
+}
+
+impl BlockDetector {
+    /// Pauses a blocking host for `pause`, doubled each time it blocks the crawler again
+    pub fn new(pause: Duration) -> Self {
+        BlockDetector {
+            pause,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Looks at a response, returning the block if its host looks like it is blocking the crawler
+    pub fn check(&self, url: &str, status: u16, body: &str) -> Option<Block> {
+        let host = host_with_port(url)?;
+        let mut hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+        let state = hosts.entry(host.clone()).or_default();
+
+        // once a host has been paused, the next sign that it still blocks the crawler is enough
+        let sign = match (challenge_marker(status, body), status) {
+            (Some(marker), _) => {
+                state.challenged += 1;
+                match state.pauses > 0 || state.challenged >= CHALLENGED_THRESHOLD {
+                    true => BlockSign::Challenge(marker),
+                    false => return None,
+                }
+            }
+            (None, 403 | 429) => {
+                state.denied += 1;
+                state.challenged = 0;
+                match state.served && (state.pauses > 0 || state.denied >= DENIED_THRESHOLD) {
+                    true => BlockSign::Denied(state.denied),
+                    false => return None,
+                }
+            }
+            (None, _) => {
+                state.served = true;
+                state.denied = 0;
+                state.challenged = 0;
+                return None;
+            }
+        };
+
+        state.denied = 0;
+        state.challenged = 0;
+        state.last_sign = Some(sign.clone());
+        if state.pauses >= MAX_PAUSES {
+            return None;
+        }
+        state.pauses += 1;
+
+        Some(Block {
+            host,
+            sign,
+            pause: self
+                .pause
+                .checked_mul(2u32.pow(state.pauses - 1))
+                .map_or(MAX_PAUSE, |pause| pause.min(MAX_PAUSE)),
+        })
+    }
+
+    /// Diagnostic of every host that blocked the crawler, by host
+    pub fn blocked_hosts(&self) -> BTreeMap<String, String> {
+        let hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+
+        hosts
+            .iter()
+            .filter_map(|(host, state)| {
+                let sign = state.last_sign.as_ref()?;
+                let diagnostic = match state.pauses >= MAX_PAUSES {
+                    true => format!("{}, still blocked after {} pauses", sign, state.pauses),
+                    false => format!("{}, paused {} times", sign, state.pauses),
+                };
+                Some((host.clone(), diagnostic))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod block_tests {
+    use std::time::Duration;
+
+    use super::{challenge_marker, Block, BlockDetector, BlockSign, MAX_PAUSES};
+    use crate::scheduler::MAX_PAUSE;
+
+    #[test]
+    fn challenge_marker_finds_interstitials_and_captchas_on_errors() {
+        assert_eq!(
+            challenge_marker(503, "<html><head><TITLE>Just a moment...</TITLE>"),
+            Some("<title>just a moment...</title>")
+        );
+        assert_eq!(
+            challenge_marker(403, "<div class=\"g-recaptcha\"></div>"),
+            Some("g-recaptcha")
+        );
+        // a contact form protected by a CAPTCHA is a page like any other
+        assert_eq!(
+            challenge_marker(200, "<div class=\"g-recaptcha\"></div>"),
+            None
+        );
+        assert_eq!(challenge_marker(403, "Forbidden"), None);
+    }
+
+    #[test]
+    fn block_detector_pauses_hosts_serving_challenge_pages_in_a_row() {
+        let detector = BlockDetector::new(Duration::from_secs(60));
+        let access_denied = "<title>Access Denied</title>";
+
+        assert_eq!(detector.check("https://a.com/1", 403, access_denied), None);
+        assert_eq!(detector.check("https://a.com/2", 200, "<p>Hi</p>"), None);
+        for page in 3..5 {
+            let url = format!("https://a.com/{}", page);
+            assert_eq!(detector.check(&url, 403, access_denied), None);
+        }
+        assert_eq!(
+            detector
+                .check("https://a.com/5", 403, access_denied)
+                .map(|block| block.sign),
+            Some(BlockSign::Challenge("<title>access denied</title>"))
+        );
+    }
+
+    #[test]
+    fn block_detector_pauses_hosts_denying_every_request() {
+        let detector = BlockDetector::new(Duration::from_secs(60));
+
+        // a host denying access from the start is not blocking the crawler, it is private
+        for _ in 0..10 {
+            assert_eq!(detector.check("https://private.com/", 403, ""), None);
+        }
+
+        assert_eq!(detector.check("https://a.com/", 200, "<p>Hi</p>"), None);
+        for _ in 1..5 {
+            assert_eq!(detector.check("https://a.com/page", 403, ""), None);
+        }
+        assert_eq!(
+            detector.check("https://a.com/page", 403, ""),
+            Some(Block {
+                host: "a.com".to_owned(),
+                sign: BlockSign::Denied(5),
+                pause: Duration::from_secs(60),
+            })
+        );
+
+        let paused_again =
+            detector.check("https://a.com/page", 503, "<title>Just a moment...</title>");
+        assert_eq!(paused_again.unwrap().pause, Duration::from_secs(120));
+        for _ in 2..MAX_PAUSES {
+            assert!(detector
+                .check("https://a.com/page", 429, "captcha")
+                .is_some());
+        }
+        // crawled regardless once it has been paused enough
+        assert_eq!(detector.check("https://a.com/page", 429, "captcha"), None);
+        assert_eq!(
+            detector.blocked_hosts()["a.com"],
+            "challenge page (`captcha`), still blocked after 3 pauses"
+        );
+    }
+
+    #[test]
+    fn block_detector_caps_long_pauses() {
+        let detector = BlockDetector::new(Duration::MAX);
+        assert_eq!(detector.check("https://a.com/", 200, "<p>Hi</p>"), None);
+        for _ in 1..5 {
+            assert_eq!(detector.check("https://a.com/page", 403, ""), None);
+        }
+
+        let block = detector.check("https://a.com/page", 403, "");
+        assert_eq!(block.unwrap().pause, MAX_PAUSE);
+        for _ in 1..MAX_PAUSES {
+            let block = detector.check("https://a.com/page", 429, "captcha");
+            assert_eq!(block.unwrap().pause, MAX_PAUSE);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+const UNITS: [(&str, u64); 9] = [
+    ("b", 1),
+    ("kb", 1_000),
+    ("mb", 1_000_000),
+    ("gb", 1_000_000_000),
+    ("tb", 1_000_000_000_000),
+    ("kib", 1 << 10),
+    ("mib", 1 << 20),
+    ("gib", 1 << 30),
+    ("tib", 1 << 40),
+];
+
+/// A number of bytes, parsed from i.e. `5GB` (decimal units) or `512MiB` (binary units)
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct ByteSize(pub u64);
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let invalid = || format!("`{}` is not a size, i.e. 500MB or 5GB", s);
+
+        let number = number.parse::<f64>().map_err(|_| invalid())?;
+        let unit = unit.trim().to_lowercase();
+        let multiplier = match unit.as_str() {
+            "" => 1,
+            unit => UNITS
+                .iter()
+                .find(|(name, _)| *name == unit)
+                .map(|(_, multiplier)| *multiplier)
+                .ok_or_else(invalid)?,
+        };
+
+        Ok(ByteSize((number * multiplier as f64) as u64))
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (unit, multiplier) = UNITS[..5]
+            .iter()
+            .rev()
+            .find(|(_, multiplier)| self.0 >= *multiplier)
+            .copied()
+            .unwrap_or(UNITS[0]);
+
+        match multiplier {
+            1 => write!(f, "{}B", self.0),
+            _ => write!(
+                f,
+                "{:.1}{}",
+                self.0 as f64 / multiplier as f64,
+                unit.to_uppercase()
+            ),
+        }
+    }
+}
+
+/// Total bytes downloaded by the crawl, against an optional budget
+#[derive(Debug, Default)]
+pub struct ByteBudget {
+    max: Option<u64>,
+    used: AtomicU64,
+}
+
+impl ByteBudget {
+    pub fn new(max: Option<ByteSize>) -> Self {
+        ByteBudget {
+            max: max.map(|max| max.0),
+            used: AtomicU64::new(0),
+        }
+    }
+
+    /// Records downloaded bytes, returning whether this addition exhausted the budget
+    pub fn add(&self, bytes: u64) -> bool {
+        let used = self.used.fetch_add(bytes, Ordering::Relaxed);
+
+        self.max
+            .is_some_and(|max| used <= max && used + bytes > max)
+    }
+
+    pub fn used(&self) -> ByteSize {
+        ByteSize(self.used.load(Ordering::Relaxed))
+    }
+
+    /// Whether more bytes have been downloaded than the budget allows
+    pub fn is_exhausted(&self) -> bool {
+        self.max.is_some_and(|max| self.used().0 > max)
+    }
+}
+
+#[cfg(test)]
+mod budget_tests {
+    use super::{ByteBudget, ByteSize};
+
+    #[test]
+    fn byte_size_parses_decimal_and_binary_units() {
+        assert_eq!("5GB".parse(), Ok(ByteSize(5_000_000_000)));
+        assert_eq!("1.5 kb".parse(), Ok(ByteSize(1_500)));
+        assert_eq!("512MiB".parse(), Ok(ByteSize(512 * 1024 * 1024)));
+        assert_eq!("1024".parse(), Ok(ByteSize(1024)));
+        assert!("5 parsecs".parse::<ByteSize>().is_err());
+        assert!("GB".parse::<ByteSize>().is_err());
+
+        assert_eq!(ByteSize(5_000_000_000).to_string(), "5.0GB");
+        assert_eq!(ByteSize(999).to_string(), "999B");
+    }
+
+    #[test]
+    fn byte_budget_is_exhausted_once_exceeded() {
+        let budget = ByteBudget::new(Some(ByteSize(100)));
+
+        assert!(!budget.add(60));
+        assert!(!budget.add(40));
+        assert!(!budget.is_exhausted());
+        assert!(budget.add(1));
+        assert!(budget.is_exhausted());
+        assert!(!budget.add(10));
+        assert_eq!(budget.used(), ByteSize(111));
+
+        let unlimited = ByteBudget::new(None);
+        assert!(!unlimited.add(u32::MAX as u64));
+        assert!(!unlimited.is_exhausted());
+    }
+}
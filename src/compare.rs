@@ -0,0 +1,125 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::{data_store::DataStore, link::normalise_url};
+
+/// Outcome of comparing a crawl against a list of expected URLs
+#[derive(Debug, PartialEq, Default)]
+pub struct Comparison {
+    /// Expected URLs that were never seen during the crawl
+    pub undiscovered: Vec<String>,
+    /// Expected URLs that were linked to but never visited
+    pub unvisited: Vec<String>,
+    /// Visited URLs that were not in the expected list
+    pub unexpected: Vec<String>,
+}
+
+impl Comparison {
+    pub fn is_match(&self) -> bool {
+        self.undiscovered.is_empty() && self.unvisited.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sections = [
+            ("Expected URLs not discovered", &self.undiscovered),
+            ("Expected URLs discovered but not visited", &self.unvisited),
+            ("Visited URLs not expected", &self.unexpected),
+        ];
+
+        for (title, urls) in sections {
+            writeln!(f, "{} ({}):", title, urls.len())?;
+            for url in urls {
+                writeln!(f, "  {}", url)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a newline separated URL list, ignoring blank lines and `#` comments
+pub fn parse_url_list(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_owned())
+        .collect()
+}
+
+pub fn compare<T: DataStore>(store: &T, expected: &[String]) -> Comparison {
+    let mut visited = BTreeSet::new();
+    let mut discovered = BTreeSet::new();
+
+    for (url, entry) in store.entries() {
+        discovered.insert(normalise_url(url));
+        if entry.visited {
+            visited.insert(normalise_url(url));
+        }
+        for url_found in &entry.urls_found {
+            discovered.insert(normalise_url(url_found));
+        }
+    }
+
+    let expected = expected
+        .iter()
+        .map(|url| normalise_url(url))
+        .collect::<BTreeSet<_>>();
+
+    let mut comparison = Comparison::default();
+    for url in &expected {
+        if !discovered.contains(url) {
+            comparison.undiscovered.push(url.clone());
+        } else if !visited.contains(url) {
+            comparison.unvisited.push(url.clone());
+        }
+    }
+    comparison.unexpected = visited.difference(&expected).cloned().collect();
+
+    comparison
+}
+
+#[cfg(test)]
+mod compare_tests {
+    use super::{compare, parse_url_list, Comparison};
+    use crate::data_store::{DataStore, Store};
+
+    #[test]
+    fn parse_url_list_skips_blank_lines_and_comments() {
+        let list = parse_url_list("# old site\nhttps://a.com/one\n\n  https://a.com/two  \n");
+
+        assert_eq!(list, vec!["https://a.com/one", "https://a.com/two"]);
+    }
+
+    #[test]
+    fn compare_reports_missing_unvisited_and_unexpected_urls() {
+        let mut s = Store::new();
+        s.add(
+            "https://a.com".to_owned(),
+            Some("https://a.com/linked".to_owned()),
+        );
+        s.visited("https://a.com");
+        s.add("https://a.com/extra".to_owned(), None);
+        s.visited("https://a.com/extra");
+
+        let expected = vec![
+            "https://a.com/".to_owned(),
+            "https://a.com/linked".to_owned(),
+            "https://a.com/gone".to_owned(),
+        ];
+
+        let actual = compare(&s, &expected);
+
+        assert_eq!(
+            actual,
+            Comparison {
+                undiscovered: vec!["https://a.com/gone".to_owned()],
+                unvisited: vec!["https://a.com/linked".to_owned()],
+                unexpected: vec!["https://a.com/extra".to_owned()],
+            }
+        );
+        assert!(!actual.is_match());
+    }
+}
@@ -0,0 +1,273 @@
+use log::info;
+use std::{
+    fmt, fs,
+    num::ParseIntError,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use tokio::sync::Notify;
+
+/// Number of requests sampled before the concurrency limit is adjusted
+const WINDOW: usize = 20;
+/// Error rate above which the concurrency limit is halved
+const MAX_ERROR_RATE: f64 = 0.1;
+/// Growth of the average latency (against the best window seen) above which the limit is halved
+const MAX_LATENCY_GROWTH: f64 = 1.5;
+/// System load average, per available CPU, above which the limit is halved
+const MAX_LOAD_AVERAGE: f64 = 0.9;
+
+/// Either a fixed number of workers or `auto`, which tunes the number of concurrent requests
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Workers {
+    Fixed(u8),
+    Auto,
+}
+
+impl Default for Workers {
+    fn default() -> Self {
+        Workers::Fixed(1)
+    }
+}
+
+impl Workers {
+    /// Number of worker tasks to spawn, `auto` spawns up to the upper bound
+    pub fn tasks(&self, max: usize) -> usize {
+        match self {
+            Workers::Fixed(workers_n) => *workers_n as usize,
+            Workers::Auto => max,
+        }
+    }
+}
+
+impl FromStr for Workers {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Workers::Auto);
+        }
+
+        s.parse().map(Workers::Fixed)
+    }
+}
+
+impl fmt::Display for Workers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Workers::Fixed(workers_n) => write!(f, "{}", workers_n),
+            Workers::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    requests: usize,
+    errors: usize,
+    latency: Duration,
+    best_latency: Option<Duration>,
+}
+
+/// Limits the number of concurrent requests, adjusting the limit (within bounds) to the
+/// observed latency, error rate and load average of the system (not the CPU use of the crawler
+/// itself): it grows by one while the crawl is healthy and halves as soon as it isn't
+#[derive(Debug)]
+pub struct AutoTuner {
+    min: usize,
+    max: usize,
+    limit: AtomicUsize,
+    active: AtomicUsize,
+    released: Notify,
+    window: Mutex<Window>,
+}
+
+impl AutoTuner {
+    pub fn new(min: usize, max: usize) -> Self {
+        let min = min.max(1);
+
+        AutoTuner {
+            min,
+            max: max.max(min),
+            limit: AtomicUsize::new(min),
+            active: AtomicUsize::new(0),
+            released: Notify::new(),
+            window: Mutex::new(Window::default()),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    /// Waits until a request can be made without going over the current limit
+    pub async fn acquire(&self) {
+        loop {
+            let active = self.active.load(Ordering::Acquire);
+            if active < self.limit()
+                && self
+                    .active
+                    .compare_exchange(active, active + 1, Ordering::AcqRel, Ordering::Acquire)
+                    .is_ok()
+            {
+                return;
+            }
+            if active >= self.limit() {
+                // the limit can also grow without a release, so don't wait on the notification forever
+                let _ = tokio::time::timeout(Duration::from_millis(100), self.released.notified())
+                    .await;
+            }
+        }
+    }
+
+    /// Releases a request acquired with `acquire`, recording how it went
+    pub fn release(&self, latency: Duration, is_error: bool) {
+        self.active.fetch_sub(1, Ordering::AcqRel);
+        self.released.notify_one();
+        self.observe(latency, is_error, load_average);
+    }
+
+    /// Records a request, `load_average` being sampled once a window is complete
+    fn observe(
+        &self,
+        latency: Duration,
+        is_error: bool,
+        load_average: impl FnOnce() -> Option<f64>,
+    ) {
+        let mut window = self.window.lock().unwrap();
+        window.requests += 1;
+        window.latency += latency;
+        if is_error {
+            window.errors += 1;
+        }
+        if window.requests < WINDOW {
+            return;
+        }
+
+        let average_latency = window.latency / window.requests as u32;
+        let error_rate = window.errors as f64 / window.requests as f64;
+        let best_latency = *window.best_latency.get_or_insert(average_latency);
+        let is_slower =
+            average_latency.as_secs_f64() > best_latency.as_secs_f64() * MAX_LATENCY_GROWTH;
+        let is_busy = load_average().is_some_and(|load_average| load_average > MAX_LOAD_AVERAGE);
+
+        window.best_latency = Some(best_latency.min(average_latency));
+        window.requests = 0;
+        window.errors = 0;
+        window.latency = Duration::ZERO;
+
+        let limit = self.limit();
+        let new_limit = if error_rate > MAX_ERROR_RATE || is_slower || is_busy {
+            (limit / 2).max(self.min)
+        } else {
+            (limit + 1).min(self.max)
+        };
+
+        if new_limit != limit {
+            self.limit.store(new_limit, Ordering::Relaxed);
+            info!(
+                "Concurrency {} -> {} (avg latency {:.0}ms, error rate {:.0}%)",
+                limit,
+                new_limit,
+                average_latency.as_secs_f64() * 1000.0,
+                error_rate * 100.0
+            );
+        }
+    }
+}
+
+/// One minute load average of the system per available CPU, where the platform reports it. A
+/// blocking read, so only sampled once per window.
+fn load_average() -> Option<f64> {
+    let load_average = fs::read_to_string("/proc/loadavg").ok()?;
+    let load_average = load_average
+        .split_whitespace()
+        .next()?
+        .parse::<f64>()
+        .ok()?;
+    let cpus = thread::available_parallelism().ok()?.get();
+
+    Some(load_average / cpus as f64)
+}
+
+#[cfg(test)]
+mod concurrency_tests {
+    use std::{cell::Cell, time::Duration};
+
+    use super::{AutoTuner, Workers, WINDOW};
+
+    #[test]
+    fn workers_parses_a_number_or_auto() {
+        assert_eq!("4".parse(), Ok(Workers::Fixed(4)));
+        assert_eq!("auto".parse(), Ok(Workers::Auto));
+        assert!("many".parse::<Workers>().is_err());
+        assert_eq!(Workers::Auto.tasks(16), 16);
+        assert_eq!(Workers::Fixed(2).tasks(16), 2);
+    }
+
+    #[test]
+    fn auto_tuner_grows_while_healthy_and_halves_on_errors() {
+        let tuner = AutoTuner::new(1, 3);
+
+        for _ in 0..WINDOW * 5 {
+            tuner.observe(Duration::from_millis(50), false, || Some(0.1));
+        }
+        assert_eq!(tuner.limit(), 3);
+
+        for _ in 0..WINDOW {
+            tuner.observe(Duration::from_millis(50), true, || Some(0.1));
+        }
+        assert_eq!(tuner.limit(), 1);
+    }
+
+    #[test]
+    fn auto_tuner_backs_off_when_latency_grows_or_the_system_is_busy() {
+        let tuner = AutoTuner::new(2, 8);
+
+        for _ in 0..WINDOW * 2 {
+            tuner.observe(Duration::from_millis(50), false, || None);
+        }
+        assert_eq!(tuner.limit(), 4);
+
+        for _ in 0..WINDOW {
+            tuner.observe(Duration::from_millis(500), false, || None);
+        }
+        assert_eq!(tuner.limit(), 2);
+
+        for _ in 0..WINDOW {
+            tuner.observe(Duration::from_millis(50), false, || Some(2.0));
+        }
+        assert_eq!(tuner.limit(), 2);
+    }
+
+    #[test]
+    fn auto_tuner_samples_the_load_average_once_per_window() {
+        let tuner = AutoTuner::new(1, 3);
+        let samples = Cell::new(0);
+
+        for _ in 0..WINDOW * 2 + 1 {
+            tuner.observe(Duration::from_millis(50), false, || {
+                samples.set(samples.get() + 1);
+                None
+            });
+        }
+        assert_eq!(samples.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn acquire_waits_for_a_release_when_at_the_limit() {
+        let tuner = AutoTuner::new(1, 1);
+
+        tuner.acquire().await;
+        let second = tokio::time::timeout(Duration::from_millis(50), tuner.acquire()).await;
+        assert!(second.is_err());
+
+        tuner.release(Duration::from_millis(1), false);
+        let third = tokio::time::timeout(Duration::from_millis(500), tuner.acquire()).await;
+        assert!(third.is_ok());
+    }
+}
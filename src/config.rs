@@ -0,0 +1,259 @@
+use std::{fmt, fs, io, path::Path};
+use toml::{Table, Value};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    /// A key whose value can't be turned into a command line option
+    Value {
+        key: String,
+        reason: String,
+    },
+}
+
+impl std::convert::From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl std::convert::From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Error::Parse(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Parse(err) => write!(f, "{}", err),
+            Error::Value { key, reason } => write!(f, "`{}`: {}", key, reason),
+        }
+    }
+}
+
+/// Options whose values are `<key>=<value>` pairs, that can be written as a table of them
+const TABLE_OPTIONS: [&str; 1] = ["path-quota"];
+
+/// Turns a crawl config into the equivalent command line options.
+///
+/// Keys are the long option names (`respect-robots = true` is `--respect-robots`) and can be
+/// grouped into tables, whose names are only there to organise the file. The options taking
+/// `<key>=<value>` pairs can also be tables, `[scope.path-quota]` then `"/tag/*" = 100` being
+/// `--path-quota /tag/*=100`.
+pub fn to_args(contents: &str) -> Result<Vec<String>, Error> {
+    let table = contents.parse::<Table>()?;
+    let mut args = vec![];
+
+    for (key, value) in table {
+        match value {
+            Value::Table(section) => {
+                for (key, value) in section {
+                    push_option(&mut args, &key, value)?;
+                }
+            }
+            value => push_option(&mut args, &key, value)?,
+        }
+    }
+
+    Ok(args)
+}
+
+pub fn load(path: &Path) -> Result<Vec<String>, Error> {
+    to_args(&fs::read_to_string(path)?)
+}
+
+fn push_option(args: &mut Vec<String>, key: &str, value: Value) -> Result<(), Error> {
+    let option = format!("--{}", key);
+
+    match value {
+        Value::Boolean(true) => args.push(option),
+        Value::Boolean(false) => {}
+        Value::String(value) => args.extend([option, value]),
+        Value::Integer(value) => args.extend([option, value.to_string()]),
+        Value::Float(value) => args.extend([option, value.to_string()]),
+        Value::Array(values) => {
+            for value in values {
+                push_option(args, key, value)?;
+            }
+        }
+        Value::Table(entries) if TABLE_OPTIONS.contains(&key) => {
+            for (name, value) in entries {
+                let value = match value {
+                    Value::String(value) => value,
+                    Value::Integer(value) => value.to_string(),
+                    Value::Float(value) => value.to_string(),
+                    _ => {
+                        return Err(Error::Value {
+                            key: format!("{}.{}", key, name),
+                            reason: "expected a string or number".to_owned(),
+                        })
+                    }
+                };
+                args.extend([option.clone(), format!("{}={}", name, value)]);
+            }
+        }
+        Value::Table(_) | Value::Datetime(_) => {
+            return Err(Error::Value {
+                key: key.to_owned(),
+                reason: "expected a string, number, boolean or array".to_owned(),
+            })
+        }
+    }
+
+    Ok(())
+}
+
+/// Answers used to fill in a starter config
+#[derive(Debug, Default)]
+pub struct Starter {
+    pub url: Vec<String>,
+    pub scope: String,
+    pub workers_n: String,
+    pub delay: u64,
+    pub respect_robots: bool,
+    pub output_file: Option<String>,
+}
+
+impl Starter {
+    /// A commented config, with the answers filled in and the other options commented out
+    pub fn render(&self) -> String {
+        let quote = |value: &str| Value::String(value.to_owned()).to_string();
+        let urls = self
+            .url
+            .iter()
+            .map(|url| quote(url))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let output_file = match &self.output_file {
+            Some(output_file) => format!("output-file = {}", quote(output_file)),
+            None => "# output-file = \"crawl.json\"".to_owned(),
+        };
+
+        format!(
+            r#"# url-crawler config, run it with `url-crawler --config <this file>`.
+# Keys are the long command line options; options given on the command line win.
+
+# URLs to start crawling from
+url = [{urls}]
+
+[scope]
+# same-domain, external-once or all-domains
+scope = {scope}
+# max-links-per-page = 500
+# use-sitemaps = true
+# crawl-get-forms = true
+
+[politeness]
+# delay (in seconds) between requests
+delay = {delay}
+# slow down deeper in the site and on hosts already requested many times
+# delay-formula = "min(delay * (1 + depth / 4) + pages / 1000, 30)"
+respect-robots = {respect_robots}
+# ramp-up-s = 30
+
+[network]
+# number of workers, or "auto"
+workers-n = {workers_n}
+# https-only = true
+# dns-prefetch = true
+# only check downloads respond, the first matching rule applies
+# fetch-rule = ["/downloads/* method=HEAD", "/api/* header=Accept:application/json"]
+
+[output]
+{output_file}
+# redirects-file = "redirects.csv"
+# stats = true
+"#,
+            scope = quote(&self.scope),
+            delay = self.delay,
+            respect_robots = self.respect_robots,
+            workers_n = match self.workers_n.parse::<u8>() {
+                Ok(workers_n) => workers_n.to_string(),
+                Err(_) => quote(&self.workers_n),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::{to_args, Error, Starter};
+
+    #[test]
+    fn to_args_turns_keys_into_long_options() {
+        let args = to_args(
+            "url = [\"https://a.com\", \"https://b.com\"]\n\
+             [politeness]\n\
+             delay = 1\n\
+             respect-robots = true\n\
+             print = false\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            args,
+            vec![
+                "--delay",
+                "1",
+                "--respect-robots",
+                "--url",
+                "https://a.com",
+                "--url",
+                "https://b.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn to_args_rejects_nested_tables() {
+        let err = to_args("[output]\n[output.csv]\npath = \"a.csv\"\n").unwrap_err();
+
+        assert!(matches!(err, Error::Value { key, .. } if key == "csv"));
+    }
+
+    #[test]
+    fn to_args_turns_tables_of_pairs_into_repeated_options() {
+        let args = to_args("[scope.path-quota]\n\"/tag/*\" = 100\n\"/archive/*\" = 20\n").unwrap();
+
+        assert_eq!(
+            args,
+            vec![
+                "--path-quota",
+                "/archive/*=20",
+                "--path-quota",
+                "/tag/*=100"
+            ]
+        );
+        assert!(to_args("[scope.path-quota]\n\"/tag/*\" = true\n").is_err());
+    }
+
+    #[test]
+    fn starter_config_fills_in_answers() {
+        let starter = Starter {
+            url: vec!["https://a.com".to_owned()],
+            scope: "same-domain".to_owned(),
+            workers_n: "auto".to_owned(),
+            delay: 2,
+            ..Default::default()
+        };
+
+        let args = to_args(&starter.render()).unwrap();
+
+        assert_eq!(
+            args,
+            vec![
+                "--workers-n",
+                "auto",
+                "--delay",
+                "2",
+                "--scope",
+                "same-domain",
+                "--url",
+                "https://a.com"
+            ]
+        );
+    }
+}
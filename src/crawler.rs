@@ -0,0 +1,2353 @@
+use log::{info, warn};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::{mpsc, RwLock},
+    task::JoinSet,
+};
+
+use crate::{
+    abort::AbortThreshold,
+    alternates::{self, Alternate, AlternateSource},
+    block::BlockDetector,
+    budget::{ByteBudget, ByteSize},
+    concurrency::{AutoTuner, Workers},
+    data_store::{DataStore, LocaleResult, Store},
+    dedupe::{DedupeKey, Deduper},
+    delay_formula::DelayFormula,
+    dns::{DnsCache, DnsPrefetcher},
+    event_log::{EventKind, EventLog},
+    fetch::{Fetch, HttpFetch, HttpFetchBuilder, RedirectPolicy},
+    fingerprint::content_hash,
+    handle::{AddedSeed, Control, CrawlHandle},
+    health::Health,
+    https::HttpsUpgrade,
+    link::{
+        host_with_port, is_internal, process_link, process_url, unique_hosts_first, url_parts,
+        Error, LinkDecision, LinkSource, Scope, UrlParts,
+    },
+    mime::{document_kind, DocumentKind},
+    parked,
+    parser::{Form, Parser},
+    policy::{LinkTextRules, ScopePolicy, UrlPolicy},
+    politeness::{Politeness, PolitenessLog, WaitReason},
+    queue::FrontierPolicy,
+    quota::{PathQuota, PathQuotas},
+    robots::{RobotsCache, UnreachablePolicy},
+    sample::Sampler,
+    search, sitemap,
+    stats::{HostStats, Phase, PhaseStats, WorkerRamp},
+    url_frontier::{
+        self, DropReason, FrontierMetrics, QueuedUrl, URLFrontier, URLFrontierBuilder,
+        URLFrontierable,
+    },
+    window::CrawlWindows,
+};
+
+const DNS_PREFETCH_QUEUE_CAPACITY: usize = 1024;
+const DNS_PREFETCH_CONCURRENCY: usize = 4;
+/// Seed of the random frontier policy in deterministic crawls
+const DETERMINISTIC_SEED: u64 = 0x5eed;
+
+/// Something that happened during a crawl, sent to the subscriber as soon as it happens
+#[derive(Debug, PartialEq, Clone)]
+pub enum CrawlEvent {
+    /// A page was fetched, `outlinks` being the absolute URLs of the links found on it
+    PageVisited {
+        url: String,
+        status: u16,
+        depth: usize,
+        outlinks: Vec<String>,
+    },
+    /// A link was found on a page, with what happened to it
+    LinkFound {
+        page: String,
+        url: String,
+        decision: LinkDecision,
+    },
+    /// A page could not be fetched
+    FetchError { url: String, error: String },
+}
+
+/// Settings that don't change while crawling
+struct Settings {
+    seeds: Vec<String>,
+    seeds_url_parts: Vec<Arc<Result<UrlParts, Error>>>,
+    scope: Scope,
+    use_sitemaps: bool,
+    body_sample_kb: Option<usize>,
+    aggressive_extraction: bool,
+    crawl_get_forms: bool,
+    max_links_per_page: Option<usize>,
+    max_depth: Option<usize>,
+    max_pages: Option<usize>,
+    link_text_rules: LinkTextRules,
+    crawl_windows: CrawlWindows,
+    locales: Vec<String>,
+    redirect_policy: RedirectPolicy,
+    deterministic: bool,
+}
+
+/// Everything the workers of a crawl share
+struct Shared<T> {
+    settings: Arc<Settings>,
+    url_frontier: Arc<RwLock<URLFrontier>>,
+    data_store: Arc<RwLock<T>>,
+    stats: Arc<PhaseStats>,
+    host_stats: Arc<HostStats>,
+    worker_ramp: Arc<WorkerRamp>,
+    robots: Option<Arc<RobotsCache>>,
+    https_upgrade: Option<Arc<HttpsUpgrade>>,
+    dns_prefetcher: Option<DnsPrefetcher>,
+    url_policy: Arc<dyn UrlPolicy>,
+    auto_tuner: Option<AutoTuner>,
+    byte_budget: ByteBudget,
+    /// Pages requested so far, against `max_pages`
+    pages: AtomicUsize,
+    events: Option<mpsc::UnboundedSender<CrawlEvent>>,
+    politeness_log: Option<Arc<PolitenessLog>>,
+    block_detector: Option<Arc<BlockDetector>>,
+    event_log: Option<Arc<EventLog>>,
+    sampler: Option<Arc<Sampler>>,
+    deduper: Option<Arc<Deduper>>,
+    path_quotas: Option<Arc<PathQuotas>>,
+    abort_threshold: Option<Arc<AbortThreshold>>,
+    health: Option<Arc<Health>>,
+    control: Arc<Control>,
+    /// Alternates the sitemaps list for their pages, by page URL
+    sitemap_alternates: HashMap<String, Vec<Alternate>>,
+}
+
+impl<T> Shared<T> {
+    fn send(&self, event: CrawlEvent) {
+        if let Some(events) = &self.events {
+            // the subscriber may have stopped listening, the crawl goes on regardless
+            let _ = events.send(event);
+        }
+    }
+
+    fn record(&self, worker: usize, kind: EventKind, url: &str, detail: &str) {
+        if let Some(event_log) = &self.event_log {
+            event_log.record(Some(worker), kind, url, detail);
+        }
+    }
+
+    /// Applies decisions on URLs to the frontier, queueing the URLs to crawl at `depth`. URLs are
+    /// queued by a worker, or by none for the seeds added while crawling.
+    async fn update_frontier<'a>(
+        &self,
+        worker: Option<usize>,
+        updates: FrontierUpdates,
+        decisions: impl Iterator<Item = (&'a String, LinkDecision)>,
+        depth: usize,
+    ) {
+        let mut url_frontier_write = self.url_frontier.write().await;
+        for (host, delay) in updates.host_delays {
+            url_frontier_write.set_host_delay(host, delay);
+        }
+        for reason in updates.dropped {
+            url_frontier_write.drop_url(reason);
+        }
+        for (url, decision) in decisions {
+            match decision {
+                LinkDecision::Enqueue => {
+                    if let Some(dns_prefetcher) = &self.dns_prefetcher {
+                        dns_prefetcher.prefetch(url);
+                    }
+                    if let Some(event_log) = &self.event_log {
+                        event_log.record(worker, EventKind::Enqueue, url, "");
+                    }
+                    url_frontier_write.enqueue(QueuedUrl::new(url.clone(), depth))
+                }
+                LinkDecision::SkipDuplicate => url_frontier_write.drop_url(DropReason::Duplicate),
+                LinkDecision::SkipBudget => url_frontier_write.drop_url(DropReason::Budget),
+                LinkDecision::SkipFiltered => {}
+                _ => url_frontier_write.drop_url(DropReason::Filtered),
+            }
+        }
+    }
+
+    /// Reserves a page request, returning whether `max_pages` allows it
+    fn reserve_page(&self) -> bool {
+        match self.settings.max_pages {
+            Some(max) => self.pages.fetch_add(1, Ordering::Relaxed) < max,
+            None => true,
+        }
+    }
+
+    /// Gives back a page request reserved for a URL that is requested again later
+    fn release_page(&self) {
+        if self.settings.max_pages.is_some() {
+            self.pages.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    fn is_over_limits(&self) -> bool {
+        self.control.is_aborted()
+            || self.byte_budget.is_exhausted()
+            || self
+                .abort_threshold
+                .as_ref()
+                .is_some_and(|abort_threshold| abort_threshold.is_aborted())
+            || self
+                .settings
+                .max_pages
+                .is_some_and(|max| self.pages.load(Ordering::Relaxed) >= max)
+    }
+}
+
+/// A crawl of one or more seed URLs, built with [`CrawlerBuilder`]
+pub struct Crawler<T = Store> {
+    settings: Arc<Settings>,
+    workers: Workers,
+    workers_min: usize,
+    workers_max: usize,
+    url_frontier: Arc<RwLock<URLFrontier>>,
+    data_store: Arc<RwLock<T>>,
+    stats: Arc<PhaseStats>,
+    host_stats: Arc<HostStats>,
+    worker_ramp: Arc<WorkerRamp>,
+    client_builder: HttpFetchBuilder,
+    robots: Option<Arc<RobotsCache>>,
+    https_upgrade: Option<Arc<HttpsUpgrade>>,
+    dns_cache: Option<DnsCache>,
+    politeness_log: Option<Arc<PolitenessLog>>,
+    block_detector: Option<Arc<BlockDetector>>,
+    event_log: Option<Arc<EventLog>>,
+    sampler: Option<Arc<Sampler>>,
+    deduper: Option<Arc<Deduper>>,
+    path_quotas: Option<Arc<PathQuotas>>,
+    abort_threshold: Option<Arc<AbortThreshold>>,
+    health: Option<Arc<Health>>,
+    control: Arc<Control>,
+    /// Seeds added through the handles, taken by `run()`
+    added_seeds: (
+        mpsc::UnboundedSender<AddedSeed>,
+        Option<mpsc::UnboundedReceiver<AddedSeed>>,
+    ),
+    url_policy: Arc<dyn UrlPolicy>,
+    max_bytes: Option<ByteSize>,
+    events: Option<mpsc::UnboundedSender<CrawlEvent>>,
+}
+
+pub struct CrawlerBuilder<T = Store> {
+    seeds: Vec<String>,
+    pending: Vec<QueuedUrl>,
+    workers: Workers,
+    workers_min: usize,
+    workers_max: usize,
+    ramp_up: Duration,
+    delay_s: u64,
+    delay_formula: Option<DelayFormula>,
+    frontier_policy: FrontierPolicy,
+    scope: Scope,
+    url_policy: Option<Arc<dyn UrlPolicy>>,
+    use_sitemaps: bool,
+    body_sample_kb: Option<usize>,
+    aggressive_extraction: bool,
+    crawl_get_forms: bool,
+    max_links_per_page: Option<usize>,
+    max_depth: Option<usize>,
+    max_pages: Option<usize>,
+    max_bytes: Option<ByteSize>,
+    link_text_rules: LinkTextRules,
+    crawl_windows: CrawlWindows,
+    locales: Vec<String>,
+    redirect_policy: RedirectPolicy,
+    deterministic: bool,
+    client_builder: HttpFetchBuilder,
+    robots: Option<Arc<RobotsCache>>,
+    https_upgrade: Option<Arc<HttpsUpgrade>>,
+    dns_cache: Option<DnsCache>,
+    politeness_log: Option<Arc<PolitenessLog>>,
+    block_detector: Option<Arc<BlockDetector>>,
+    event_log: Option<Arc<EventLog>>,
+    sampler: Option<Arc<Sampler>>,
+    deduper: Option<Arc<Deduper>>,
+    path_quotas: Option<Arc<PathQuotas>>,
+    abort_threshold: Option<Arc<AbortThreshold>>,
+    health: Option<Arc<Health>>,
+    data_store: T,
+}
+
+impl Default for CrawlerBuilder {
+    fn default() -> Self {
+        CrawlerBuilder::new()
+    }
+}
+
+impl CrawlerBuilder {
+    /// A single worker crawl of the seed domains, 2 seconds apart, into an in-memory store
+    pub fn new() -> CrawlerBuilder {
+        CrawlerBuilder {
+            seeds: vec![],
+            pending: vec![],
+            workers: Workers::default(),
+            workers_min: 1,
+            workers_max: 16,
+            ramp_up: Duration::ZERO,
+            delay_s: 2,
+            delay_formula: None,
+            frontier_policy: FrontierPolicy::default(),
+            scope: Scope::default(),
+            url_policy: None,
+            use_sitemaps: false,
+            body_sample_kb: None,
+            aggressive_extraction: false,
+            crawl_get_forms: false,
+            max_links_per_page: None,
+            max_depth: None,
+            max_pages: None,
+            max_bytes: None,
+            link_text_rules: LinkTextRules::default(),
+            crawl_windows: CrawlWindows::default(),
+            locales: vec![],
+            redirect_policy: RedirectPolicy::default(),
+            deterministic: false,
+            client_builder: HttpFetchBuilder::new(),
+            robots: None,
+            https_upgrade: None,
+            dns_cache: None,
+            politeness_log: None,
+            block_detector: None,
+            event_log: None,
+            sampler: None,
+            deduper: None,
+            path_quotas: None,
+            abort_threshold: None,
+            health: None,
+            data_store: Store::new(),
+        }
+    }
+}
+
+impl<T: DataStore + Send + Sync + 'static> CrawlerBuilder<T> {
+    /// Adds a seed URL, a URL is in scope if it belongs to any seed's domain
+    pub fn seed(mut self, url: &str) -> Self {
+        self.seeds.push(url.to_owned());
+        self
+    }
+
+    /// Adds URLs that were still queued when a previous run of the crawl stopped
+    pub fn pending(mut self, pending: Vec<QueuedUrl>) -> Self {
+        self.pending.extend(pending);
+        self
+    }
+
+    /// Number of workers, or `Workers::Auto` to tune the number of concurrent requests while crawling
+    pub fn workers(mut self, workers: Workers) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Bounds on the number of concurrent requests with `Workers::Auto`
+    pub fn workers_range(mut self, min: usize, max: usize) -> Self {
+        self.workers_min = min;
+        self.workers_max = max;
+        self
+    }
+
+    /// Start with one worker and add the rest gradually over this period
+    pub fn ramp_up(mut self, ramp_up: Duration) -> Self {
+        self.ramp_up = ramp_up;
+        self
+    }
+
+    /// Politeness delay (in seconds) between two requests to the same host
+    pub fn delay_s(mut self, delay_s: u64) -> Self {
+        self.delay_s = delay_s;
+        self
+    }
+
+    /// Scales the politeness delay of each request with its depth or the requests made to its host
+    pub fn delay_formula(mut self, delay_formula: DelayFormula) -> Self {
+        self.delay_formula = Some(delay_formula);
+        self
+    }
+
+    pub fn frontier_policy(mut self, frontier_policy: FrontierPolicy) -> Self {
+        self.frontier_policy = frontier_policy;
+        self
+    }
+
+    pub fn scope(mut self, scope: Scope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Decides which links are enqueued instead of the scope
+    pub fn url_policy(mut self, url_policy: Arc<dyn UrlPolicy>) -> Self {
+        self.url_policy = Some(url_policy);
+        self
+    }
+
+    /// Make the crawl reproducible run to run: a single worker whatever [`Self::workers`] says, the
+    /// links of each page handled in sorted order and the random frontier policy seeded
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Enqueue the URLs of sitemaps listed in the seeds' robots.txt
+    pub fn use_sitemaps(mut self, use_sitemaps: bool) -> Self {
+        self.use_sitemaps = use_sitemaps;
+        self
+    }
+
+    /// Keep the first N KB of each page body in the data store
+    pub fn body_sample_kb(mut self, body_sample_kb: usize) -> Self {
+        self.body_sample_kb = Some(body_sample_kb);
+        self
+    }
+
+    /// Also extract URLs from JS navigation
+    pub fn aggressive_extraction(mut self, aggressive_extraction: bool) -> Self {
+        self.aggressive_extraction = aggressive_extraction;
+        self
+    }
+
+    /// Crawl the actions of GET forms, submitted with empty fields
+    pub fn crawl_get_forms(mut self, crawl_get_forms: bool) -> Self {
+        self.crawl_get_forms = crawl_get_forms;
+        self
+    }
+
+    /// Enqueue at most this many links from a single page, the first link to each host first
+    pub fn max_links_per_page(mut self, max_links_per_page: usize) -> Self {
+        self.max_links_per_page = Some(max_links_per_page);
+        self
+    }
+
+    /// Don't follow links further than this many links away from a seed
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Stop the crawl once this many pages have been requested
+    pub fn max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = Some(max_pages);
+        self
+    }
+
+    /// Stop the crawl once this much has been downloaded
+    pub fn max_bytes(mut self, max_bytes: ByteSize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn link_text_rules(mut self, link_text_rules: LinkTextRules) -> Self {
+        self.link_text_rules = link_text_rules;
+        self
+    }
+
+    /// Times of day the crawl may run at, it pauses outside of them
+    pub fn crawl_windows(mut self, crawl_windows: CrawlWindows) -> Self {
+        self.crawl_windows = crawl_windows;
+        self
+    }
+
+    /// Requests every page once per locale, with the locale as `Accept-Language`, and records what each
+    /// locale was served. Links are only followed from the first locale's response.
+    pub fn locales(mut self, locales: Vec<String>) -> Self {
+        self.locales = locales;
+        self
+    }
+
+    /// Whether the crawl follows redirects, records them as links to their target or fails them
+    pub fn redirect_policy(mut self, redirect_policy: RedirectPolicy) -> Self {
+        self.redirect_policy = redirect_policy;
+        self
+    }
+
+    /// Builder for the HTTP clients, a client per worker
+    pub fn client_builder(mut self, client_builder: HttpFetchBuilder) -> Self {
+        self.client_builder = client_builder;
+        self
+    }
+
+    /// Skip URLs disallowed by robots.txt and honour its `Crawl-delay`
+    pub fn robots(mut self, robots: Arc<RobotsCache>) -> Self {
+        self.robots = Some(robots);
+        self
+    }
+
+    /// Upgrade same host http:// links to https://, when the host serves https
+    pub fn https_upgrade(mut self, https_upgrade: Arc<HttpsUpgrade>) -> Self {
+        self.https_upgrade = Some(https_upgrade);
+        self
+    }
+
+    /// Resolve the hosts of queued URLs in the background, sharing the cache with the HTTP clients
+    pub fn dns_cache(mut self, dns_cache: DnsCache) -> Self {
+        self.dns_cache = Some(dns_cache);
+        self
+    }
+
+    /// Log every politeness decision - which host, how long the request waited and why
+    pub fn politeness_log(mut self, politeness_log: Arc<PolitenessLog>) -> Self {
+        self.politeness_log = Some(politeness_log);
+        self
+    }
+
+    /// Pause requests to hosts that look like they are blocking the crawler - uniform 403s, CAPTCHA
+    /// pages or challenge interstitials - for `pause`, doubled each time, rather than recording errors
+    pub fn block_pause(mut self, pause: Duration) -> Self {
+        self.block_detector = Some(Arc::new(BlockDetector::new(pause)));
+        self
+    }
+
+    /// Log when URLs are enqueued and when each worker fetches them, to replay the crawl as a timeline
+    pub fn event_log(mut self, event_log: Arc<EventLog>) -> Self {
+        self.event_log = Some(event_log);
+        self
+    }
+
+    /// Crawl a sample of the discovered URLs, `rate` being the fraction of them to crawl, see
+    /// [`Sampler`]. The seeds are always crawled.
+    pub fn sample(mut self, rate: f64) -> Self {
+        self.sampler = Some(Arc::new(Sampler::new(rate)));
+        self
+    }
+
+    /// What makes two URLs the same page for the visited check, the URL itself by default
+    pub fn dedupe_key(mut self, key: DedupeKey) -> Self {
+        self.deduper = match key {
+            // the data store already checks URLs
+            DedupeKey::Url => None,
+            key => Some(Arc::new(Deduper::new(key))),
+        };
+        self
+    }
+
+    /// Crawl at most the number of URLs of each quota under its path pattern, see [`PathQuotas`]
+    pub fn path_quotas(mut self, quotas: Vec<PathQuota>) -> Self {
+        self.path_quotas = Some(Arc::new(PathQuotas::new(quotas)));
+        self
+    }
+
+    /// Abort the crawl once more than `max_error_rate` of its requests fail, see [`AbortThreshold`]
+    pub fn abort_error_rate(mut self, max_error_rate: f64) -> Self {
+        self.abort_threshold = Some(Arc::new(AbortThreshold::new(max_error_rate)));
+        self
+    }
+
+    /// Report the crawl's progress to health checks, which may be shared with other crawls
+    pub fn health(mut self, health: Arc<Health>) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Crawl into another data store, i.e. a persistent one
+    pub fn data_store<U: DataStore + Send + Sync + 'static>(
+        self,
+        data_store: U,
+    ) -> CrawlerBuilder<U> {
+        CrawlerBuilder {
+            seeds: self.seeds,
+            pending: self.pending,
+            workers: self.workers,
+            workers_min: self.workers_min,
+            workers_max: self.workers_max,
+            ramp_up: self.ramp_up,
+            delay_s: self.delay_s,
+            delay_formula: self.delay_formula,
+            frontier_policy: self.frontier_policy,
+            scope: self.scope,
+            url_policy: self.url_policy,
+            use_sitemaps: self.use_sitemaps,
+            body_sample_kb: self.body_sample_kb,
+            aggressive_extraction: self.aggressive_extraction,
+            crawl_get_forms: self.crawl_get_forms,
+            max_links_per_page: self.max_links_per_page,
+            max_depth: self.max_depth,
+            max_pages: self.max_pages,
+            max_bytes: self.max_bytes,
+            link_text_rules: self.link_text_rules,
+            crawl_windows: self.crawl_windows,
+            locales: self.locales,
+            redirect_policy: self.redirect_policy,
+            deterministic: self.deterministic,
+            client_builder: self.client_builder,
+            robots: self.robots,
+            https_upgrade: self.https_upgrade,
+            dns_cache: self.dns_cache,
+            politeness_log: self.politeness_log,
+            block_detector: self.block_detector,
+            event_log: self.event_log,
+            sampler: self.sampler,
+            deduper: self.deduper,
+            path_quotas: self.path_quotas,
+            abort_threshold: self.abort_threshold,
+            health: self.health,
+            data_store,
+        }
+    }
+
+    pub fn build(self) -> Crawler<T> {
+        if let Some(deduper) = &self.deduper {
+            // a resumed crawl doesn't fetch the pages it visited or queued again under other URLs
+            let visited = self
+                .data_store
+                .entries()
+                .into_iter()
+                .filter(|(_, entry)| entry.visited)
+                .map(|(url, _)| url);
+            for url in visited
+                .chain(&self.seeds)
+                .chain(self.pending.iter().map(|queued| &queued.url))
+            {
+                deduper.claim(url);
+            }
+        }
+        if let Some(path_quotas) = &self.path_quotas {
+            // a resumed crawl counts the pages it visited or queued against their quotas
+            let visited = self
+                .data_store
+                .entries()
+                .into_iter()
+                .filter(|(_, entry)| entry.visited)
+                .map(|(url, _)| url);
+            let seeds = self
+                .seeds
+                .iter()
+                .filter(|url| !self.data_store.has_visited(url));
+            for url in visited
+                .chain(seeds)
+                .chain(self.pending.iter().map(|queued| &queued.url))
+            {
+                path_quotas.claim(url);
+            }
+        }
+        if let Some(event_log) = &self.event_log {
+            let seeds = self
+                .seeds
+                .iter()
+                .filter(|url| !self.data_store.has_visited(url));
+            for url in seeds.chain(self.pending.iter().map(|queued| &queued.url)) {
+                event_log.record(None, EventKind::Enqueue, url, "");
+            }
+        }
+        let mut url_frontier = self
+            .seeds
+            .iter()
+            // a resumed crawl doesn't start over from seeds it already visited
+            .filter(|url| !self.data_store.has_visited(url))
+            .fold(URLFrontierBuilder::new(), |builder, url| {
+                builder.value(url.to_owned())
+            })
+            .queued(self.pending)
+            .delay_s(self.delay_s)
+            .policy(self.frontier_policy);
+        if let Some(delay_formula) = self.delay_formula {
+            url_frontier = url_frontier.delay_formula(delay_formula);
+        }
+        if self.deterministic {
+            url_frontier = url_frontier.random_seed(DETERMINISTIC_SEED);
+        }
+        let url_frontier = url_frontier.build();
+        // concurrent workers would fetch and find URLs in whatever order their requests complete
+        let workers = match self.deterministic {
+            true => Workers::Fixed(1),
+            false => self.workers,
+        };
+        let seeds_url_parts = self
+            .seeds
+            .iter()
+            .map(|url| Arc::new(url_parts(url)))
+            .collect::<Vec<_>>();
+        let url_policy = self
+            .url_policy
+            .unwrap_or_else(|| Arc::new(ScopePolicy::new(seeds_url_parts.clone(), self.scope)));
+        let client_builder = match &self.dns_cache {
+            Some(dns_cache) => self.client_builder.dns_cache(dns_cache.clone()),
+            None => self.client_builder,
+        };
+        let client_builder = match self.locales.first() {
+            Some(locale) => client_builder.accept_language(locale),
+            None => client_builder,
+        };
+
+        Crawler {
+            settings: Arc::new(Settings {
+                seeds: self.seeds,
+                seeds_url_parts,
+                scope: self.scope,
+                use_sitemaps: self.use_sitemaps,
+                body_sample_kb: self.body_sample_kb,
+                aggressive_extraction: self.aggressive_extraction,
+                crawl_get_forms: self.crawl_get_forms,
+                max_links_per_page: self.max_links_per_page,
+                max_depth: self.max_depth,
+                max_pages: self.max_pages,
+                link_text_rules: self.link_text_rules,
+                crawl_windows: self.crawl_windows,
+                locales: self.locales,
+                redirect_policy: self.redirect_policy,
+                deterministic: self.deterministic,
+            }),
+            workers,
+            workers_min: self.workers_min,
+            workers_max: self.workers_max,
+            url_frontier: Arc::new(RwLock::new(url_frontier)),
+            data_store: Arc::new(RwLock::new(self.data_store)),
+            stats: Arc::new(PhaseStats::default()),
+            host_stats: Arc::new(HostStats::default()),
+            worker_ramp: Arc::new(WorkerRamp::new(
+                workers.tasks(self.workers_max),
+                self.ramp_up,
+            )),
+            client_builder,
+            robots: self.robots,
+            https_upgrade: self.https_upgrade,
+            dns_cache: self.dns_cache,
+            politeness_log: self.politeness_log,
+            block_detector: self.block_detector,
+            event_log: self.event_log,
+            sampler: self.sampler,
+            deduper: self.deduper,
+            path_quotas: self.path_quotas,
+            abort_threshold: self.abort_threshold,
+            health: self.health,
+            control: Arc::new(Control::default()),
+            added_seeds: {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                (sender, Some(receiver))
+            },
+            url_policy,
+            max_bytes: self.max_bytes,
+            events: None,
+        }
+    }
+}
+
+impl<T: DataStore + Send + Sync + 'static> Crawler<T> {
+    /// Sends the events of the crawl to the returned receiver, replacing any previous subscriber.
+    /// The receiver is closed once the crawl has finished.
+    pub fn subscribe(&mut self) -> mpsc::UnboundedReceiver<CrawlEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.events = Some(sender);
+
+        receiver
+    }
+
+    pub fn frontier(&self) -> Arc<RwLock<URLFrontier>> {
+        self.url_frontier.clone()
+    }
+
+    pub async fn frontier_metrics(&self) -> FrontierMetrics {
+        self.url_frontier.read().await.metrics()
+    }
+
+    /// Time spent per crawl phase, summed across workers
+    pub fn stats(&self) -> Arc<PhaseStats> {
+        self.stats.clone()
+    }
+
+    pub fn host_stats(&self) -> Arc<HostStats> {
+        self.host_stats.clone()
+    }
+
+    pub fn worker_ramp(&self) -> Arc<WorkerRamp> {
+        self.worker_ramp.clone()
+    }
+
+    pub fn robots(&self) -> Option<Arc<RobotsCache>> {
+        self.robots.clone()
+    }
+
+    pub fn block_detector(&self) -> Option<Arc<BlockDetector>> {
+        self.block_detector.clone()
+    }
+
+    pub fn sampler(&self) -> Option<Arc<Sampler>> {
+        self.sampler.clone()
+    }
+
+    pub fn abort_threshold(&self) -> Option<Arc<AbortThreshold>> {
+        self.abort_threshold.clone()
+    }
+
+    pub fn health(&self) -> Option<Arc<Health>> {
+        self.health.clone()
+    }
+
+    /// A handle to pause, resume, extend or abort the crawl from another task while it runs
+    pub fn handle(&self) -> CrawlHandle {
+        CrawlHandle::new(
+            self.control.clone(),
+            self.url_frontier.clone(),
+            self.added_seeds.0.clone(),
+        )
+    }
+
+    /// Crawls until nothing is left in the frontier or a limit is reached, returning the data store
+    pub async fn run(&mut self) -> Arc<RwLock<T>> {
+        let sitemap_alternates = match self.settings.use_sitemaps {
+            true => self.enqueue_sitemaps().await,
+            false => HashMap::new(),
+        };
+
+        let workers_n = self.workers.tasks(self.workers_max);
+        let shared = Arc::new(Shared {
+            settings: self.settings.clone(),
+            url_frontier: self.url_frontier.clone(),
+            data_store: self.data_store.clone(),
+            stats: self.stats.clone(),
+            host_stats: self.host_stats.clone(),
+            worker_ramp: self.worker_ramp.clone(),
+            robots: self.robots.clone(),
+            https_upgrade: self.https_upgrade.clone(),
+            dns_prefetcher: self.dns_cache.clone().map(|dns_cache| {
+                DnsPrefetcher::spawn(
+                    dns_cache,
+                    DNS_PREFETCH_QUEUE_CAPACITY,
+                    DNS_PREFETCH_CONCURRENCY,
+                )
+            }),
+            url_policy: self.url_policy.clone(),
+            auto_tuner: (self.workers == Workers::Auto)
+                .then(|| AutoTuner::new(self.workers_min, self.workers_max)),
+            byte_budget: ByteBudget::new(self.max_bytes),
+            pages: AtomicUsize::new(0),
+            // the receiver is closed once the workers, and so the sender, are gone
+            events: self.events.take(),
+            politeness_log: self.politeness_log.clone(),
+            block_detector: self.block_detector.clone(),
+            event_log: self.event_log.clone(),
+            sampler: self.sampler.clone(),
+            deduper: self.deduper.clone(),
+            path_quotas: self.path_quotas.clone(),
+            abort_threshold: self.abort_threshold.clone(),
+            health: self.health.clone(),
+            control: self.control.clone(),
+            sitemap_alternates,
+        });
+
+        let mut tasks = JoinSet::new();
+        for worker in 0..workers_n {
+            // a HTTP client per worker, and one following redirects whatever the redirect policy,
+            // for robots.txt and HTTPS upgrade probes
+            let client = self
+                .client_builder
+                .clone()
+                .redirect_policy(self.settings.redirect_policy)
+                .build();
+            let follow_client = self.client_builder.clone().build();
+            let locale_clients = self
+                .settings
+                .locales
+                .iter()
+                .skip(1)
+                .map(|locale| {
+                    let client = self
+                        .client_builder
+                        .clone()
+                        .accept_language(locale)
+                        .redirect_policy(self.settings.redirect_policy);
+                    (locale.clone(), client.build())
+                })
+                .collect();
+            tasks.spawn(crawl(
+                worker,
+                workers_n,
+                shared.clone(),
+                client,
+                follow_client,
+                locale_clients,
+            ));
+        }
+        // seeds added after the crawl are refused once the receiver is gone
+        let intake = self.added_seeds.1.take().map(|added_seeds| {
+            let follow_client = self.client_builder.clone().build();
+            tokio::spawn(add_seeds(shared.clone(), follow_client, added_seeds))
+        });
+        drop(shared);
+        if let Some(health) = &self.health {
+            health.start();
+        }
+
+        while let Some(_res) = tasks.join_next().await {
+            info!("Worker completed");
+        }
+        if let Some(intake) = intake {
+            intake.abort();
+            let _ = intake.await;
+        }
+        self.control.finish();
+        if let Some(health) = &self.health {
+            health.finish();
+        }
+
+        self.data_store.clone()
+    }
+
+    /// Enqueues the pages of the seeds' sitemaps, returning the alternates listed for them
+    async fn enqueue_sitemaps(&self) -> HashMap<String, Vec<Alternate>> {
+        let client = self.client_builder.clone().build();
+        let sitemap_robots = self.robots.clone().unwrap_or_else(|| {
+            Arc::new(RobotsCache::new(Duration::ZERO, UnreachablePolicy::Allow))
+        });
+        let mut url_frontier_write = self.url_frontier.write().await;
+        let mut alternates = HashMap::new();
+
+        for seed in &self.settings.seeds {
+            for sitemap_url in sitemap::discover(&client, &sitemap_robots, seed).await {
+                if !sitemap_url.alternates.is_empty() {
+                    alternates.insert(sitemap_url.loc.clone(), sitemap_url.alternates);
+                }
+                let sitemap_url = sitemap_url.loc;
+                if self.url_policy.decide(&sitemap_url, seed) != LinkDecision::Enqueue {
+                    url_frontier_write.drop_url(DropReason::Filtered);
+                    continue;
+                }
+                if let Some(sampler) = &self.sampler {
+                    if !sampler.sample(&sitemap_url) {
+                        url_frontier_write.drop_url(DropReason::Budget);
+                        continue;
+                    }
+                }
+                if let Some(robots) = &self.robots {
+                    if !robots.is_allowed(&client, &sitemap_url).await {
+                        url_frontier_write.drop_url(DropReason::Robots);
+                        continue;
+                    }
+                }
+
+                if let Some(event_log) = &self.event_log {
+                    event_log.record(None, EventKind::Enqueue, &sitemap_url, "");
+                }
+                url_frontier_write.enqueue(QueuedUrl::new(sitemap_url, 1));
+            }
+        }
+
+        alternates
+    }
+}
+
+/// What deciding on links changes in the frontier besides the links queued
+#[derive(Default)]
+struct FrontierUpdates {
+    dropped: Vec<DropReason>,
+    /// `Crawl-delay`s of robots.txt files, by host
+    host_delays: Vec<(String, Duration)>,
+}
+
+/// Checks a URL the URL policy enqueues against the other crawl rules: HTTPS upgrade, link
+/// texts, visited pages, sampling, robots.txt, `over_budget`, path quotas and dedupe, in that
+/// order. Seeds added while crawling have no link `texts`. Returns the URL, upgraded to HTTPS.
+async fn decide<T: DataStore>(
+    shared: &Shared<T>,
+    follow_client: &HttpFetch,
+    mut url: String,
+    mut decision: LinkDecision,
+    texts: Option<&[String]>,
+    over_budget: bool,
+    updates: &mut FrontierUpdates,
+) -> (String, LinkDecision) {
+    if let (LinkDecision::Enqueue, Some(https_upgrade)) = (decision, &shared.https_upgrade) {
+        if url.starts_with("http://") && is_internal(&url, &shared.settings.seeds_url_parts) {
+            match https_upgrade.upgrade(follow_client, &url).await {
+                Some(https_url) => url = https_url,
+                None => {
+                    warn!("Could not upgrade to HTTPS: {}", url);
+                    decision = LinkDecision::SkipScheme;
+                }
+            }
+        }
+    }
+
+    if let (LinkDecision::Enqueue, Some(texts)) = (decision, texts) {
+        if !shared.settings.link_text_rules.allows(texts) {
+            info!("Skipped by link text rules: {}", url);
+            updates.dropped.push(DropReason::Filtered);
+            decision = LinkDecision::SkipFiltered;
+        }
+    }
+
+    if decision == LinkDecision::Enqueue && shared.data_store.read().await.has_visited(&url) {
+        decision = LinkDecision::SkipDuplicate;
+    }
+    if let (LinkDecision::Enqueue, Some(sampler)) = (decision, &shared.sampler) {
+        if !sampler.sample(&url) {
+            info!("Left out of the sample: {}", url);
+            decision = LinkDecision::SkipBudget;
+        }
+    }
+    if let (LinkDecision::Enqueue, Some(robots)) = (decision, &shared.robots) {
+        if !robots.is_allowed(follow_client, &url).await {
+            info!("Disallowed by robots.txt: {}", url);
+            updates.dropped.push(DropReason::Robots);
+            decision = LinkDecision::SkipFiltered;
+        } else if let (Some(crawl_delay), Some(host)) = (
+            robots.crawl_delay(follow_client, &url).await,
+            host_with_port(&url),
+        ) {
+            updates.host_delays.push((host, crawl_delay));
+        }
+    }
+
+    if decision == LinkDecision::Enqueue && over_budget {
+        decision = LinkDecision::SkipBudget;
+    }
+    if let (LinkDecision::Enqueue, Some(path_quotas)) = (decision, &shared.path_quotas) {
+        if !path_quotas.claim(&url) {
+            if let Some(quota) = path_quotas.quota_for(&url) {
+                info!("Over the quota of {}: {}", quota, url);
+            }
+            decision = LinkDecision::SkipBudget;
+        }
+    }
+    // last, so that only URLs that are crawled claim their page
+    if let (LinkDecision::Enqueue, Some(deduper)) = (decision, &shared.deduper) {
+        if !deduper.claim(&url) {
+            decision = LinkDecision::SkipDuplicate;
+        }
+    }
+
+    (url, decision)
+}
+
+/// Queues the seeds added through the crawl's handles, deciding on them like on links found on
+/// a page of their own
+async fn add_seeds<T: DataStore + Send + Sync + 'static>(
+    shared: Arc<Shared<T>>,
+    follow_client: HttpFetch,
+    mut added_seeds: mpsc::UnboundedReceiver<AddedSeed>,
+) {
+    while let Some(AddedSeed {
+        url,
+        lease,
+        decided,
+    }) = added_seeds.recv().await
+    {
+        // the workers have stopped, nothing would crawl the seed
+        if shared.is_over_limits() {
+            continue;
+        }
+
+        let (url, decision) = process_link(url.clone(), &url, shared.url_policy.as_ref());
+        let mut updates = FrontierUpdates::default();
+        let (url, decision) = decide(
+            &shared,
+            &follow_client,
+            url,
+            decision,
+            None,
+            false,
+            &mut updates,
+        )
+        .await;
+        info!("Added seed {}: {}", url, decision);
+
+        if decision == LinkDecision::Enqueue {
+            let mut data_store_write = shared.data_store.write().await;
+            data_store_write.queued(&QueuedUrl::new(url.clone(), 0));
+            if let Err(e) = data_store_write.flush() {
+                warn!("Could not persist the added seed {} - {}", url, e);
+            }
+        }
+        shared
+            .update_frontier(None, updates, [(&url, decision)].into_iter(), 0)
+            .await;
+
+        // queued before the lease is dropped, so that the workers don't finish meanwhile
+        drop(lease);
+        let _ = decided.send(decision);
+    }
+}
+
+/// A worker, crawling URLs from the frontier until it is done or a limit is reached
+async fn crawl<T: DataStore + Send + Sync + 'static>(
+    worker: usize,
+    workers_n: usize,
+    shared: Arc<Shared<T>>,
+    client: HttpFetch,
+    follow_client: HttpFetch,
+    locale_clients: Vec<(String, HttpFetch)>,
+) {
+    let settings = shared.settings.clone();
+    let delay = shared.worker_ramp.delay(worker);
+    // a pause is waited out before the first request
+    tokio::select! {
+        _ = tokio::time::sleep(delay) => {}
+        _ = shared.control.aborted() => return,
+    }
+    info!(
+        "Worker {} started after {:.1}s ({}/{} workers started)",
+        worker,
+        delay.as_secs_f64(),
+        shared.worker_ramp.start(),
+        workers_n
+    );
+
+    loop {
+        if shared.is_over_limits() {
+            return;
+        }
+        if shared.control.is_paused() {
+            info!("Crawl paused, worker {} waiting", worker);
+            let _waiting = shared.health.as_deref().map(Health::waiting);
+            shared.control.resumed().await;
+            continue;
+        }
+
+        let wait = settings.crawl_windows.wait();
+        if !wait.is_zero() {
+            info!(
+                "Outside of the crawl windows, worker {} pausing for {:.0}s",
+                worker,
+                wait.as_secs_f64()
+            );
+            if let Some(politeness_log) = &shared.politeness_log {
+                let politeness = Politeness {
+                    host: String::new(),
+                    waited: wait,
+                    delay: Duration::ZERO,
+                    reason: WaitReason::CrawlWindow,
+                };
+                politeness_log.record(None, &politeness);
+            }
+            let _waiting = shared.health.as_deref().map(Health::waiting);
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                // looks at the limits and the pause again
+                _ = shared.control.interrupted() => continue,
+            }
+        }
+
+        // the frontier is only locked to dequeue, the lease keeps the other workers
+        // waiting for the links of this URL rather than exiting while it is crawled
+        let started_at = Instant::now();
+        let next = url_frontier::next_url(
+            &shared.url_frontier,
+            &shared.control,
+            shared.health.as_deref(),
+        )
+        .await;
+        shared
+            .stats
+            .record(Phase::DequeueWait, started_at.elapsed());
+        let Some((
+            QueuedUrl {
+                url: current_url,
+                depth,
+            },
+            politeness,
+            _lease,
+        )) = next
+        else {
+            return;
+        };
+
+        // the claim keeps other workers from fetching the same URL while the store isn't locked
+        if !shared.data_store.write().await.try_claim(&current_url) {
+            shared
+                .url_frontier
+                .write()
+                .await
+                .drop_url(DropReason::Duplicate);
+            continue;
+        }
+        if !shared.reserve_page() {
+            info!("Reached the maximum number of pages, stopping the crawl");
+            shared.data_store.write().await.release_claim(&current_url);
+            return;
+        }
+
+        info!("Visiting URL: {}", current_url);
+        if let Some(politeness_log) = &shared.politeness_log {
+            politeness_log.record(Some(&current_url), &politeness);
+        }
+
+        if let Some(auto_tuner) = &shared.auto_tuner {
+            auto_tuner.acquire().await;
+        }
+        let started_at = Instant::now();
+        shared.record(worker, EventKind::FetchStart, &current_url, "");
+        let response = client
+            .fetch(&current_url)
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|response| match response.unfollowed_redirect() {
+                Some(redirect) if settings.redirect_policy == RedirectPolicy::Error => Err(
+                    format!("redirected to {} ({})", redirect.to, redirect.status),
+                ),
+                _ => Ok(response),
+            });
+        shared.stats.record(Phase::Fetch, started_at.elapsed());
+        match &response {
+            Ok(response) => shared.record(
+                worker,
+                EventKind::FetchEnd,
+                &current_url,
+                &response.status.to_string(),
+            ),
+            Err(e) => shared.record(worker, EventKind::Error, &current_url, e),
+        }
+        let host_delay = shared.url_frontier.read().await.delay_for(&current_url);
+        shared.host_stats.record(
+            &current_url,
+            response
+                .as_ref()
+                .ok()
+                .map(|response| (response.status, response.body.len())),
+            started_at.elapsed(),
+            host_delay,
+        );
+        let is_error = response.as_ref().map_or(true, |response| {
+            response.status >= 500 || response.status == 429
+        });
+        if let Some(auto_tuner) = &shared.auto_tuner {
+            auto_tuner.release(started_at.elapsed(), is_error);
+        }
+        shared.control.progress();
+        if let Some(health) = &shared.health {
+            health.progress();
+        }
+        if let Some(abort_threshold) = &shared.abort_threshold {
+            if abort_threshold.record(is_error) {
+                warn!(
+                    "Aborting the crawl, {}",
+                    abort_threshold.reason().unwrap_or_default()
+                );
+            }
+        }
+        let response = match response {
+            Ok(val) => val,
+            Err(e) => {
+                warn!("Error requesting URL {} - {}", current_url, e);
+                shared.send(CrawlEvent::FetchError {
+                    url: current_url.clone(),
+                    error: e.to_string(),
+                });
+                let mut data_store_write = shared.data_store.write().await;
+                data_store_write.release_claim(&current_url);
+                data_store_write.failed(&current_url);
+                if let Err(e) = data_store_write.flush() {
+                    warn!("Could not persist {} - {}", current_url, e);
+                }
+                continue;
+            }
+        };
+        if shared.byte_budget.add(response.body.len() as u64) {
+            warn!(
+                "Downloaded {}, over the byte budget, stopping the crawl",
+                shared.byte_budget.used()
+            );
+        }
+        if let Some(block) = shared.block_detector.as_ref().and_then(|block_detector| {
+            block_detector.check(&current_url, response.status, &response.body)
+        }) {
+            warn!(
+                "{} looks like it is blocking the crawler ({} on {}), pausing requests to it for {:.0}s",
+                block.host,
+                block.sign,
+                current_url,
+                block.pause.as_secs_f64()
+            );
+            shared.data_store.write().await.release_claim(&current_url);
+            shared.release_page();
+            let mut url_frontier_write = shared.url_frontier.write().await;
+            url_frontier_write.pause_host(block.host, block.pause);
+            // crawled again once the pause is over
+            shared.record(worker, EventKind::Enqueue, &current_url, "");
+            url_frontier_write.enqueue(QueuedUrl::new(current_url, depth));
+            continue;
+        }
+
+        if response.streaming {
+            info!(
+                "Skipping streaming resource {} ({})",
+                current_url,
+                response
+                    .content_type
+                    .as_deref()
+                    .unwrap_or("no Content-Type")
+            );
+            let mut data_store_write = shared.data_store.write().await;
+            data_store_write.try_mark_visited(&current_url);
+            data_store_write.release_claim(&current_url);
+            data_store_write.set_status(&current_url, response.status);
+            data_store_write.set_streaming(&current_url);
+            data_store_write.set_redirects(&current_url, response.redirects);
+            if let Err(e) = data_store_write.flush() {
+                warn!("Could not persist {} - {}", current_url, e);
+            }
+            shared.send(CrawlEvent::PageVisited {
+                url: current_url.clone(),
+                status: response.status,
+                depth,
+                outlinks: vec![],
+            });
+            continue;
+        }
+
+        let started_at = Instant::now();
+        {
+            let mut data_store_write = shared.data_store.write().await;
+            data_store_write.try_mark_visited(&current_url);
+            data_store_write.release_claim(&current_url);
+            data_store_write.set_status(&current_url, response.status);
+            let hash = content_hash(&response.body);
+            if let Some(locale) = settings.locales.first() {
+                let result = LocaleResult {
+                    status: response.status,
+                    url: response.url.clone(),
+                    content_hash: hash.clone(),
+                };
+                data_store_write.set_locale_result(&current_url, locale.clone(), result);
+            }
+            // the first bytes of files of the same format are often the same, they aren't compared
+            match response.partial_content() {
+                Some(partial_content) => {
+                    data_store_write.set_partial_content(&current_url, partial_content)
+                }
+                None => data_store_write.set_content_hash(&current_url, hash),
+            }
+            if let Some(type_mismatch) = response.type_mismatch() {
+                data_store_write.set_type_mismatch(&current_url, type_mismatch);
+            }
+            if let Some(body_sample_kb) = settings.body_sample_kb {
+                data_store_write.set_body_sample(
+                    &current_url,
+                    response.body_sample(body_sample_kb * 1024).to_owned(),
+                );
+            }
+            data_store_write.set_redirects(&current_url, response.redirects.clone());
+        }
+        shared.stats.record(Phase::Store, started_at.elapsed());
+
+        // the page is requested again in the other locales, as far apart as any requests to its host
+        for (locale, locale_client) in &locale_clients {
+            if let Some(politeness_log) = &shared.politeness_log {
+                let politeness = Politeness {
+                    waited: host_delay,
+                    ..politeness.clone()
+                };
+                politeness_log.record(Some(&current_url), &politeness);
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(host_delay) => {}
+                // the other locales are left out of an aborted crawl
+                _ = shared.control.aborted() => break,
+            }
+            let started_at = Instant::now();
+            let locale_response = locale_client.fetch(&current_url).await;
+            shared.stats.record(Phase::Fetch, started_at.elapsed());
+            match locale_response {
+                Ok(locale_response) => {
+                    shared.byte_budget.add(locale_response.body.len() as u64);
+                    let result = LocaleResult {
+                        status: locale_response.status,
+                        content_hash: content_hash(&locale_response.body),
+                        url: locale_response.url,
+                    };
+                    shared.data_store.write().await.set_locale_result(
+                        &current_url,
+                        locale.clone(),
+                        result,
+                    );
+                }
+                Err(e) => warn!(
+                    "Error requesting URL {} in locale {} - {}",
+                    current_url, locale, e
+                ),
+            }
+        }
+
+        let in_scope = settings
+            .scope
+            .parses(is_internal(&current_url, &settings.seeds_url_parts));
+        if !in_scope || !response.is_parseable() {
+            match in_scope {
+                true => info!(
+                    "Not parsing {}, not a document with links ({})",
+                    current_url,
+                    response
+                        .content_type
+                        .as_deref()
+                        .unwrap_or("no Content-Type")
+                ),
+                false => info!(
+                    "Checked external URL: {} ({})",
+                    current_url, response.status
+                ),
+            }
+            // the `Link` header is how documents other than HTML declare their canonical URL
+            if let Some(canonical) = response
+                .header_links("canonical")
+                .next()
+                .filter(|_| in_scope)
+            {
+                shared
+                    .data_store
+                    .write()
+                    .await
+                    .set_canonical(&current_url, canonical.to_owned());
+            }
+            if let Some(parked) = (!in_scope)
+                .then(|| parked::detect(&current_url, &response))
+                .flatten()
+            {
+                info!("{} looks like a parked domain, {}", current_url, parked);
+                shared
+                    .data_store
+                    .write()
+                    .await
+                    .set_parked(&current_url, parked);
+            }
+            shared.send(CrawlEvent::PageVisited {
+                url: current_url.clone(),
+                status: response.status,
+                depth,
+                outlinks: vec![],
+            });
+            if let Err(e) = shared.data_store.write().await.flush() {
+                warn!("Could not persist {} - {}", current_url, e);
+            }
+            continue;
+        }
+
+        let started_at = Instant::now();
+        let search_page = search::detect(&current_url, &response.body);
+        // with the record-only policy, the target of a redirect is an edge of the page
+        let redirected_to = response
+            .unfollowed_redirect()
+            .map(|redirect| redirect.to.clone());
+        // `Link` headers are handled as the `<link>` elements of the page, which win over them
+        let header_canonical = response.header_links("canonical").next().map(str::to_owned);
+        let header_next = response
+            .header_links("next")
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        let mut resources = response
+            .header_links("preload")
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        // the parsed document is not `Send`, so it must not live across an await
+        let (
+            canonical,
+            title,
+            description,
+            mobile,
+            mut urls_found,
+            link_elements,
+            forms,
+            anchor_texts,
+            html_alternates,
+        ) = {
+            let kind = document_kind(response.content_type.as_deref(), response.body.as_bytes());
+            let parser = Parser::for_document(response.body, kind);
+            let mut urls_found = match settings.aggressive_extraction {
+                true => parser.script_links(),
+                false => vec![],
+            };
+            let canonical = parser.canonical().or(header_canonical);
+            let title = parser.title();
+            let description = parser.meta_description();
+            // feeds and other XML are never laid out on a screen
+            let mobile = (kind != DocumentKind::Xml).then(|| parser.mobile_signals());
+            let forms = parser.forms();
+            let html_alternates = parser.alternates();
+            let anchor_texts = match settings.link_text_rules.is_empty() {
+                true => Default::default(),
+                false => parser.anchor_texts(),
+            };
+            let next = parser.head_links("next");
+            let link_elements = parser.link_elements();
+            resources.extend(parser.head_links("preload"));
+            urls_found.extend(parser.all_links());
+            urls_found.extend(next);
+            urls_found.extend(header_next);
+            urls_found.extend(redirected_to);
+            (
+                canonical,
+                title,
+                description,
+                mobile,
+                urls_found,
+                link_elements,
+                forms,
+                anchor_texts,
+                html_alternates,
+            )
+        };
+        {
+            let mut data_store_write = shared.data_store.write().await;
+            if let Some(title) = title {
+                data_store_write.set_title(&current_url, title);
+            }
+            if let Some(description) = description {
+                data_store_write.set_description(&current_url, description);
+            }
+            // error pages are rarely designed for any screen
+            if let (Some(mobile), true) = (mobile, response.status < 400) {
+                data_store_write.set_mobile(&current_url, mobile);
+            }
+            if let Some(has_results) = search_page {
+                data_store_write.set_search_page(&current_url, has_results);
+            }
+            let canonical = canonical.and_then(|canonical| {
+                let resolved = process_url(&canonical, &response.url);
+                if resolved.is_none() {
+                    warn!(
+                        "Skipping the canonical URL {} of {}, it can't be resolved",
+                        canonical, current_url
+                    );
+                }
+                resolved
+            });
+            if let Some(canonical) = canonical {
+                if let Some(deduper) = &shared.deduper {
+                    if !deduper.canonical(&current_url, &canonical) {
+                        // the same page as one already crawled, with the same links
+                        info!(
+                            "Same page as its already crawled canonical URL: {}",
+                            canonical
+                        );
+                        urls_found.clear();
+                    }
+                }
+                data_store_write.set_canonical(&current_url, canonical);
+            }
+            let alternates = shared
+                .sitemap_alternates
+                .get(&current_url)
+                .into_iter()
+                .flatten()
+                .cloned()
+                .chain(alternates::from_header_links(
+                    &response.url,
+                    &response.links,
+                ))
+                .chain(html_alternates.iter().filter_map(|(hreflang, href)| {
+                    Alternate::new(&response.url, hreflang, href, AlternateSource::Html)
+                }))
+                .collect::<Vec<_>>();
+            if !alternates.is_empty() {
+                data_store_write.set_alternates(&current_url, alternates);
+            }
+            if !resources.is_empty() {
+                let resources = resources
+                    .into_iter()
+                    .filter_map(|resource| {
+                        let resolved = process_url(&resource, &response.url);
+                        if resolved.is_none() {
+                            warn!(
+                                "Skipping the resource {} of {}, it can't be resolved",
+                                resource, current_url
+                            );
+                        }
+                        resolved
+                    })
+                    .collect();
+                data_store_write.set_resources(&current_url, resources);
+            }
+            for form in forms {
+                let Some(action) = process_url(&form.action, &response.url) else {
+                    warn!(
+                        "Skipping a form of {}, its action {} can't be resolved",
+                        current_url, form.action
+                    );
+                    continue;
+                };
+                let form = Form { action, ..form };
+                // POST forms are recorded, never submitted
+                if settings.crawl_get_forms {
+                    urls_found.extend(form.empty_submission());
+                }
+                data_store_write.add_form(&current_url, form);
+            }
+        }
+        shared.stats.record(Phase::Parse, started_at.elapsed());
+
+        // HTTPS upgrades and robots.txt lookups go over the network, so decisions are
+        // collected without holding a lock and applied to the store and frontier at once
+        let started_at = Instant::now();
+        let mut links = vec![];
+        let mut updates = FrontierUpdates::default();
+        let mut links_enqueued = 0;
+        let mut sources = vec![];
+        // positions on the page, before the links are reordered
+        let mut positions = HashMap::new();
+        for (i, url) in urls_found.iter().enumerate() {
+            positions.entry(url.clone()).or_insert(i + 1);
+        }
+        if settings.deterministic {
+            urls_found.sort();
+            urls_found.dedup();
+        }
+        if settings.max_links_per_page.is_some() {
+            urls_found = unique_hosts_first(urls_found, &response.url);
+        }
+        for url in urls_found {
+            // links that aren't anchors (frames, forms, ...) have no text, so match no rule
+            let texts = anchor_texts
+                .get(&url)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let element = link_elements.get(&url);
+            let source = LinkSource {
+                position: positions.get(&url).copied().unwrap_or_default(),
+                tag: element.map(|element| element.tag.clone()),
+                rel: element
+                    .map(|element| element.rel.clone())
+                    .unwrap_or_default(),
+            };
+            let (url, decision) = process_link(url, &response.url, shared.url_policy.as_ref());
+            info!("Found URL: {}", url);
+            let link = url.clone();
+            let over_budget = settings.max_depth.is_some_and(|max| depth >= max)
+                || settings
+                    .max_links_per_page
+                    .is_some_and(|max| links_enqueued >= max);
+            let (url, decision) = decide(
+                &shared,
+                &follow_client,
+                url,
+                decision,
+                Some(texts),
+                over_budget,
+                &mut updates,
+            )
+            .await;
+            if decision == LinkDecision::Enqueue {
+                links_enqueued += 1;
+            }
+
+            links.push((link, url, decision));
+            sources.push(source);
+        }
+
+        {
+            let mut data_store_write = shared.data_store.write().await;
+            for ((link, url, decision), source) in links.iter().zip(sources) {
+                data_store_write.add(current_url.clone(), Some(link.clone()));
+                data_store_write.set_link_decision(&current_url, link.clone(), *decision);
+                data_store_write.set_link_source(&current_url, link.clone(), source);
+                if *decision == LinkDecision::Enqueue {
+                    data_store_write.queued(&QueuedUrl::new(url.clone(), depth + 1));
+                }
+            }
+            if let Err(e) = data_store_write.flush() {
+                warn!("Could not persist {} - {}", current_url, e);
+            }
+        }
+
+        shared
+            .update_frontier(
+                Some(worker),
+                updates,
+                links.iter().map(|(_, url, decision)| (url, *decision)),
+                depth + 1,
+            )
+            .await;
+        shared.stats.record(Phase::Enqueue, started_at.elapsed());
+
+        if shared.events.is_some() {
+            let mut outlinks = vec![];
+            for (link, _, decision) in links {
+                outlinks.push(link.clone());
+                shared.send(CrawlEvent::LinkFound {
+                    page: current_url.clone(),
+                    url: link,
+                    decision,
+                });
+            }
+            shared.send(CrawlEvent::PageVisited {
+                url: current_url.clone(),
+                status: response.status,
+                depth,
+                outlinks,
+            });
+        }
+
+        info!("--------------------------------------------");
+    }
+}
+
+#[cfg(test)]
+mod crawler_tests {
+    use wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use std::time::Duration;
+
+    use super::{CrawlEvent, CrawlerBuilder};
+    use crate::{
+        abort::MIN_REQUESTS,
+        concurrency::Workers,
+        data_store::{DataStore, LocaleResult},
+        dedupe::DedupeKey,
+        fetch::RedirectPolicy,
+        fingerprint::content_hash,
+        handle::AddSeedError,
+        link::LinkDecision,
+        policy::{link_text_pattern, LinkTextRules},
+        queue::FrontierPolicy,
+        quota::PathQuota,
+    };
+
+    /// A chain of pages, `/0` linking to `/1` and so on
+    async fn chain_site(pages: usize) -> MockServer {
+        let mock_server = MockServer::start().await;
+        for page in 0..pages {
+            let body = format!("<a href=\"/{}\">next</a>", page + 1);
+            Mock::given(method("GET"))
+                .and(path(format!("/{}", page)))
+                .respond_with(ResponseTemplate::new(200).set_body_string(body))
+                .mount(&mock_server)
+                .await;
+        }
+
+        mock_server
+    }
+
+    fn visited<T: DataStore>(store: &T) -> usize {
+        store
+            .entries()
+            .into_iter()
+            .filter(|(_, entry)| entry.visited)
+            .count()
+    }
+
+    #[tokio::test]
+    async fn crawler_stops_at_max_depth_and_max_pages() {
+        let mock_server = chain_site(5).await;
+        let seed = format!("{}/0", mock_server.uri());
+
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&seed)
+            .delay_s(0)
+            .max_depth(2)
+            .build();
+        let data_store = crawler.run().await;
+        let data_store = data_store.read().await;
+
+        assert_eq!(visited(&*data_store), 3);
+        assert_eq!(
+            data_store
+                .get(&format!("{}/2", mock_server.uri()))
+                .unwrap()
+                .link_decisions[&format!("{}/3", mock_server.uri())],
+            LinkDecision::SkipBudget
+        );
+        assert_eq!(crawler.frontier_metrics().await.dropped_budget, 1);
+
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&seed)
+            .delay_s(0)
+            .max_pages(2)
+            .build();
+        let data_store = crawler.run().await;
+
+        assert_eq!(visited(&*data_store.read().await), 2);
+    }
+
+    #[tokio::test]
+    async fn crawler_sends_events_as_it_crawls() {
+        let mock_server = chain_site(1).await;
+        let uri = mock_server.uri();
+
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&format!("{}/0", uri))
+            .delay_s(0)
+            .build();
+        let mut events = crawler.subscribe();
+        crawler.run().await;
+
+        let mut received = vec![];
+        while let Some(event) = events.recv().await {
+            received.push(event);
+        }
+
+        assert_eq!(
+            received,
+            vec![
+                CrawlEvent::LinkFound {
+                    page: format!("{}/0", uri),
+                    url: format!("{}/1", uri),
+                    decision: LinkDecision::Enqueue,
+                },
+                CrawlEvent::PageVisited {
+                    url: format!("{}/0", uri),
+                    status: 200,
+                    depth: 0,
+                    outlinks: vec![format!("{}/1", uri)],
+                },
+                CrawlEvent::PageVisited {
+                    url: format!("{}/1", uri),
+                    status: 404,
+                    depth: 1,
+                    outlinks: vec![],
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn crawler_records_each_locale_of_a_page() {
+        let mock_server = MockServer::start().await;
+        Mock::given(header("accept-language", "en"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("hello"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(header("accept-language", "fr"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("bonjour"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(header("accept-language", "de"))
+            .respond_with(ResponseTemplate::new(406))
+            .mount(&mock_server)
+            .await;
+        let seed = format!("{}/", mock_server.uri());
+
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&seed)
+            .delay_s(0)
+            .locales(vec!["en".to_owned(), "fr".to_owned(), "de".to_owned()])
+            .build();
+        let data_store = crawler.run().await;
+        let data_store = data_store.read().await;
+        let entry = data_store.get(&seed).unwrap();
+
+        assert_eq!(entry.content_hash, Some(content_hash("hello")));
+        assert_eq!(
+            entry.locales["fr"],
+            LocaleResult {
+                status: 200,
+                url: seed.clone(),
+                content_hash: content_hash("bonjour"),
+            }
+        );
+        assert_eq!(entry.locales["en"].content_hash, content_hash("hello"));
+        assert_eq!(entry.locales["de"].status, 406);
+    }
+
+    #[tokio::test]
+    async fn crawler_pauses_hosts_serving_challenge_pages() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(r#"<a href="/1">1</a><a href="/2">2</a><a href="/3">3</a>"#),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(503).set_body_string("<title>Just a moment...</title>"),
+            )
+            .mount(&mock_server)
+            .await;
+        let seed = format!("{}/", mock_server.uri());
+
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&seed)
+            .delay_s(0)
+            .block_pause(Duration::from_millis(10))
+            // the requests of a paused page don't count against it
+            .max_pages(4)
+            .build();
+        let data_store = crawler.run().await;
+
+        // the first two challenge pages are recorded as they are, the third pauses the host and is
+        // requested again after each pause, then recorded as is
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 7);
+        let data_store = data_store.read().await;
+        for page in 1..=3 {
+            let url = format!("{}/{}", mock_server.uri(), page);
+            assert_eq!(data_store.get(&url).unwrap().status, Some(503));
+        }
+        let blocked_hosts = crawler.block_detector().unwrap().blocked_hosts();
+        assert_eq!(
+            blocked_hosts.values().collect::<Vec<_>>(),
+            vec![
+                "challenge page (`<title>just a moment...</title>`), still blocked after 3 pauses"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn crawler_handles_link_headers_as_link_elements() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string("<p>Page 1</p>")
+                    .insert_header(
+                    "Link",
+                    "</page-1>; rel=canonical, </2>; rel=next, </app.css>; rel=preload; as=style",
+                ),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/2"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<p>Page 2</p>"))
+            .mount(&mock_server)
+            .await;
+        let seed = format!("{}/", mock_server.uri());
+
+        let mut crawler = CrawlerBuilder::new().seed(&seed).delay_s(0).build();
+        let data_store = crawler.run().await;
+
+        let data_store = data_store.read().await;
+        let entry = data_store.get(&seed).unwrap();
+        assert_eq!(
+            entry.canonical,
+            Some(format!("{}/page-1", mock_server.uri()))
+        );
+        assert_eq!(entry.urls_found, vec![format!("{}/2", mock_server.uri())]);
+        assert_eq!(
+            entry.resources,
+            vec![format!("{}/app.css", mock_server.uri())]
+        );
+        assert!(
+            data_store
+                .get(&format!("{}/2", mock_server.uri()))
+                .unwrap()
+                .visited
+        );
+    }
+
+    #[tokio::test]
+    async fn crawler_applies_link_text_rules_to_links_without_text() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<a href="/docs">Docs</a><a href="/blog">Blog</a><iframe src="/frame"></iframe>"#,
+            ))
+            .mount(&mock_server)
+            .await;
+        let seed = format!("{}/", mock_server.uri());
+
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&seed)
+            .delay_s(0)
+            .link_text_rules(LinkTextRules {
+                follow: vec![link_text_pattern("docs").unwrap()],
+                skip: vec![],
+            })
+            .build();
+        let data_store = crawler.run().await;
+
+        let data_store = data_store.read().await;
+        let link_decisions = &data_store.get(&seed).unwrap().link_decisions;
+        assert_eq!(
+            link_decisions[&format!("{}/docs", mock_server.uri())],
+            LinkDecision::Enqueue
+        );
+        for skipped in ["/blog", "/frame"] {
+            assert_eq!(
+                link_decisions[&format!("{}{}", mock_server.uri(), skipped)],
+                LinkDecision::SkipFiltered
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn crawler_skips_urls_it_cannot_resolve() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<link rel="canonical" href="//["><link rel="preload" href="//["><link rel="preload" href="/app.css"><form action="//["></form><a href="/2">2</a>"#,
+            ))
+            .mount(&mock_server)
+            .await;
+        let seed = format!("{}/", mock_server.uri());
+
+        let mut crawler = CrawlerBuilder::new().seed(&seed).delay_s(0).build();
+        let data_store = crawler.run().await;
+
+        let data_store = data_store.read().await;
+        let entry = data_store.get(&seed).unwrap();
+        assert!(entry.visited);
+        assert_eq!(entry.canonical, None);
+        assert!(entry.forms.is_empty());
+        assert_eq!(
+            entry.resources,
+            vec![format!("{}/app.css", mock_server.uri())]
+        );
+        assert!(data_store.has_visited(&format!("{}/2", mock_server.uri())));
+    }
+
+    #[tokio::test]
+    async fn crawler_records_mobile_signals_of_html_pages_only() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<meta name="viewport" content="width=device-width"><a href="/feed.xml">Feed</a>"#,
+            ))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/feed.xml"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                "<rss><channel><item><link>/post</link></item></channel></rss>",
+                "application/rss+xml",
+            ))
+            .mount(&mock_server)
+            .await;
+        let seed = format!("{}/", mock_server.uri());
+
+        let mut crawler = CrawlerBuilder::new().seed(&seed).delay_s(0).build();
+        let data_store = crawler.run().await;
+
+        let data_store = data_store.read().await;
+        let mobile = data_store.get(&seed).unwrap().mobile.clone().unwrap();
+        assert!(mobile.viewport.is_some());
+        let feed = data_store
+            .get(&format!("{}/feed.xml", mock_server.uri()))
+            .unwrap();
+        assert!(feed.visited);
+        assert_eq!(feed.mobile, None);
+    }
+
+    #[tokio::test]
+    async fn crawler_applies_the_redirect_policy() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/"))
+            .respond_with(ResponseTemplate::new(301).insert_header("Location", "/moved"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/moved"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<p>Moved</p>"))
+            .mount(&mock_server)
+            .await;
+        let seed = format!("{}/", mock_server.uri());
+        let moved = format!("{}/moved", mock_server.uri());
+
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&seed)
+            .delay_s(0)
+            .redirect_policy(RedirectPolicy::RecordOnly)
+            .build();
+        let data_store = crawler.run().await;
+
+        // the target is a link of the redirecting page, crawled as a page of its own
+        let data_store = data_store.read().await;
+        let entry = data_store.get(&seed).unwrap();
+        assert_eq!(entry.status, Some(301));
+        assert_eq!(entry.urls_found, vec![moved.clone()]);
+        assert_eq!(data_store.get(&moved).unwrap().status, Some(200));
+
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&seed)
+            .delay_s(0)
+            .redirect_policy(RedirectPolicy::Error)
+            .build();
+        let data_store = crawler.run().await;
+
+        // a failure, the target is neither requested nor recorded
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 3);
+        assert!(data_store.read().await.get(&moved).is_none());
+    }
+
+    #[tokio::test]
+    async fn crawler_aborts_once_over_the_error_rate() {
+        let mock_server = MockServer::start().await;
+        let links = (0..50)
+            .map(|i| format!("<a href=\"/{}\">{}</a>", i, i))
+            .collect::<String>();
+        Mock::given(path("/"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(links))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&mock_server)
+            .await;
+
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&format!("{}/", mock_server.uri()))
+            .delay_s(0)
+            .abort_error_rate(0.5)
+            .build();
+        crawler.run().await;
+
+        // the request that crossed the threshold is the last one
+        assert_eq!(
+            mock_server.received_requests().await.unwrap().len(),
+            MIN_REQUESTS
+        );
+        assert!(crawler.abort_threshold().unwrap().is_aborted());
+    }
+
+    #[tokio::test]
+    async fn crawler_crawls_a_page_once_per_dedupe_key() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<a href="/page?ref=nav">Page</a><a href="/page?ref=footer">Page</a>"#,
+            ))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/page"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<p>Page</p>"))
+            .mount(&mock_server)
+            .await;
+        let seed = format!("{}/", mock_server.uri());
+
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&seed)
+            .delay_s(0)
+            .dedupe_key(DedupeKey::NoQuery)
+            .build();
+        let data_store = crawler.run().await;
+
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 2);
+        let data_store = data_store.read().await;
+        let link_decisions = &data_store.get(&seed).unwrap().link_decisions;
+        assert_eq!(
+            link_decisions[&format!("{}/page?ref=nav", mock_server.uri())],
+            LinkDecision::Enqueue
+        );
+        assert_eq!(
+            link_decisions[&format!("{}/page?ref=footer", mock_server.uri())],
+            LinkDecision::SkipDuplicate
+        );
+    }
+
+    #[tokio::test]
+    async fn crawler_crawls_up_to_the_quota_of_a_path() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<a href="/tag/1">1</a><a href="/tag/2">2</a><a href="/tag/3">3</a><a href="/about">About</a>"#,
+            ))
+            .mount(&mock_server)
+            .await;
+        let seed = format!("{}/", mock_server.uri());
+
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&seed)
+            .delay_s(0)
+            .path_quotas(vec![PathQuota {
+                pattern: "/tag/*".to_owned(),
+                max: 2,
+            }])
+            .build();
+        let data_store = crawler.run().await;
+
+        // the seed, two tags and the about page
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 4);
+        let data_store = data_store.read().await;
+        let link_decisions = &data_store.get(&seed).unwrap().link_decisions;
+        assert_eq!(
+            link_decisions[&format!("{}/tag/3", mock_server.uri())],
+            LinkDecision::SkipBudget
+        );
+        assert_eq!(
+            link_decisions[&format!("{}/about", mock_server.uri())],
+            LinkDecision::Enqueue
+        );
+    }
+
+    #[tokio::test]
+    async fn crawler_counts_urls_linked_from_several_pages_once_against_their_quota() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(r#"<a href="/a">a</a><a href="/tag/1">1</a>"#),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/a"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(r#"<a href="/tag/1">1</a><a href="/tag/2">2</a>"#),
+            )
+            .mount(&mock_server)
+            .await;
+        // still being crawled when /a links it again
+        Mock::given(path("/tag/1"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+            .mount(&mock_server)
+            .await;
+        let seed = format!("{}/", mock_server.uri());
+
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&seed)
+            .delay_s(0)
+            .path_quotas(vec![PathQuota {
+                pattern: "/tag/*".to_owned(),
+                max: 2,
+            }])
+            .build();
+        let data_store = crawler.run().await;
+
+        // the seed, /a and both tags
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 4);
+        let data_store = data_store.read().await;
+        assert!(data_store.has_visited(&format!("{}/tag/2", mock_server.uri())));
+    }
+
+    #[tokio::test]
+    async fn deterministic_crawls_request_pages_in_the_same_order() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<a href="/d">d</a><a href="/b">b</a><a href="/e">e</a><a href="/a">a</a><a href="/c">c</a>"#,
+            ))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/b"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(r#"<a href="/g">g</a><a href="/f">f</a>"#),
+            )
+            .mount(&mock_server)
+            .await;
+        let seed = format!("{}/", mock_server.uri());
+
+        for _ in 0..2 {
+            let mut crawler = CrawlerBuilder::new()
+                .seed(&seed)
+                .delay_s(0)
+                .workers(Workers::Fixed(4))
+                .frontier_policy(FrontierPolicy::Random)
+                .deterministic(true)
+                .build();
+            crawler.run().await;
+        }
+        let paths = mock_server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| request.url.path().to_owned())
+            .collect::<Vec<_>>();
+
+        // the seed and seven links, twice
+        assert_eq!(paths.len(), 16);
+        assert_eq!(paths[..8], paths[8..]);
+    }
+
+    #[tokio::test]
+    async fn crawl_handle_pauses_resumes_and_adds_seeds() {
+        let mock_server = chain_site(3).await;
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&format!("{}/0", mock_server.uri()))
+            .delay_s(0)
+            .build();
+        let handle = crawler.handle();
+        handle.pause();
+
+        let control = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(mock_server.received_requests().await.unwrap().is_empty());
+            let extra = format!("{}/extra", mock_server.uri());
+            assert_eq!(handle.add_seed(&extra).await, Ok(LinkDecision::Enqueue));
+            assert_eq!(
+                handle.add_seed("https://other.example/").await,
+                Ok(LinkDecision::SkipExternal)
+            );
+            assert!(matches!(
+                handle.add_seed("not a url").await,
+                Err(AddSeedError::InvalidUrl(_))
+            ));
+            let stats = handle.stats().await;
+            assert_eq!((stats.queued, stats.paused), (2, true));
+            handle.resume();
+        };
+        tokio::join!(crawler.run(), control);
+        assert_eq!(
+            handle
+                .add_seed(&format!("{}/late", mock_server.uri()))
+                .await,
+            Err(AddSeedError::CrawlDone)
+        );
+
+        // the chain, the 404 it ends with and the added seed
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 5);
+        let stats = handle.stats().await;
+        assert_eq!((stats.requests, stats.queued), (5, 0));
+        assert_eq!(
+            stats.to_string(),
+            "running, 5 requests, 0 queued, 0 in flight"
+        );
+    }
+
+    #[tokio::test]
+    async fn crawl_handle_aborts_the_crawl() {
+        let mock_server = chain_site(3).await;
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&format!("{}/0", mock_server.uri()))
+            .delay_s(0)
+            .build();
+        let handle = crawler.handle();
+        handle.pause();
+
+        let control = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            // wakes up the paused workers, which stop without crawling
+            handle.abort();
+        };
+        tokio::join!(crawler.run(), control);
+
+        assert!(mock_server.received_requests().await.unwrap().is_empty());
+        assert!(handle.stats().await.aborted);
+        assert!(handle.is_done());
+    }
+
+    #[tokio::test]
+    async fn crawl_handle_cuts_politeness_waits_short() {
+        let mock_server = chain_site(3).await;
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&format!("{}/0", mock_server.uri()))
+            .delay_s(3600)
+            .build();
+        let handle = crawler.handle();
+
+        let control = async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            // the worker waits an hour before requesting `/1`
+            handle.pause();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            handle.abort();
+        };
+        let (run, _) = tokio::join!(
+            tokio::time::timeout(Duration::from_secs(5), crawler.run()),
+            control
+        );
+
+        assert!(run.is_ok());
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn crawl_handle_cuts_locale_waits_short() {
+        let mock_server = chain_site(1).await;
+        let seed = format!("{}/0", mock_server.uri());
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&seed)
+            .delay_s(3600)
+            .locales(vec!["en".to_owned(), "fr".to_owned()])
+            .build();
+        let handle = crawler.handle();
+
+        let control = async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            // the worker waits an hour before requesting the page in French
+            handle.abort();
+        };
+        let (run, _) = tokio::join!(
+            tokio::time::timeout(Duration::from_secs(5), crawler.run()),
+            control
+        );
+
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 1);
+        let data_store = run.unwrap();
+        let data_store = data_store.read().await;
+        assert!(!data_store.get(&seed).unwrap().locales.contains_key("fr"));
+    }
+}
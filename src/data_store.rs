@@ -1,29 +1,278 @@
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    path::PathBuf,
+    str::FromStr,
+};
 
-#[derive(Debug, PartialEq)]
+use crate::{
+    alternates::Alternate,
+    export::{self, OutputFormat},
+    fetch::{PartialContent, Redirect},
+    link::{normalise_url, LinkDecision, LinkSource},
+    mime::TypeMismatch,
+    mobile::MobileSignals,
+    parked::ParkedSign,
+    parser::Form,
+    url_frontier::QueuedUrl,
+};
+
+/// Where the crawled pages are kept, other than in memory
+#[derive(Debug, PartialEq, Clone)]
+pub enum StoreBackend {
+    /// An SQLite database file, i.e. `sqlite:crawl.db`
+    Sqlite(PathBuf),
+}
+
+impl FromStr for StoreBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("sqlite", path)) if !path.is_empty() => Ok(StoreBackend::Sqlite(path.into())),
+            _ => Err(format!("`{}` is not a store, i.e. sqlite:crawl.db", s)),
+        }
+    }
+}
+
+impl fmt::Display for StoreBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreBackend::Sqlite(path) => write!(f, "sqlite:{}", path.display()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Sqlite(rusqlite::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Sqlite(e) => write!(f, "{}", e),
+            Error::Json(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl std::convert::From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        Error::Sqlite(err)
+    }
+}
+
+impl std::convert::From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+/// What a page responded with when requested in a given locale
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct LocaleResult {
+    pub status: u16,
+    /// URL the page was served from, after following any redirects
+    pub url: String,
+    pub content_hash: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
 pub struct DataStoreEntry {
     pub visited: bool,
+    /// Links found on the page, without duplicates and in the order they were found
     pub urls_found: Vec<String>,
+    /// Number of times each link appears on the page
+    pub link_counts: BTreeMap<String, usize>,
+    pub status: Option<u16>,
+    pub canonical: Option<String>,
+    pub redirects: Vec<Redirect>,
+    pub content_hash: Option<String>,
+    /// How the body differs from its declared `Content-Type`, when it is missing or wrong
+    #[serde(default)]
+    pub type_mismatch: Option<TypeMismatch>,
+    /// Text of the page's `<title>`
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Content of the page's `<meta name="description">`
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Start of the page body, when body sampling is enabled
+    pub body_sample: Option<String>,
+    /// Forms found on the page, with their action resolved against the page URL
+    pub forms: Vec<Form>,
+    /// What happened to each link found on the page
+    pub link_decisions: BTreeMap<String, LinkDecision>,
+    /// Whether the page has results, when it looks like an internal search or filter page
+    #[serde(default)]
+    pub search_page: Option<bool>,
+    /// The page as served in each locale, when crawling once per locale
+    #[serde(default)]
+    pub locales: BTreeMap<String, LocaleResult>,
+    /// Why the external page looks like a parked or expired domain, when checked
+    #[serde(default)]
+    pub parked: Option<ParkedSign>,
+    /// Language versions of the page declared in its HTML, its `Link` headers and the sitemaps
+    #[serde(default)]
+    pub alternates: Vec<Alternate>,
+    /// Subresources the page asks to preload, in `<link rel="preload">` elements or `Link` headers
+    #[serde(default)]
+    pub resources: Vec<String>,
+    /// Whether the page was skipped as a streaming resource, an endpoint that never stops sending
+    #[serde(default)]
+    pub streaming: bool,
+    /// Size and type of a binary resource of which only the first bytes were fetched, with `--range-probe`
+    #[serde(default)]
+    pub partial_content: Option<PartialContent>,
+    /// Viewport and responsive design signals of an HTML page
+    #[serde(default)]
+    pub mobile: Option<MobileSignals>,
+    /// Element, `rel` and position of each link found on the page, where it was first found
+    #[serde(default)]
+    pub link_sources: BTreeMap<String, LinkSource>,
+}
+
+impl DataStoreEntry {
+    fn add_url_found(&mut self, url: String) {
+        let count = self.link_counts.entry(url.clone()).or_default();
+        *count += 1;
+        if *count == 1 {
+            self.urls_found.push(url);
+        }
+    }
+
+    /// Combines the entry of the same URL from another crawl, i.e. another shard of a distributed
+    /// crawl. Links are unioned, the rest is taken from the entry of a crawl that visited the page.
+    pub fn merge(&mut self, mut other: DataStoreEntry) {
+        if other.visited && !self.visited {
+            std::mem::swap(self, &mut other);
+        }
+
+        for url in other.urls_found {
+            if !self.link_counts.contains_key(&url) {
+                self.urls_found.push(url);
+            }
+        }
+        // the same page crawled twice has the same links, they aren't counted twice
+        for (url, count) in other.link_counts {
+            let self_count = self.link_counts.entry(url).or_default();
+            *self_count = (*self_count).max(count);
+        }
+        for (url, decision) in other.link_decisions {
+            self.link_decisions.entry(url).or_insert(decision);
+        }
+        for (url, source) in other.link_sources {
+            self.link_sources.entry(url).or_insert(source);
+        }
+    }
 }
 
 pub trait DataStore {
     fn new() -> Self;
     fn add(&mut self, key: String, value: Option<String>);
     fn visited(&mut self, key: &str);
+    fn set_status(&mut self, key: &str, status: u16);
+    fn set_canonical(&mut self, key: &str, canonical: String);
+    fn set_redirects(&mut self, key: &str, redirects: Vec<Redirect>);
+    fn set_content_hash(&mut self, key: &str, content_hash: String);
+    fn set_body_sample(&mut self, key: &str, body_sample: String);
+    fn set_title(&mut self, key: &str, title: String);
+    fn set_description(&mut self, key: &str, description: String);
+    fn set_search_page(&mut self, key: &str, has_results: bool);
+    fn set_type_mismatch(&mut self, key: &str, type_mismatch: TypeMismatch);
+    fn set_parked(&mut self, key: &str, parked: ParkedSign);
+    fn set_alternates(&mut self, key: &str, alternates: Vec<Alternate>);
+    fn set_resources(&mut self, key: &str, resources: Vec<String>);
+    fn set_streaming(&mut self, key: &str);
+    fn set_partial_content(&mut self, key: &str, partial_content: PartialContent);
+    fn set_mobile(&mut self, key: &str, mobile: MobileSignals);
+    fn add_form(&mut self, key: &str, form: Form);
+    fn set_link_decision(&mut self, key: &str, url: String, decision: LinkDecision);
+    fn set_link_source(&mut self, key: &str, url: String, source: LinkSource);
+    fn set_locale_result(&mut self, key: &str, locale: String, result: LocaleResult);
     fn has_visited(&self, key: &str) -> bool;
+    /// Marks the key as visited, adding it if needed, unless it already was.
+    /// Returns whether this call marked it, i.e. whether the caller should fetch it.
+    fn try_mark_visited(&mut self, key: &str) -> bool;
+    /// Claims a URL that is neither visited nor being fetched by another worker, by its normalised form.
+    /// Returns whether the claim succeeded, i.e. whether the caller should fetch it.
+    fn try_claim(&mut self, key: &str) -> bool;
+    /// Releases a claim once the URL was fetched, or so that it can be retried after a failure
+    fn release_claim(&mut self, key: &str);
     fn exists(&self, key: &str) -> bool;
     fn get(&self, key: &str) -> Option<&DataStoreEntry>;
+    fn entries(&self) -> Vec<(&String, &DataStoreEntry)>;
+
+    /// Records a URL added to the frontier, so that a resumed crawl queues it again until it is visited
+    fn queued(&mut self, _url: &QueuedUrl) {}
+
+    /// Records a failed fetch of a URL, so that a resumed crawl backs off from it
+    fn failed(&mut self, _key: &str) {}
+
+    /// Writes the changes made since the last flush to persistent storage, a no-op for in-memory stores
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// The stored pages in a machine readable format
+    fn export(&self, format: OutputFormat) -> Result<String, export::Error>
+    where
+        Self: Sized,
+    {
+        export::export(self, format)
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Store {
     data: HashMap<String, DataStoreEntry>,
+    /// Normalised URLs currently being fetched
+    in_flight: HashSet<String>,
+}
+
+impl Store {
+    /// Adds or replaces a whole entry, i.e. one loaded from persistent storage
+    pub fn insert(&mut self, key: String, entry: DataStoreEntry) {
+        self.data.insert(key, entry);
+    }
+
+    /// Adds a whole entry, merging it into the one already stored for the key if any
+    pub fn merge(&mut self, key: String, entry: DataStoreEntry) {
+        match self.data.get_mut(&key) {
+            Some(stored) => stored.merge(entry),
+            None => self.insert(key, entry),
+        }
+    }
+
+    /// Keeps the entries for which `keep` returns true, returning the keys of the removed ones.
+    /// `keep` may also change the entries it keeps.
+    pub fn retain(
+        &mut self,
+        mut keep: impl FnMut(&str, &mut DataStoreEntry) -> bool,
+    ) -> Vec<String> {
+        let mut removed = vec![];
+        self.data.retain(|key, entry| {
+            let kept = keep(key, entry);
+            if !kept {
+                removed.push(key.clone());
+            }
+            kept
+        });
+
+        removed
+    }
 }
 
 impl DataStore for Store {
     fn new() -> Self {
         Store {
             data: HashMap::<String, DataStoreEntry>::new(),
+            in_flight: HashSet::new(),
         }
     }
 
@@ -32,23 +281,17 @@ impl DataStore for Store {
 
         if let Some(item) = item {
             if let Some(value) = value {
-                return item.urls_found.push(value);
+                return item.add_url_found(value);
             }
         }
 
-        self.data.insert(
-            key.clone(),
-            DataStoreEntry {
-                visited: false,
-                urls_found: vec![],
-            },
-        );
+        self.data.insert(key.clone(), DataStoreEntry::default());
 
         if let Some(value) = value {
             let item = self.data.get_mut(&key);
 
             if let Some(item) = item {
-                item.urls_found.push(value)
+                item.add_url_found(value)
             }
         }
     }
@@ -64,6 +307,10 @@ impl DataStore for Store {
         self.data.get(key)
     }
 
+    fn entries(&self) -> Vec<(&String, &DataStoreEntry)> {
+        self.data.iter().collect()
+    }
+
     fn visited(&mut self, key: &str) {
         let item = self.data.get_mut(key);
 
@@ -72,6 +319,172 @@ impl DataStore for Store {
         }
     }
 
+    fn set_status(&mut self, key: &str, status: u16) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.status = Some(status)
+        }
+    }
+
+    fn set_canonical(&mut self, key: &str, canonical: String) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.canonical = Some(canonical)
+        }
+    }
+
+    fn set_redirects(&mut self, key: &str, redirects: Vec<Redirect>) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.redirects = redirects
+        }
+    }
+
+    fn set_content_hash(&mut self, key: &str, content_hash: String) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.content_hash = Some(content_hash)
+        }
+    }
+
+    fn set_body_sample(&mut self, key: &str, body_sample: String) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.body_sample = Some(body_sample)
+        }
+    }
+
+    fn set_title(&mut self, key: &str, title: String) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.title = Some(title)
+        }
+    }
+
+    fn set_description(&mut self, key: &str, description: String) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.description = Some(description)
+        }
+    }
+
+    fn set_search_page(&mut self, key: &str, has_results: bool) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.search_page = Some(has_results)
+        }
+    }
+
+    fn set_type_mismatch(&mut self, key: &str, type_mismatch: TypeMismatch) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.type_mismatch = Some(type_mismatch)
+        }
+    }
+
+    fn set_parked(&mut self, key: &str, parked: ParkedSign) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.parked = Some(parked)
+        }
+    }
+
+    fn set_alternates(&mut self, key: &str, alternates: Vec<Alternate>) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.alternates = alternates
+        }
+    }
+
+    fn set_resources(&mut self, key: &str, resources: Vec<String>) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.resources = resources
+        }
+    }
+
+    fn set_streaming(&mut self, key: &str) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.streaming = true
+        }
+    }
+
+    fn set_partial_content(&mut self, key: &str, partial_content: PartialContent) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.partial_content = Some(partial_content)
+        }
+    }
+
+    fn set_mobile(&mut self, key: &str, mobile: MobileSignals) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.mobile = Some(mobile)
+        }
+    }
+
+    fn add_form(&mut self, key: &str, form: Form) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.forms.push(form)
+        }
+    }
+
+    fn set_link_decision(&mut self, key: &str, url: String, decision: LinkDecision) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.link_decisions.insert(url, decision);
+        }
+    }
+
+    fn set_link_source(&mut self, key: &str, url: String, source: LinkSource) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.link_sources.entry(url).or_insert(source);
+        }
+    }
+
+    fn set_locale_result(&mut self, key: &str, locale: String, result: LocaleResult) {
+        let item = self.data.get_mut(key);
+
+        if let Some(item) = item {
+            item.locales.insert(locale, result);
+        }
+    }
+
+    fn try_mark_visited(&mut self, key: &str) -> bool {
+        let item = self.data.entry(key.to_owned()).or_default();
+
+        !std::mem::replace(&mut item.visited, true)
+    }
+
+    fn try_claim(&mut self, key: &str) -> bool {
+        !self.has_visited(key) && self.in_flight.insert(normalise_url(key))
+    }
+
+    fn release_claim(&mut self, key: &str) {
+        self.in_flight.remove(&normalise_url(key));
+    }
+
     fn has_visited(&self, key: &str) -> bool {
         if let Some(key) = self.data.get(key) {
             return key.visited;
@@ -82,9 +495,11 @@ impl DataStore for Store {
 
 #[cfg(test)]
 mod data_store_tests {
+    use std::collections::BTreeMap;
+
     use crate::data_store::DataStoreEntry;
 
-    use super::{DataStore, Store};
+    use super::{DataStore, Store, StoreBackend};
 
     #[test]
     fn data_store_adds_key_and_value_correctly() {
@@ -99,7 +514,9 @@ mod data_store_tests {
             s.get(&key),
             Some(&DataStoreEntry {
                 visited: false,
-                urls_found: vec![val]
+                urls_found: vec![val.clone()],
+                link_counts: BTreeMap::from([(val, 1)]),
+                ..Default::default()
             })
         );
     }
@@ -119,11 +536,28 @@ mod data_store_tests {
             s.get(&key),
             Some(&DataStoreEntry {
                 visited: false,
-                urls_found: vec![val, val2]
+                urls_found: vec![val.clone(), val2.clone()],
+                link_counts: BTreeMap::from([(val, 1), (val2, 1)]),
+                ..Default::default()
             })
         );
     }
 
+    #[test]
+    fn data_store_counts_repeated_links_instead_of_duplicating_them() {
+        let mut s = Store::new();
+        let key = "key".to_owned();
+
+        s.add(key.clone(), Some("a".to_owned()));
+        s.add(key.clone(), Some("b".to_owned()));
+        s.add(key.clone(), Some("a".to_owned()));
+
+        let entry = s.get(&key).unwrap();
+        assert_eq!(entry.urls_found, vec!["a", "b"]);
+        assert_eq!(entry.link_counts.get("a"), Some(&2));
+        assert_eq!(entry.link_counts.get("b"), Some(&1));
+    }
+
     #[test]
     fn data_store_adds_key_without_value_correctly() {
         let mut s = Store::new();
@@ -136,7 +570,8 @@ mod data_store_tests {
             s.get(&key),
             Some(&DataStoreEntry {
                 visited: false,
-                urls_found: vec![]
+                urls_found: vec![],
+                ..Default::default()
             })
         );
     }
@@ -163,4 +598,104 @@ mod data_store_tests {
 
         assert!(s.has_visited(&key.clone()));
     }
+
+    #[test]
+    fn data_store_try_mark_visited_only_succeeds_once() {
+        let mut s = Store::new();
+        let key = "key".to_owned();
+
+        s.add(key.clone(), Some("val".to_owned()));
+
+        assert!(s.try_mark_visited(&key));
+        assert!(!s.try_mark_visited(&key));
+        assert!(s.has_visited(&key));
+        assert_eq!(s.get(&key).unwrap().urls_found, vec!["val"]);
+        assert!(s.try_mark_visited("new"));
+    }
+
+    #[test]
+    fn data_store_try_claim_rejects_urls_in_flight_until_released() {
+        let mut s = Store::new();
+
+        assert!(s.try_claim("https://a.com/page"));
+        assert!(!s.try_claim("HTTPS://A.COM:443/page"));
+
+        s.release_claim("https://a.com/page");
+        assert!(s.try_claim("https://a.com/page"));
+
+        s.try_mark_visited("https://a.com/page");
+        s.release_claim("https://a.com/page");
+        assert!(!s.try_claim("https://a.com/page"));
+    }
+
+    #[test]
+    fn data_store_sets_status_of_existing_key() {
+        let mut s = Store::new();
+        let key = "key".to_owned();
+
+        s.add(key.clone(), None);
+        s.set_status(&key, 404);
+
+        assert_eq!(s.get(&key).unwrap().status, Some(404));
+    }
+
+    #[test]
+    fn data_store_merge_unions_links_and_prefers_visited_entries() {
+        let mut shard = Store::new();
+        shard.add(
+            "https://a.com/".to_owned(),
+            Some("https://a.com/1".to_owned()),
+        );
+        shard.add(
+            "https://a.com/".to_owned(),
+            Some("https://a.com/1".to_owned()),
+        );
+        let mut other_shard = Store::new();
+        other_shard.add(
+            "https://a.com/".to_owned(),
+            Some("https://a.com/2".to_owned()),
+        );
+        other_shard.add(
+            "https://a.com/".to_owned(),
+            Some("https://a.com/1".to_owned()),
+        );
+        other_shard.try_mark_visited("https://a.com/");
+        other_shard.set_status("https://a.com/", 200);
+
+        let mut merged = Store::new();
+        for store in [&shard, &other_shard] {
+            for (key, entry) in store.entries() {
+                merged.merge(key.clone(), entry.clone());
+            }
+        }
+        let entry = merged.get("https://a.com/").unwrap();
+
+        assert!(entry.visited);
+        assert_eq!(entry.status, Some(200));
+        assert_eq!(
+            entry.urls_found,
+            vec!["https://a.com/2".to_owned(), "https://a.com/1".to_owned()]
+        );
+        assert_eq!(
+            entry.link_counts,
+            BTreeMap::from([
+                ("https://a.com/1".to_owned(), 2),
+                ("https://a.com/2".to_owned(), 1)
+            ])
+        );
+    }
+
+    #[test]
+    fn store_backend_parses_sqlite_paths() {
+        assert_eq!(
+            "sqlite:crawl.db".parse(),
+            Ok(StoreBackend::Sqlite("crawl.db".into()))
+        );
+        assert_eq!(
+            StoreBackend::Sqlite("/tmp/crawl.db".into()).to_string(),
+            "sqlite:/tmp/crawl.db"
+        );
+        assert!("sqlite:".parse::<StoreBackend>().is_err());
+        assert!("sled:crawl".parse::<StoreBackend>().is_err());
+    }
 }
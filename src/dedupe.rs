@@ -0,0 +1,139 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Mutex,
+};
+use url::Url;
+
+/// What makes two URLs the same page for the visited check
+#[derive(clap::ValueEnum, PartialEq, Debug, Clone, Copy, Default)]
+pub enum DedupeKey {
+    /// The URL as found, only identical URLs are the same page
+    #[default]
+    Url,
+    /// The normalised URL, without its fragment and with its query parameters sorted
+    Normalized,
+    /// The normalised URL without its query, for sites that only use it for tracking
+    NoQuery,
+    /// The normalised URL, and a page is the same as the canonical URL it declares
+    Canonical,
+}
+
+impl DedupeKey {
+    /// Key of a URL, the same for URLs that are the same page
+    pub fn key(&self, url: &str) -> String {
+        if *self == DedupeKey::Url {
+            return url.to_owned();
+        }
+        let Ok(mut parsed) = Url::parse(url) else {
+            return url.to_owned();
+        };
+
+        parsed.set_fragment(None);
+        match self {
+            DedupeKey::NoQuery => parsed.set_query(None),
+            _ => {
+                let mut pairs = parsed
+                    .query_pairs()
+                    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                    .collect::<Vec<_>>();
+                pairs.sort();
+                match pairs.is_empty() {
+                    true => parsed.set_query(None),
+                    false => {
+                        parsed.query_pairs_mut().clear().extend_pairs(pairs);
+                    }
+                }
+            }
+        }
+
+        parsed.to_string()
+    }
+}
+
+/// Visited check on [`DedupeKey`]s, on top of the data store's check of the URLs themselves
+#[derive(Debug)]
+pub struct Deduper {
+    key: DedupeKey,
+    /// The first URL claimed for each key
+    seen: Mutex<HashMap<String, String>>,
+}
+
+impl Deduper {
+    pub fn new(key: DedupeKey) -> Self {
+        Deduper {
+            key,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Claims the page of a URL, returning whether it is the first URL of its page to be, i.e.
+    /// whether it should be crawled
+    pub fn claim(&self, url: &str) -> bool {
+        match self.seen.lock().unwrap().entry(self.key.key(url)) {
+            Entry::Occupied(first) => first.get() == url,
+            Entry::Vacant(entry) => {
+                entry.insert(url.to_owned());
+                true
+            }
+        }
+    }
+
+    /// Records that a crawled page declares `canonical` as its URL, returning whether it is the
+    /// first page to be that canonical URL. Only the canonical key looks at canonical URLs.
+    pub fn canonical(&self, url: &str, canonical: &str) -> bool {
+        if self.key != DedupeKey::Canonical {
+            return true;
+        }
+
+        match self.seen.lock().unwrap().entry(self.key.key(canonical)) {
+            Entry::Occupied(first) => first.get() == url,
+            Entry::Vacant(entry) => {
+                entry.insert(url.to_owned());
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod dedupe_tests {
+    use super::{DedupeKey, Deduper};
+
+    #[test]
+    fn dedupe_key_strictness() {
+        let url = "HTTPS://a.com:443/page?b=2&a=1#top";
+
+        assert_eq!(DedupeKey::Url.key(url), url);
+        assert_eq!(DedupeKey::Normalized.key(url), "https://a.com/page?a=1&b=2");
+        assert_eq!(DedupeKey::NoQuery.key(url), "https://a.com/page");
+        assert_eq!(DedupeKey::Normalized.key("not a url"), "not a url");
+    }
+
+    #[test]
+    fn deduper_claims_a_page_once_across_its_urls() {
+        let deduper = Deduper::new(DedupeKey::NoQuery);
+
+        assert!(deduper.claim("https://a.com/page?utm_source=x"));
+        // the same URL claimed again, i.e. by a resumed crawl, is still the first
+        assert!(deduper.claim("https://a.com/page?utm_source=x"));
+        assert!(!deduper.claim("https://a.com/page"));
+        assert!(!deduper.claim("https://a.com/page#section"));
+        assert!(deduper.claim("https://a.com/other"));
+    }
+
+    #[test]
+    fn deduper_treats_a_page_and_its_canonical_url_as_the_same_page() {
+        let deduper = Deduper::new(DedupeKey::Canonical);
+
+        assert!(deduper.claim("https://a.com/p?id=1"));
+        assert!(deduper.canonical("https://a.com/p?id=1", "https://a.com/product"));
+        assert!(!deduper.claim("https://a.com/product"));
+
+        assert!(deduper.claim("https://a.com/p?id=1&ref=home"));
+        assert!(!deduper.canonical("https://a.com/p?id=1&ref=home", "https://a.com/product"));
+
+        let deduper = Deduper::new(DedupeKey::Normalized);
+        assert!(deduper.canonical("https://a.com/p?id=1", "https://a.com/product"));
+        assert!(deduper.claim("https://a.com/product"));
+    }
+}
@@ -0,0 +1,287 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+/// Politeness delays longer than this are taken as a mistake in the formula
+const MAX_DELAY_S: f64 = 3600.0;
+
+/// What a delay formula can refer to
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Variable {
+    /// The host's delay without the formula, `--delay` or its robots.txt `Crawl-delay`, in seconds
+    Delay,
+    /// Links followed from a seed to reach the URL
+    Depth,
+    /// Requests already made to the host
+    Pages,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Min,
+    Max,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Expr {
+    Number(f64),
+    Variable(Variable),
+    Neg(Box<Expr>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, delay: f64, depth: f64, pages: f64) -> f64 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Variable(Variable::Delay) => delay,
+            Expr::Variable(Variable::Depth) => depth,
+            Expr::Variable(Variable::Pages) => pages,
+            Expr::Neg(expr) => -expr.eval(delay, depth, pages),
+            Expr::Binary(operator, left, right) => {
+                let (left, right) = (
+                    left.eval(delay, depth, pages),
+                    right.eval(delay, depth, pages),
+                );
+                match operator {
+                    Operator::Add => left + right,
+                    Operator::Sub => left - right,
+                    Operator::Mul => left * right,
+                    Operator::Div => left / right,
+                    Operator::Min => left.min(right),
+                    Operator::Max => left.max(right),
+                }
+            }
+        }
+    }
+}
+
+/// Recursive descent parser of the formula grammar:
+/// `expr = term (('+' | '-') term)*`, `term = factor (('*' | '/') factor)*` and
+/// `factor = '-' factor | number | variable | ('min' | 'max') '(' expr ',' expr ')' | '(' expr ')'`
+struct FormulaParser<'a> {
+    formula: &'a str,
+    position: usize,
+}
+
+impl<'a> FormulaParser<'a> {
+    fn peek(&mut self) -> Option<char> {
+        let rest = &self.formula[self.position..];
+        let trimmed = rest.trim_start();
+        self.position += rest.len() - trimmed.len();
+        trimmed.chars().next()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.position += 1;
+                Ok(())
+            }
+            _ => Err(self.unexpected(&format!("`{}`", expected))),
+        }
+    }
+
+    fn unexpected(&mut self, expected: &str) -> String {
+        match self.peek() {
+            Some(_) => format!(
+                "expected {} at `{}`",
+                expected,
+                &self.formula[self.position..]
+            ),
+            None => format!("expected {} at the end", expected),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut expr = self.term()?;
+        while let Some(operator @ ('+' | '-')) = self.peek() {
+            self.position += 1;
+            let operator = match operator {
+                '+' => Operator::Add,
+                _ => Operator::Sub,
+            };
+            expr = Expr::Binary(operator, Box::new(expr), Box::new(self.term()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut expr = self.factor()?;
+        while let Some(operator @ ('*' | '/')) = self.peek() {
+            self.position += 1;
+            let operator = match operator {
+                '*' => Operator::Mul,
+                _ => Operator::Div,
+            };
+            expr = Expr::Binary(operator, Box::new(expr), Box::new(self.factor()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn factor(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some('-') => {
+                self.position += 1;
+                Ok(Expr::Neg(Box::new(self.factor()?)))
+            }
+            Some('(') => {
+                self.position += 1;
+                let expr = self.expr()?;
+                self.expect(')')?;
+                Ok(expr)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let number = self.take_while(|c| c.is_ascii_digit() || c == '.');
+                number
+                    .parse()
+                    .map(Expr::Number)
+                    .map_err(|_| format!("`{}` is not a number", number))
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                let variable = match name {
+                    "delay" => Variable::Delay,
+                    "depth" => Variable::Depth,
+                    "pages" => Variable::Pages,
+                    "min" | "max" => {
+                        self.expect('(')?;
+                        let left = self.expr()?;
+                        self.expect(',')?;
+                        let right = self.expr()?;
+                        self.expect(')')?;
+                        let operator = match name {
+                            "min" => Operator::Min,
+                            _ => Operator::Max,
+                        };
+                        return Ok(Expr::Binary(operator, Box::new(left), Box::new(right)));
+                    }
+                    name => {
+                        return Err(format!(
+                            "unknown name `{}`, expected delay, depth, pages, min or max",
+                            name
+                        ))
+                    }
+                };
+                Ok(Expr::Variable(variable))
+            }
+            _ => Err(self.unexpected("a number, a name or `(`")),
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let rest = &self.formula[self.position..];
+        let end = rest.find(|c| !predicate(c)).unwrap_or(rest.len());
+        self.position += end;
+
+        &rest[..end]
+    }
+}
+
+/// Politeness delay of a request as a formula of the host's delay, the depth of the URL and the
+/// requests already made to the host, i.e. `delay * (1 + depth / 4)` or `min(delay + pages / 500, 10)`,
+/// to slow down deep in a site or progressively back off on very large hosts
+#[derive(Debug, PartialEq, Clone)]
+pub struct DelayFormula {
+    formula: String,
+    expr: Expr,
+}
+
+impl DelayFormula {
+    /// The delay before a request at `depth` to a host already requested `pages` times, the host's
+    /// delay being `delay`. Falls back to `delay` when the formula has no finite result.
+    pub fn delay(&self, delay: Duration, depth: usize, pages: usize) -> Duration {
+        let seconds = self
+            .expr
+            .eval(delay.as_secs_f64(), depth as f64, pages as f64);
+
+        match seconds.is_finite() {
+            true => Duration::from_secs_f64(seconds.clamp(0.0, MAX_DELAY_S)),
+            false => delay,
+        }
+    }
+}
+
+impl FromStr for DelayFormula {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = FormulaParser {
+            formula: s,
+            position: 0,
+        };
+        let expr = parser.expr()?;
+        if parser.peek().is_some() {
+            return Err(parser.unexpected("an operator"));
+        }
+
+        Ok(DelayFormula {
+            formula: s.to_owned(),
+            expr,
+        })
+    }
+}
+
+impl fmt::Display for DelayFormula {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.formula)
+    }
+}
+
+#[cfg(test)]
+mod delay_formula_tests {
+    use std::time::Duration;
+
+    use super::DelayFormula;
+
+    fn delay(formula: &str, delay_s: u64, depth: usize, pages: usize) -> Duration {
+        formula
+            .parse::<DelayFormula>()
+            .unwrap()
+            .delay(Duration::from_secs(delay_s), depth, pages)
+    }
+
+    #[test]
+    fn delay_formula_scales_with_depth_and_pages() {
+        assert_eq!(
+            delay("delay * (1 + depth / 4)", 2, 2, 0),
+            Duration::from_secs(3)
+        );
+        assert_eq!(
+            delay("min(delay + pages / 500, 10)", 2, 0, 1000),
+            Duration::from_secs(4)
+        );
+        assert_eq!(
+            delay("min(delay + pages / 500, 10)", 2, 0, 100_000),
+            Duration::from_secs(10)
+        );
+        assert_eq!(delay("2 - -1 * 3", 0, 0, 0), Duration::from_secs(5));
+        // never negative, and the host's delay when the formula has no result
+        assert_eq!(delay("delay - 5", 2, 0, 0), Duration::ZERO);
+        assert_eq!(delay("delay / pages", 2, 0, 0), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn delay_formula_rejects_invalid_formulas() {
+        assert_eq!(
+            "delay * width".parse::<DelayFormula>(),
+            Err("unknown name `width`, expected delay, depth, pages, min or max".to_owned())
+        );
+        assert_eq!(
+            "delay *".parse::<DelayFormula>(),
+            Err("expected a number, a name or `(` at the end".to_owned())
+        );
+        assert_eq!(
+            "(delay + 1".parse::<DelayFormula>(),
+            Err("expected `)` at the end".to_owned())
+        );
+        assert_eq!(
+            "delay depth".parse::<DelayFormula>(),
+            Err("expected an operator at `depth`".to_owned())
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+};
+use tokio::sync::{mpsc, Semaphore};
+use url::Url;
+
+#[cfg(feature = "doh")]
+use crate::doh::DohResolver;
+
+/// Resolved addresses shared between the prefetcher and the HTTP clients.
+/// Entries are kept for the whole crawl, record TTLs are not honoured.
+#[derive(Clone, Default)]
+pub struct DnsCache {
+    entries: Arc<Mutex<HashMap<String, Vec<SocketAddr>>>>,
+    #[cfg(feature = "doh")]
+    doh: Option<Arc<DohResolver>>,
+}
+
+impl DnsCache {
+    pub fn new() -> Self {
+        DnsCache::default()
+    }
+
+    /// A cache resolving hosts with DNS over HTTPS instead of the system resolver
+    #[cfg(feature = "doh")]
+    pub fn with_doh(doh: DohResolver) -> Self {
+        DnsCache {
+            doh: Some(Arc::new(doh)),
+            ..DnsCache::default()
+        }
+    }
+
+    pub fn get(&self, host: &str) -> Option<Vec<SocketAddr>> {
+        self.entries.lock().unwrap().get(host).cloned()
+    }
+
+    async fn lookup(&self, host: &str) -> Result<Vec<SocketAddr>, io::Error> {
+        if let Some(addrs) = self.get(host) {
+            return Ok(addrs);
+        }
+
+        let addrs = self.resolve_host(host).await?;
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(host.to_owned(), addrs.clone());
+
+        Ok(addrs)
+    }
+
+    #[cfg(feature = "doh")]
+    async fn resolve_host(&self, host: &str) -> Result<Vec<SocketAddr>, io::Error> {
+        match &self.doh {
+            Some(doh) => Ok(doh
+                .lookup(host)
+                .await?
+                .into_iter()
+                .map(|address| SocketAddr::new(address, 0))
+                .collect()),
+            None => Ok(tokio::net::lookup_host((host, 0)).await?.collect()),
+        }
+    }
+
+    #[cfg(not(feature = "doh"))]
+    async fn resolve_host(&self, host: &str) -> Result<Vec<SocketAddr>, io::Error> {
+        Ok(tokio::net::lookup_host((host, 0)).await?.collect())
+    }
+
+    /// Resolves the host ahead of its first request, errors are left for the request to report
+    pub async fn prefetch(&self, host: &str) {
+        let _ = self.lookup(host).await;
+    }
+}
+
+impl Resolve for DnsCache {
+    fn resolve(&self, name: Name) -> Resolving {
+        let cache = self.clone();
+
+        Box::pin(async move {
+            let addrs = cache.lookup(name.as_str()).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Background task resolving the hosts of enqueued URLs, bounded in both
+/// the number of hosts waiting and the number of concurrent lookups
+pub struct DnsPrefetcher {
+    sender: mpsc::Sender<String>,
+    seen: Mutex<HashSet<String>>,
+}
+
+impl DnsPrefetcher {
+    pub fn spawn(cache: DnsCache, capacity: usize, concurrency: usize) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<String>(capacity);
+        let semaphore = Arc::new(Semaphore::new(concurrency));
+
+        tokio::spawn(async move {
+            while let Some(host) = receiver.recv().await {
+                let Ok(permit) = semaphore.clone().acquire_owned().await else {
+                    break;
+                };
+                let cache = cache.clone();
+
+                tokio::spawn(async move {
+                    cache.prefetch(&host).await;
+                    drop(permit);
+                });
+            }
+        });
+
+        DnsPrefetcher {
+            sender,
+            seen: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Queues the URL's host for resolution, without waiting - hosts are skipped when the queue is full
+    pub fn prefetch(&self, url: &str) {
+        let Some(host) = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_owned()))
+        else {
+            return;
+        };
+
+        if host.parse::<IpAddr>().is_ok() || !self.seen.lock().unwrap().insert(host.clone()) {
+            return;
+        }
+
+        if self.sender.try_send(host.clone()).is_err() {
+            // allow the host to be queued again later
+            self.seen.lock().unwrap().remove(&host);
+        }
+    }
+}
+
+#[cfg(test)]
+mod dns_tests {
+    use std::time::Duration;
+
+    use super::{DnsCache, DnsPrefetcher};
+
+    #[tokio::test]
+    async fn prefetch_caches_resolved_addresses() {
+        let cache = DnsCache::new();
+
+        cache.prefetch("localhost").await;
+
+        assert!(cache
+            .get("localhost")
+            .is_some_and(|addrs| !addrs.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn dns_prefetcher_resolves_hosts_of_queued_urls() {
+        let cache = DnsCache::new();
+        let prefetcher = DnsPrefetcher::spawn(cache.clone(), 8, 2);
+
+        prefetcher.prefetch("http://localhost:8080/page");
+        prefetcher.prefetch("http://127.0.0.1/page");
+
+        for _ in 0..50 {
+            if cache.get("localhost").is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        assert!(cache.get("localhost").is_some());
+        assert!(cache.get("127.0.0.1").is_none());
+    }
+}
@@ -0,0 +1,239 @@
+use reqwest::{header::CONTENT_TYPE, Client};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+use url::Url;
+
+const DNS_MESSAGE: &str = "application/dns-message";
+const DOH_TIMEOUT: Duration = Duration::from_secs(10);
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+const RCODE_NXDOMAIN: u16 = 3;
+
+/// Resolves hosts with DNS over HTTPS (RFC 8484), for environments where the system resolver is
+/// unreliable or filtered. The DoH server's own host is resolved by the system, so an address,
+/// i.e. `https://1.1.1.1/dns-query`, avoids depending on it at all.
+#[derive(Debug, Clone)]
+pub struct DohResolver {
+    client: Client,
+    url: Url,
+}
+
+impl DohResolver {
+    pub fn new(url: Url) -> Self {
+        DohResolver {
+            client: Client::builder()
+                .timeout(DOH_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            url,
+        }
+    }
+
+    /// IPv4 and IPv6 addresses of the host, an error only when neither query succeeded
+    pub async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>, io::Error> {
+        let (v4, v6) = tokio::join!(self.query(host, TYPE_A), self.query(host, TYPE_AAAA));
+
+        let addrs = match (v4, v6) {
+            (Err(error), Err(_)) => return Err(error),
+            (v4, v6) => [v4, v6]
+                .into_iter()
+                .flat_map(|addrs| addrs.unwrap_or_default())
+                .collect::<Vec<_>>(),
+        };
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} has no addresses", host),
+            ));
+        }
+
+        Ok(addrs)
+    }
+
+    async fn query(&self, host: &str, record_type: u16) -> Result<Vec<IpAddr>, io::Error> {
+        let response = self
+            .client
+            .post(self.url.clone())
+            .header(CONTENT_TYPE, DNS_MESSAGE)
+            .body(query(host, record_type)?)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(io::Error::other)?;
+        let message = response.bytes().await.map_err(io::Error::other)?;
+
+        addresses(&message)
+    }
+}
+
+/// DNS query message for the records of a host, with ID 0 as RFC 8484 recommends for caching
+fn query(host: &str, record_type: u16) -> Result<Vec<u8>, io::Error> {
+    // ID, flags (recursion desired), 1 question, no answer, authority or additional records
+    let mut message = vec![0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in host.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a valid host name", host),
+            ));
+        }
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&record_type.to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    Ok(message)
+}
+
+/// Addresses of the A and AAAA records answering a query, other records (i.e. the CNAMEs leading
+/// to them) are skipped. A host that doesn't exist has no addresses.
+fn addresses(message: &[u8]) -> Result<Vec<IpAddr>, io::Error> {
+    let malformed = || io::Error::new(io::ErrorKind::InvalidData, "malformed DNS response");
+    let u16_at = |at: usize| {
+        message
+            .get(at..at + 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+            .ok_or_else(malformed)
+    };
+
+    match u16_at(2)? & 0x000f {
+        0 => {}
+        RCODE_NXDOMAIN => return Ok(vec![]),
+        rcode => {
+            return Err(io::Error::other(format!(
+                "DNS server responded with error code {}",
+                rcode
+            )))
+        }
+    }
+
+    let (questions, answers) = (u16_at(4)?, u16_at(6)?);
+    let mut at = 12;
+    for _ in 0..questions {
+        // name, type and class
+        at = skip_name(message, at).ok_or_else(malformed)? + 4;
+    }
+
+    let mut addrs = vec![];
+    for _ in 0..answers {
+        at = skip_name(message, at).ok_or_else(malformed)?;
+        // type, class, TTL and data length precede the data
+        let record_type = u16_at(at)?;
+        let length = u16_at(at + 8)? as usize;
+        let data = message
+            .get(at + 10..at + 10 + length)
+            .ok_or_else(malformed)?;
+        match (
+            record_type,
+            <[u8; 4]>::try_from(data),
+            <[u8; 16]>::try_from(data),
+        ) {
+            (TYPE_A, Ok(octets), _) => addrs.push(IpAddr::V4(Ipv4Addr::from(octets))),
+            (TYPE_AAAA, _, Ok(octets)) => addrs.push(IpAddr::V6(Ipv6Addr::from(octets))),
+            _ => {}
+        }
+        at += 10 + length;
+    }
+
+    Ok(addrs)
+}
+
+/// Position after the (possibly compressed) name starting at `at`
+fn skip_name(message: &[u8], mut at: usize) -> Option<usize> {
+    loop {
+        match *message.get(at)? {
+            0 => return Some(at + 1),
+            // a pointer to a name earlier in the message ends it
+            length if length & 0xc0 == 0xc0 => return Some(at + 2),
+            length => at += 1 + length as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod doh_tests {
+    use std::net::IpAddr;
+
+    use url::Url;
+    use wiremock::{
+        matchers::{body_bytes, header, method},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::{addresses, query, DohResolver, DNS_MESSAGE, TYPE_A, TYPE_AAAA};
+
+    /// Response to `query` with a CNAME record and one address record
+    fn response(query: &[u8], record_type: u16, address: &[u8]) -> Vec<u8> {
+        let mut message = query.to_vec();
+        // a response, with 2 answers
+        message[2] = 0x81;
+        message[3] = 0x80;
+        message[7] = 2;
+        // CNAME of the question's name to "b" + the question's name
+        message.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 4, 1, b'b', 0xc0, 12]);
+        let cname = message.len() - 4;
+        message.extend_from_slice(&[0xc0, cname as u8]);
+        message.extend_from_slice(&record_type.to_be_bytes());
+        message.extend_from_slice(&[0, 1, 0, 0, 0, 60, 0, address.len() as u8]);
+        message.extend_from_slice(address);
+
+        message
+    }
+
+    #[test]
+    fn addresses_reads_the_address_records_of_a_response() {
+        let message = query("a.com", TYPE_A).unwrap();
+        assert_eq!(
+            message,
+            [0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, b'a', 3, b'c', b'o', b'm', 0, 0, 1, 0, 1]
+        );
+
+        assert_eq!(
+            addresses(&response(&message, TYPE_A, &[93, 184, 216, 34])).unwrap(),
+            vec!["93.184.216.34".parse::<IpAddr>().unwrap()]
+        );
+
+        let mut nxdomain = message.clone();
+        nxdomain[3] = 0x83;
+        assert!(addresses(&nxdomain).unwrap().is_empty());
+        assert!(addresses(&response(&message, TYPE_A, &[93, 184])[..30]).is_err());
+        assert!(query("a..com", TYPE_A).is_err());
+    }
+
+    #[tokio::test]
+    async fn doh_resolver_queries_both_address_families() {
+        let mock_server = MockServer::start().await;
+        for (record_type, address) in [
+            (TYPE_A, vec![127, 0, 0, 1]),
+            (TYPE_AAAA, [0; 15].into_iter().chain([1]).collect()),
+        ] {
+            let query = query("crawl.test", record_type).unwrap();
+            Mock::given(method("POST"))
+                .and(header("content-type", DNS_MESSAGE))
+                .and(body_bytes(query.clone()))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_raw(response(&query, record_type, &address), DNS_MESSAGE),
+                )
+                .mount(&mock_server)
+                .await;
+        }
+        let resolver = DohResolver::new(Url::parse(&mock_server.uri()).unwrap());
+
+        assert_eq!(
+            resolver.lookup("crawl.test").await.unwrap(),
+            vec![
+                "127.0.0.1".parse::<IpAddr>().unwrap(),
+                "::1".parse::<IpAddr>().unwrap()
+            ]
+        );
+        assert!(resolver.lookup("other.test").await.is_err());
+    }
+}
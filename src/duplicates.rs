@@ -0,0 +1,119 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::data_store::{DataStore, DataStoreEntry};
+
+/// Pages sharing the same title or meta description, by value
+#[derive(Debug, PartialEq, Default)]
+pub struct DuplicateMetadata {
+    pub titles: BTreeMap<String, Vec<String>>,
+    pub descriptions: BTreeMap<String, Vec<String>>,
+}
+
+impl DuplicateMetadata {
+    /// Groups the successfully crawled pages by title and by description. Pages declaring another
+    /// URL as canonical are expected to duplicate it, so they are left out.
+    pub fn from_store<T: DataStore>(store: &T) -> Self {
+        let pages = store
+            .entries()
+            .into_iter()
+            .filter(|(_, entry)| {
+                entry
+                    .status
+                    .is_some_and(|status| (200..300).contains(&status))
+            })
+            .filter(|(url, entry)| entry.canonical.iter().all(|canonical| canonical == *url))
+            .collect::<Vec<_>>();
+
+        DuplicateMetadata {
+            titles: duplicates(&pages, |entry| entry.title.as_ref()),
+            descriptions: duplicates(&pages, |entry| entry.description.as_ref()),
+        }
+    }
+}
+
+/// Groups of at least two pages sharing a value, with sorted URLs
+fn duplicates(
+    pages: &[(&String, &DataStoreEntry)],
+    value: impl Fn(&DataStoreEntry) -> Option<&String>,
+) -> BTreeMap<String, Vec<String>> {
+    let mut groups = BTreeMap::<String, Vec<String>>::new();
+
+    for (url, entry) in pages {
+        if let Some(value) = value(entry) {
+            groups
+                .entry(value.clone())
+                .or_default()
+                .push(url.to_string());
+        }
+    }
+    groups.retain(|_, urls| urls.len() > 1);
+    for urls in groups.values_mut() {
+        urls.sort();
+    }
+
+    groups
+}
+
+impl fmt::Display for DuplicateMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, groups) in [
+            ("titles", &self.titles),
+            ("descriptions", &self.descriptions),
+        ] {
+            writeln!(f, "Duplicate {} ({}):", name, groups.len())?;
+            for (value, urls) in groups {
+                writeln!(f, "  \"{}\"", value)?;
+                for url in urls {
+                    writeln!(f, "    {}", url)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod duplicates_tests {
+    use std::collections::BTreeMap;
+
+    use super::DuplicateMetadata;
+    use crate::data_store::{DataStore, Store};
+
+    #[test]
+    fn from_store_groups_pages_sharing_a_title_or_description() {
+        let mut s = Store::new();
+        for (url, title, description) in [
+            ("https://a.com/1", "Shop", "Our products"),
+            ("https://a.com/2", "Shop", "Our products"),
+            ("https://a.com/3", "About", "Our products"),
+            ("https://a.com/4", "Contact", "Get in touch"),
+        ] {
+            s.try_mark_visited(url);
+            s.set_status(url, 200);
+            s.set_title(url, title.to_owned());
+            s.set_description(url, description.to_owned());
+        }
+        // an alias of a canonical page and an error page are expected to share a title
+        s.set_canonical("https://a.com/2", "https://a.com/1".to_owned());
+        s.try_mark_visited("https://a.com/missing");
+        s.set_status("https://a.com/missing", 404);
+        s.set_title("https://a.com/missing", "Contact".to_owned());
+
+        let actual = DuplicateMetadata::from_store(&s);
+
+        assert_eq!(
+            actual,
+            DuplicateMetadata {
+                titles: BTreeMap::new(),
+                descriptions: BTreeMap::from([(
+                    "Our products".to_owned(),
+                    vec!["https://a.com/1".to_owned(), "https://a.com/3".to_owned()]
+                )]),
+            }
+        );
+        assert!(actual.to_string().starts_with(
+            "Duplicate titles (0):\nDuplicate descriptions (1):\n  \"Our products\"\n"
+        ));
+    }
+}
@@ -0,0 +1,318 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::{File, OpenOptions},
+    io,
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Fetches listed under the timeline chart, the slowest first
+const SLOWEST_FETCHES: usize = 5;
+
+/// What happened to a URL
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventKind {
+    /// Added to the frontier
+    Enqueue,
+    /// A worker started requesting it
+    FetchStart,
+    /// The response was received, with its status
+    FetchEnd,
+    /// The request failed, with the error
+    Error,
+}
+
+/// An event of the log, a row of its CSV
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct LoggedEvent {
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: u128,
+    /// The worker the event happened on, none for URLs enqueued before the workers started
+    pub worker: Option<usize>,
+    pub kind: EventKind,
+    pub url: String,
+    /// Response status of `fetch-end` events, error of `error` events
+    pub detail: String,
+}
+
+/// An append-only CSV log of what each worker did and when, to replay a crawl as a timeline
+pub struct EventLog {
+    writer: Mutex<csv::Writer<Box<dyn io::Write + Send>>>,
+}
+
+impl EventLog {
+    /// Appends to the log at `path`, so that the runs of a resumed crawl add up to one timeline
+    pub fn create(path: &Path) -> Result<Self, io::Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let has_headers = file.metadata()?.len() == 0;
+
+        Ok(Self::with_headers(Box::new(file), has_headers))
+    }
+
+    pub fn new(writer: Box<dyn io::Write + Send>) -> Self {
+        Self::with_headers(writer, true)
+    }
+
+    fn with_headers(writer: Box<dyn io::Write + Send>, has_headers: bool) -> Self {
+        EventLog {
+            writer: Mutex::new(
+                csv::WriterBuilder::new()
+                    .has_headers(has_headers)
+                    .from_writer(writer),
+            ),
+        }
+    }
+
+    pub fn record(&self, worker: Option<usize>, kind: EventKind, url: &str, detail: &str) {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let event = LoggedEvent {
+            timestamp_ms,
+            worker,
+            kind,
+            url: url.to_owned(),
+            detail: detail.to_owned(),
+        };
+
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        // flushed every time, so that the log shows where a crawl that had to be killed stalled
+        if let Err(e) = writer.serialize(event).and_then(|_| Ok(writer.flush()?)) {
+            warn!("Could not write the event log - {}", e);
+        }
+    }
+}
+
+/// Reads back the events of a log
+pub fn read(path: &Path) -> Result<Vec<LoggedEvent>, csv::Error> {
+    csv::Reader::from_reader(File::open(path)?)
+        .deserialize()
+        .collect()
+}
+
+/// A request, from its `fetch-start` to its `fetch-end` or `error` event
+#[derive(Debug, PartialEq, Clone)]
+pub struct Fetch {
+    pub url: String,
+    pub start_ms: u128,
+    /// None when the log ends while the request is in flight, i.e. the crawl was killed
+    pub end_ms: Option<u128>,
+    pub failed: bool,
+}
+
+/// The events of a log as a per worker Gantt-style chart, to see where a crawl stalled
+#[derive(Debug, PartialEq)]
+pub struct Timeline {
+    pub start_ms: u128,
+    pub end_ms: u128,
+    /// Requests of each worker, in the order they started
+    pub workers: BTreeMap<usize, Vec<Fetch>>,
+    /// When URLs were enqueued
+    pub enqueued_ms: Vec<u128>,
+    /// Columns of the chart
+    pub width: usize,
+}
+
+impl Timeline {
+    pub fn new(events: &[LoggedEvent], width: usize) -> Self {
+        let mut workers = BTreeMap::<usize, Vec<Fetch>>::new();
+        let mut enqueued_ms = vec![];
+
+        for event in events {
+            match (event.kind, event.worker) {
+                (EventKind::Enqueue, _) => enqueued_ms.push(event.timestamp_ms),
+                (EventKind::FetchStart, Some(worker)) => {
+                    workers.entry(worker).or_default().push(Fetch {
+                        url: event.url.clone(),
+                        start_ms: event.timestamp_ms,
+                        end_ms: None,
+                        failed: false,
+                    })
+                }
+                (EventKind::FetchEnd | EventKind::Error, Some(worker)) => {
+                    let fetch = workers.get_mut(&worker).and_then(|fetches| {
+                        fetches
+                            .iter_mut()
+                            .rev()
+                            .find(|fetch| fetch.url == event.url && fetch.end_ms.is_none())
+                    });
+                    if let Some(fetch) = fetch {
+                        fetch.end_ms = Some(event.timestamp_ms);
+                        fetch.failed = event.kind == EventKind::Error;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let timestamps = events.iter().map(|event| event.timestamp_ms);
+        Timeline {
+            start_ms: timestamps.clone().min().unwrap_or_default(),
+            end_ms: timestamps.max().unwrap_or_default(),
+            workers,
+            enqueued_ms,
+            width: width.max(1),
+        }
+    }
+
+    fn column(&self, timestamp_ms: u128) -> usize {
+        let duration = self.end_ms.saturating_sub(self.start_ms).max(1);
+        let column = timestamp_ms.saturating_sub(self.start_ms) * self.width as u128 / duration;
+
+        (column as usize).min(self.width - 1)
+    }
+
+    /// Requests that took the longest, still in flight ones first
+    pub fn slowest(&self) -> Vec<&Fetch> {
+        let mut fetches = self.workers.values().flatten().collect::<Vec<_>>();
+        fetches.sort_by_key(|fetch| {
+            std::cmp::Reverse(
+                fetch
+                    .end_ms
+                    .unwrap_or(u128::MAX)
+                    .saturating_sub(fetch.start_ms),
+            )
+        });
+        fetches.truncate(SLOWEST_FETCHES);
+
+        fetches
+    }
+}
+
+impl fmt::Display for Timeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let duration_ms = self.end_ms.saturating_sub(self.start_ms);
+        writeln!(
+            f,
+            "Timeline of {:.1}s, a column is {}ms ('=' fetching, 'x' failed, '>' still in flight at the end, '+' URLs enqueued):",
+            duration_ms as f64 / 1000.0,
+            duration_ms.div_ceil(self.width as u128)
+        )?;
+
+        let mut enqueued = vec![' '; self.width];
+        for timestamp_ms in &self.enqueued_ms {
+            enqueued[self.column(*timestamp_ms)] = '+';
+        }
+        writeln!(
+            f,
+            "  {:>9} |{}|",
+            "enqueue",
+            enqueued.iter().collect::<String>()
+        )?;
+
+        for (worker, fetches) in &self.workers {
+            let mut row = vec!['.'; self.width];
+            for fetch in fetches {
+                let start = self.column(fetch.start_ms);
+                // the system clock may have been set back while the request was in flight
+                let end = self.column(fetch.end_ms.unwrap_or(self.end_ms)).max(start);
+                row[start..=end].fill('=');
+                row[end] = match (fetch.end_ms, fetch.failed) {
+                    (None, _) => '>',
+                    (Some(_), true) => 'x',
+                    (Some(_), false) => '=',
+                };
+            }
+            let name = format!("worker {}", worker);
+            writeln!(f, "  {:>9} |{}|", name, row.iter().collect::<String>())?;
+        }
+
+        writeln!(f, "Slowest fetches:")?;
+        for fetch in self.slowest() {
+            match fetch.end_ms {
+                Some(end_ms) => writeln!(
+                    f,
+                    "  {}ms {}",
+                    end_ms.saturating_sub(fetch.start_ms),
+                    fetch.url
+                )?,
+                None => writeln!(f, "  unfinished {}", fetch.url)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod event_log_tests {
+    use super::{EventKind, EventLog, LoggedEvent, Timeline};
+
+    fn event(timestamp_ms: u128, worker: usize, kind: EventKind, url: &str) -> LoggedEvent {
+        LoggedEvent {
+            timestamp_ms,
+            worker: Some(worker),
+            kind,
+            url: url.to_owned(),
+            detail: String::new(),
+        }
+    }
+
+    #[test]
+    fn event_log_appends_to_an_existing_log() {
+        let path =
+            std::env::temp_dir().join(format!("url-crawler-event-log-{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        EventLog::create(&path).unwrap().record(
+            Some(0),
+            EventKind::FetchStart,
+            "https://a.com/",
+            "",
+        );
+        EventLog::create(&path).unwrap().record(
+            Some(0),
+            EventKind::FetchEnd,
+            "https://a.com/",
+            "200",
+        );
+        let events = super::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].kind, EventKind::FetchEnd);
+        assert_eq!(events[1].detail, "200");
+    }
+
+    #[test]
+    fn timeline_charts_the_fetches_of_each_worker() {
+        let events = vec![
+            event(0, 0, EventKind::Enqueue, "https://a.com/1"),
+            event(0, 0, EventKind::FetchStart, "https://a.com/"),
+            event(400, 0, EventKind::FetchEnd, "https://a.com/"),
+            event(200, 1, EventKind::FetchStart, "https://a.com/2"),
+            event(600, 1, EventKind::Error, "https://a.com/2"),
+            event(800, 0, EventKind::FetchStart, "https://a.com/1"),
+            event(1000, 1, EventKind::Enqueue, "https://a.com/3"),
+        ];
+
+        let chart = Timeline::new(&events, 10).to_string();
+
+        assert!(chart.starts_with("Timeline of 1.0s, a column is 100ms"));
+        assert!(chart.contains("    enqueue |+        +|\n"));
+        assert!(chart.contains("   worker 0 |=====...=>|\n"));
+        assert!(chart.contains("   worker 1 |..====x...|\n"));
+        assert!(chart.contains("Slowest fetches:\n  unfinished https://a.com/1\n  400ms"));
+    }
+
+    #[test]
+    fn timeline_charts_fetches_ending_before_they_start() {
+        // the system clock was set back during the request
+        let events = vec![
+            event(500, 0, EventKind::FetchStart, "https://a.com/"),
+            event(100, 0, EventKind::FetchEnd, "https://a.com/"),
+            event(1000, 0, EventKind::Enqueue, "https://a.com/1"),
+        ];
+
+        let chart = Timeline::new(&events, 10).to_string();
+
+        assert!(chart.contains("   worker 0 |....=.....|\n"));
+        assert!(chart.contains("Slowest fetches:\n  0ms https://a.com/"));
+    }
+}
@@ -0,0 +1,498 @@
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt, io};
+
+use crate::data_store::{DataStore, DataStoreEntry, Store};
+
+/// Format the data store is exported in
+#[derive(clap::ValueEnum, PartialEq, Debug, Clone, Copy, Default)]
+pub enum OutputFormat {
+    /// Crawled pages grouped by canonical URL, with the links found on them
+    #[default]
+    Json,
+    /// The link graph, a `source,target,decision,count,tag,rel,position` row per link found on a
+    /// page
+    Csv,
+    /// A sitemap.xml of the crawled pages that responded with a 2xx status
+    Sitemap,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Json(serde_json::Error),
+    Csv(csv::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Json(e) => write!(f, "{}", e),
+            Error::Csv(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl std::convert::From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+impl std::convert::From<csv::Error> for Error {
+    fn from(err: csv::Error) -> Self {
+        Error::Csv(err)
+    }
+}
+
+/// Metadata of a crawl attached to every exported record, i.e. `env=staging`
+pub type Tags = BTreeMap<String, String>;
+
+/// Parses a `key=value` tag
+pub fn tag(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_owned(), value.trim().to_owned()))
+        }
+        _ => Err(format!("`{}` is not a key=value tag", s)),
+    }
+}
+
+/// A record with the tags of the crawl next to its own fields, none if the crawl has no tags
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Tagged<'a, T> {
+    #[serde(flatten)]
+    pub record: T,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: &'a Tags,
+}
+
+/// Streams written to stdout while crawling
+#[derive(clap::ValueEnum, PartialEq, Debug, Clone, Copy)]
+pub enum Emit {
+    /// A JSON object per visited page, one per line
+    Ndjson,
+}
+
+/// A visited page, emitted as soon as it has been crawled
+#[derive(Serialize, Debug, PartialEq)]
+pub struct PageEvent<'a> {
+    pub url: &'a str,
+    pub status: u16,
+    pub depth: usize,
+    /// Absolute URLs of the links found on the page
+    pub outlinks: &'a [String],
+}
+
+impl PageEvent<'_> {
+    /// The event, with the tags of the crawl, as a single line of JSON terminated by a newline
+    pub fn to_ndjson(&self, tags: &Tags) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&Tagged { record: self, tags }).map(|json| json + "\n")
+    }
+}
+
+/// A single logical page - every crawled URL that declares the same canonical URL
+#[derive(Serialize, Debug, PartialEq)]
+pub struct PageGroup<'a> {
+    pub url: String,
+    pub aliases: Vec<String>,
+    #[serde(flatten)]
+    pub entry: &'a DataStoreEntry,
+}
+
+/// Groups stored pages by their canonical URL (or their own URL when no canonical is declared)
+pub fn group_by_canonical<T: DataStore>(store: &T) -> Vec<PageGroup<'_>> {
+    let mut groups = BTreeMap::<String, Vec<&String>>::new();
+
+    for (url, entry) in store.entries() {
+        let canonical = entry.canonical.clone().unwrap_or(url.clone());
+        groups.entry(canonical).or_default().push(url);
+    }
+
+    groups
+        .into_iter()
+        .filter_map(|(canonical, mut urls)| {
+            urls.sort();
+
+            // prefer the entry of the canonical URL itself, if it has been crawled
+            let key = urls
+                .iter()
+                .find(|url| ***url == canonical)
+                .or(urls.first())
+                .map(|url| url.to_string())?;
+            let entry = store.get(&key)?;
+            let aliases = urls
+                .into_iter()
+                .filter(|url| **url != canonical)
+                .cloned()
+                .collect();
+
+            Some(PageGroup {
+                url: canonical,
+                aliases,
+                entry,
+            })
+        })
+        .collect()
+}
+
+pub fn to_json<T: DataStore>(store: &T, tags: &Tags) -> Result<String, serde_json::Error> {
+    let groups = group_by_canonical(store)
+        .into_iter()
+        .map(|record| Tagged { record, tags })
+        .collect::<Vec<_>>();
+
+    serde_json::to_string_pretty(&groups)
+}
+
+/// The link graph as `source,target,decision,count,tag,rel,position` CSV rows, sorted by source
+/// and target, with a column per tag of the crawl. `rel` tokens are separated by spaces.
+pub fn to_csv<T: DataStore>(store: &T, tags: &Tags) -> Result<String, Error> {
+    let mut entries = store.entries();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    // a graph without edges is an empty file, without a header
+    if entries
+        .iter()
+        .any(|(_, entry)| !entry.link_counts.is_empty())
+    {
+        let columns = [
+            "source", "target", "decision", "count", "tag", "rel", "position",
+        ];
+        writer.write_record(columns.into_iter().chain(tags.keys().map(String::as_str)))?;
+    }
+    for (url, entry) in entries {
+        for (link, count) in &entry.link_counts {
+            let decision = entry
+                .link_decisions
+                .get(link)
+                .map(|decision| decision.to_string())
+                .unwrap_or_default();
+            let count = count.to_string();
+            let source = entry.link_sources.get(link);
+            let tag = source
+                .and_then(|source| source.tag.as_deref())
+                .unwrap_or_default();
+            let rel = source
+                .map(|source| source.rel.join(" "))
+                .unwrap_or_default();
+            let position = source
+                .map(|source| source.position.to_string())
+                .unwrap_or_default();
+            let edge = [url.as_str(), link, &decision, &count, tag, &rel, &position];
+            writer.write_record(edge.into_iter().chain(tags.values().map(String::as_str)))?;
+        }
+    }
+
+    let csv = writer
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))?;
+    Ok(String::from_utf8_lossy(&csv).into_owned())
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\'', "&apos;")
+        .replace('"', "&quot;")
+}
+
+/// A sitemap.xml listing the canonical URL of every crawled page that responded with a 2xx status
+pub fn to_sitemap<T: DataStore>(store: &T) -> String {
+    let mut sitemap = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+
+    for group in group_by_canonical(store) {
+        if group.entry.visited
+            && group
+                .entry
+                .status
+                .is_some_and(|status| (200..300).contains(&status))
+        {
+            sitemap.push_str(&format!(
+                "  <url><loc>{}</loc></url>\n",
+                escape_xml(&group.url)
+            ));
+        }
+    }
+    sitemap.push_str("</urlset>\n");
+
+    sitemap
+}
+
+/// A copy of the store that doesn't depend on the order its pages were crawled in, so that the
+/// exports of two crawls can be compared with `diff`. Pages and links are sorted, the data itself is
+/// left as crawled.
+pub fn sorted<T: DataStore>(store: &T) -> Store {
+    let mut sorted = Store::new();
+
+    for (url, entry) in store.entries() {
+        let mut entry = entry.clone();
+        entry.urls_found.sort();
+        sorted.insert(url.clone(), entry);
+    }
+
+    sorted
+}
+
+/// The data store in the given format
+pub fn export<T: DataStore>(store: &T, format: OutputFormat) -> Result<String, Error> {
+    export_tagged(store, format, &Tags::new())
+}
+
+/// The data store in the given format, with the tags of the crawl in every record. Sitemaps
+/// have a fixed schema, they are left untagged.
+pub fn export_tagged<T: DataStore>(
+    store: &T,
+    format: OutputFormat,
+    tags: &Tags,
+) -> Result<String, Error> {
+    match format {
+        OutputFormat::Json => Ok(to_json(store, tags)?),
+        OutputFormat::Csv => to_csv(store, tags),
+        OutputFormat::Sitemap => Ok(to_sitemap(store)),
+    }
+}
+
+/// Writes every redirect hop encountered during the crawl as `from,to,status` CSV rows
+pub fn write_redirects_csv<T: DataStore, W: io::Write>(
+    store: &T,
+    writer: W,
+) -> Result<(), csv::Error> {
+    let mut redirects = store
+        .entries()
+        .into_iter()
+        .flat_map(|(_, entry)| entry.redirects.iter())
+        .collect::<Vec<_>>();
+    redirects.sort_by(|a, b| (&a.from, &a.to).cmp(&(&b.from, &b.to)));
+    redirects.dedup();
+
+    let mut writer = csv::Writer::from_writer(writer);
+    for redirect in redirects {
+        writer.serialize(redirect)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod export_tests {
+    use super::{
+        export_tagged, group_by_canonical, sorted, tag, write_redirects_csv, OutputFormat,
+        PageEvent, Tags,
+    };
+    use crate::{
+        data_store::{DataStore, Store},
+        fetch::Redirect,
+        link::{LinkDecision, LinkSource},
+    };
+
+    #[test]
+    fn export_writes_the_link_graph_as_csv() {
+        let mut s = Store::new();
+        s.add(
+            "https://a.com/".to_owned(),
+            Some("https://a.com/1".to_owned()),
+        );
+        s.add(
+            "https://a.com/".to_owned(),
+            Some("https://b.com/".to_owned()),
+        );
+        s.add(
+            "https://a.com/".to_owned(),
+            Some("https://a.com/1".to_owned()),
+        );
+        s.set_link_decision(
+            "https://a.com/",
+            "https://a.com/1".to_owned(),
+            LinkDecision::Enqueue,
+        );
+        s.set_link_source(
+            "https://a.com/",
+            "https://a.com/1".to_owned(),
+            LinkSource {
+                position: 1,
+                tag: Some("a".to_owned()),
+                rel: vec!["nofollow".to_owned(), "noopener".to_owned()],
+            },
+        );
+        s.add("https://a.com/1".to_owned(), None);
+
+        assert_eq!(
+            s.export(OutputFormat::Csv).unwrap(),
+            "source,target,decision,count,tag,rel,position\n\
+             https://a.com/,https://a.com/1,enqueue,2,a,nofollow noopener,1\n\
+             https://a.com/,https://b.com/,,1,,,\n"
+        );
+    }
+
+    #[test]
+    fn sorted_export_is_independent_of_the_crawl_order() {
+        let crawl = |pages: [&str; 2]| {
+            let mut s = Store::new();
+            for page in pages {
+                s.add(page.to_owned(), Some("https://a.com/3".to_owned()));
+                s.add(page.to_owned(), Some("https://a.com/0".to_owned()));
+                let decision = match page {
+                    "https://a.com/1" => LinkDecision::Enqueue,
+                    _ => LinkDecision::SkipDuplicate,
+                };
+                s.set_link_decision(page, "https://a.com/3".to_owned(), decision);
+            }
+            s
+        };
+
+        let first = sorted(&crawl(["https://a.com/1", "https://a.com/2"]));
+        let second = sorted(&crawl(["https://a.com/2", "https://a.com/1"]));
+
+        assert_eq!(
+            first.export(OutputFormat::Json).unwrap(),
+            second.export(OutputFormat::Json).unwrap()
+        );
+        assert_eq!(
+            first.get("https://a.com/1").unwrap().urls_found,
+            vec!["https://a.com/0".to_owned(), "https://a.com/3".to_owned()]
+        );
+        // decisions are exported as they were taken
+        assert_eq!(
+            first.get("https://a.com/2").unwrap().link_decisions["https://a.com/3"],
+            LinkDecision::SkipDuplicate
+        );
+    }
+
+    #[test]
+    fn export_writes_a_sitemap_of_successfully_crawled_pages() {
+        let mut s = Store::new();
+        for (url, status) in [
+            ("https://a.com/", 200),
+            ("https://a.com/?a=1&b=2", 200),
+            ("https://a.com/missing", 404),
+        ] {
+            s.add(url.to_owned(), None);
+            s.visited(url);
+            s.set_status(url, status);
+        }
+        s.add("https://a.com/queued".to_owned(), None);
+
+        assert_eq!(
+            s.export(OutputFormat::Sitemap).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n  \
+             <url><loc>https://a.com/</loc></url>\n  \
+             <url><loc>https://a.com/?a=1&amp;b=2</loc></url>\n\
+             </urlset>\n"
+        );
+    }
+
+    #[test]
+    fn group_by_canonical_merges_urls_sharing_a_canonical_url() {
+        let mut s = Store::new();
+        for url in [
+            "https://a.com/p",
+            "https://a.com/p?x=1",
+            "https://a.com/p?x=2",
+        ] {
+            s.add(url.to_owned(), None);
+            s.set_canonical(url, "https://a.com/p".to_owned());
+        }
+        s.add("https://a.com/other".to_owned(), None);
+
+        let groups = group_by_canonical(&s);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].url, "https://a.com/other");
+        assert!(groups[0].aliases.is_empty());
+        assert_eq!(groups[1].url, "https://a.com/p");
+        assert_eq!(
+            groups[1].aliases,
+            vec!["https://a.com/p?x=1", "https://a.com/p?x=2"]
+        );
+    }
+
+    #[test]
+    fn group_by_canonical_lists_all_urls_as_aliases_if_canonical_was_not_crawled() {
+        let mut s = Store::new();
+        s.add("https://a.com/p?x=1".to_owned(), None);
+        s.set_canonical("https://a.com/p?x=1", "https://a.com/p".to_owned());
+
+        let groups = group_by_canonical(&s);
+
+        assert_eq!(groups[0].url, "https://a.com/p");
+        assert_eq!(groups[0].aliases, vec!["https://a.com/p?x=1"]);
+    }
+
+    #[test]
+    fn page_event_is_serialised_as_a_single_json_line() {
+        let outlinks = vec!["https://a.com/1".to_owned(), "https://b.com/".to_owned()];
+        let event = PageEvent {
+            url: "https://a.com/",
+            status: 200,
+            depth: 0,
+            outlinks: &outlinks,
+        };
+
+        assert_eq!(
+            event.to_ndjson(&Tags::new()).unwrap(),
+            "{\"url\":\"https://a.com/\",\"status\":200,\"depth\":0,\"outlinks\":[\"https://a.com/1\",\"https://b.com/\"]}\n"
+        );
+    }
+
+    #[test]
+    fn tagged_exports_carry_the_tags_in_every_record() {
+        let tags = Tags::from([tag("env=staging").unwrap(), tag("team = search").unwrap()]);
+        let mut s = Store::new();
+        s.add(
+            "https://a.com/".to_owned(),
+            Some("https://a.com/1".to_owned()),
+        );
+
+        assert_eq!(
+            export_tagged(&s, OutputFormat::Csv, &tags).unwrap(),
+            "source,target,decision,count,tag,rel,position,env,team\n\
+             https://a.com/,https://a.com/1,,1,,,,staging,search\n"
+        );
+        let json = export_tagged(&s, OutputFormat::Json, &tags).unwrap();
+        let records = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert_eq!(records[0]["url"], "https://a.com/");
+        assert_eq!(records[0]["tags"]["team"], "search");
+        assert!(!s.export(OutputFormat::Json).unwrap().contains("tags"));
+        assert!(tag("staging").is_err());
+    }
+
+    #[test]
+    fn write_redirects_csv_writes_a_row_per_redirect_hop() {
+        let mut s = Store::new();
+        s.add("https://a.com/old".to_owned(), None);
+        s.set_redirects(
+            "https://a.com/old",
+            vec![
+                Redirect {
+                    from: "https://a.com/old".to_owned(),
+                    to: "https://a.com/new".to_owned(),
+                    status: 301,
+                },
+                Redirect {
+                    from: "https://a.com/new".to_owned(),
+                    to: "https://a.com/newer".to_owned(),
+                    status: 302,
+                },
+            ],
+        );
+
+        let mut csv = vec![];
+        write_redirects_csv(&s, &mut csv).unwrap();
+
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "from,to,status\n\
+             https://a.com/new,https://a.com/newer,302\n\
+             https://a.com/old,https://a.com/new,301\n"
+        );
+    }
+}
@@ -1,33 +1,698 @@
-use reqwest::{Client, Error};
+use encoding_rs::{Encoding, UTF_8};
+use log::{debug, warn};
+use reqwest::{
+    header::{
+        HeaderMap, HeaderName, HeaderValue, ACCEPT_LANGUAGE, CONTENT_RANGE, CONTENT_TYPE, HOST,
+        LINK, LOCATION, RANGE,
+    },
+    redirect::Policy,
+    Client, Error, Method, Response, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    dns::DnsCache,
+    link,
+    link_header::{self, HeaderLink},
+    mime::{self, MediaType, TypeMismatch},
+    robots::pattern_matches,
+};
+use tokio::sync::Semaphore;
+use url::Url;
+
+/// Redirect hops followed per request, unless another limit is given
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+/// How long a body without a `Content-Length` may take to read before it is taken as a stream
+pub const DEFAULT_STREAM_DEADLINE: Duration = Duration::from_secs(30);
+/// How long an idle keep-alive connection stays open when requests are capped, so that the
+/// connections to hosts no longer crawled don't hold on to file descriptors
+const CAPPED_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// User agent sent with every request, and matched against robots.txt groups, unless another one is given
+pub const DEFAULT_USER_AGENT: &str =
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// What happens to the target of a 3xx response
+#[derive(clap::ValueEnum, PartialEq, Debug, Clone, Copy, Default)]
+pub enum RedirectPolicy {
+    /// Request the target, up to the maximum number of redirects
+    #[default]
+    Follow,
+    /// Record the redirect as a link to its target, without requesting it
+    RecordOnly,
+    /// Treat the redirect as a failed request
+    Error,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Redirect {
+    pub from: String,
+    pub to: String,
+    pub status: u16,
+}
+
+/// Requests to a host go to this address instead of the one its DNS resolves to, i.e. `example.com:10.0.0.1`
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResolveRule {
+    pub host: String,
+    pub address: IpAddr,
+}
+
+impl FromStr for ResolveRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("`{}` is not a <host>:<address> rule", s);
+        let (host, address) = s.split_once(':').ok_or_else(invalid)?;
+        let address = address
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse()
+            .map_err(|_| invalid())?;
+
+        match host.is_empty() {
+            true => Err(invalid()),
+            false => Ok(ResolveRule {
+                host: host.to_lowercase(),
+                address,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for ResolveRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.host, self.address)
+    }
+}
+
+/// Size and type of a binary resource of which only the first bytes were fetched
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PartialContent {
+    /// Size of the whole resource in bytes, from `Content-Range` or `Content-Length`, when declared
+    pub size: Option<u64>,
+    /// The declared media type, without its parameters
+    pub content_type: Option<String>,
+    /// Kind of document, sniffed from the first bytes over the declared type
+    pub media_type: Option<MediaType>,
+}
+
+/// Method and headers of the requests to URLs whose path matches a pattern, i.e.
+/// `/downloads/* method=HEAD` or `/api/* header=Accept:application/json`
+#[derive(Debug, PartialEq, Clone)]
+pub struct FetchRule {
+    /// Path (and query) pattern, with robots.txt `*` wildcards and `$` end anchor
+    pub pattern: String,
+    /// `GET`, or `HEAD` to only check the URL responds
+    pub method: Method,
+    /// Headers sent on top of, or instead of, the ones of every request
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl FetchRule {
+    pub fn matches(&self, url: &str) -> bool {
+        let Ok(url) = Url::parse(url) else {
+            return false;
+        };
+
+        match url.query() {
+            Some(query) => pattern_matches(&self.pattern, &format!("{}?{}", url.path(), query)),
+            None => pattern_matches(&self.pattern, url.path()),
+        }
+    }
+}
+
+impl FromStr for FetchRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut settings = s.split_whitespace();
+        let pattern = match settings.next() {
+            Some(pattern) if pattern.starts_with('/') => pattern.to_owned(),
+            _ => {
+                return Err(format!(
+                    "`{}` doesn't start with a path pattern, i.e. /downloads/*",
+                    s
+                ))
+            }
+        };
+        let mut rule = FetchRule {
+            pattern,
+            method: Method::GET,
+            headers: vec![],
+        };
+
+        for setting in settings {
+            match setting.split_once('=') {
+                Some(("method", method)) => {
+                    rule.method = match method.to_uppercase().as_str() {
+                        "GET" => Method::GET,
+                        "HEAD" => Method::HEAD,
+                        _ => {
+                            return Err(format!(
+                                "`{}` is not a method, expected GET or HEAD",
+                                method
+                            ))
+                        }
+                    }
+                }
+                Some(("header", header)) => {
+                    let invalid = || format!("`{}` is not a <name>:<value> header", header);
+                    let (name, value) = header.split_once(':').ok_or_else(invalid)?;
+                    rule.headers.push((
+                        HeaderName::from_str(name.trim()).map_err(|_| invalid())?,
+                        HeaderValue::from_str(value.trim()).map_err(|_| invalid())?,
+                    ));
+                }
+                _ => {
+                    return Err(format!(
+                        "unknown setting `{}`, expected method=<method> or header=<name>:<value>",
+                        setting
+                    ))
+                }
+            }
+        }
+
+        Ok(rule)
+    }
+}
+
+impl fmt::Display for FetchRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} method={}", self.pattern, self.method)?;
+        for (name, value) in &self.headers {
+            write!(f, " header={}:{}", name, value.to_str().unwrap_or_default())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FetchResponse {
+    pub status: u16,
+    pub body: String,
+    /// URL the body was served from, after following any redirects
+    pub url: String,
+    pub redirects: Vec<Redirect>,
+    /// The declared `Content-Type`
+    pub content_type: Option<String>,
+    /// Kind of document the body is, from its first bytes
+    pub sniffed_type: Option<MediaType>,
+    /// Links of the `Link` headers, with their targets resolved against the URL
+    pub links: Vec<HeaderLink>,
+    /// Whether the body was left unread, as the endpoint streams indefinitely (Server-Sent Events,
+    /// long-polling, ...) - it declared a streaming type, or sent no `Content-Length` and was still
+    /// sending at the deadline
+    pub streaming: bool,
+    /// Whether the body is only the first bytes of a binary resource, fetched with a range request
+    pub partial: bool,
+    /// Size of the whole body in bytes, as declared by the server, when the body is partial
+    pub size: Option<u64>,
+}
+
+impl FetchResponse {
+    /// Whether the body can have links to parse, going by its sniffed type over its declared one
+    pub fn is_parseable(&self) -> bool {
+        !self.streaming
+            && !self.partial
+            && mime::is_parseable(self.content_type.as_deref(), self.sniffed_type)
+    }
+
+    /// How the body differs from the declared `Content-Type`, if it does
+    pub fn type_mismatch(&self) -> Option<TypeMismatch> {
+        TypeMismatch::new(self.content_type.as_deref(), self.sniffed_type)
+    }
+
+    /// Targets of the `Link` headers with this relation type, i.e. `canonical`
+    pub fn header_links<'a>(&'a self, rel: &'a str) -> impl Iterator<Item = &'a str> {
+        self.links
+            .iter()
+            .filter(move |link| link.has_rel(rel))
+            .map(|link| link.target.as_str())
+    }
+
+    /// Size and type of the resource, when only its first bytes were fetched
+    pub fn partial_content(&self) -> Option<PartialContent> {
+        self.partial.then(|| PartialContent {
+            size: self.size,
+            content_type: self.content_type.as_deref().map(mime::essence),
+            media_type: self.sniffed_type.or(self
+                .content_type
+                .as_deref()
+                .map(MediaType::from_content_type)),
+        })
+    }
+
+    /// The redirect the response itself is, when its target wasn't requested
+    pub fn unfollowed_redirect(&self) -> Option<&Redirect> {
+        self.redirects
+            .last()
+            .filter(|redirect| redirect.from == self.url)
+    }
+
+    /// The first `max_bytes` of the body, cut back to the nearest character boundary
+    pub fn body_sample(&self, max_bytes: usize) -> &str {
+        if self.body.len() <= max_bytes {
+            return &self.body;
+        }
+
+        let mut end = max_bytes;
+        while !self.body.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        &self.body[..end]
+    }
+}
 
 pub trait Fetch {
     fn new() -> Self;
     fn get(&self, url: &str) -> impl std::future::Future<Output = Result<String, Error>> + Send;
+    fn fetch(
+        &self,
+        url: &str,
+    ) -> impl std::future::Future<Output = Result<FetchResponse, Error>> + Send;
 }
 
 #[derive(Default)]
 pub struct HttpFetch {
     client: Client,
+    /// Redirect hops followed per request, `DEFAULT_MAX_REDIRECTS` if none
+    max_redirects: Option<usize>,
+    stream_deadline: Duration,
+    /// Bytes requested of binary resources, all of them if none
+    range_probe: Option<u64>,
+    fetch_rules: Vec<FetchRule>,
+    host_header: Option<HostHeader>,
+    /// Permits for requests in flight, shared by every client of the same builder
+    in_flight: Option<Arc<Semaphore>>,
 }
 
-impl Fetch for HttpFetch {
-    fn new() -> HttpFetch {
+/// `Host` header sent instead of the URL's host with the requests to some hosts
+#[derive(Debug, Clone)]
+struct HostHeader {
+    host: String,
+    /// Hosts, with their port when it isn't the scheme's default one, the header is sent to
+    targets: Vec<String>,
+}
+
+impl HostHeader {
+    fn applies_to(&self, url: &str) -> bool {
+        link::host_with_port(url).is_some_and(|host| self.targets.contains(&host))
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct HttpFetchBuilder {
+    dns_cache: Option<DnsCache>,
+    user_agent: Option<String>,
+    host_header: Option<HostHeader>,
+    accept_language: Option<String>,
+    resolve: Vec<ResolveRule>,
+    http1_only: bool,
+    http2_prior_knowledge: bool,
+    /// Unspecified address of the only IP family connected to, if restricted
+    local_address: Option<IpAddr>,
+    max_redirects: Option<usize>,
+    redirect_policy: RedirectPolicy,
+    stream_deadline: Option<Duration>,
+    range_probe: Option<u64>,
+    fetch_rules: Vec<FetchRule>,
+    in_flight: Option<Arc<Semaphore>>,
+}
+
+impl HttpFetchBuilder {
+    pub fn new() -> HttpFetchBuilder {
+        HttpFetchBuilder::default()
+    }
+
+    /// Resolves hosts through a shared (prefetched) DNS cache
+    pub fn dns_cache(mut self, dns_cache: DnsCache) -> HttpFetchBuilder {
+        self.dns_cache = Some(dns_cache);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> HttpFetchBuilder {
+        self.user_agent = Some(user_agent.to_owned());
+        self
+    }
+
+    /// `Host` header sent instead of the URL's host with the requests to `targets`, i.e. to crawl
+    /// an IP address (a load balancer or a server before a DNS cutover) as the site it will serve.
+    /// Targets are hosts or IP addresses, with their port when it isn't the scheme's default one;
+    /// requests to other hosts, i.e. external links, keep their own `Host`.
+    pub fn host_header(mut self, host: &str, targets: Vec<String>) -> HttpFetchBuilder {
+        self.host_header = Some(HostHeader {
+            host: host.to_owned(),
+            targets,
+        });
+        self
+    }
+
+    /// `Accept-Language` header sent with every request, i.e. `fr-FR` or `en-GB,en;q=0.8`
+    pub fn accept_language(mut self, accept_language: &str) -> HttpFetchBuilder {
+        self.accept_language = Some(accept_language.to_owned());
+        self
+    }
+
+    /// Sends the requests to a host to the rule's address, bypassing DNS
+    pub fn resolve(mut self, rule: ResolveRule) -> HttpFetchBuilder {
+        self.resolve.push(rule);
+        self
+    }
+
+    /// Only speaks HTTP/1.1, for servers that misbehave when HTTP/2 is negotiated
+    pub fn http1_only(mut self) -> HttpFetchBuilder {
+        self.http1_only = true;
+        self
+    }
+
+    /// Speaks HTTP/2 straight away, without negotiating it, i.e. to servers only serving cleartext HTTP/2
+    pub fn http2_prior_knowledge(mut self) -> HttpFetchBuilder {
+        self.http2_prior_knowledge = true;
+        self
+    }
+
+    /// Only connects over IPv4, skipping the AAAA addresses of dual-stack hosts
+    pub fn ipv4_only(mut self) -> HttpFetchBuilder {
+        self.local_address = Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        self
+    }
+
+    /// Only connects over IPv6, skipping the A addresses of dual-stack hosts
+    pub fn ipv6_only(mut self) -> HttpFetchBuilder {
+        self.local_address = Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED));
+        self
+    }
+
+    /// Redirect hops followed per request, 10 by default
+    pub fn max_redirects(mut self, max_redirects: usize) -> HttpFetchBuilder {
+        self.max_redirects = Some(max_redirects);
+        self
+    }
+
+    /// How long a body without a `Content-Length` may take to read, 30s by default, before the
+    /// endpoint is taken as streaming indefinitely and the request aborted
+    pub fn stream_deadline(mut self, stream_deadline: Duration) -> HttpFetchBuilder {
+        self.stream_deadline = Some(stream_deadline);
+        self
+    }
+
+    /// Only requests the first `bytes` of URLs with the extension of a binary file, i.e. `.pdf` or
+    /// `.zip`, enough to record their size and sniff their type. Servers ignoring the `Range` header
+    /// have their response cut short all the same.
+    pub fn range_probe(mut self, bytes: u64) -> HttpFetchBuilder {
+        self.range_probe = Some(bytes.max(1));
+        self
+    }
+
+    /// Keeps at most `max` requests in flight at once across the clients built from this builder,
+    /// further requests wait for one to finish. Idle keep-alive connections aren't requests: at
+    /// most one is kept per host, for a few seconds.
+    pub fn max_concurrent_requests(mut self, max: usize) -> HttpFetchBuilder {
+        self.in_flight = Some(Arc::new(Semaphore::new(max.max(1))));
+        self
+    }
+
+    /// Method and headers of the requests to URLs matching the rule, the first matching rule applies
+    pub fn fetch_rule(mut self, rule: FetchRule) -> HttpFetchBuilder {
+        self.fetch_rules.push(rule);
+        self
+    }
+
+    /// Whether redirects are followed, or only recorded, in which case the response is the redirect itself
+    pub fn redirect_policy(mut self, redirect_policy: RedirectPolicy) -> HttpFetchBuilder {
+        self.redirect_policy = redirect_policy;
+        self
+    }
+
+    /// Builds the client, falling back to a default one, without the options of the builder, if
+    /// they are rejected (i.e. a local address or TLS backend the platform doesn't support). Use
+    /// `try_build` to check them first.
+    pub fn build(self) -> HttpFetch {
+        let fallback = self.clone();
+        self.try_build().unwrap_or_else(|e| {
+            warn!(
+                "Could not build the HTTP client, using a default one - {}",
+                e
+            );
+            fallback.with_client(Client::default())
+        })
+    }
+
+    /// Builds the client, failing if reqwest rejects the options
+    pub fn try_build(self) -> Result<HttpFetch, reqwest::Error> {
+        // redirects are followed manually so that every hop can be recorded
+        let mut client_builder = Client::builder()
+            .redirect(Policy::none())
+            .user_agent(self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT));
+        if self.http1_only {
+            client_builder = client_builder.http1_only();
+        }
+        if self.http2_prior_knowledge {
+            client_builder = client_builder.http2_prior_knowledge();
+        }
+        // bound to an address of one family, only addresses of that family are connected to
+        if let Some(local_address) = self.local_address {
+            client_builder = client_builder.local_address(local_address);
+        }
+        if let Some(dns_cache) = &self.dns_cache {
+            client_builder = client_builder.dns_resolver(Arc::new(dns_cache.clone()));
+        }
+        if self.in_flight.is_some() {
+            client_builder = client_builder
+                .pool_idle_timeout(CAPPED_POOL_IDLE_TIMEOUT)
+                .pool_max_idle_per_host(1);
+        }
+        // the port is ignored, requests go to the port of the URL
+        for rule in &self.resolve {
+            client_builder = client_builder.resolve(&rule.host, SocketAddr::new(rule.address, 0));
+        }
+        let headers = [(ACCEPT_LANGUAGE, self.accept_language.as_deref())]
+            .into_iter()
+            .filter_map(|(name, value)| Some((name, HeaderValue::from_str(value?).ok()?)));
+        client_builder = client_builder.default_headers(HeaderMap::from_iter(headers));
+
+        let client = client_builder.build()?;
+
+        Ok(self.with_client(client))
+    }
+
+    fn with_client(self, client: Client) -> HttpFetch {
+        let max_redirects = match self.redirect_policy {
+            RedirectPolicy::Follow => self.max_redirects,
+            RedirectPolicy::RecordOnly | RedirectPolicy::Error => Some(0),
+        };
+
         HttpFetch {
-            client: Client::new(),
+            client,
+            max_redirects,
+            stream_deadline: self.stream_deadline.unwrap_or(DEFAULT_STREAM_DEADLINE),
+            range_probe: self.range_probe,
+            fetch_rules: self.fetch_rules,
+            host_header: self.host_header,
+            in_flight: self.in_flight,
         }
     }
+}
+
+impl Fetch for HttpFetch {
+    fn new() -> HttpFetch {
+        HttpFetchBuilder::new().build()
+    }
 
     async fn get(&self, url: &str) -> Result<String, Error> {
-        let response = self.client.get(url).send().await?;
-        response.text().await
+        let response = self.fetch(url).await?;
+        Ok(response.body)
+    }
+
+    async fn fetch(&self, url: &str) -> Result<FetchResponse, Error> {
+        let mut current_url = url.to_owned();
+        let mut redirects = vec![];
+        let mut range_probe = self.range_probe;
+        // held until the body is read, redirects are followed on the same permit
+        let _permit = match &self.in_flight {
+            Some(in_flight) => in_flight.acquire().await.ok(),
+            None => None,
+        };
+
+        loop {
+            let rule = self
+                .fetch_rules
+                .iter()
+                .find(|rule| rule.matches(&current_url));
+            let method = rule.map_or(Method::GET, |rule| rule.method.clone());
+            let range = range_probe
+                .filter(|_| method == Method::GET && mime::has_binary_extension(&current_url));
+            let mut request = self.client.request(method, &current_url);
+            for (name, value) in rule.iter().flat_map(|rule| &rule.headers) {
+                request = request.header(name, value);
+            }
+            // checked per hop, a redirect to another host gets that host
+            if let Some(host_header) = &self.host_header {
+                if host_header.applies_to(&current_url) {
+                    request = request.header(HOST, &host_header.host);
+                }
+            }
+            if let Some(bytes) = range {
+                request = request.header(RANGE, format!("bytes=0-{}", bytes - 1));
+            }
+            let response = request.send().await?;
+            let status = response.status();
+            // an empty file has no first bytes, it is requested whole
+            if range.is_some() && status == StatusCode::RANGE_NOT_SATISFIABLE {
+                range_probe = None;
+                continue;
+            }
+            debug!("{} {:?} {}", current_url, response.version(), status);
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .and_then(|location| response.url().join(location).ok());
+
+            if let (true, Some(location)) = (status.is_redirection(), location) {
+                // a redirect that isn't followed is recorded all the same, the response is the redirect
+                redirects.push(Redirect {
+                    from: current_url.clone(),
+                    to: location.to_string(),
+                    status: status.as_u16(),
+                });
+                if redirects.len() <= self.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS) {
+                    current_url = location.to_string();
+                    continue;
+                }
+            }
+
+            let content_type = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|content_type| content_type.to_str().ok())
+                .map(str::to_owned);
+            // decoded as `Response::text` would, the raw bytes are kept to sniff the type
+            let encoding = content_type
+                .as_deref()
+                .and_then(|content_type| {
+                    content_type.split(';').skip(1).find_map(|param| {
+                        let (name, value) = param.split_once('=')?;
+                        (name.trim().eq_ignore_ascii_case("charset"))
+                            .then(|| value.trim().trim_matches('"'))
+                    })
+                })
+                .and_then(|charset| Encoding::for_label(charset.as_bytes()))
+                .unwrap_or(UTF_8);
+            let links = response
+                .headers()
+                .get_all(LINK)
+                .iter()
+                .filter_map(|link| link.to_str().ok())
+                .flat_map(link_header::parse)
+                .filter_map(|mut link| {
+                    link.target = response.url().join(&link.target).ok()?.to_string();
+                    Some(link)
+                })
+                .collect();
+            let is_streaming = content_type.as_deref().is_some_and(mime::is_streaming);
+            let deadline = match response.content_length() {
+                Some(_) => None,
+                None => Some(self.stream_deadline),
+            };
+            let size = match status {
+                StatusCode::PARTIAL_CONTENT => response
+                    .headers()
+                    .get(CONTENT_RANGE)
+                    .and_then(|content_range| content_range.to_str().ok())
+                    .and_then(|content_range| content_range.rsplit_once('/'))
+                    .and_then(|(_, size)| size.trim().parse().ok()),
+                _ => response.content_length(),
+            };
+            let limit = range.map(|bytes| bytes as usize);
+            let bytes = match is_streaming {
+                true => None,
+                false => read_body(response, deadline, limit).await?,
+            };
+            let (body, _, _) = encoding.decode(bytes.as_deref().unwrap_or_default());
+            let partial = range.is_some()
+                && (status == StatusCode::PARTIAL_CONTENT
+                    || size.is_some_and(|size| {
+                        size > bytes.as_deref().unwrap_or_default().len() as u64
+                    }));
+
+            return Ok(FetchResponse {
+                // the partial content is recorded as the full response it stands for
+                status: match status {
+                    StatusCode::PARTIAL_CONTENT if partial => StatusCode::OK.as_u16(),
+                    status => status.as_u16(),
+                },
+                body: body.into_owned(),
+                url: current_url,
+                redirects,
+                content_type,
+                sniffed_type: bytes.as_deref().and_then(mime::sniff),
+                links,
+                streaming: bytes.is_none(),
+                partial,
+                size: size.filter(|_| partial),
+            });
+        }
     }
 }
 
+/// Reads a body, none if it is still being sent at the deadline, or its first `limit` bytes.
+/// Dropping the response aborts the request.
+async fn read_body(
+    mut response: Response,
+    deadline: Option<Duration>,
+    limit: Option<usize>,
+) -> Result<Option<Vec<u8>>, Error> {
+    let mut body = vec![];
+    let read = async {
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if let Some(limit) = limit.filter(|limit| body.len() >= *limit) {
+                body.truncate(limit);
+                break;
+            }
+        }
+        Ok(())
+    };
+
+    let read = match deadline {
+        Some(deadline) => match tokio::time::timeout(deadline, read).await {
+            Ok(read) => read,
+            Err(_) => return Ok(None),
+        },
+        None => read.await,
+    };
+    read.map(|_| Some(body))
+}
+
 #[cfg(test)]
 mod fetch_tests {
-    use wiremock::{matchers::any, Mock, MockServer, ResponseTemplate};
+    use reqwest::Method;
+    use std::time::{Duration, Instant};
+    use tokio::io::AsyncWriteExt;
+    use wiremock::{
+        matchers::{any, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
 
-    use crate::fetch::{Fetch, HttpFetch};
+    use crate::{
+        fetch::{
+            Fetch, FetchResponse, FetchRule, HttpFetch, HttpFetchBuilder, PartialContent, Redirect,
+            RedirectPolicy, ResolveRule, DEFAULT_USER_AGENT,
+        },
+        link_header::HeaderLink,
+        mime::{MediaType, TypeMismatch},
+    };
 
     #[tokio::test]
     async fn get_makes_a_call_and_returns_a_response() {
@@ -45,4 +710,478 @@ mod fetch_tests {
 
         assert_eq!(response.unwrap(), "Hello".to_owned());
     }
+
+    #[tokio::test]
+    async fn fetch_returns_status_and_body() {
+        let f: HttpFetch = Fetch::new();
+
+        let mock_server = MockServer::start().await;
+
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(404)
+                    .set_body_string("Not here")
+                    .insert_header("Link", "</fr>; rel=alternate; hreflang=fr"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let response = f.fetch(&mock_server.uri()).await;
+
+        assert_eq!(
+            response.unwrap(),
+            FetchResponse {
+                status: 404,
+                body: "Not here".to_owned(),
+                url: mock_server.uri(),
+                redirects: vec![],
+                content_type: Some("text/plain".to_owned()),
+                sniffed_type: None,
+                links: vec![HeaderLink {
+                    target: format!("{}/fr", mock_server.uri()),
+                    params: vec![
+                        ("rel".to_owned(), "alternate".to_owned()),
+                        ("hreflang".to_owned(), "fr".to_owned())
+                    ],
+                }],
+                streaming: false,
+                partial: false,
+                size: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_follows_and_records_redirects() {
+        let f: HttpFetch = Fetch::new();
+
+        let mock_server = MockServer::start().await;
+        let uri = mock_server.uri();
+
+        Mock::given(path("/old"))
+            .respond_with(ResponseTemplate::new(301).insert_header("Location", "/new"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/new"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("New"))
+            .mount(&mock_server)
+            .await;
+
+        let response = f.fetch(&format!("{}/old", uri)).await.unwrap();
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, "New".to_owned());
+        assert_eq!(response.url, format!("{}/new", uri));
+        assert_eq!(
+            response.redirects,
+            vec![Redirect {
+                from: format!("{}/old", uri),
+                to: format!("{}/new", uri),
+                status: 301,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_records_the_redirects_it_does_not_follow() {
+        let mock_server = MockServer::start().await;
+        let uri = mock_server.uri();
+
+        for (from, to) in [("/1", "/2"), ("/2", "/3")] {
+            Mock::given(path(from))
+                .respond_with(ResponseTemplate::new(301).insert_header("Location", to))
+                .mount(&mock_server)
+                .await;
+        }
+
+        let limited = HttpFetchBuilder::new().max_redirects(1).build();
+        let record_only = HttpFetchBuilder::new()
+            .redirect_policy(RedirectPolicy::RecordOnly)
+            .build();
+        let limited = limited.fetch(&format!("{}/1", uri)).await.unwrap();
+        let recorded = record_only.fetch(&format!("{}/1", uri)).await.unwrap();
+
+        assert_eq!(limited.status, 301);
+        assert_eq!(limited.url, format!("{}/2", uri));
+        assert_eq!(limited.redirects.len(), 2);
+        assert_eq!(
+            limited.unfollowed_redirect().map(|redirect| &redirect.to),
+            Some(&format!("{}/3", uri))
+        );
+        assert_eq!(recorded.url, format!("{}/1", uri));
+        assert_eq!(
+            recorded.redirects,
+            vec![Redirect {
+                from: format!("{}/1", uri),
+                to: format!("{}/2", uri),
+                status: 301,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_sends_the_configured_user_agent() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(header("user-agent", DEFAULT_USER_AGENT))
+            .respond_with(ResponseTemplate::new(200).set_body_string("default"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(header("user-agent", "my-bot/1.0"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("custom"))
+            .mount(&mock_server)
+            .await;
+
+        let default: HttpFetch = Fetch::new();
+        let custom = HttpFetchBuilder::new().user_agent("my-bot/1.0").build();
+
+        assert_eq!(default.get(&mock_server.uri()).await.unwrap(), "default");
+        assert_eq!(custom.get(&mock_server.uri()).await.unwrap(), "custom");
+    }
+
+    #[test]
+    fn body_sample_truncates_on_a_character_boundary() {
+        let response = FetchResponse {
+            status: 200,
+            body: "abcé".to_owned(),
+            url: "https://a.com".to_owned(),
+            redirects: vec![],
+            content_type: None,
+            sniffed_type: None,
+            links: vec![],
+            streaming: false,
+            partial: false,
+            size: None,
+        };
+
+        assert_eq!(response.body_sample(4), "abc");
+        assert_eq!(response.body_sample(5), "abcé");
+        assert_eq!(response.body_sample(1024), "abcé");
+    }
+
+    #[tokio::test]
+    async fn fetch_decodes_the_charset_and_sniffs_the_body() {
+        let f: HttpFetch = Fetch::new();
+        let mock_server = MockServer::start().await;
+
+        Mock::given(path("/latin1"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(b"<p>caf\xe9</p>".to_vec(), "text/html; charset=ISO-8859-1"),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/report"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(b"%PDF-1.7\n".to_vec(), "text/html"),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let page = f
+            .fetch(&format!("{}/latin1", mock_server.uri()))
+            .await
+            .unwrap();
+        let report = f
+            .fetch(&format!("{}/report", mock_server.uri()))
+            .await
+            .unwrap();
+
+        assert_eq!(page.body, "<p>café</p>");
+        assert_eq!(page.type_mismatch(), None);
+        assert!(page.is_parseable());
+        assert_eq!(report.sniffed_type, Some(MediaType::Pdf));
+        assert_eq!(
+            report.type_mismatch(),
+            Some(TypeMismatch {
+                declared: Some("text/html".to_owned()),
+                sniffed: MediaType::Pdf,
+            })
+        );
+        assert!(!report.is_parseable());
+    }
+
+    #[tokio::test]
+    async fn fetch_only_requests_the_first_bytes_of_binaries() {
+        let mock_server = MockServer::start().await;
+        let pdf = [b"%PDF-1.7".as_slice(), &[0; 4088]].concat();
+
+        Mock::given(path("/report.pdf"))
+            .and(header("range", "bytes=0-1023"))
+            .respond_with(
+                ResponseTemplate::new(206)
+                    .set_body_raw(pdf[..1024].to_vec(), "application/pdf")
+                    .insert_header("Content-Range", "bytes 0-1023/4096"),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/archive.zip"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(vec![b'P'; 8192], "application/zip"),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/empty.zip"))
+            .and(header("range", "bytes=0-1023"))
+            .respond_with(ResponseTemplate::new(416))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/empty.zip"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/page"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("a page"))
+            .mount(&mock_server)
+            .await;
+
+        let f = HttpFetchBuilder::new().range_probe(1024).build();
+        let url = |path: &str| format!("{}{}", mock_server.uri(), path);
+
+        let report = f.fetch(&url("/report.pdf")).await.unwrap();
+        assert_eq!((report.status, report.partial), (200, true));
+        assert!(!report.is_parseable());
+        assert_eq!(
+            report.partial_content(),
+            Some(PartialContent {
+                size: Some(4096),
+                content_type: Some("application/pdf".to_owned()),
+                media_type: Some(MediaType::Pdf),
+            })
+        );
+        // the server ignored the range, the body is cut short
+        let archive = f.fetch(&url("/archive.zip")).await.unwrap();
+        assert_eq!(archive.body.len(), 1024);
+        assert_eq!((archive.partial, archive.size), (true, Some(8192)));
+
+        let empty = f.fetch(&url("/empty.zip")).await.unwrap();
+        assert_eq!((empty.status, empty.partial), (200, false));
+        assert_eq!(
+            f.fetch(&url("/page")).await.unwrap().partial_content(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_gives_up_on_streaming_endpoints() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/events"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw("data: 1\n\n", "text/event-stream"),
+            )
+            .mount(&mock_server)
+            .await;
+        // a chunked body whose end never comes
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            socket
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\
+                      Transfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n",
+                )
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        let f = HttpFetchBuilder::new()
+            .stream_deadline(Duration::from_millis(100))
+            .build();
+
+        let events = f
+            .fetch(&format!("{}/events", mock_server.uri()))
+            .await
+            .unwrap();
+        let long_poll = f.fetch(&format!("http://{}/", address)).await.unwrap();
+
+        assert!(events.streaming);
+        assert_eq!(events.body, "");
+        assert!(!events.is_parseable());
+        assert!(long_poll.streaming);
+        assert_eq!(long_poll.status, 200);
+        assert!(!f.fetch(&mock_server.uri()).await.unwrap().streaming);
+    }
+
+    #[tokio::test]
+    async fn fetch_can_pin_the_http_version() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_string("pinned"))
+            .mount(&mock_server)
+            .await;
+
+        for client in [
+            HttpFetchBuilder::new().http1_only().build(),
+            HttpFetchBuilder::new().http2_prior_knowledge().build(),
+        ] {
+            assert_eq!(client.get(&mock_server.uri()).await.unwrap(), "pinned");
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_only_connects_over_the_selected_ip_family() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_string("over IPv4"))
+            .mount(&mock_server)
+            .await;
+
+        assert_eq!(
+            HttpFetchBuilder::new()
+                .ipv4_only()
+                .build()
+                .get(&mock_server.uri())
+                .await
+                .unwrap(),
+            "over IPv4"
+        );
+        assert!(HttpFetchBuilder::new()
+            .ipv6_only()
+            .build()
+            .get(&mock_server.uri())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn fetch_waits_for_a_request_to_finish_over_the_cap() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(300)))
+            .mount(&mock_server)
+            .await;
+
+        let builder = HttpFetchBuilder::new().max_concurrent_requests(1);
+        let (a, b) = (builder.clone().build(), builder.build());
+        let uri = mock_server.uri();
+        let started_at = Instant::now();
+        let (a, b) = tokio::join!(a.fetch(&uri), b.fetch(&uri));
+
+        assert_eq!((a.unwrap().status, b.unwrap().status), (200, 200));
+        assert!(started_at.elapsed() >= Duration::from_millis(600));
+    }
+
+    #[tokio::test]
+    async fn fetch_sends_the_host_header_to_the_resolved_address() {
+        let mock_server = MockServer::start().await;
+        let port = mock_server.address().port();
+
+        Mock::given(header("host", "example.com"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("through the balancer"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(header("host", format!("site.test:{}", port).as_str()))
+            .respond_with(ResponseTemplate::new(200).set_body_string("before the cutover"))
+            .mount(&mock_server)
+            .await;
+
+        let other_host = MockServer::start().await;
+        Mock::given(header("host", other_host.address().to_string().as_str()))
+            .respond_with(ResponseTemplate::new(200).set_body_string("its own host"))
+            .mount(&other_host)
+            .await;
+
+        let balancer = HttpFetchBuilder::new()
+            .host_header("example.com", vec![mock_server.address().to_string()])
+            .build();
+        let resolved = HttpFetchBuilder::new()
+            .resolve("site.test:127.0.0.1".parse().unwrap())
+            .build();
+
+        assert_eq!(
+            balancer.get(&mock_server.uri()).await.unwrap(),
+            "through the balancer"
+        );
+        assert_eq!(
+            balancer.get(&other_host.uri()).await.unwrap(),
+            "its own host"
+        );
+        assert_eq!(
+            resolved
+                .get(&format!("http://site.test:{}/", port))
+                .await
+                .unwrap(),
+            "before the cutover"
+        );
+    }
+
+    #[test]
+    fn resolve_rule_parses_host_and_address() {
+        assert_eq!(
+            "Example.com:10.0.0.1".parse(),
+            Ok(ResolveRule {
+                host: "example.com".to_owned(),
+                address: "10.0.0.1".parse().unwrap(),
+            })
+        );
+        assert_eq!(
+            "example.com:[::1]"
+                .parse::<ResolveRule>()
+                .unwrap()
+                .to_string(),
+            "example.com:::1"
+        );
+        assert!("example.com".parse::<ResolveRule>().is_err());
+        assert!("example.com:lb.internal".parse::<ResolveRule>().is_err());
+    }
+
+    #[test]
+    fn fetch_rule_parses_pattern_method_and_headers() {
+        let rule = "/downloads/*.zip$ method=head header=X-Token:a:b"
+            .parse::<FetchRule>()
+            .unwrap();
+
+        assert_eq!(rule.method, Method::HEAD);
+        assert_eq!(
+            rule.to_string(),
+            "/downloads/*.zip$ method=HEAD header=x-token:a:b"
+        );
+        assert!(rule.matches("https://a.com/downloads/v1/app.zip"));
+        assert!(!rule.matches("https://a.com/downloads/app.zip?v=1"));
+        assert!(!rule.matches("https://a.com/app.zip"));
+        assert!("downloads/* method=HEAD".parse::<FetchRule>().is_err());
+        assert!("/app/* method=POST".parse::<FetchRule>().is_err());
+        assert!("/app/* backend=browser".parse::<FetchRule>().is_err());
+    }
+
+    #[tokio::test]
+    async fn fetch_applies_the_first_matching_fetch_rule() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("HEAD"))
+            .and(path("/downloads/app.zip"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/items"))
+            .and(header("accept", "application/json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+            .mount(&mock_server)
+            .await;
+
+        let f = HttpFetchBuilder::new()
+            .fetch_rule("/downloads/* method=HEAD".parse().unwrap())
+            .fetch_rule("/api/* header=Accept:application/json".parse().unwrap())
+            .fetch_rule("/api/items header=Accept:text/html".parse().unwrap())
+            .build();
+
+        let download = f
+            .fetch(&format!("{}/downloads/app.zip", mock_server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(download.status, 200);
+        assert_eq!(
+            f.get(&format!("{}/api/items", mock_server.uri()))
+                .await
+                .unwrap(),
+            "[]"
+        );
+    }
 }
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, fmt, fs, io, path::Path};
+
+use crate::data_store::DataStore;
+
+/// Hex encoded SHA-256 of a page body
+pub fn content_hash(body: &str) -> String {
+    format!("{:x}", Sha256::digest(body.as_bytes()))
+}
+
+/// Content hashes of crawled URLs, persisted between crawls
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct FingerprintIndex {
+    hashes: BTreeMap<String, String>,
+}
+
+/// Pages whose content differs from the previous crawl
+#[derive(Debug, PartialEq, Default)]
+pub struct Changes {
+    /// URLs that were not in the index before
+    pub new: Vec<String>,
+    /// URLs whose content hash differs from the one in the index
+    pub changed: Vec<String>,
+}
+
+impl Changes {
+    /// Newline separated list of every new or changed URL
+    pub fn feed(&self) -> String {
+        self.new
+            .iter()
+            .chain(self.changed.iter())
+            .map(|url| format!("{}\n", url))
+            .collect()
+    }
+}
+
+impl fmt::Display for Changes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (title, urls) in [("New pages", &self.new), ("Changed pages", &self.changed)] {
+            writeln!(f, "{} ({}):", title, urls.len())?;
+            for url in urls {
+                writeln!(f, "  {}", url)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FingerprintIndex {
+    pub fn load(path: &Path) -> Result<Self, io::Error> {
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// The index at `path`, empty if there is none yet. An index that can't be read or parsed is
+    /// an error rather than empty, so that it isn't overwritten.
+    pub fn load_or_default(path: &Path) -> Result<Self, io::Error> {
+        match FingerprintIndex::load(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(FingerprintIndex::default()),
+            loaded => loaded,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), io::Error> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn changes<T: DataStore>(&self, store: &T) -> Changes {
+        let mut changes = Changes::default();
+        let mut entries = store.entries();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (url, entry) in entries {
+            let Some(content_hash) = &entry.content_hash else {
+                continue;
+            };
+
+            match self.hashes.get(url) {
+                None => changes.new.push(url.clone()),
+                Some(previous) if previous != content_hash => changes.changed.push(url.clone()),
+                Some(_) => {}
+            }
+        }
+
+        changes
+    }
+
+    /// Records the content hashes of the latest crawl, keeping those of pages not crawled this time
+    pub fn update<T: DataStore>(&mut self, store: &T) {
+        for (url, entry) in store.entries() {
+            if let Some(content_hash) = &entry.content_hash {
+                self.hashes.insert(url.clone(), content_hash.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod fingerprint_tests {
+    use super::{content_hash, Changes, FingerprintIndex};
+    use crate::data_store::{DataStore, Store};
+
+    fn store_with(pages: &[(&str, &str)]) -> Store {
+        let mut s = Store::new();
+        for (url, body) in pages {
+            s.add(url.to_string(), None);
+            s.set_content_hash(url, content_hash(body));
+        }
+        s
+    }
+
+    #[test]
+    fn content_hash_is_stable_for_same_content() {
+        assert_eq!(content_hash("<p>Hi</p>"), content_hash("<p>Hi</p>"));
+        assert_ne!(content_hash("<p>Hi</p>"), content_hash("<p>Bye</p>"));
+    }
+
+    #[test]
+    fn changes_reports_new_and_changed_pages() {
+        let mut index = FingerprintIndex::default();
+        index.update(&store_with(&[
+            ("https://a.com/1", "one"),
+            ("https://a.com/2", "two"),
+        ]));
+
+        let changes = index.changes(&store_with(&[
+            ("https://a.com/1", "one"),
+            ("https://a.com/2", "two, edited"),
+            ("https://a.com/3", "three"),
+        ]));
+
+        assert_eq!(
+            changes,
+            Changes {
+                new: vec!["https://a.com/3".to_owned()],
+                changed: vec!["https://a.com/2".to_owned()],
+            }
+        );
+        assert_eq!(changes.feed(), "https://a.com/3\nhttps://a.com/2\n");
+    }
+
+    #[test]
+    fn load_or_default_only_defaults_missing_indexes() {
+        let dir =
+            std::env::temp_dir().join(format!("url-crawler-fingerprints-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            FingerprintIndex::load_or_default(&dir.join("missing.json")).unwrap(),
+            FingerprintIndex::default()
+        );
+        let corrupt = dir.join("corrupt.json");
+        std::fs::write(&corrupt, "{\"hashes\": ").unwrap();
+        assert!(FingerprintIndex::load_or_default(&corrupt).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
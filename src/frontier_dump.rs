@@ -0,0 +1,166 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    data_store::Error,
+    link::host_with_port,
+    queue::FrontierPolicy,
+    sqlite_store::{self, FrontierUrl, SqliteStore, MAX_ATTEMPTS},
+    url_frontier::QueuedUrl,
+};
+
+/// What a resumed crawl will do with the URLs left in a saved frontier
+#[derive(Debug, PartialEq)]
+pub struct FrontierDump {
+    /// URLs a resumed crawl fetches, in the order the frontier policy hands them out
+    pub pending: Vec<QueuedUrl>,
+    /// URLs still backing off from a failed fetch, skipped by a crawl resumed now
+    pub backing_off: Vec<FrontierUrl>,
+    /// URLs that failed too many times to be fetched again
+    pub given_up: Vec<FrontierUrl>,
+    policy: FrontierPolicy,
+    now: u64,
+}
+
+impl FrontierDump {
+    /// Sorts out the frontier of a saved store, `now` being the time the crawl would resume at
+    pub fn new(frontier: Vec<FrontierUrl>, policy: FrontierPolicy, now: u64) -> Self {
+        let mut queue = policy.queue();
+        let mut backing_off = vec![];
+        let mut given_up = vec![];
+
+        for url in frontier {
+            if url.is_given_up() {
+                given_up.push(url);
+            } else if url.is_backing_off(now) {
+                backing_off.push(url);
+            } else {
+                queue.push(url.queued);
+            }
+        }
+
+        let mut pending = vec![];
+        while let Some(queued) = queue.pop() {
+            pending.push(queued);
+        }
+
+        FrontierDump {
+            pending,
+            backing_off,
+            given_up,
+            policy,
+            now,
+        }
+    }
+
+    /// The frontier of a saved store, as a crawl resumed from it now with `policy` would see it.
+    /// With the random policy the order is only one of the orders the crawl may take.
+    pub fn from_store(store: &SqliteStore, policy: FrontierPolicy) -> Result<Self, Error> {
+        Ok(FrontierDump::new(
+            store.frontier()?,
+            policy,
+            sqlite_store::now(),
+        ))
+    }
+}
+
+impl fmt::Display for FrontierDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the position of each URL in the order it is fetched in, by host
+        let mut hosts = BTreeMap::<String, Vec<(usize, &QueuedUrl)>>::new();
+        for (position, queued) in self.pending.iter().enumerate() {
+            let host = host_with_port(&queued.url).unwrap_or_default();
+            hosts.entry(host).or_default().push((position + 1, queued));
+        }
+
+        writeln!(
+            f,
+            "{} URLs pending on {} hosts, in {:?} order:",
+            self.pending.len(),
+            hosts.len(),
+            self.policy
+        )?;
+        for (host, urls) in &hosts {
+            writeln!(f, "  {} ({} pending)", host, urls.len())?;
+            for (position, queued) in urls {
+                writeln!(
+                    f,
+                    "    #{:<6} depth {:<3} {}",
+                    position, queued.depth, queued.url
+                )?;
+            }
+        }
+
+        if !self.backing_off.is_empty() {
+            writeln!(f, "{} URLs backing off:", self.backing_off.len())?;
+            for url in &self.backing_off {
+                writeln!(
+                    f,
+                    "  {} for another {}s after {} failures",
+                    url.queued.url,
+                    url.retry_at - self.now,
+                    url.attempts
+                )?;
+            }
+        }
+        if !self.given_up.is_empty() {
+            writeln!(
+                f,
+                "{} URLs given up on after {} failures:",
+                self.given_up.len(),
+                MAX_ATTEMPTS
+            )?;
+            for url in &self.given_up {
+                writeln!(f, "  {}", url.queued.url)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod frontier_dump_tests {
+    use super::FrontierDump;
+    use crate::{
+        queue::FrontierPolicy,
+        sqlite_store::{FrontierUrl, MAX_ATTEMPTS},
+        url_frontier::QueuedUrl,
+    };
+
+    fn frontier_url(url: &str, depth: usize, attempts: u32, retry_at: u64) -> FrontierUrl {
+        FrontierUrl {
+            queued: QueuedUrl::new(url.to_owned(), depth),
+            attempts,
+            retry_at,
+        }
+    }
+
+    #[test]
+    fn frontier_dump_orders_pending_urls_like_the_policy_and_sets_aside_failures() {
+        let frontier = vec![
+            frontier_url("https://a.com/deep", 3, 0, 0),
+            frontier_url("https://b.com/", 1, 0, 0),
+            frontier_url("https://a.com/flaky", 1, 1, 1_060),
+            frontier_url("https://a.com/retried", 2, 1, 900),
+            frontier_url("https://a.com/down", 1, MAX_ATTEMPTS, 5_000),
+        ];
+
+        let dump = FrontierDump::new(frontier, FrontierPolicy::Priority, 1_000);
+
+        assert_eq!(
+            dump.pending,
+            vec![
+                QueuedUrl::new("https://b.com/".to_owned(), 1),
+                QueuedUrl::new("https://a.com/retried".to_owned(), 2),
+                QueuedUrl::new("https://a.com/deep".to_owned(), 3),
+            ]
+        );
+        assert_eq!(dump.backing_off.len(), 1);
+        assert_eq!(dump.given_up.len(), 1);
+
+        let dump = dump.to_string();
+        assert!(dump.starts_with("3 URLs pending on 2 hosts, in Priority order:\n  a.com (2 pending)\n    #2      depth 2   https://a.com/retried\n"));
+        assert!(dump.contains("  https://a.com/flaky for another 60s after 1 failures\n"));
+        assert!(dump.contains("URLs given up on after 3 failures:\n  https://a.com/down\n"));
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::{mpsc, oneshot, Notify, RwLock};
+use url::Url;
+
+use crate::{
+    link::LinkDecision,
+    url_frontier::{FrontierMetrics, Lease, URLFrontier, URLFrontierable},
+};
+
+/// Pause and abort requests of a crawl, shared by its workers and handles
+#[derive(Debug, Default)]
+pub struct Control {
+    paused: AtomicBool,
+    aborted: AtomicBool,
+    /// Whether `run()` has returned
+    done: AtomicBool,
+    /// Requests completed so far, successful or not
+    requests: AtomicUsize,
+    changed: Notify,
+}
+
+impl Control {
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::Acquire)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+    }
+
+    /// Records that the crawl has finished, once its workers have stopped
+    pub fn finish(&self) {
+        self.done.store(true, Ordering::Release);
+    }
+
+    /// Records a completed request
+    pub fn progress(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Waits until the crawl is resumed or aborted
+    pub async fn resumed(&self) {
+        self.wait_for(|control| !control.is_paused() || control.is_aborted())
+            .await
+    }
+
+    /// Waits until the crawl is aborted, to cut a wait short
+    pub async fn aborted(&self) {
+        self.wait_for(Control::is_aborted).await
+    }
+
+    /// Waits until the crawl is paused or aborted, to cut a wait short
+    pub async fn interrupted(&self) {
+        self.wait_for(|control| control.is_paused() || control.is_aborted())
+            .await
+    }
+
+    async fn wait_for(&self, state: impl Fn(&Self) -> bool) {
+        loop {
+            // created before looking at the state, so that no change is missed
+            let changed = self.changed.notified();
+            if state(self) {
+                return;
+            }
+            changed.await;
+        }
+    }
+}
+
+/// What a running crawl has done so far
+#[derive(Debug, PartialEq, Clone)]
+pub struct CrawlStats {
+    pub requests: usize,
+    /// URLs waiting in the frontier
+    pub queued: usize,
+    /// URLs dequeued and still being crawled
+    pub in_flight: usize,
+    pub frontier: FrontierMetrics,
+    pub paused: bool,
+    pub aborted: bool,
+}
+
+impl fmt::Display for CrawlStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match (self.aborted, self.paused) {
+            (true, _) => "aborted",
+            (false, true) => "paused",
+            (false, false) => "running",
+        };
+        write!(
+            f,
+            "{}, {} requests, {} queued, {} in flight",
+            state, self.requests, self.queued, self.in_flight
+        )
+    }
+}
+
+/// A seed added through a handle, waiting for the crawl to decide on it like on a link
+#[derive(Debug)]
+pub struct AddedSeed {
+    pub url: String,
+    /// Keeps the crawl from finishing before the seed is queued
+    pub lease: Lease,
+    /// Dropped without a decision if the crawl is over
+    pub decided: oneshot::Sender<LinkDecision>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AddSeedError {
+    InvalidUrl(url::ParseError),
+    /// The crawl has finished or stopped, no more URLs are crawled
+    CrawlDone,
+}
+
+impl fmt::Display for AddSeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddSeedError::InvalidUrl(e) => write!(f, "{}", e),
+            AddSeedError::CrawlDone => write!(f, "the crawl is done"),
+        }
+    }
+}
+
+impl std::error::Error for AddSeedError {}
+
+impl std::convert::From<url::ParseError> for AddSeedError {
+    fn from(err: url::ParseError) -> Self {
+        AddSeedError::InvalidUrl(err)
+    }
+}
+
+/// Controls a crawl from another task while it runs, i.e. a signal handler, got from
+/// [`crate::crawler::Crawler::handle`]. Clones control the same crawl.
+#[derive(Clone)]
+pub struct CrawlHandle {
+    control: Arc<Control>,
+    url_frontier: Arc<RwLock<URLFrontier>>,
+    added_seeds: mpsc::UnboundedSender<AddedSeed>,
+}
+
+impl CrawlHandle {
+    pub fn new(
+        control: Arc<Control>,
+        url_frontier: Arc<RwLock<URLFrontier>>,
+        added_seeds: mpsc::UnboundedSender<AddedSeed>,
+    ) -> Self {
+        CrawlHandle {
+            control,
+            url_frontier,
+            added_seeds,
+        }
+    }
+
+    /// Holds the workers back once they are done with the URL they are crawling
+    pub fn pause(&self) {
+        self.control.paused.store(true, Ordering::Release);
+        self.control.changed.notify_waiters();
+    }
+
+    pub fn resume(&self) {
+        self.control.paused.store(false, Ordering::Release);
+        self.control.changed.notify_waiters();
+    }
+
+    /// Stops the crawl once the workers are done with the URLs they are crawling, `run()` then
+    /// returns what was crawled so far
+    pub fn abort(&self) {
+        self.control.aborted.store(true, Ordering::Release);
+        self.control.changed.notify_waiters();
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.control.is_aborted()
+    }
+
+    /// Whether the crawl has finished, `run()` having returned
+    pub fn is_done(&self) -> bool {
+        self.control.is_done()
+    }
+
+    /// Queues a URL to crawl at depth 0, returning whether it is crawled. It goes through the
+    /// checks of the links found on pages (scope, robots.txt, duplicates, quotas, ...) and its
+    /// links are scoped by the seeds the crawl was built with. Waits for `run()` to be called,
+    /// and fails once the crawl is done.
+    pub async fn add_seed(&self, url: &str) -> Result<LinkDecision, AddSeedError> {
+        Url::parse(url)?;
+        if self.control.is_aborted() || self.control.is_done() {
+            return Err(AddSeedError::CrawlDone);
+        }
+        let lease = self
+            .url_frontier
+            .read()
+            .await
+            .lease()
+            .ok_or(AddSeedError::CrawlDone)?;
+
+        let (decided, decision) = oneshot::channel();
+        self.added_seeds
+            .send(AddedSeed {
+                url: url.to_owned(),
+                lease,
+                decided,
+            })
+            .map_err(|_| AddSeedError::CrawlDone)?;
+        decision.await.map_err(|_| AddSeedError::CrawlDone)
+    }
+
+    pub async fn stats(&self) -> CrawlStats {
+        let url_frontier = self.url_frontier.read().await;
+
+        CrawlStats {
+            requests: self.control.requests.load(Ordering::Relaxed),
+            queued: url_frontier.queue_len(),
+            in_flight: url_frontier.activity().in_flight(),
+            frontier: url_frontier.metrics(),
+            paused: self.control.is_paused(),
+            aborted: self.control.is_aborted(),
+        }
+    }
+}
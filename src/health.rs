@@ -0,0 +1,229 @@
+use log::warn;
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// Liveness and readiness of the crawls of the process, for orchestrators supervising long crawls
+#[derive(Debug)]
+pub struct Health {
+    /// How long a running crawl may go without completing a request before it counts as stalled
+    stall_timeout: Duration,
+    /// Crawls running, several with isolated seeds
+    running: AtomicUsize,
+    requests: AtomicUsize,
+    last_progress: Mutex<Instant>,
+    /// Workers waiting on purpose, for a crawl window, a paused host or a paused crawl
+    waiting: AtomicUsize,
+}
+
+/// Keeps the crawls from counting as stalled while a worker waits on purpose, the stall timeout
+/// starts over once it is dropped
+#[derive(Debug)]
+pub struct Waiting<'a> {
+    health: &'a Health,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.health.waiting.fetch_sub(1, Ordering::Relaxed);
+        *self.health.last_progress.lock().unwrap() = Instant::now();
+    }
+}
+
+impl Health {
+    pub fn new(stall_timeout: Duration) -> Self {
+        Health {
+            stall_timeout,
+            running: AtomicUsize::new(0),
+            requests: AtomicUsize::new(0),
+            last_progress: Mutex::new(Instant::now()),
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    /// Records that a crawl's workers started
+    pub fn start(&self) {
+        self.running.fetch_add(1, Ordering::Relaxed);
+        *self.last_progress.lock().unwrap() = Instant::now();
+    }
+
+    /// Records that a crawl's workers are done
+    pub fn finish(&self) {
+        self.running.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Records a completed request, successful or not
+    pub fn progress(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        *self.last_progress.lock().unwrap() = Instant::now();
+    }
+
+    /// Records that a worker waits on purpose until the guard is dropped, i.e. outside of the
+    /// crawl windows, rather than being stuck
+    pub fn waiting(&self) -> Waiting<'_> {
+        self.waiting.fetch_add(1, Ordering::Relaxed);
+        Waiting { health: self }
+    }
+
+    /// Whether a running crawl went longer than the stall timeout without completing a request,
+    /// with no worker waiting on purpose
+    pub fn is_stalled(&self) -> bool {
+        self.running.load(Ordering::Relaxed) > 0
+            && self.waiting.load(Ordering::Relaxed) == 0
+            && self.last_progress.lock().unwrap().elapsed() > self.stall_timeout
+    }
+
+    /// Status and body of a health endpoint: `/healthz` fails once a running crawl stalls,
+    /// `/readyz` only succeeds while a crawl is running
+    pub fn check(&self, path: &str) -> (u16, String) {
+        let running = self.running.load(Ordering::Relaxed);
+        let requests = self.requests.load(Ordering::Relaxed);
+        let since_progress = self.last_progress.lock().unwrap().elapsed();
+
+        match path {
+            "/healthz" if self.is_stalled() => (
+                503,
+                format!(
+                    "stalled: no request completed for {}s\n",
+                    since_progress.as_secs()
+                ),
+            ),
+            "/healthz" => (200, "ok\n".to_owned()),
+            "/readyz" if running == 0 => (503, "not crawling\n".to_owned()),
+            "/readyz" => (
+                200,
+                format!(
+                    "ok: {} requests, last {}s ago\n",
+                    requests,
+                    since_progress.as_secs()
+                ),
+            ),
+            _ => (404, "not found\n".to_owned()),
+        }
+    }
+}
+
+/// Answers `/healthz` and `/readyz` requests on the listener until its task is aborted
+pub async fn serve(listener: TcpListener, health: Arc<Health>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("Could not accept a health check - {}", e);
+                continue;
+            }
+        };
+        let health = health.clone();
+        tokio::spawn(async move {
+            if let Err(e) = respond(stream, &health).await {
+                warn!("Could not answer a health check - {}", e);
+            }
+        });
+    }
+}
+
+async fn respond(mut stream: TcpStream, health: &Health) -> Result<(), io::Error> {
+    // the request line is all that is needed, and fits the first read of a probe
+    let mut request = [0; 1024];
+    let read = stream.read(&mut request).await?;
+    let request = String::from_utf8_lossy(&request[..read]);
+    let path = request
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap_or("/");
+
+    let (status, body) = health.check(path);
+    let reason = match status {
+        200 => "OK",
+        404 => "Not Found",
+        _ => "Service Unavailable",
+    };
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {} {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{}",
+                status,
+                reason,
+                body.len(),
+                body
+            )
+            .as_bytes(),
+        )
+        .await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod health_tests {
+    use std::{sync::Arc, time::Duration};
+    use tokio::net::TcpListener;
+
+    use super::{serve, Health};
+
+    #[test]
+    fn health_reflects_running_and_stalled_crawls() {
+        let health = Health::new(Duration::from_secs(60));
+
+        assert_eq!(health.check("/healthz").0, 200);
+        assert_eq!(health.check("/readyz"), (503, "not crawling\n".to_owned()));
+        assert!(!health.is_stalled());
+
+        health.start();
+        health.progress();
+        assert_eq!(
+            health.check("/readyz"),
+            (200, "ok: 1 requests, last 0s ago\n".to_owned())
+        );
+        health.finish();
+        assert_eq!(health.check("/readyz").0, 503);
+        assert_eq!(health.check("/metrics").0, 404);
+
+        let stalled = Health::new(Duration::ZERO);
+        stalled.start();
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(stalled.check("/healthz").0, 503);
+        assert!(stalled.is_stalled());
+        // waiting for a crawl window isn't stalling
+        let waiting = stalled.waiting();
+        assert_eq!(stalled.check("/healthz").0, 200);
+        drop(waiting);
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(stalled.is_stalled());
+        // a finished crawl can't stall
+        stalled.finish();
+        assert!(!stalled.is_stalled());
+    }
+
+    #[tokio::test]
+    async fn serve_answers_health_checks_over_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let health = Arc::new(Health::new(Duration::from_secs(60)));
+        let server = tokio::spawn(serve(listener, health.clone()));
+
+        let readyz = format!("http://{}/readyz", addr);
+        let response = reqwest::get(&readyz).await.unwrap();
+        assert_eq!(response.status(), 503);
+
+        health.start();
+        let response = reqwest::get(&readyz).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.text().await.unwrap(),
+            "ok: 0 requests, last 0s ago\n"
+        );
+
+        server.abort();
+    }
+}
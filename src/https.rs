@@ -0,0 +1,147 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::Mutex,
+};
+use url::Url;
+
+use crate::{data_store::DataStore, fetch::Fetch};
+
+/// Upgrades http:// URLs to https://, once a host has been verified to serve https
+#[derive(Default)]
+pub struct HttpsUpgrade {
+    hosts: Mutex<HashMap<String, bool>>,
+}
+
+impl HttpsUpgrade {
+    pub fn new() -> Self {
+        HttpsUpgrade::default()
+    }
+
+    async fn serves_https<F: Fetch>(&self, client: &F, origin: &str) -> bool {
+        if let Some(serves_https) = self.hosts.lock().unwrap().get(origin) {
+            return *serves_https;
+        }
+
+        let serves_https = client.fetch(&format!("{}/", origin)).await.is_ok();
+        self.hosts
+            .lock()
+            .unwrap()
+            .insert(origin.to_owned(), serves_https);
+
+        serves_https
+    }
+
+    /// Returns the https:// version of a http:// URL, if its host resolves over https
+    pub async fn upgrade<F: Fetch>(&self, client: &F, url: &str) -> Option<String> {
+        let mut url = Url::parse(url).ok()?;
+        if url.scheme() != "http" || url.set_scheme("https").is_err() {
+            return None;
+        }
+
+        let origin = url.origin().ascii_serialization();
+        if self.serves_https(client, &origin).await {
+            Some(url.to_string())
+        } else {
+            None
+        }
+    }
+}
+
+/// Pages that still link to plain http:// URLs
+#[derive(Debug, PartialEq, Default)]
+pub struct InsecureLinks(pub BTreeMap<String, Vec<String>>);
+
+impl InsecureLinks {
+    pub fn from_store<T: DataStore>(store: &T) -> Self {
+        let mut pages = BTreeMap::new();
+
+        for (url, entry) in store.entries() {
+            let mut links = entry
+                .urls_found
+                .iter()
+                .filter(|link| link.starts_with("http://"))
+                .cloned()
+                .collect::<Vec<_>>();
+            links.sort();
+            links.dedup();
+
+            if !links.is_empty() {
+                pages.insert(url.clone(), links);
+            }
+        }
+
+        InsecureLinks(pages)
+    }
+}
+
+impl fmt::Display for InsecureLinks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Pages linking to plain HTTP ({}):", self.0.len())?;
+        for (page, links) in &self.0 {
+            writeln!(f, "  {}", page)?;
+            for link in links {
+                writeln!(f, "    {}", link)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod https_tests {
+    use std::collections::BTreeMap;
+    use wiremock::MockServer;
+
+    use super::{HttpsUpgrade, InsecureLinks};
+    use crate::{
+        data_store::{DataStore, Store},
+        fetch::{Fetch, HttpFetch},
+    };
+
+    #[tokio::test]
+    async fn upgrade_keeps_url_when_host_does_not_serve_https() {
+        let mock_server = MockServer::start().await;
+        let client: HttpFetch = Fetch::new();
+        let upgrade = HttpsUpgrade::new();
+
+        let actual = upgrade
+            .upgrade(&client, &format!("{}/page", mock_server.uri()))
+            .await;
+
+        assert_eq!(actual, None);
+    }
+
+    #[tokio::test]
+    async fn upgrade_ignores_urls_that_are_not_http() {
+        let client: HttpFetch = Fetch::new();
+        let upgrade = HttpsUpgrade::new();
+
+        assert_eq!(upgrade.upgrade(&client, "https://a.com/").await, None);
+    }
+
+    #[test]
+    fn insecure_links_lists_plain_http_links_per_page() {
+        let mut s = Store::new();
+        s.add(
+            "https://a.com".to_owned(),
+            Some("http://a.com/old".to_owned()),
+        );
+        s.add(
+            "https://a.com".to_owned(),
+            Some("https://a.com/new".to_owned()),
+        );
+        s.add("https://a.com/new".to_owned(), None);
+
+        let actual = InsecureLinks::from_store(&s);
+
+        assert_eq!(
+            actual,
+            InsecureLinks(BTreeMap::from([(
+                "https://a.com".to_owned(),
+                vec!["http://a.com/old".to_owned()]
+            )]))
+        );
+    }
+}
@@ -1,5 +1,55 @@
+pub mod abort;
+pub mod alternates;
+pub mod anonymize;
+pub mod bench;
+pub mod block;
+pub mod budget;
+pub mod compare;
+pub mod concurrency;
+pub mod config;
+pub mod crawler;
 pub mod data_store;
+pub mod dedupe;
+pub mod delay_formula;
+pub mod dns;
+#[cfg(feature = "doh")]
+pub mod doh;
+pub mod duplicates;
+pub mod event_log;
+pub mod export;
 pub mod fetch;
+pub mod fingerprint;
+pub mod frontier_dump;
+pub mod handle;
+pub mod health;
+pub mod https;
+pub mod limits;
 pub mod link;
+pub mod link_header;
+pub mod mime;
+pub mod mobile;
+#[cfg(feature = "object-store")]
+pub mod object_store;
+pub mod parked;
 pub mod parser;
+pub mod policy;
+pub mod politeness;
+pub mod porcelain;
+pub mod prune;
+pub mod queue;
+pub mod quota;
+pub mod robots;
+pub mod sample;
+pub mod scheduler;
+pub mod search;
+pub mod service;
+pub mod sitemap;
+pub mod snapshot;
+pub mod sqlite_store;
+pub mod stats;
+pub mod summary;
 pub mod url_frontier;
+pub mod watched_store;
+pub mod webhook;
+pub mod window;
+pub mod xml;
@@ -0,0 +1,49 @@
+use std::fs;
+
+/// File descriptors left for what isn't a connection: stdio, output files, the SQLite store and
+/// the DNS resolver
+pub const RESERVED_FILES: usize = 64;
+
+/// Soft limit on the open files of the process, `ulimit -n`, when it can be read
+pub fn open_files_limit() -> Option<usize> {
+    // procfs is Linux only, elsewhere the requests are only capped when asked to
+    parse_open_files_limit(&fs::read_to_string("/proc/self/limits").ok()?)
+}
+
+/// Soft limit of the `Max open files` line of a `/proc/<pid>/limits` table
+fn parse_open_files_limit(limits: &str) -> Option<usize> {
+    limits
+        .lines()
+        .find_map(|line| line.strip_prefix("Max open files"))
+        .and_then(|values| values.split_whitespace().next())
+        // `unlimited` needs no cap
+        .and_then(|soft| soft.parse().ok())
+}
+
+/// Requests a crawl may keep in flight at once without running out of file descriptors, none if
+/// the limit of the process is unknown or unlimited. Half of the descriptors left for connections
+/// are kept for idle keep-alive connections.
+pub fn default_max_concurrent_requests() -> Option<usize> {
+    open_files_limit().map(|limit| (limit.saturating_sub(RESERVED_FILES) / 2).max(1))
+}
+
+#[cfg(test)]
+mod limits_tests {
+    use super::parse_open_files_limit;
+
+    #[test]
+    fn parse_open_files_limit_reads_the_soft_limit() {
+        let limits = "Limit                     Soft Limit           Hard Limit           Units     \n\
+                      Max processes             63704                63704                processes \n\
+                      Max open files            1024                 524288               files     \n";
+
+        assert_eq!(parse_open_files_limit(limits), Some(1024));
+        assert_eq!(
+            parse_open_files_limit(
+                "Max open files            unlimited            unlimited            files"
+            ),
+            None
+        );
+        assert_eq!(parse_open_files_limit(""), None);
+    }
+}
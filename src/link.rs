@@ -1,7 +1,10 @@
 use addr::parse_domain_name;
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, sync::Arc};
 use url::{ParseError, Url};
 
+use crate::policy::UrlPolicy;
+
 #[derive(Debug, PartialEq)]
 pub enum Error {
     ParseError(ParseError),
@@ -20,12 +23,49 @@ impl std::convert::From<url::ParseError> for Error {
     }
 }
 
+/// Determines how far the crawl reaches beyond the seed domain
+#[derive(clap::ValueEnum, PartialEq, Debug, Clone, Copy, Default)]
+pub enum Scope {
+    /// Only crawl URLs on the seed domain
+    #[default]
+    SameDomain,
+    /// Fetch off-site URLs once to verify their status but do not parse them
+    ExternalOnce,
+    /// Crawl every discovered URL, regardless of domain
+    AllDomains,
+}
+
+impl Scope {
+    /// Whether a discovered URL should be added to the URL frontier
+    pub fn follows(&self, is_internal: bool) -> bool {
+        match self {
+            Scope::SameDomain => is_internal,
+            Scope::ExternalOnce | Scope::AllDomains => true,
+        }
+    }
+
+    /// Whether the content of a fetched URL should be parsed for further links
+    pub fn parses(&self, is_internal: bool) -> bool {
+        match self {
+            Scope::SameDomain | Scope::ExternalOnce => is_internal,
+            Scope::AllDomains => true,
+        }
+    }
+}
+
 #[derive(PartialEq, PartialOrd, Debug, Clone)]
 pub struct UrlParts {
     sub_domain: String,
     root_domain: String,
 }
 
+impl UrlParts {
+    /// Registrable domain, i.e. `example.co.uk` for `www.example.co.uk`
+    pub fn root_domain(&self) -> &str {
+        &self.root_domain
+    }
+}
+
 pub fn url_parts(url: &str) -> Result<UrlParts, Error> {
     let parsed_url = Url::parse(url)?;
 
@@ -56,26 +96,143 @@ pub fn url_parts(url: &str) -> Result<UrlParts, Error> {
     })
 }
 
-pub fn process_url(url: String, original_url: &str) -> String {
-    if Url::parse(&url) == Err(ParseError::RelativeUrlWithoutBase) {
-        let original_url = Url::parse(original_url).unwrap();
-        let absolute_url = original_url.join(&url);
-        absolute_url.unwrap().as_str().to_owned()
-    } else {
-        url
+/// Resolves a URL found on a page against the page's URL, `None` if it can't be resolved
+pub fn process_url(url: &str, page_url: &str) -> Option<String> {
+    match Url::parse(url) {
+        Ok(_) => Some(url.to_owned()),
+        Err(ParseError::RelativeUrlWithoutBase) => Url::parse(page_url)
+            .and_then(|page_url| page_url.join(url))
+            .ok()
+            .map(|absolute_url| absolute_url.to_string()),
+        Err(_) => None,
+    }
+}
+
+/// Normalised form of a URL (lowercase scheme and host, default port removed, ...), or the URL as is if it doesn't parse
+pub fn normalise_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(url) => url.to_string(),
+        Err(_) => url.to_owned(),
+    }
+}
+
+/// Host of a URL, with its port when it isn't the scheme's default one
+pub fn host_with_port(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+
+    match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => Some(format!("{}:{}", host, port)),
+        (Some(host), None) => Some(host.to_owned()),
+        _ => None,
     }
 }
 
-pub fn filter_url(url: String, original_url_parts: Arc<Result<UrlParts, Error>>) -> Option<String> {
+/// Where on its page a link was found
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct LinkSource {
+    /// Ordinal of the link among the links found on the page, from 1
+    pub position: usize,
+    /// Name of the element the link was found in, i.e. `a` or `iframe`, none for the links of
+    /// `Link` headers, redirects and forms
+    pub tag: Option<String>,
+    /// Tokens of the element's `rel` attribute, i.e. `nofollow`
+    pub rel: Vec<String>,
+}
+
+/// What happens to a link discovered on a page
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkDecision {
+    /// Added to the URL frontier
+    Enqueue,
+    /// Outside of the crawl scope
+    SkipExternal,
+    /// Not a http(s) URL, i.e. `mailto:` or `javascript:`
+    SkipScheme,
+    /// Excluded by robots.txt or another crawl rule
+    SkipFiltered,
+    /// Already visited
+    SkipDuplicate,
+    /// Over the page's outlink cap or another crawl budget
+    SkipBudget,
+    /// Could not be parsed as a URL
+    Invalid,
+}
+
+impl std::fmt::Display for LinkDecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            LinkDecision::Enqueue => "enqueue",
+            LinkDecision::SkipExternal => "skip-external",
+            LinkDecision::SkipScheme => "skip-scheme",
+            LinkDecision::SkipFiltered => "skip-filtered",
+            LinkDecision::SkipDuplicate => "skip-duplicate",
+            LinkDecision::SkipBudget => "skip-budget",
+            LinkDecision::Invalid => "invalid",
+        };
+        f.pad(name)
+    }
+}
+
+pub fn filter_url(url: String, original_url_parts: Arc<Result<UrlParts, Error>>) -> LinkDecision {
+    let Ok(parsed_url) = Url::parse(&url) else {
+        return LinkDecision::Invalid;
+    };
+
+    if !matches!(parsed_url.scheme(), "http" | "https") {
+        return LinkDecision::SkipScheme;
+    }
+
     let current_url_parts = url_parts(&url);
-    let original_url_parts = original_url_parts.clone();
     if current_url_parts == *original_url_parts {
-        Some(url.to_owned())
+        LinkDecision::Enqueue
     } else {
-        None
+        LinkDecision::SkipExternal
     }
 }
 
+/// Whether the URL belongs to the domain of any of the seed URLs
+pub fn is_internal(url: &str, seeds_url_parts: &[Arc<Result<UrlParts, Error>>]) -> bool {
+    seeds_url_parts
+        .iter()
+        .any(|url_parts| filter_url(url.to_owned(), url_parts.clone()) == LinkDecision::Enqueue)
+}
+
+/// Resolves a link found on a page and asks the URL policy whether it should be enqueued
+pub fn process_link(url: String, page_url: &str, policy: &dyn UrlPolicy) -> (String, LinkDecision) {
+    let absolute_url = match Url::parse(&url) {
+        Ok(_) => url,
+        Err(ParseError::RelativeUrlWithoutBase) => {
+            match Url::parse(page_url).and_then(|page_url| page_url.join(&url)) {
+                Ok(absolute_url) => absolute_url.to_string(),
+                Err(_) => return (url, LinkDecision::Invalid),
+            }
+        }
+        Err(_) => return (url, LinkDecision::Invalid),
+    };
+
+    let decision = policy.decide(&absolute_url, page_url);
+    (absolute_url, decision)
+}
+
+/// Orders a page's links so that the first link to each host comes first, keeping document order otherwise
+pub fn unique_hosts_first(links: Vec<String>, page_url: &str) -> Vec<String> {
+    let page_url = Url::parse(page_url).ok();
+    let mut hosts = HashSet::new();
+
+    let (firsts, rest): (Vec<_>, Vec<_>) = links.into_iter().partition(|link| {
+        let host = match &page_url {
+            Some(page_url) => page_url.join(link).ok(),
+            None => Url::parse(link).ok(),
+        }
+        .and_then(|url| url.host_str().map(|host| host.to_owned()));
+
+        hosts.insert(host)
+    });
+
+    firsts.into_iter().chain(rest).collect()
+}
+
 #[cfg(test)]
 mod link_tests {
     use std::sync::Arc;
@@ -84,7 +241,33 @@ mod link_tests {
     use url::ParseError;
 
     use super::url_parts;
-    use crate::link::{filter_url, process_url, UrlParts};
+    use crate::link::{
+        filter_url, is_internal, process_link, process_url, unique_hosts_first, LinkDecision,
+        Scope, UrlParts,
+    };
+    use crate::policy::ScopePolicy;
+
+    #[test]
+    fn unique_hosts_first_moves_first_link_to_each_host_forward() {
+        let links = vec![
+            "/one".to_owned(),
+            "/two".to_owned(),
+            "https://b.com/one".to_owned(),
+            "https://b.com/two".to_owned(),
+            "https://c.com/".to_owned(),
+        ];
+
+        assert_eq!(
+            unique_hosts_first(links, "https://a.com/"),
+            vec![
+                "/one",
+                "https://b.com/one",
+                "https://c.com/",
+                "/two",
+                "https://b.com/two"
+            ]
+        );
+    }
 
     #[test]
     fn url_parts_constructs_url_with_www_correctly() {
@@ -133,14 +316,21 @@ mod link_tests {
 
     #[test]
     fn process_url_converts_relative_urls_to_absolute() {
-        let link = "/users".to_owned();
-        let expected = "https://github.com/users".to_owned();
+        let link = "/users";
+        let expected = Some("https://github.com/users".to_owned());
 
         let actual = process_url(link, "https://github.com");
 
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn process_url_returns_none_if_url_cannot_be_resolved() {
+        assert_eq!(process_url("//[", "https://github.com"), None);
+        assert_eq!(process_url("http://[::1", "https://github.com"), None);
+        assert_eq!(process_url("/users", "not a url"), None);
+    }
+
     #[test]
     fn filter_url_filters_out_external_domain_urls() {
         let link = "https://www.stackoverflow.com".to_owned();
@@ -150,7 +340,7 @@ mod link_tests {
         };
 
         let actual = filter_url(link, Arc::new(Ok(original_url_parts)));
-        let expected = None;
+        let expected = LinkDecision::SkipExternal;
 
         assert_eq!(actual, expected);
     }
@@ -164,8 +354,87 @@ mod link_tests {
         };
 
         let actual = filter_url(link, Arc::new(Ok(original_url_parts)));
-        let expected = Some("https://google.com".to_owned());
+        let expected = LinkDecision::Enqueue;
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn scope_same_domain_follows_and_parses_internal_urls_only() {
+        let scope = Scope::SameDomain;
+
+        assert!(scope.follows(true));
+        assert!(!scope.follows(false));
+        assert!(scope.parses(true));
+        assert!(!scope.parses(false));
+    }
+
+    #[test]
+    fn scope_external_once_follows_external_urls_without_parsing_them() {
+        let scope = Scope::ExternalOnce;
+
+        assert!(scope.follows(false));
+        assert!(!scope.parses(false));
+        assert!(scope.parses(true));
+    }
+
+    #[test]
+    fn scope_all_domains_follows_and_parses_external_urls() {
+        let scope = Scope::AllDomains;
+
+        assert!(scope.follows(false));
+        assert!(scope.parses(false));
+    }
+
+    #[test]
+    fn is_internal_matches_url_against_every_seed_domain() {
+        let seeds_url_parts = ["https://google.com", "https://github.com"]
+            .into_iter()
+            .map(|seed| Arc::new(url_parts(seed)))
+            .collect::<Vec<_>>();
+
+        assert!(is_internal("https://github.com/users", &seeds_url_parts));
+        assert!(is_internal("https://www.google.com/maps", &seeds_url_parts));
+        assert!(!is_internal(
+            "https://www.stackoverflow.com",
+            &seeds_url_parts
+        ));
+    }
+
+    #[test]
+    fn filter_url_skips_non_http_schemes() {
+        let original_url_parts = Arc::new(url_parts("https://google.com"));
+
+        let actual = filter_url("mailto:hi@google.com".to_owned(), original_url_parts);
+
+        assert_eq!(actual, LinkDecision::SkipScheme);
+    }
+
+    #[test]
+    fn process_link_resolves_and_classifies_links() {
+        let seeds_url_parts = vec![Arc::new(url_parts("https://google.com"))];
+        let same_domain = ScopePolicy::new(seeds_url_parts.clone(), Scope::SameDomain);
+        let all_domains = ScopePolicy::new(seeds_url_parts, Scope::AllDomains);
+        let page = "https://google.com/about/";
+
+        assert_eq!(
+            process_link("team".to_owned(), page, &same_domain),
+            (
+                "https://google.com/about/team".to_owned(),
+                LinkDecision::Enqueue
+            )
+        );
+        assert_eq!(
+            process_link("https://github.com".to_owned(), page, &same_domain),
+            ("https://github.com".to_owned(), LinkDecision::SkipExternal)
+        );
+        assert_eq!(
+            process_link("https://github.com".to_owned(), page, &all_domains),
+            ("https://github.com".to_owned(), LinkDecision::Enqueue)
+        );
+        assert_eq!(
+            process_link("http://[::1".to_owned(), page, &same_domain),
+            ("http://[::1".to_owned(), LinkDecision::Invalid)
+        );
+    }
 }
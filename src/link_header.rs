@@ -0,0 +1,121 @@
+/// A link of an HTTP `Link` header, i.e. `<https://a.com/fr>; rel="alternate"; hreflang="fr"`
+#[derive(Debug, PartialEq, Clone)]
+pub struct HeaderLink {
+    /// The target, resolved against the response URL once fetched
+    pub target: String,
+    /// Parameters with their lowercase name, unquoted, in the order they were given
+    pub params: Vec<(String, String)>,
+}
+
+impl HeaderLink {
+    /// Value of the first parameter with this (lowercase) name
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Whether the link has this relation type, `rel` can list several separated by spaces
+    pub fn has_rel(&self, rel: &str) -> bool {
+        self.param("rel").is_some_and(|rels| {
+            rels.split_whitespace()
+                .any(|value| value.eq_ignore_ascii_case(rel))
+        })
+    }
+}
+
+/// Reads the links of a `Link` header value, skipping the rest of the value once it is malformed.
+/// Targets are in angle brackets and quoted parameters may contain commas and semicolons.
+pub fn parse(header: &str) -> Vec<HeaderLink> {
+    let mut links = vec![];
+    let mut rest = header;
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        let Some((target, after)) = rest
+            .strip_prefix('<')
+            .and_then(|after| after.split_once('>'))
+        else {
+            break;
+        };
+        rest = after;
+
+        let mut params = vec![];
+        while let Some(after) = rest.trim_start().strip_prefix(';') {
+            let after = after.trim_start();
+            let name_end = after.find(['=', ';', ',']).unwrap_or(after.len());
+            let name = after[..name_end].trim().to_lowercase();
+            rest = &after[name_end..];
+
+            let value = match rest.strip_prefix('=').map(str::trim_start) {
+                Some(after) => match after.strip_prefix('"') {
+                    Some(quoted) => {
+                        let end = quoted.find('"').unwrap_or(quoted.len());
+                        rest = &quoted[(end + 1).min(quoted.len())..];
+                        quoted[..end].to_owned()
+                    }
+                    None => {
+                        let end = after.find([';', ',']).unwrap_or(after.len());
+                        rest = &after[end..];
+                        after[..end].trim().to_owned()
+                    }
+                },
+                None => String::new(),
+            };
+            if !name.is_empty() {
+                params.push((name, value));
+            }
+        }
+
+        links.push(HeaderLink {
+            target: target.trim().to_owned(),
+            params,
+        });
+    }
+
+    links
+}
+
+#[cfg(test)]
+mod link_header_tests {
+    use super::{parse, HeaderLink};
+
+    #[test]
+    fn parse_reads_every_link_and_its_parameters() {
+        let links = parse(
+            "<https://a.com/fr?a=1,2>; rel=\"alternate\"; HrefLang=fr, \
+             </style.css>;rel=preload;as=style,<https://a.com/2>; rel=\"next prefetch\"; title=\"a; b, c\"",
+        );
+
+        assert_eq!(
+            links,
+            vec![
+                HeaderLink {
+                    target: "https://a.com/fr?a=1,2".to_owned(),
+                    params: vec![
+                        ("rel".to_owned(), "alternate".to_owned()),
+                        ("hreflang".to_owned(), "fr".to_owned())
+                    ],
+                },
+                HeaderLink {
+                    target: "/style.css".to_owned(),
+                    params: vec![
+                        ("rel".to_owned(), "preload".to_owned()),
+                        ("as".to_owned(), "style".to_owned())
+                    ],
+                },
+                HeaderLink {
+                    target: "https://a.com/2".to_owned(),
+                    params: vec![
+                        ("rel".to_owned(), "next prefetch".to_owned()),
+                        ("title".to_owned(), "a; b, c".to_owned())
+                    ],
+                },
+            ]
+        );
+        assert!(links[2].has_rel("next"));
+        assert!(!links[1].has_rel("next"));
+        assert_eq!(parse("not a link"), vec![]);
+    }
+}
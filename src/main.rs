@@ -1,169 +1,1986 @@
-use clap::Parser as ClapParser;
+use clap::{Args as ClapArgs, CommandFactory, Parser as ClapParser, Subcommand, ValueEnum};
+use clap_complete::Shell;
 use env_logger::Env;
 use log::{info, warn};
-use std::{io::Error, sync::Arc};
-use tokio::sync::RwLock;
-use tokio::task::JoinSet;
+use regex::Regex;
+use std::{
+    env,
+    ffi::OsString,
+    fmt,
+    fs::{self, File},
+    io::{self, Error, IsTerminal, Write},
+    iter,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{net::TcpListener, task::JoinSet};
+use url::Url;
 use url_crawler::{
-    data_store::{DataStore, Store},
-    fetch::{Fetch, HttpFetch},
-    link::{filter_url, process_url, url_parts},
-    parser::Parser,
-    url_frontier::{URLFrontier, URLFrontierBuilder, URLFrontierable},
+    abort,
+    alternates::AlternatesAudit,
+    anonymize::Anonymizer,
+    bench::{AllocationStats, BenchReport, BenchSite, CountingAllocator},
+    budget::ByteSize,
+    compare::{compare, parse_url_list},
+    concurrency::Workers,
+    config,
+    crawler::{CrawlEvent, CrawlerBuilder},
+    data_store::{DataStore, Store, StoreBackend},
+    dedupe::DedupeKey,
+    delay_formula::DelayFormula,
+    dns::DnsCache,
+    duplicates::DuplicateMetadata,
+    event_log::{self, EventLog, Timeline},
+    export::{self, Emit, OutputFormat, PageEvent, Tags},
+    fetch::{
+        FetchRule, HttpFetchBuilder, RedirectPolicy, ResolveRule, DEFAULT_MAX_REDIRECTS,
+        DEFAULT_STREAM_DEADLINE,
+    },
+    fingerprint::FingerprintIndex,
+    frontier_dump::FrontierDump,
+    handle::CrawlHandle,
+    health::{self, Health},
+    https::{HttpsUpgrade, InsecureLinks},
+    limits,
+    link::{host_with_port, url_parts, Scope},
+    mobile::MobileAudit,
+    parked::ParkedLinks,
+    policy::{link_text_pattern, LinkTextRules, PolicyChain, ScopePolicy},
+    politeness::PolitenessLog,
+    porcelain,
+    prune::{self, Prune},
+    queue::FrontierPolicy,
+    quota::PathQuota,
+    robots::{RobotsCache, UnreachablePolicy},
+    sample,
+    search::{SearchPagePolicy, SearchPages},
+    service::{self, Notifier},
+    snapshot::{self, external_roots},
+    sqlite_store::SqliteStore,
+    stats::{PhaseStats, WorkerRamp},
+    summary::{CrawlSummary, MergedSummary},
+    url_frontier::QueuedUrl,
+    webhook::{CrawlOutcome, WebhookReport},
+    window::{CrawlWindow, CrawlWindows, UtcOffset},
 };
 
-struct Dependencies {
-    url_frontier: Arc<RwLock<URLFrontier>>,
-    data_store: Arc<RwLock<Store>>,
-}
+#[cfg(feature = "doh")]
+use url_crawler::doh::DohResolver;
+#[cfg(feature = "object-store")]
+use url_crawler::object_store::{ObjectStore, ObjectUrl};
+
+const EXTERNAL_SNAPSHOT_CONCURRENCY: usize = 4;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
 
 #[derive(ClapParser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    /// URL to crawl
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    // options from a config file come first, so the command line can override them
+    args_override_self = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    crawl: Option<Args>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Crawl a generated in-process site and report throughput and allocation stats
+    Bench(BenchArgs),
+    /// Print a shell completion script, i.e. `url-crawler completions bash > /etc/bash_completion.d/url-crawler`
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Write a commented starter config file, asking for the answers not given as options
+    Init(InitArgs),
+    /// Validate a config file without starting a crawl
+    CheckConfig {
+        /// The config file to validate
+        path: PathBuf,
+    },
+    /// Shrink a saved store (see `--store`) before sharing or archiving it
+    Prune(PruneArgs),
+    /// Combine saved stores, i.e. of the shards of a distributed crawl, into one
+    Merge(MergeArgs),
+    /// Chart an event log (see `--event-log`) per worker, to see where a crawl stalled
+    Timeline(TimelineArgs),
+    /// Cross-check what the pages of a saved store (see `--store`) declare
+    Audit {
+        #[command(subcommand)]
+        audit: Audit,
+    },
+    /// Inspect the frontier of a saved store (see `--store`), i.e. before resuming its crawl
+    Frontier {
+        #[command(subcommand)]
+        frontier: Frontier,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum Audit {
+    /// Check that the hreflang alternates of each page agree across its HTML, its `Link` headers
+    /// and the sitemaps (see `--use-sitemaps`), respond and link back to the page
+    Alternates {
+        /// The store to audit, i.e. `sqlite:crawl.db`
+        store: StoreBackend,
+    },
+    /// Report the mobile readiness of the HTML pages: their viewport, media queries and responsive
+    /// images, and the pages rendered at desktop width or that can't be zoomed
+    Mobile {
+        /// The store to audit, i.e. `sqlite:crawl.db`
+        store: StoreBackend,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum Frontier {
+    /// List the URLs a resumed crawl will fetch, in the order it fetches them and by host, and the
+    /// ones it backs off from or gave up on
+    Dump {
+        /// The store to inspect, i.e. `sqlite:crawl.db`
+        store: StoreBackend,
+        /// The frontier policy the crawl will be resumed with
+        #[arg(long, value_enum, default_value_t = FrontierPolicy::Fifo)]
+        frontier_policy: FrontierPolicy,
+    },
+}
+
+#[derive(ClapArgs, Debug)]
+struct TimelineArgs {
+    /// The event log to chart
+    path: PathBuf,
+
+    /// Columns of the chart
+    #[arg(long, default_value_t = 80)]
+    width: usize,
+}
+
+#[derive(ClapArgs, Debug)]
+struct MergeArgs {
+    /// The stores to merge, i.e. `sqlite:shard-1.db sqlite:shard-2.db`
+    #[arg(required = true)]
+    stores: Vec<StoreBackend>,
+
+    /// The store to merge them into, created if needed, i.e. `sqlite:crawl.db`
+    #[arg(long)]
+    into: StoreBackend,
+}
+
+#[derive(ClapArgs, Debug)]
+struct PruneArgs {
+    /// The store to prune, i.e. `sqlite:crawl.db`
+    store: StoreBackend,
+
+    /// Drop the links to other domains than the page's own
+    #[arg(long)]
+    drop_external_edges: bool,
+
+    /// Drop the page body samples
+    #[arg(long)]
+    drop_bodies: bool,
+
+    /// Only keep the pages that responded with a 4xx or 5xx status
+    #[arg(long)]
+    errors_only: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+struct InitArgs {
+    /// Where to write the config file
+    #[arg(default_value = "crawl.toml")]
+    path: PathBuf,
+
+    /// URL to crawl (repeat to crawl several seeds)
+    #[arg(short, long)]
+    url: Vec<String>,
+
+    /// How far the crawl reaches beyond the seed domain
+    #[arg(long, value_enum)]
+    scope: Option<Scope>,
+
+    /// Number of worker threads, or `auto`
     #[arg(short, long)]
-    url: String,
+    workers_n: Option<Workers>,
+
+    /// Politeness delay (in seconds) between requests
+    #[arg(short, long)]
+    delay: Option<u64>,
+
+    /// Skip URLs disallowed by the host's robots.txt
+    #[arg(long)]
+    respect_robots: Option<bool>,
+
+    /// Write the crawled pages as JSON to this file
+    #[arg(long)]
+    output_file: Option<String>,
+
+    /// Overwrite the config file if it already exists
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+struct BenchArgs {
+    /// Number of pages on the generated site
+    #[arg(long, default_value_t = 500)]
+    pages: usize,
+
+    /// Approximate size of each page (in bytes)
+    #[arg(long, default_value_t = 10_240)]
+    page_size: usize,
+
+    /// Number of links on each page
+    #[arg(long, default_value_t = 10)]
+    links_per_page: usize,
 
     /// Number of worker threads
     #[arg(short, long, default_value_t = 1)]
     workers_n: u8,
+}
+
+#[derive(ClapArgs, Debug, Clone, Default)]
+struct Args {
+    /// URL to crawl (repeat to crawl several seeds)
+    #[arg(short, long, required = true)]
+    url: Vec<String>,
+
+    /// TOML file with default values for any of the options below (see `url-crawler init`)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Number of worker threads, or `auto` to tune the number of concurrent requests while crawling
+    #[arg(short, long, default_value_t = Workers::Fixed(1), help_heading = "Network")]
+    workers_n: Workers,
+
+    /// Lowest number of concurrent requests with `--workers-n auto`
+    #[arg(long, default_value_t = 1, help_heading = "Network")]
+    workers_min: usize,
+
+    /// Highest number of concurrent requests with `--workers-n auto`
+    #[arg(long, default_value_t = 16, help_heading = "Network")]
+    workers_max: usize,
+
+    /// Crawl the same way every run: one worker, the links of each page in sorted order, a seeded
+    /// `--frontier-policy random` and `--sorted-export`, to reproduce a crawl or compare two
+    #[arg(long, help_heading = "Network")]
+    deterministic: bool,
+
+    /// Start with one worker and add the rest gradually over this many seconds
+    #[arg(long, default_value_t = 0, help_heading = "Politeness")]
+    ramp_up_s: u64,
 
     /// Politeness delay (in seconds) between requests
-    #[arg(short, long, default_value_t = 2)]
+    #[arg(short, long, default_value_t = 2, help_heading = "Politeness")]
     delay: u64,
 
-    /// Print data store at the end of the crawl (boolean value)
-    #[arg(short, long)]
+    /// Delay (in seconds) of each request as a formula of `delay` (the host's delay), `depth` and
+    /// `pages` (requests made to the host so far), with + - * / ( ) min(a, b) and max(a, b),
+    /// i.e. `min(delay * (1 + pages / 1000), 30)`
+    #[arg(long, help_heading = "Politeness")]
+    delay_formula: Option<DelayFormula>,
+
+    /// Only crawl between these times of day, i.e. `01:00-05:00`, pausing outside of them (can be repeated)
+    #[arg(long, help_heading = "Politeness")]
+    crawl_window: Vec<CrawlWindow>,
+
+    /// Offset from UTC of the time the crawl windows are in, i.e. `+02:00` for the crawled sites' local time
+    #[arg(long, default_value_t = UtcOffset::default(), allow_hyphen_values = true, help_heading = "Politeness")]
+    crawl_window_utc_offset: UtcOffset,
+
+    /// Order in which queued URLs are crawled
+    #[arg(long, value_enum, default_value_t = FrontierPolicy::Fifo, help_heading = "Politeness")]
+    frontier_policy: FrontierPolicy,
+
+    /// Log every politeness decision - host, how long the request waited and why - as CSV to this file
+    #[arg(long, help_heading = "Politeness")]
+    politeness_log: Option<PathBuf>,
+
+    /// Pause requests (in seconds) to hosts that look like they are blocking the crawler - uniform 403s,
+    /// CAPTCHA or challenge pages in a row - doubled each time, rather than recording errors, i.e. 300
+    #[arg(long, default_value_t = 0, help_heading = "Politeness")]
+    block_pause_s: u64,
+
+    /// Print data store, in the output format, at the end of the crawl (boolean value)
+    #[arg(short, long, help_heading = "Output")]
     print: bool,
+
+    /// How far the crawl reaches beyond the seed domain
+    #[arg(long, value_enum, default_value_t = Scope::SameDomain, help_heading = "Scope")]
+    scope: Scope,
+
+    /// Write the crawled pages, in the output format, to this file, or upload them to an
+    /// `s3://bucket/key` or `gs://bucket/key` object
+    #[arg(long, help_heading = "Output")]
+    output_file: Option<PathBuf>,
+
+    /// Format of the printed and written pages
+    #[arg(long, value_enum, default_value_t = OutputFormat::Json, help_heading = "Output")]
+    output_format: OutputFormat,
+
+    /// File with expected URLs (one per line) to compare the crawl against
+    #[arg(long, help_heading = "Output")]
+    expect: Option<PathBuf>,
+
+    /// Write every redirect encountered as `from,to,status` CSV to this file
+    #[arg(long, help_heading = "Output")]
+    redirects_file: Option<PathBuf>,
+
+    /// File that persists a content hash per URL across crawls
+    #[arg(long, help_heading = "Output")]
+    fingerprints: Option<PathBuf>,
+
+    /// Report pages whose content changed since the previous crawl (requires --fingerprints)
+    #[arg(long, requires = "fingerprints", help_heading = "Output")]
+    monitor: bool,
+
+    /// Write the URLs of new or changed pages, one per line, to this file (requires --monitor)
+    #[arg(long, requires = "monitor", help_heading = "Output")]
+    changed_feed: Option<PathBuf>,
+
+    /// Print a breakdown of time spent per crawl phase at the end of the crawl
+    #[arg(long, help_heading = "Output")]
+    stats: bool,
+
+    /// Write the crawl summary, including per-host metrics, as JSON to this file
+    #[arg(long, help_heading = "Output")]
+    summary_file: Option<PathBuf>,
+
+    /// Write the crawl summary, including per-host latency percentiles, as Prometheus metrics to
+    /// this file, i.e. in the node exporter's textfile collector directory
+    #[arg(long, help_heading = "Output")]
+    metrics_file: Option<PathBuf>,
+
+    /// POST the summary of a crawl that was aborted or could not run, and where its partial export
+    /// was written, as JSON to this URL
+    #[arg(long, value_name = "URL", help_heading = "Output")]
+    webhook_url: Option<Url>,
+
+    /// Attach key=value metadata, i.e. env=staging, to every exported record and emitted event and
+    /// to the summary (can be repeated)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = export::tag, help_heading = "Output")]
+    tag: Vec<(String, String)>,
+
+    /// Hash the URLs of `--print` and `--output-file` with this key, leaving out page text, to share a crawl without its paths
+    #[arg(long, help_heading = "Output")]
+    anonymize_key: Option<String>,
+
+    /// Write `--print` and `--output-file` in a canonical sorted order, so two crawls can be compared with `diff`
+    #[arg(long, help_heading = "Output")]
+    sorted_export: bool,
+
+    /// Append when URLs are enqueued and fetched, and by which worker, as CSV to this file (see `timeline`)
+    #[arg(long, help_heading = "Output")]
+    event_log: Option<PathBuf>,
+
+    /// Report groups of pages sharing the same title or meta description at the end of the crawl
+    #[arg(long, help_heading = "Output")]
+    duplicates: bool,
+
+    /// Crawl each seed as an isolated sub-crawl with its own scope and outputs
+    #[arg(long, conflicts_with_all = ["expect", "fingerprints"], help_heading = "Scope")]
+    isolate_seeds: bool,
+
+    /// Skip URLs disallowed by the host's robots.txt
+    #[arg(long, help_heading = "Politeness")]
+    respect_robots: bool,
+
+    /// How long (in seconds) a host's robots.txt is cached before it is fetched again
+    #[arg(long, default_value_t = 3600, help_heading = "Politeness")]
+    robots_cache_ttl: u64,
+
+    /// Whether to crawl a host whose robots.txt cannot be fetched
+    #[arg(long, value_enum, default_value_t = UnreachablePolicy::Deny, help_heading = "Politeness")]
+    robots_unreachable: UnreachablePolicy,
+
+    /// Enqueue the URLs of sitemaps listed in the seeds' robots.txt
+    #[arg(long, help_heading = "Scope")]
+    use_sitemaps: bool,
+
+    /// File of internal search page patterns: links like the search pages of previous crawls are skipped, and the ones of this crawl added
+    #[arg(long, help_heading = "Scope")]
+    exclude_search_pages: Option<PathBuf>,
+
+    /// Upgrade same host http:// links to https:// (when the host serves https) and report pages linking to plain HTTP
+    #[arg(long, help_heading = "Network")]
+    https_only: bool,
+
+    /// User agent sent with every request and matched against robots.txt groups [default: url-crawler/<version>]
+    #[arg(long, help_heading = "Network")]
+    user_agent: Option<String>,
+
+    /// `Host` header sent with the requests to the seeds' host, i.e. to crawl a load balancer's IP address as the site it serves
+    #[arg(long, help_heading = "Network")]
+    host_header: Option<String>,
+
+    /// Send the requests to a host to this address instead of the one in DNS, i.e. `example.com:10.0.0.1` (can be repeated)
+    #[arg(long, help_heading = "Network")]
+    resolve: Vec<ResolveRule>,
+
+    /// `Accept-Language` header sent with every request, i.e. `fr-FR` (can be repeated)
+    #[arg(long, help_heading = "Network")]
+    accept_language: Vec<String>,
+
+    /// Request every page once per `--accept-language` locale and record what each locale was served
+    #[arg(long, requires = "accept_language", help_heading = "Network")]
+    per_locale: bool,
+
+    /// Only speak HTTP/1.1, to debug servers that misbehave when HTTP/2 is negotiated
+    #[arg(
+        long,
+        conflicts_with = "http2_prior_knowledge",
+        help_heading = "Network"
+    )]
+    http1_only: bool,
+
+    /// Speak HTTP/2 without negotiating it first, i.e. with servers only serving cleartext HTTP/2
+    #[arg(long, help_heading = "Network")]
+    http2_prior_knowledge: bool,
+
+    /// Only connect over IPv4, i.e. to rule out a broken AAAA record of a dual-stack host
+    #[arg(long, conflicts_with = "ipv6_only", help_heading = "Network")]
+    ipv4_only: bool,
+
+    /// Only connect over IPv6, i.e. to check a dual-stack host's AAAA record on its own
+    #[arg(long, help_heading = "Network")]
+    ipv6_only: bool,
+
+    /// Resolve the hosts of queued URLs in the background, ahead of their first request
+    #[arg(long, help_heading = "Network")]
+    dns_prefetch: bool,
+
+    /// Keep at most this many requests in flight at once, further requests wait for one to finish.
+    /// Defaults to half of what the open files limit (`ulimit -n`) leaves for connections
+    #[arg(long, help_heading = "Network")]
+    max_concurrent_requests: Option<usize>,
+
+    /// Resolve hosts with DNS over HTTPS through this server, i.e. https://1.1.1.1/dns-query
+    #[cfg(feature = "doh")]
+    #[arg(long, value_name = "URL", help_heading = "Network")]
+    doh_url: Option<Url>,
+
+    /// Follow at most N redirects in a row, the last one is recorded but not followed
+    #[arg(long, default_value_t = DEFAULT_MAX_REDIRECTS, help_heading = "Network")]
+    max_redirects: usize,
+
+    /// Follow redirects, only record their targets as links of the page, or treat them as failures
+    #[arg(long, value_enum, default_value_t = RedirectPolicy::Follow, help_heading = "Network")]
+    redirect_policy: RedirectPolicy,
+
+    /// Seconds a body without a `Content-Length` may take to read before the page is skipped as a
+    /// streaming resource (Server-Sent Events, long-polling, ...)
+    #[arg(long, default_value_t = DEFAULT_STREAM_DEADLINE.as_secs(), help_heading = "Network")]
+    stream_deadline_s: u64,
+
+    /// Method and headers of the requests to URLs whose path matches a pattern, i.e.
+    /// "/downloads/* method=HEAD" or "/api/* header=Accept:application/json" (can be repeated, the
+    /// first matching rule applies)
+    #[arg(long, value_name = "RULE", help_heading = "Network")]
+    fetch_rule: Vec<FetchRule>,
+
+    /// Only request the first SIZE of binary files (i.e. `.pdf`, `.zip`, `.mp4`), enough to record
+    /// their size and type without downloading them, i.e. 4KiB
+    #[arg(long, value_name = "SIZE", help_heading = "Network")]
+    range_probe: Option<ByteSize>,
+
+    /// Keep the first N KB of each page body in the data store
+    #[arg(long, help_heading = "Output")]
+    body_sample_kb: Option<usize>,
+
+    /// Also extract URLs from `data-href`/`data-url` attributes and `onclick` location changes
+    #[arg(long, help_heading = "Scope")]
+    aggressive_extraction: bool,
+
+    /// Fetch the root page of every external site linked to, once, recording its status, redirect and title
+    #[arg(long, help_heading = "Scope")]
+    snapshot_external: bool,
+
+    /// Only follow links whose text matches this (case insensitive) pattern, can be repeated
+    #[arg(long, value_parser = link_text_pattern, help_heading = "Scope")]
+    follow_link_text: Vec<Regex>,
+
+    /// Never follow links whose text matches this (case insensitive) pattern, can be repeated
+    #[arg(long, value_parser = link_text_pattern, help_heading = "Scope")]
+    skip_link_text: Vec<Regex>,
+
+    /// Crawl the actions of GET forms, submitted with empty fields
+    #[arg(long, help_heading = "Scope")]
+    crawl_get_forms: bool,
+
+    /// Enqueue at most this many links from a single page, the first link to each host first
+    #[arg(long, help_heading = "Scope")]
+    max_links_per_page: Option<usize>,
+
+    /// Stop the crawl once this much has been downloaded, i.e. `500MB` or `5GB`
+    #[arg(long, help_heading = "Scope")]
+    max_bytes: Option<ByteSize>,
+
+    /// Don't follow links further than this many links away from a seed
+    #[arg(long, help_heading = "Scope")]
+    max_depth: Option<usize>,
+
+    /// Stop the crawl once this many pages have been requested
+    #[arg(long, help_heading = "Scope")]
+    max_pages: Option<usize>,
+
+    /// Crawl only this fraction of the discovered URLs, i.e. `0.1`, sampled per host and first path
+    /// segment, to estimate the health of a site too large to crawl in full
+    #[arg(long, value_name = "RATE", value_parser = sample::sample_rate, help_heading = "Scope")]
+    sample: Option<f64>,
+
+    /// Crawl at most N URLs whose path matches the pattern, i.e. "/tag/*=100", so that tag or archive
+    /// sections don't use up the page budget (can be repeated, the longest matching pattern applies)
+    #[arg(long, value_name = "PATTERN=N", help_heading = "Scope")]
+    path_quota: Vec<PathQuota>,
+
+    /// Abort the crawl once more than this fraction of its requests, i.e. `0.5`, fail with a
+    /// connection error or a 5xx or 429 status, keeping the partial results
+    #[arg(long, value_name = "RATE", value_parser = abort::error_rate, help_heading = "Scope")]
+    abort_error_rate: Option<f64>,
+
+    /// What makes two URLs the same page, so that it is only crawled once
+    #[arg(long, value_enum, default_value_t = DedupeKey::Url, help_heading = "Scope")]
+    dedupe_key: DedupeKey,
+
+    /// Write each visited page to stdout as soon as it has been crawled
+    #[arg(long, value_enum, help_heading = "Output")]
+    emit: Option<Emit>,
+
+    /// Write the crawl to stdout in a stable, versioned, tab separated format for scripts
+    #[arg(long, conflicts_with_all = ["emit", "isolate_seeds"], help_heading = "Output")]
+    porcelain: bool,
+
+    /// Run as a systemd `Type=notify` service: report readiness and ping the watchdog over
+    /// `NOTIFY_SOCKET` unless the crawl stalls, and log with journald priorities instead of
+    /// timestamps
+    #[arg(long, help_heading = "Output")]
+    service: bool,
+
+    /// Serve `/healthz` and `/readyz` on this address, i.e. `0.0.0.0:8080`, for container orchestrators
+    #[arg(long, help_heading = "Output")]
+    health_addr: Option<SocketAddr>,
+
+    /// Seconds a running crawl may go without completing a request before `/healthz` fails, and
+    /// the systemd watchdog is no longer pinged
+    #[arg(long, default_value_t = 600, help_heading = "Output")]
+    health_stall_s: u64,
+
+    /// Keep the crawled pages and queued URLs in a database, i.e. `sqlite:crawl.db`, not only in memory
+    #[arg(long, help_heading = "Output")]
+    store: Option<StoreBackend>,
+
+    /// Resume the crawl saved in the --store database instead of starting over
+    #[arg(long, requires = "store", help_heading = "Output")]
+    resume: bool,
 }
 
-async fn execute(cli_args: Args, dependencies: Dependencies) -> Result<Arc<RwLock<Store>>, Error> {
-    let Args { url, workers_n, .. } = cli_args;
-    let Dependencies {
-        url_frontier,
-        data_store,
-    } = dependencies;
+impl Args {
+    /// Problems clap can't catch on its own, each worded so that it can be fixed straight away
+    fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        for url in &self.url {
+            match Url::parse(url) {
+                Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+                Ok(_) => problems.push(format!("url `{}` must be a http:// or https:// URL", url)),
+                Err(e) => problems.push(format!("url `{}` is not a valid URL ({})", url, e)),
+            }
+        }
+
+        if self.workers_n == Workers::Auto && self.workers_min > self.workers_max {
+            problems.push(format!(
+                "workers-min ({}) must not be greater than workers-max ({})",
+                self.workers_min, self.workers_max
+            ));
+        }
 
-    let original_url_parts = Arc::new(url_parts(&url));
-    let mut tasks = JoinSet::new();
+        let store = self
+            .store
+            .as_ref()
+            .map(|StoreBackend::Sqlite(path)| path.clone());
+        let output_files = [
+            ("store", &store),
+            ("output-file", &self.output_file),
+            ("redirects-file", &self.redirects_file),
+            ("changed-feed", &self.changed_feed),
+            ("fingerprints", &self.fingerprints),
+            ("summary-file", &self.summary_file),
+            ("metrics-file", &self.metrics_file),
+            ("exclude-search-pages", &self.exclude_search_pages),
+            ("politeness-log", &self.politeness_log),
+            ("event-log", &self.event_log),
+        ];
+        if let Some(output_file) = self
+            .output_file
+            .as_deref()
+            .filter(|path| is_object_url(path))
+        {
+            #[cfg(feature = "object-store")]
+            if output_file.to_str().and_then(ObjectUrl::parse).is_none() {
+                problems.push(format!(
+                    "output-file: `{}` is not an s3://bucket/key or gs://bucket/key URL",
+                    output_file.display()
+                ));
+            }
+            #[cfg(not(feature = "object-store"))]
+            problems.push(format!(
+                "output-file: uploading to `{}` needs a build with --features object-store",
+                output_file.display()
+            ));
+        }
+        for (option, path) in output_files {
+            if path.as_deref().is_some_and(is_object_url) {
+                continue;
+            }
+            let Some(parent) = path.as_deref().and_then(Path::parent) else {
+                continue;
+            };
+            if !parent.as_os_str().is_empty() && !parent.is_dir() {
+                problems.push(format!(
+                    "{}: directory `{}` does not exist",
+                    option,
+                    parent.display()
+                ));
+            }
+        }
+
+        if let Some(expect) = &self.expect {
+            if !expect.is_file() {
+                problems.push(format!(
+                    "expect: file `{}` does not exist",
+                    expect.display()
+                ));
+            }
+        }
+
+        problems
+    }
+
+    /// The crawl described by the options, into an in-memory store
+    fn crawler(&self) -> CrawlerBuilder {
+        let mut crawler = self
+            .url
+            .iter()
+            .fold(CrawlerBuilder::new(), |crawler, url| crawler.seed(url))
+            .workers(self.workers_n)
+            .workers_range(self.workers_min, self.workers_max)
+            .deterministic(self.deterministic)
+            .ramp_up(Duration::from_secs(self.ramp_up_s))
+            .delay_s(self.delay)
+            .frontier_policy(self.frontier_policy)
+            .scope(self.scope)
+            .use_sitemaps(self.use_sitemaps)
+            .aggressive_extraction(self.aggressive_extraction)
+            .crawl_get_forms(self.crawl_get_forms)
+            .link_text_rules(LinkTextRules {
+                follow: self.follow_link_text.clone(),
+                skip: self.skip_link_text.clone(),
+            })
+            .crawl_windows(CrawlWindows {
+                windows: self.crawl_window.clone(),
+                utc_offset: self.crawl_window_utc_offset,
+            })
+            .redirect_policy(self.redirect_policy)
+            .dedupe_key(self.dedupe_key)
+            .client_builder(self.client_builder());
+
+        if let Some(body_sample_kb) = self.body_sample_kb {
+            crawler = crawler.body_sample_kb(body_sample_kb);
+        }
+        if let Some(delay_formula) = &self.delay_formula {
+            crawler = crawler.delay_formula(delay_formula.clone());
+        }
+        if let Some(max_links_per_page) = self.max_links_per_page {
+            crawler = crawler.max_links_per_page(max_links_per_page);
+        }
+        if let Some(max_depth) = self.max_depth {
+            crawler = crawler.max_depth(max_depth);
+        }
+        if let Some(max_pages) = self.max_pages {
+            crawler = crawler.max_pages(max_pages);
+        }
+        if let Some(max_bytes) = self.max_bytes {
+            crawler = crawler.max_bytes(max_bytes);
+        }
+        if let Some(rate) = self.sample {
+            crawler = crawler.sample(rate);
+        }
+        if !self.path_quota.is_empty() {
+            crawler = crawler.path_quotas(self.path_quota.clone());
+        }
+        if let Some(max_error_rate) = self.abort_error_rate {
+            crawler = crawler.abort_error_rate(max_error_rate);
+        }
+        if self.per_locale {
+            crawler = crawler.locales(self.accept_language.clone());
+        }
+        if let Some(path) = &self.exclude_search_pages {
+            let seeds_url_parts = self
+                .url
+                .iter()
+                .map(|url| Arc::new(url_parts(url)))
+                .collect();
+            crawler = crawler.url_policy(Arc::new(PolicyChain(vec![
+                Arc::new(ScopePolicy::new(seeds_url_parts, self.scope)),
+                Arc::new(load_search_page_policy(path)),
+            ])));
+        }
+        if let Some(path) = &self.politeness_log {
+            match PolitenessLog::create(path) {
+                Ok(politeness_log) => crawler = crawler.politeness_log(Arc::new(politeness_log)),
+                Err(e) => warn!("Could not write {}: {}", path.display(), e),
+            }
+        }
+        if let Some(path) = &self.event_log {
+            match EventLog::create(path) {
+                Ok(event_log) => crawler = crawler.event_log(Arc::new(event_log)),
+                Err(e) => warn!("Could not write {}: {}", path.display(), e),
+            }
+        }
+        if self.block_pause_s > 0 {
+            crawler = crawler.block_pause(Duration::from_secs(self.block_pause_s));
+        }
+        if self.respect_robots {
+            let robots = RobotsCache::new(
+                Duration::from_secs(self.robots_cache_ttl),
+                self.robots_unreachable,
+            );
+            crawler = crawler.robots(Arc::new(match &self.user_agent {
+                Some(user_agent) => robots.with_user_agent(user_agent),
+                None => robots,
+            }));
+        }
+        if self.https_only {
+            crawler = crawler.https_upgrade(Arc::new(HttpsUpgrade::new()));
+        }
+        if self.dns_prefetch {
+            crawler = crawler.dns_cache(self.dns_cache());
+        }
+
+        crawler
+    }
+
+    /// The `--tag`s, the last value of a key given more than once wins
+    fn tags(&self) -> Tags {
+        self.tag.iter().cloned().collect()
+    }
+
+    /// Builder for the HTTP clients of the crawl
+    fn client_builder(&self) -> HttpFetchBuilder {
+        let mut client_builder = self
+            .resolve
+            .iter()
+            .fold(HttpFetchBuilder::new(), |client_builder, rule| {
+                client_builder.resolve(rule.clone())
+            })
+            .max_redirects(self.max_redirects)
+            .stream_deadline(Duration::from_secs(self.stream_deadline_s));
+        for rule in &self.fetch_rule {
+            client_builder = client_builder.fetch_rule(rule.clone());
+        }
+        if let Some(ByteSize(bytes)) = self.range_probe {
+            client_builder = client_builder.range_probe(bytes);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            client_builder = client_builder.user_agent(user_agent);
+        }
+        if let Some(host_header) = &self.host_header {
+            let seed_hosts = self.url.iter().filter_map(|url| host_with_port(url));
+            client_builder = client_builder.host_header(host_header, seed_hosts.collect());
+        }
+        // with `--per-locale` the crawler sends a single locale per request
+        if !self.accept_language.is_empty() && !self.per_locale {
+            client_builder = client_builder.accept_language(&self.accept_language.join(", "));
+        }
+        if self.http1_only {
+            client_builder = client_builder.http1_only();
+        }
+        if self.http2_prior_knowledge {
+            client_builder = client_builder.http2_prior_knowledge();
+        }
+        if self.ipv4_only {
+            client_builder = client_builder.ipv4_only();
+        }
+        if self.ipv6_only {
+            client_builder = client_builder.ipv6_only();
+        }
+        if let Some(max_concurrent_requests) = self.max_concurrent_requests() {
+            client_builder = client_builder.max_concurrent_requests(max_concurrent_requests);
+        }
+        // with `--dns-prefetch` the crawler's shared cache replaces this one
+        #[cfg(feature = "doh")]
+        if self.doh_url.is_some() {
+            client_builder = client_builder.dns_cache(self.dns_cache());
+        }
+
+        client_builder
+    }
 
-    for _ in 0..workers_n {
-        let url_frontier = url_frontier.clone();
-        let data_store = data_store.clone();
-        let urls_parts = original_url_parts.clone();
+    /// DNS cache of the crawl, resolving with `--doh-url` when given
+    fn dns_cache(&self) -> DnsCache {
+        #[cfg(feature = "doh")]
+        if let Some(doh_url) = &self.doh_url {
+            return DnsCache::with_doh(DohResolver::new(doh_url.clone()));
+        }
+
+        DnsCache::new()
+    }
+
+    /// Requests the crawl keeps in flight at once, none if the open files limit is unknown
+    fn max_concurrent_requests(&self) -> Option<usize> {
+        self.max_concurrent_requests
+            .or_else(limits::default_max_concurrent_requests)
+    }
+
+    /// Arguments for an isolated sub-crawl of a single seed, with per-seed output files
+    fn for_seed(&self, seed: &str) -> Args {
+        let for_seed =
+            |path: &Option<PathBuf>| path.as_deref().map(|path| path_for_seed(path, seed));
+
+        Args {
+            url: vec![seed.to_owned()],
+            output_file: for_seed(&self.output_file),
+            redirects_file: for_seed(&self.redirects_file),
+            changed_feed: for_seed(&self.changed_feed),
+            summary_file: for_seed(&self.summary_file),
+            metrics_file: for_seed(&self.metrics_file),
+            exclude_search_pages: for_seed(&self.exclude_search_pages),
+            politeness_log: for_seed(&self.politeness_log),
+            event_log: for_seed(&self.event_log),
+            store: self
+                .store
+                .as_ref()
+                .map(|StoreBackend::Sqlite(path)| StoreBackend::Sqlite(path_for_seed(path, seed))),
+            isolate_seeds: false,
+            // the isolated crawls run at once, sharing the file descriptors of the process
+            max_concurrent_requests: self
+                .max_concurrent_requests()
+                .map(|max| (max / self.url.len()).max(1)),
+            ..self.clone()
+        }
+    }
+}
+
+/// The search page patterns learned by previous crawls, none before the first one
+fn load_search_page_policy(path: &Path) -> SearchPagePolicy {
+    match SearchPagePolicy::load(path) {
+        Ok(policy) => policy,
+        Err(e) => {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!("Could not read {}: {}", path.display(), e);
+            }
+            SearchPagePolicy::default()
+        }
+    }
+}
+
+/// Inserts the seed's host into a file name, i.e. `out.json` becomes `out.example.com.json`
+fn path_for_seed(path: &Path, seed: &str) -> PathBuf {
+    let host = match Url::parse(seed) {
+        Ok(url) => match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}_{}", host, port),
+            (Some(host), None) => host.to_owned(),
+            _ => "seed".to_owned(),
+        },
+        Err(_) => "seed".to_owned(),
+    };
+
+    let mut file_name = path.file_stem().unwrap_or_default().to_os_string();
+    file_name.push(".");
+    file_name.push(host);
+    if let Some(extension) = path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+
+    path.with_file_name(file_name)
+}
+
+/// Whether an output path is an object storage URL rather than a local file
+fn is_object_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|path| path.starts_with("s3://") || path.starts_with("gs://"))
+}
+
+/// Content type of the uploaded exports
+#[cfg(feature = "object-store")]
+fn content_type(output_format: OutputFormat) -> &'static str {
+    match output_format {
+        OutputFormat::Json => "application/json",
+        OutputFormat::Csv => "text/csv",
+        OutputFormat::Sitemap => "application/xml",
+    }
+}
+
+/// Whether the export can be uploaded to an object storage URL, checked before crawling rather
+/// than once the crawl is done
+async fn check_upload(output_file: &Path, output_format: OutputFormat) -> Result<(), Error> {
+    #[cfg(feature = "object-store")]
+    {
+        let object = output_file
+            .to_str()
+            .and_then(ObjectUrl::parse)
+            .ok_or_else(|| Error::other("not an s3://bucket/key or gs://bucket/key URL"))?;
+        ObjectStore::from_env(&object, content_type(output_format))?
+            .check_credentials()
+            .await
+    }
+    #[cfg(not(feature = "object-store"))]
+    {
+        let _ = (output_file, output_format);
+        Err(Error::other(
+            "uploading needs a build with --features object-store",
+        ))
+    }
+}
+
+/// Writes an export to its output file, or uploads it if the file is an object storage URL
+async fn write_output_file(
+    output_file: &Path,
+    export: String,
+    output_format: OutputFormat,
+) -> Result<(), Error> {
+    #[cfg(feature = "object-store")]
+    if let Some(object) = output_file.to_str().and_then(ObjectUrl::parse) {
+        return ObjectStore::from_env(&object, content_type(output_format))?
+            .put(&object.key, export.into_bytes())
+            .await;
+    }
+    #[cfg(not(feature = "object-store"))]
+    let _ = output_format;
+
+    fs::write(output_file, export)
+}
+
+/// Writes a visited page to stdout straight away, logs go to stderr so the two don't mix
+fn emit_page(event: &PageEvent, tags: &Tags) {
+    match event.to_ndjson(tags) {
+        Ok(line) => {
+            let mut stdout = io::stdout().lock();
+            if let Err(e) = stdout
+                .write_all(line.as_bytes())
+                .and_then(|_| stdout.flush())
+            {
+                warn!("Could not emit {}: {}", event.url, e);
+            }
+        }
+        Err(e) => warn!("Could not serialise {}: {}", event.url, e),
+    }
+}
+
+/// Writes the requested exports and reports once the crawl has finished
+async fn write_outputs<T: DataStore>(
+    cli_args: &Args,
+    data_store: &T,
+    summary: &CrawlSummary,
+    stats: &PhaseStats,
+    worker_ramp: &WorkerRamp,
+    expected_urls: Option<Vec<String>>,
+) {
+    // stdout is reserved for the porcelain output, human readable reports go to stderr then
+    let report = |report: &dyn fmt::Display| match cli_args.porcelain {
+        true => eprint!("{}", report),
+        false => print!("{}", report),
+    };
+
+    if cli_args.stats {
+        report(stats);
+        report(worker_ramp);
+    }
+
+    let anonymized = cli_args
+        .anonymize_key
+        .as_deref()
+        .filter(|_| cli_args.print || cli_args.output_file.is_some())
+        .map(|key| Anonymizer::new(key).store(data_store));
+    let sorted = (cli_args.sorted_export || cli_args.deterministic).then(|| match &anonymized {
+        Some(anonymized) => export::sorted(anonymized),
+        None => export::sorted(data_store),
+    });
+    let tags = cli_args.tags();
+    let export = || match (&sorted, &anonymized) {
+        (Some(sorted), _) => export::export_tagged(sorted, cli_args.output_format, &tags),
+        (None, Some(anonymized)) => {
+            export::export_tagged(anonymized, cli_args.output_format, &tags)
+        }
+        (None, None) => export::export_tagged(data_store, cli_args.output_format, &tags),
+    };
+
+    if cli_args.print {
+        match export() {
+            Ok(export) => report(&format!("{}\n", export.trim_end())),
+            Err(e) => warn!("Could not export the data store: {}", e),
+        }
+    }
+
+    if let Some(output_file) = &cli_args.output_file {
+        let written = match export() {
+            Ok(export) => write_output_file(output_file, export, cli_args.output_format).await,
+            Err(e) => Err(Error::other(e)),
+        };
+
+        if let Err(e) = written {
+            warn!("Could not write {}: {}", output_file.display(), e);
+        }
+    }
 
-        let client: HttpFetch = Fetch::new(); // a HTTP client per worker
-        let mut is_initial_crawl = true; // if multi-threaded, threads won't quit after the initial single URL crawl
+    if let Some(summary_file) = &cli_args.summary_file {
+        let written = summary
+            .to_json()
+            .map_err(Error::from)
+            .and_then(|json| fs::write(summary_file, json));
 
-        let task = tokio::spawn(async move {
+        if let Err(e) = written {
+            warn!("Could not write {}: {}", summary_file.display(), e);
+        }
+    }
+
+    if let Some(metrics_file) = &cli_args.metrics_file {
+        if let Err(e) = fs::write(metrics_file, summary.to_prometheus()) {
+            warn!("Could not write {}: {}", metrics_file.display(), e);
+        }
+    }
+
+    if let Some(redirects_file) = &cli_args.redirects_file {
+        let written = File::create(redirects_file)
+            .map_err(csv::Error::from)
+            .and_then(|file| export::write_redirects_csv(data_store, file));
+
+        if let Err(e) = written {
+            warn!("Could not write {}: {}", redirects_file.display(), e);
+        }
+    }
+
+    if let Some(path) = &cli_args.exclude_search_pages {
+        let search_pages = SearchPages::from_store(data_store);
+        report(&search_pages);
+
+        let mut policy = load_search_page_policy(path);
+        policy.learn(&search_pages);
+        if let Err(e) = policy.save(path) {
+            warn!("Could not write {}: {}", path.display(), e);
+        }
+    }
+
+    if cli_args.https_only {
+        report(&InsecureLinks::from_store(data_store));
+    }
+
+    if cli_args.duplicates {
+        report(&DuplicateMetadata::from_store(data_store));
+    }
+
+    if cli_args.scope == Scope::ExternalOnce {
+        report(&ParkedLinks::from_store(data_store));
+    }
+
+    if let Some(expected_urls) = expected_urls {
+        report(&compare(data_store, &expected_urls));
+    }
+
+    let fingerprints = cli_args
+        .fingerprints
+        .as_ref()
+        .and_then(|fingerprints_file| {
+            match FingerprintIndex::load_or_default(fingerprints_file) {
+                Ok(index) => Some((fingerprints_file, index)),
+                Err(e) => {
+                    // an empty index would report every page as new and replace the previous one
+                    warn!(
+                        "Could not read {}, skipping change detection: {}",
+                        fingerprints_file.display(),
+                        e
+                    );
+                    None
+                }
+            }
+        });
+    if let Some((fingerprints_file, mut index)) = fingerprints {
+        if cli_args.monitor {
+            let changes = index.changes(data_store);
+            report(&changes);
+
+            if let Some(changed_feed) = &cli_args.changed_feed {
+                if let Err(e) = fs::write(changed_feed, changes.feed()) {
+                    warn!("Could not write {}: {}", changed_feed.display(), e);
+                }
+            }
+        }
+
+        index.update(data_store);
+        if let Err(e) = index.save(fingerprints_file) {
+            warn!("Could not write {}: {}", fingerprints_file.display(), e);
+        }
+    }
+
+    if cli_args.porcelain {
+        print!("{}", porcelain::render(data_store));
+    }
+}
+
+async fn crawl(cli_args: Args, crawls: Crawls) {
+    let health = Arc::new(Health::new(Duration::from_secs(cli_args.health_stall_s)));
+    let Some(notifier) = cli_args.service.then(Notifier::from_env) else {
+        return crawl_seeds(cli_args, health, crawls).await;
+    };
+
+    let seeds = cli_args.url.join(", ");
+    if let Err(e) = notifier.ready(&format!("crawling {}", seeds)) {
+        warn!("Could not notify systemd: {}", e);
+    }
+    let watchdog = service::watchdog_interval().map(|interval| {
+        let (notifier, health) = (notifier.clone(), health.clone());
+        tokio::spawn(async move {
+            let mut pings = tokio::time::interval(interval);
             loop {
-                let mut url_frontier_write = url_frontier.write().await;
-                let url = url_frontier_write.dequeue().await;
-                let current_url = match url {
-                    Some(val) => val,
-                    None => return,
-                };
-
-                let mut data_store_write = data_store.write().await;
-                if data_store_write.has_visited(&current_url) {
+                pings.tick().await;
+                // a stalled crawl is left for the watchdog to restart
+                if health.is_stalled() {
                     continue;
                 }
+                if let Err(e) = notifier.watchdog() {
+                    warn!("Could not ping the systemd watchdog: {}", e);
+                }
+            }
+        })
+    });
 
-                info!("Visiting URL: {}", current_url);
+    crawl_seeds(cli_args, health, crawls).await;
 
-                let content = match client.get(&current_url).await {
-                    Ok(val) => val,
-                    Err(e) => {
-                        warn!("Error requesting URL {} - {}", current_url, e);
-                        continue;
-                    }
-                };
+    if let Some(watchdog) = watchdog {
+        watchdog.abort();
+    }
+    if let Err(e) = notifier.stopping(&format!("crawled {}", seeds)) {
+        warn!("Could not notify systemd: {}", e);
+    }
+}
 
-                data_store_write.add(current_url.clone(), None);
-                data_store_write.visited(&current_url);
+/// Crawls the seeds, `health` tracking their progress for the health checks and the watchdog
+async fn crawl_seeds(cli_args: Args, health: Arc<Health>, crawls: Crawls) {
+    // the crawler falls back to a default client, without the network options, if they are rejected
+    if let Err(e) = cli_args.client_builder().try_build() {
+        warn!("Could not set up the HTTP client - {}", e);
+        let reason = format!("could not set up the HTTP client - {}", e);
+        notify_webhook(&cli_args, CrawlOutcome::Failed, &reason, None).await;
+        return;
+    }
+    if let Some(output_file) = cli_args
+        .output_file
+        .as_deref()
+        .filter(|path| is_object_url(path))
+    {
+        if let Err(e) = check_upload(output_file, cli_args.output_format).await {
+            warn!("Could not upload to {} - {}", output_file.display(), e);
+            let reason = format!("could not upload to {} - {}", output_file.display(), e);
+            notify_webhook(&cli_args, CrawlOutcome::Failed, &reason, None).await;
+            return;
+        }
+    }
 
-                let urls_found = Parser::new(content).all_links();
-                for url in urls_found {
-                    let url = process_url(url, &current_url);
-                    info!("Found URL: {}", url);
+    let expected_urls = match &cli_args.expect {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(contents) => Some(parse_url_list(&contents)),
+            Err(e) => {
+                warn!("Could not read {}: {}", path.display(), e);
+                return;
+            }
+        },
+        None => None,
+    };
 
-                    data_store_write.add(current_url.clone(), Some(url.clone()));
+    let health_server = match cli_args.health_addr {
+        Some(addr) => match TcpListener::bind(addr).await {
+            Ok(listener) => {
+                info!("Serving health checks on {}", addr);
+                Some(tokio::spawn(health::serve(listener, health.clone())))
+            }
+            Err(e) => {
+                warn!("Could not serve health checks on {} - {}", addr, e);
+                return;
+            }
+        },
+        None => None,
+    };
+    let health = (health_server.is_some() || cli_args.service).then_some(health);
 
-                    if let Some(url) = filter_url(url, urls_parts.clone()) {
-                        if !data_store_write.has_visited(&url) {
-                            url_frontier_write.enqueue(url);
-                        }
-                    };
-                }
+    if !cli_args.isolate_seeds {
+        run_crawl(cli_args, expected_urls, health, crawls).await;
+    } else {
+        let mut tasks = JoinSet::new();
+        for seed in &cli_args.url {
+            tasks.spawn(run_crawl(
+                cli_args.for_seed(seed),
+                None,
+                health.clone(),
+                crawls.clone(),
+            ));
+        }
 
-                info!("--------------------------------------------");
+        let mut summaries = vec![];
+        while let Some(res) = tasks.join_next().await {
+            if let Ok(Some(summary)) = res {
+                summaries.push(summary);
+            }
+        }
+        summaries.sort_by(|a, b| a.seed.cmp(&b.seed));
 
-                if is_initial_crawl {
-                    is_initial_crawl = false;
-                    continue;
+        print!("{}", MergedSummary(summaries));
+    }
+
+    if let Some(health_server) = health_server {
+        health_server.abort();
+    }
+}
+
+async fn run_crawl(
+    cli_args: Args,
+    expected_urls: Option<Vec<String>>,
+    health: Option<Arc<Health>>,
+    crawls: Crawls,
+) -> Option<CrawlSummary> {
+    let Some(StoreBackend::Sqlite(path)) = &cli_args.store else {
+        return run_crawl_with(
+            cli_args,
+            expected_urls,
+            Store::new(),
+            vec![],
+            health,
+            crawls,
+        )
+        .await;
+    };
+
+    let opened = SqliteStore::open(path, cli_args.resume)
+        .and_then(|store| store.pending().map(|pending| (store, pending)));
+    let (data_store, pending) = match opened {
+        Ok(val) => val,
+        Err(e) => {
+            warn!("Could not open {} - {}", path.display(), e);
+            let reason = format!("could not open {} - {}", path.display(), e);
+            notify_webhook(&cli_args, CrawlOutcome::Failed, &reason, None).await;
+            return None;
+        }
+    };
+    if cli_args.resume {
+        info!(
+            "Resuming the crawl with {} visited and {} queued URLs",
+            data_store
+                .entries()
+                .into_iter()
+                .filter(|(_, entry)| entry.visited)
+                .count(),
+            pending.len()
+        );
+    }
+
+    run_crawl_with(cli_args, expected_urls, data_store, pending, health, crawls).await
+}
+
+/// Crawls into the given data store, `pending` being the URLs still queued when a resumed crawl stopped
+async fn run_crawl_with<T: DataStore + Send + Sync + 'static>(
+    cli_args: Args,
+    expected_urls: Option<Vec<String>>,
+    data_store: T,
+    pending: Vec<QueuedUrl>,
+    health: Option<Arc<Health>>,
+    crawls: Crawls,
+) -> Option<CrawlSummary> {
+    let mut crawler = cli_args.crawler().data_store(data_store).pending(pending);
+    if let Some(health) = health {
+        crawler = crawler.health(health);
+    }
+    let mut crawler = crawler.build();
+    crawls.add(crawler.handle());
+    let emitter = (cli_args.emit == Some(Emit::Ndjson)).then(|| {
+        let mut events = crawler.subscribe();
+        let tags = cli_args.tags();
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                if let CrawlEvent::PageVisited {
+                    url,
+                    status,
+                    depth,
+                    outlinks,
+                } = event
+                {
+                    emit_page(
+                        &PageEvent {
+                            url: &url,
+                            status,
+                            depth,
+                            outlinks: &outlinks,
+                        },
+                        &tags,
+                    );
                 }
             }
+        })
+    });
+
+    let data_store = crawler.run().await;
+    if let Some(emitter) = emitter {
+        let _ = emitter.await;
+    }
+    info!("Done!");
+
+    let data_store_read = data_store.read().await;
+    let mut summary = CrawlSummary::from_store(&cli_args.url.join(", "), &*data_store_read);
+    summary.frontier = crawler.frontier_metrics().await;
+    summary.tags = cli_args.tags();
+    summary.hosts = crawler.host_stats().snapshot();
+    summary.sample = crawler.sampler().map(|sampler| sampler.report());
+    summary.aborted = crawler
+        .abort_threshold()
+        .and_then(|abort_threshold| abort_threshold.reason())
+        .or_else(|| {
+            crawler
+                .handle()
+                .is_aborted()
+                .then(|| "stopped by a signal".to_owned())
         });
+    if let Some(block_detector) = crawler.block_detector() {
+        summary.blocked_hosts = block_detector.blocked_hosts();
+    }
+    if cli_args.snapshot_external {
+        let client = cli_args.client_builder().build();
+        summary.external_sites = snapshot::snapshot(
+            Arc::new(client),
+            crawler.robots(),
+            external_roots(&*data_store_read),
+            EXTERNAL_SNAPSHOT_CONCURRENCY,
+        )
+        .await;
+    }
+    for line in summary.to_string().lines() {
+        info!("{}", line);
+    }
 
-        tasks.spawn(task);
+    write_outputs(
+        &cli_args,
+        &*data_store_read,
+        &summary,
+        &crawler.stats(),
+        &crawler.worker_ramp(),
+        expected_urls,
+    )
+    .await;
+    if let Some(reason) = &summary.aborted {
+        notify_webhook(&cli_args, CrawlOutcome::Aborted, reason, Some(&summary)).await;
     }
+    Some(summary)
+}
+
+/// The crawls of the process, the signals control them together
+#[derive(Clone, Default)]
+struct Crawls(Arc<Mutex<Vec<CrawlHandle>>>);
+
+impl Crawls {
+    fn add(&self, handle: CrawlHandle) {
+        self.0.lock().unwrap().push(handle);
+    }
+
+    /// The crawls that haven't finished yet
+    fn running(&self) -> Vec<CrawlHandle> {
+        let mut handles = self.0.lock().unwrap();
+        handles.retain(|handle| !handle.is_done());
+        handles.clone()
+    }
+
+    /// Stops the running crawls, still writing what they crawled so far. Returns false if there
+    /// is no crawl left to stop, i.e. they have finished and their outputs are being written.
+    fn stop(&self) -> bool {
+        let crawls = self
+            .running()
+            .into_iter()
+            .filter(|handle| !handle.is_aborted())
+            .collect::<Vec<_>>();
+        for handle in &crawls {
+            handle.abort();
+        }
 
-    while let Some(_res) = tasks.join_next().await {
-        info!("Worker completed");
+        !crawls.is_empty()
     }
+}
 
-    Ok(data_store)
+/// Pauses the crawls on SIGUSR1 and resumes them on SIGUSR2, and stops them on SIGINT or SIGTERM
+/// still writing what was crawled so far. A second SIGINT or SIGTERM, or one once the crawls are
+/// over, exits at once.
+#[cfg(unix)]
+async fn control_by_signals(crawls: Crawls) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let signals = (
+        signal(SignalKind::user_defined1()),
+        signal(SignalKind::user_defined2()),
+        signal(SignalKind::interrupt()),
+        signal(SignalKind::terminate()),
+    );
+    let (Ok(mut pause), Ok(mut resume), Ok(mut interrupt), Ok(mut terminate)) = signals else {
+        warn!("Could not listen for signals, the crawl can't be paused or stopped early");
+        return;
+    };
+
+    loop {
+        tokio::select! {
+            _ = pause.recv() => {
+                for handle in crawls.running() {
+                    handle.pause();
+                    info!("Pausing the crawl ({}), send SIGUSR2 to resume", handle.stats().await);
+                }
+            }
+            _ = resume.recv() => {
+                for handle in crawls.running() {
+                    handle.resume();
+                    info!("Resuming the crawl ({})", handle.stats().await);
+                }
+            }
+            _ = interrupt.recv() => {
+                if !crawls.stop() {
+                    process::exit(130);
+                }
+                warn!("Stopping the crawl, interrupt again to exit without writing the outputs");
+            }
+            _ = terminate.recv() => {
+                if !crawls.stop() {
+                    process::exit(143);
+                }
+                warn!("Stopping the crawl on SIGTERM");
+            }
+        }
+    }
 }
 
-#[tokio::main]
-async fn main() {
-    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+/// Stops the crawls on Ctrl-C still writing what was crawled so far. A second Ctrl-C, or one once
+/// the crawls are over, exits at once.
+#[cfg(not(unix))]
+async fn control_by_signals(crawls: Crawls) {
+    while tokio::signal::ctrl_c().await.is_ok() {
+        if !crawls.stop() {
+            process::exit(130);
+        }
+        warn!("Stopping the crawl, interrupt again to exit without writing the outputs");
+    }
+}
 
-    let cli_args = Args::parse();
-    let should_print_results = cli_args.print;
+/// Reports a crawl that didn't run to completion to the webhook, if there is one
+async fn notify_webhook(
+    cli_args: &Args,
+    outcome: CrawlOutcome,
+    reason: &str,
+    summary: Option<&CrawlSummary>,
+) {
+    let Some(webhook_url) = &cli_args.webhook_url else {
+        return;
+    };
 
-    let url_frontier = Arc::new(RwLock::new(
-        URLFrontierBuilder::new()
-            .value(cli_args.url.to_owned())
-            .delay_s(cli_args.delay)
-            .build(),
-    ));
-    let data_store = Arc::new(RwLock::new(DataStore::new()));
-    let dependencies = Dependencies {
-        url_frontier,
-        data_store,
+    let report = WebhookReport {
+        seed: &cli_args.url.join(", "),
+        outcome,
+        reason,
+        summary,
+        export: cli_args
+            .output_file
+            .as_deref()
+            .filter(|_| summary.is_some()),
     };
+    if let Err(e) = report.post(webhook_url.as_str()).await {
+        warn!("Could not notify {}: {}", webhook_url, e);
+    }
+}
 
-    match execute(cli_args, dependencies).await {
-        Ok(val) => {
-            info!("Done!");
+async fn bench(bench_args: BenchArgs) {
+    let site = BenchSite {
+        pages: bench_args.pages,
+        page_size: bench_args.page_size,
+        links_per_page: bench_args.links_per_page,
+    };
 
-            if should_print_results {
-                let data_store_read = val.read().await;
-                println!("{:?}", *data_store_read);
-            }
+    let base_url = match site.serve().await {
+        Ok(val) => val,
+        Err(e) => {
+            warn!("Could not start bench server: {}", e);
+            return;
+        }
+    };
+
+    let mut crawler = CrawlerBuilder::new()
+        .seed(&format!("{}/page/0", base_url))
+        .workers(Workers::Fixed(bench_args.workers_n))
+        .delay_s(0)
+        .build();
+
+    let allocations_before = AllocationStats::now();
+    let started_at = Instant::now();
+
+    let data_store = crawler.run().await;
+    let elapsed = started_at.elapsed();
+    let allocations = AllocationStats::now().since(&allocations_before);
+    let pages = data_store
+        .read()
+        .await
+        .entries()
+        .into_iter()
+        .filter(|(_, entry)| entry.visited)
+        .count();
+
+    print!(
+        "{}",
+        BenchReport {
+            pages,
+            elapsed,
+            allocations,
+        }
+    );
+}
+
+fn prune_store(prune_args: PruneArgs) -> Result<(), String> {
+    let StoreBackend::Sqlite(path) = &prune_args.store;
+    // opening a missing database would create an empty one
+    if !path.is_file() {
+        return Err(format!("{}: no such store", prune_args.store));
+    }
+
+    let mut store =
+        SqliteStore::open(path, true).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let prune = Prune {
+        drop_external_edges: prune_args.drop_external_edges,
+        drop_bodies: prune_args.drop_bodies,
+        errors_only: prune_args.errors_only,
+    };
+    let report =
+        prune::prune(&mut store, prune).map_err(|e| format!("{}: {}", path.display(), e))?;
+    print!("{}", report);
+
+    Ok(())
+}
+
+fn audit(audit: Audit) -> Result<(), String> {
+    let (Audit::Alternates { store } | Audit::Mobile { store }) = &audit;
+    let StoreBackend::Sqlite(path) = store;
+    // opening a missing database would create an empty one
+    if !path.is_file() {
+        return Err(format!("{}: no such store", store));
+    }
+
+    let store = SqliteStore::open(path, true).map_err(|e| format!("{}: {}", path.display(), e))?;
+    match audit {
+        Audit::Alternates { .. } => print!("{}", AlternatesAudit::from_store(&store)),
+        Audit::Mobile { .. } => print!("{}", MobileAudit::from_store(&store)),
+    }
+
+    Ok(())
+}
+
+fn frontier(frontier: Frontier) -> Result<(), String> {
+    let Frontier::Dump {
+        store,
+        frontier_policy,
+    } = frontier;
+    let StoreBackend::Sqlite(path) = &store;
+    // opening a missing database would create an empty one
+    if !path.is_file() {
+        return Err(format!("{}: no such store", store));
+    }
+
+    let store = SqliteStore::open(path, true).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let dump = FrontierDump::from_store(&store, frontier_policy)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    print!("{}", dump);
+
+    Ok(())
+}
+
+fn timeline(timeline_args: TimelineArgs) -> Result<(), String> {
+    let path = &timeline_args.path;
+    let events = event_log::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if events.is_empty() {
+        return Err(format!("{}: no events", path.display()));
+    }
+    print!("{}", Timeline::new(&events, timeline_args.width));
+
+    Ok(())
+}
+
+fn merge_stores(merge_args: MergeArgs) -> Result<(), String> {
+    let StoreBackend::Sqlite(into_path) = &merge_args.into;
+    let mut into = SqliteStore::open(into_path, true)
+        .map_err(|e| format!("{}: {}", into_path.display(), e))?;
+
+    let mut shared = 0;
+    for store in &merge_args.stores {
+        let StoreBackend::Sqlite(path) = store;
+        // opening a missing database would create an empty one
+        if !path.is_file() {
+            return Err(format!("{}: no such store", store));
         }
+        let store =
+            SqliteStore::open(path, true).map_err(|e| format!("{}: {}", path.display(), e))?;
+        shared += into.merge(&store);
+    }
+    into.flush()
+        .map_err(|e| format!("{}: {}", into_path.display(), e))?;
+
+    println!(
+        "Merged {} stores into {}: {} pages, {} of them found in several stores",
+        merge_args.stores.len(),
+        merge_args.into,
+        into.entries().len(),
+        shared
+    );
+
+    Ok(())
+}
+
+/// Asks a question on the terminal, falling back to the default on an empty answer
+fn prompt(question: &str, default: &str) -> String {
+    print!("{} [{}]: ", question, default);
+    let _ = io::stdout().flush();
+
+    let mut answer = String::new();
+    let _ = io::stdin().read_line(&mut answer);
+    match answer.trim() {
+        "" => default.to_owned(),
+        answer => answer.to_owned(),
+    }
+}
+
+fn init(init_args: InitArgs) {
+    if init_args.path.exists() && !init_args.force {
+        warn!(
+            "{} already exists, use --force to overwrite it",
+            init_args.path.display()
+        );
+        return;
+    }
+
+    // only ask when someone is there to answer
+    let interactive = io::stdin().is_terminal();
+    let ask = |question: &str, default: &str| match interactive {
+        true => prompt(question, default),
+        false => default.to_owned(),
+    };
+
+    let url = match init_args.url.is_empty() {
+        true => vec![ask("URL to crawl", "https://example.com")],
+        false => init_args.url,
+    };
+    let scope = match init_args.scope {
+        Some(scope) => scope
+            .to_possible_value()
+            .map(|value| value.get_name().to_owned()),
+        None => Some(ask(
+            "Scope (same-domain, external-once, all-domains)",
+            "same-domain",
+        )),
+    };
+    let workers_n = match init_args.workers_n {
+        Some(workers_n) => workers_n.to_string(),
+        None => ask("Number of workers, or auto", "1"),
+    };
+    let delay = match init_args.delay {
+        Some(delay) => delay,
+        None => ask("Delay between requests (in seconds)", "2")
+            .parse()
+            .unwrap_or(2),
+    };
+    let respect_robots = match init_args.respect_robots {
+        Some(respect_robots) => respect_robots,
+        None => !ask("Respect robots.txt (y/n)", "y").eq_ignore_ascii_case("n"),
+    };
+    let output_file = match init_args.output_file {
+        Some(output_file) => Some(output_file),
+        None => Some(ask("JSON output file (leave - for none)", "-")).filter(|path| path != "-"),
+    };
+
+    let starter = config::Starter {
+        url,
+        scope: scope.unwrap_or("same-domain".to_owned()),
+        workers_n,
+        delay,
+        respect_robots,
+        output_file,
+    };
+    match fs::write(&init_args.path, starter.render()) {
+        Ok(_) => info!(
+            "Wrote {}, crawl with `url-crawler --config {}`",
+            init_args.path.display(),
+            init_args.path.display()
+        ),
+        Err(e) => warn!("Could not write {}: {}", init_args.path.display(), e),
+    }
+}
+
+/// Every problem with a config file, empty if it can be crawled with
+fn check_config(path: &Path) -> Vec<String> {
+    let args = match config::load(path) {
+        Ok(args) => args,
+        Err(e) => return vec![e.to_string()],
+    };
+
+    let args = iter::once("url-crawler".to_owned()).chain(args);
+    match Cli::try_parse_from(args) {
+        Ok(Cli {
+            crawl: Some(args), ..
+        }) => args.validate(),
+        Ok(_) => vec!["no crawl options found".to_owned()],
+        // options are named after the config keys, so clap's message points at the right key
         Err(e) => {
-            warn!("There's been an error: {}", e);
+            let message = e.render().to_string();
+            let message = message.split("\n\n").next().unwrap_or_default();
+            vec![message.trim_start_matches("error: ").to_owned()]
+        }
+    }
+}
+
+/// Inserts the options of the `--config` file (if any) ahead of the command line ones
+fn args_with_config(args: Vec<OsString>) -> Result<Vec<OsString>, config::Error> {
+    let path = args.iter().enumerate().find_map(|(i, arg)| {
+        let arg = arg.to_str()?;
+        match arg.strip_prefix("--config") {
+            Some("") => args.get(i + 1).map(PathBuf::from),
+            Some(path) => path.strip_prefix('=').map(PathBuf::from),
+            None => None,
+        }
+    });
+    let Some(path) = path else {
+        return Ok(args);
+    };
+
+    let mut args = args.into_iter();
+    Ok(args
+        .next()
+        .into_iter()
+        .chain(config::load(&path)?.into_iter().map(OsString::from))
+        .chain(args)
+        .collect())
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = match args_with_config(env::args_os().collect()) {
+        Ok(args) => Cli::parse_from(args),
+        Err(e) => {
+            eprintln!("Could not load config: {}", e);
+            process::exit(2);
         }
     };
+
+    // per URL logging would dominate the benchmark
+    let default_log_level = match cli.command {
+        Some(Command::Bench(_)) => "warn",
+        Some(
+            Command::Completions { .. }
+            | Command::Init(_)
+            | Command::CheckConfig { .. }
+            | Command::Prune(_)
+            | Command::Merge(_)
+            | Command::Timeline(_)
+            | Command::Audit { .. }
+            | Command::Frontier { .. },
+        )
+        | None => "info",
+    };
+    let mut logger =
+        env_logger::Builder::from_env(Env::default().default_filter_or(default_log_level));
+    if cli.crawl.as_ref().is_some_and(|cli_args| cli_args.service) {
+        // journald timestamps each line itself, and reads its priority from the prefix
+        logger.format(|buf, record| {
+            writeln!(
+                buf,
+                "{}{}",
+                service::journald_prefix(record.level()),
+                record.args()
+            )
+        });
+    }
+    logger.init();
+
+    match cli.command {
+        Some(Command::Bench(bench_args)) => bench(bench_args).await,
+        Some(Command::Init(init_args)) => init(init_args),
+        Some(Command::Prune(prune_args)) => {
+            if let Err(e) = prune_store(prune_args) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Merge(merge_args)) => {
+            if let Err(e) = merge_stores(merge_args) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Timeline(timeline_args)) => {
+            if let Err(e) = timeline(timeline_args) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Audit {
+            audit: audit_command,
+        }) => {
+            if let Err(e) = audit(audit_command) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Frontier {
+            frontier: frontier_command,
+        }) => {
+            if let Err(e) = frontier(frontier_command) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::CheckConfig { path }) => {
+            let problems = check_config(&path);
+            if !problems.is_empty() {
+                for problem in problems {
+                    eprintln!("{}: {}", path.display(), problem);
+                }
+                process::exit(1);
+            }
+            println!("{}: ok", path.display());
+        }
+        Some(Command::Completions { shell }) => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_owned();
+            clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+        }
+        None => {
+            if let Some(cli_args) = cli.crawl {
+                // listened for until the process exits, so that a signal received while the
+                // outputs are written still stops it
+                let crawls = Crawls::default();
+                tokio::spawn(control_by_signals(crawls.clone()));
+                crawl(cli_args, crawls).await
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::path::{Path, PathBuf};
 
-    use tokio::sync::RwLock;
     use url_crawler::{
+        concurrency::Workers,
         data_store::{DataStore, Store},
-        url_frontier::URLFrontierBuilder,
+        dedupe::DedupeKey,
+        export::OutputFormat,
+        fetch::{RedirectPolicy, DEFAULT_MAX_REDIRECTS, DEFAULT_STREAM_DEADLINE},
+        fingerprint::content_hash,
+        link::{LinkDecision, LinkSource, Scope},
+        mime::{MediaType, TypeMismatch},
+        mobile::MobileSignals,
+        queue::FrontierPolicy,
+        robots::UnreachablePolicy,
     };
     use wiremock::{
         matchers::{method, path},
         Mock, MockServer, ResponseTemplate,
     };
 
-    use crate::{execute, Args, Dependencies};
+    use clap::{CommandFactory, Parser};
+
+    use crate::{args_with_config, check_config, path_for_seed, Args, Cli};
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn command_line_options_override_config_file_ones() {
+        let config =
+            std::env::temp_dir().join(format!("url-crawler-config-{}.toml", std::process::id()));
+        std::fs::write(
+            &config,
+            "url = [\"https://a.com\"]\ndelay = 5\nstats = true\n",
+        )
+        .unwrap();
+
+        let args = args_with_config(
+            [
+                "url-crawler",
+                "--config",
+                config.to_str().unwrap(),
+                "-d",
+                "1",
+            ]
+            .map(Into::into)
+            .to_vec(),
+        )
+        .unwrap();
+        let args = Cli::try_parse_from(args).unwrap().crawl.unwrap();
+
+        assert_eq!(args.url, vec!["https://a.com"]);
+        assert_eq!(args.delay, 1);
+        assert!(args.stats);
+        let _ = std::fs::remove_file(&config);
+    }
+
+    #[test]
+    fn check_config_reports_invalid_values_and_conflicts() {
+        let config = std::env::temp_dir().join(format!(
+            "url-crawler-check-config-{}.toml",
+            std::process::id()
+        ));
+
+        std::fs::write(&config, "url = [\"https://a.com\"]\n").unwrap();
+        assert!(check_config(&config).is_empty());
+
+        std::fs::write(
+            &config,
+            "url = [\"ftp://a.com\"]\noutput-file = \"/missing/out.json\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            check_config(&config),
+            vec![
+                "url `ftp://a.com` must be a http:// or https:// URL",
+                "output-file: directory `/missing` does not exist"
+            ]
+        );
+
+        std::fs::write(
+            &config,
+            "url = [\"https://a.com\"]\nemit = \"ndjson\"\nporcelain = true\n",
+        )
+        .unwrap();
+        assert!(check_config(&config)[0].contains("--emit"));
+
+        std::fs::write(&config, "url = [\"https://a.com\"\n").unwrap();
+        assert_eq!(check_config(&config).len(), 1);
+        let _ = std::fs::remove_file(&config);
+    }
+
+    #[test]
+    fn completions_are_generated_for_every_flag() {
+        let mut script = vec![];
+        clap_complete::generate(
+            clap_complete::Shell::Bash,
+            &mut Cli::command(),
+            "url-crawler",
+            &mut script,
+        );
+        let script = String::from_utf8(script).unwrap();
+
+        assert!(script.contains("--respect-robots"));
+        assert!(script.contains("completions"));
+    }
 
     fn make_hrefs(base_uri: &str) -> Vec<String> {
         let url1 = format!("{}/about", &base_uri);
@@ -189,7 +2006,7 @@ mod tests {
         let anchors = make_anchors(hrefs.to_vec());
 
         // mock http requests
-        let response = ResponseTemplate::new(200).set_body_string(anchors);
+        let response = ResponseTemplate::new(200).set_body_string(anchors.clone());
         Mock::given(method("GET"))
             .and(path("/"))
             .respond_with(response)
@@ -197,28 +2014,88 @@ mod tests {
             .await;
 
         let cli_args = Args {
-            url: mock_server.uri().to_owned(),
-            workers_n: 1,
+            url: vec![mock_server.uri().to_owned()],
+            config: None,
+            workers_n: Workers::Fixed(1),
+            workers_min: 1,
+            workers_max: 16,
             delay: 0,
+            delay_formula: None,
+            frontier_policy: FrontierPolicy::Fifo,
+            politeness_log: None,
+            block_pause_s: 0,
+            crawl_window: vec![],
+            crawl_window_utc_offset: Default::default(),
             print: false,
-        };
-
-        // dependencies
-        let url_frontier = Arc::new(RwLock::new(
-            URLFrontierBuilder::new()
-                .value(cli_args.url.to_owned())
-                .delay_s(cli_args.delay)
-                .build(),
-        ));
-        let data_store = Arc::new(RwLock::new(Store::new()));
-        let dependencies = Dependencies {
-            url_frontier,
-            data_store,
+            scope: Scope::SameDomain,
+            output_file: None,
+            output_format: OutputFormat::Json,
+            expect: None,
+            redirects_file: None,
+            fingerprints: None,
+            monitor: false,
+            changed_feed: None,
+            stats: false,
+            summary_file: None,
+            metrics_file: None,
+            webhook_url: None,
+            tag: vec![],
+            anonymize_key: None,
+            duplicates: false,
+            sorted_export: false,
+            deterministic: false,
+            event_log: None,
+            isolate_seeds: false,
+            respect_robots: false,
+            robots_cache_ttl: 3600,
+            robots_unreachable: UnreachablePolicy::Deny,
+            use_sitemaps: false,
+            exclude_search_pages: None,
+            https_only: false,
+            user_agent: None,
+            host_header: None,
+            resolve: vec![],
+            accept_language: vec![],
+            per_locale: false,
+            http1_only: false,
+            http2_prior_knowledge: false,
+            ipv4_only: false,
+            ipv6_only: false,
+            dns_prefetch: false,
+            max_concurrent_requests: None,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            #[cfg(feature = "doh")]
+            doh_url: None,
+            stream_deadline_s: DEFAULT_STREAM_DEADLINE.as_secs(),
+            fetch_rule: vec![],
+            range_probe: None,
+            redirect_policy: RedirectPolicy::Follow,
+            body_sample_kb: None,
+            ramp_up_s: 0,
+            aggressive_extraction: false,
+            snapshot_external: false,
+            follow_link_text: vec![],
+            skip_link_text: vec![],
+            crawl_get_forms: false,
+            max_links_per_page: None,
+            max_bytes: None,
+            max_depth: None,
+            max_pages: None,
+            sample: None,
+            path_quota: vec![],
+            abort_error_rate: None,
+            dedupe_key: DedupeKey::Url,
+            emit: None,
+            porcelain: false,
+            service: false,
+            health_addr: None,
+            health_stall_s: 600,
+            store: None,
+            resume: false,
         };
 
         // --- act
-        let actual = execute(cli_args, dependencies).await;
-        let data_store = actual.unwrap();
+        let data_store = cli_args.crawler().build().run().await;
         let actual = data_store.read().await;
 
         // --- assert
@@ -227,13 +2104,54 @@ mod tests {
             expected.add(mock_server_uri.to_owned(), Some(url.clone()));
             expected.visited(&mock_server_uri);
         }
+        expected.set_status(&mock_server_uri, 200);
+        expected.set_content_hash(&mock_server_uri, content_hash(&anchors));
+        // the mock serves the page as text/plain
+        expected.set_type_mismatch(
+            &mock_server_uri,
+            TypeMismatch {
+                declared: Some("text/plain".to_owned()),
+                sniffed: MediaType::Html,
+            },
+        );
+        expected.set_mobile(&mock_server_uri, MobileSignals::default());
+        expected.set_link_decision(&mock_server_uri, hrefs[0].clone(), LinkDecision::Enqueue);
+        expected.set_link_decision(&mock_server_uri, hrefs[1].clone(), LinkDecision::Enqueue);
+        expected.set_link_decision(
+            &mock_server_uri,
+            hrefs[2].clone(),
+            LinkDecision::SkipExternal,
+        );
+        for (i, href) in hrefs.iter().enumerate() {
+            expected.set_link_source(
+                &mock_server_uri,
+                href.clone(),
+                LinkSource {
+                    position: i + 1,
+                    tag: Some("a".to_owned()),
+                    rel: vec![],
+                },
+            );
+        }
 
+        // unmocked paths respond with 404
         expected.add(hrefs[0].clone(), None);
         expected.visited(&hrefs[0]);
+        expected.set_status(&hrefs[0], 404);
+        expected.set_content_hash(&hrefs[0], content_hash(""));
 
         expected.add(hrefs[1].clone(), None);
         expected.visited(&hrefs[1]);
+        expected.set_status(&hrefs[1], 404);
+        expected.set_content_hash(&hrefs[1], content_hash(""));
 
         assert_eq!(expected, *actual);
     }
+
+    #[test]
+    fn path_for_seed_inserts_seed_host_into_file_name() {
+        let actual = path_for_seed(Path::new("out/pages.json"), "https://www.example.com/");
+
+        assert_eq!(actual, PathBuf::from("out/pages.www.example.com.json"));
+    }
 }
@@ -0,0 +1,355 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use url::Url;
+
+/// Bytes at the start of the body looked at to sniff its type
+const SNIFF_BYTES: usize = 1024;
+/// Lowercase starts of HTML documents, each followed by a space or `>`
+const HTML_PREFIXES: [&[u8]; 17] = [
+    b"<!doctype html",
+    b"<html",
+    b"<head",
+    b"<script",
+    b"<iframe",
+    b"<h1",
+    b"<div",
+    b"<font",
+    b"<table",
+    b"<a",
+    b"<style",
+    b"<title",
+    b"<b",
+    b"<body",
+    b"<br",
+    b"<p",
+    b"<!--",
+];
+/// Magic bytes of binary formats
+const SIGNATURES: [(&[u8], MediaType); 9] = [
+    (b"%PDF-", MediaType::Pdf),
+    (b"\x89PNG\r\n\x1a\n", MediaType::Image),
+    (b"GIF87a", MediaType::Image),
+    (b"GIF89a", MediaType::Image),
+    (b"\xff\xd8\xff", MediaType::Image),
+    (b"PK\x03\x04", MediaType::Archive),
+    (b"\x1f\x8b\x08", MediaType::Archive),
+    (b"Rar!\x1a\x07", MediaType::Archive),
+    (b"7z\xbc\xaf\x27\x1c", MediaType::Archive),
+];
+
+/// What kind of document a response is, as declared by its `Content-Type` or sniffed from its body
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MediaType {
+    Html,
+    Xml,
+    Json,
+    Text,
+    Pdf,
+    Image,
+    Archive,
+    Other,
+}
+
+impl MediaType {
+    /// Kind of document of a `Content-Type`, i.e. `text/html; charset=utf-8`
+    pub fn from_content_type(content_type: &str) -> Self {
+        let essence = essence(content_type);
+
+        match essence.as_str() {
+            "text/html" | "application/xhtml+xml" => MediaType::Html,
+            "application/json" => MediaType::Json,
+            "text/xml" | "application/xml" => MediaType::Xml,
+            "application/pdf" => MediaType::Pdf,
+            "application/zip"
+            | "application/gzip"
+            | "application/x-gzip"
+            | "application/x-tar"
+            | "application/x-rar-compressed"
+            | "application/x-7z-compressed" => MediaType::Archive,
+            _ if essence.ends_with("+json") => MediaType::Json,
+            _ if essence.ends_with("+xml") => MediaType::Xml,
+            _ if essence.starts_with("image/") => MediaType::Image,
+            _ if essence.starts_with("text/") => MediaType::Text,
+            _ => MediaType::Other,
+        }
+    }
+
+    /// Whether documents of this kind can't have links to parse
+    pub fn is_binary(&self) -> bool {
+        matches!(self, MediaType::Pdf | MediaType::Image | MediaType::Archive)
+    }
+}
+
+impl fmt::Display for MediaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MediaType::Html => "html",
+            MediaType::Xml => "xml",
+            MediaType::Json => "json",
+            MediaType::Text => "text",
+            MediaType::Pdf => "pdf",
+            MediaType::Image => "image",
+            MediaType::Archive => "archive",
+            MediaType::Other => "other",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// URL path extensions of large binary files, with no links to parse
+const BINARY_EXTENSIONS: [&str; 28] = [
+    "7z", "apk", "avi", "bin", "bz2", "dmg", "exe", "flac", "gif", "gz", "iso", "jpeg", "jpg",
+    "m4v", "mkv", "mov", "mp3", "mp4", "msi", "ogg", "pdf", "png", "rar", "tar", "tgz", "wav",
+    "webm", "zip",
+];
+
+/// Whether the URL's path ends with the extension of a binary file, i.e. `.pdf` or `.zip`
+pub fn has_binary_extension(url: &str) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+    let file_name = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or_default();
+
+    match file_name.rsplit_once('.') {
+        Some((_, extension)) => BINARY_EXTENSIONS.contains(&extension.to_lowercase().as_str()),
+        None => false,
+    }
+}
+
+/// Media types of responses that never end, streaming events or frames for as long as they are read
+const STREAMING_TYPES: [&str; 2] = ["text/event-stream", "multipart/x-mixed-replace"];
+
+/// Whether a `Content-Type` declares a response that streams indefinitely, i.e. Server-Sent Events
+pub fn is_streaming(content_type: &str) -> bool {
+    STREAMING_TYPES.contains(&essence(content_type).as_str())
+}
+
+/// The media type of a `Content-Type`, without its parameters, lowercase
+pub fn essence(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+/// How a parseable document is read for its links
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DocumentKind {
+    /// Read leniently, as browsers do
+    Html,
+    /// HTML written as XML, read as XML first
+    Xhtml,
+    /// Any other XML, i.e. a feed, whose links are read from its elements
+    Xml,
+}
+
+/// Kind of a parseable document, from its `Content-Type`, XML declaration and sniffed type
+pub fn document_kind(declared: Option<&str>, body: &[u8]) -> DocumentKind {
+    let essence = declared.map(essence).unwrap_or_default();
+    if essence == "application/xhtml+xml" {
+        return DocumentKind::Xhtml;
+    }
+
+    match sniff(body) {
+        // only XHTML starts with both an XML declaration and `<html`
+        Some(MediaType::Html) if document_start(body).starts_with(b"<?xml") => DocumentKind::Xhtml,
+        Some(MediaType::Xml) => DocumentKind::Xml,
+        Some(_) => DocumentKind::Html,
+        // feeds don't always start with an XML declaration
+        None if MediaType::from_content_type(&essence) == MediaType::Xml
+            || essence.ends_with("+xml") =>
+        {
+            DocumentKind::Xml
+        }
+        None => DocumentKind::Html,
+    }
+}
+
+/// The sniffed start of a body, lowercase and without its byte order mark and leading whitespace
+fn document_start(body: &[u8]) -> Vec<u8> {
+    let body = &body[..body.len().min(SNIFF_BYTES)];
+    let start = body.strip_prefix(b"\xef\xbb\xbf").unwrap_or(body);
+    let whitespace = start
+        .iter()
+        .take_while(|byte| byte.is_ascii_whitespace())
+        .count();
+
+    start[whitespace..].to_ascii_lowercase()
+}
+
+/// Kind of document the body is, from its magic bytes or the start of an HTML or XML document
+pub fn sniff(body: &[u8]) -> Option<MediaType> {
+    let body = &body[..body.len().min(SNIFF_BYTES)];
+    if let Some((_, media_type)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| body.starts_with(signature))
+    {
+        return Some(*media_type);
+    }
+
+    let start = document_start(body);
+    if start.starts_with(b"<?xml") {
+        // XHTML documents start with an XML declaration too
+        return match start.windows(5).any(|window| window == b"<html") {
+            true => Some(MediaType::Html),
+            false => Some(MediaType::Xml),
+        };
+    }
+    HTML_PREFIXES
+        .iter()
+        .any(|prefix| {
+            start.starts_with(prefix) && matches!(start.get(prefix.len()), Some(b' ' | b'>'))
+        })
+        .then_some(MediaType::Html)
+}
+
+/// A response whose body is not of the kind its `Content-Type` declares, or that has none
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct TypeMismatch {
+    /// The declared media type, without its parameters
+    pub declared: Option<String>,
+    pub sniffed: MediaType,
+}
+
+impl TypeMismatch {
+    /// The mismatch between the declared `Content-Type` and the sniffed type, if they differ
+    pub fn new(declared: Option<&str>, sniffed: Option<MediaType>) -> Option<Self> {
+        let sniffed = sniffed?;
+
+        match declared.map(MediaType::from_content_type) == Some(sniffed) {
+            true => None,
+            false => Some(TypeMismatch {
+                declared: declared.map(essence),
+                sniffed,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for TypeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.declared {
+            Some(declared) => write!(f, "declared {}, sniffed {}", declared, self.sniffed),
+            None => write!(f, "no Content-Type, sniffed {}", self.sniffed),
+        }
+    }
+}
+
+/// Whether a body can have links to parse. The sniffed type wins over the declared one, as it is
+/// missing or wrong whenever they differ, and bodies of unknown types are parsed.
+pub fn is_parseable(declared: Option<&str>, sniffed: Option<MediaType>) -> bool {
+    !sniffed
+        .or(declared.map(MediaType::from_content_type))
+        .is_some_and(|media_type| media_type.is_binary())
+}
+
+#[cfg(test)]
+mod mime_tests {
+    use super::{
+        document_kind, has_binary_extension, is_parseable, is_streaming, sniff, DocumentKind,
+        MediaType, TypeMismatch,
+    };
+
+    #[test]
+    fn sniff_finds_html_xml_and_magic_bytes() {
+        assert_eq!(
+            sniff(b"\xef\xbb\xbf\n  <!DOCTYPE HTML><html>"),
+            Some(MediaType::Html)
+        );
+        assert_eq!(sniff(b"<p>Hi</p>"), Some(MediaType::Html));
+        assert_eq!(
+            sniff(b"<?xml version=\"1.0\"?><html xmlns=\"http://www.w3.org/1999/xhtml\">"),
+            Some(MediaType::Html)
+        );
+        assert_eq!(
+            sniff(b"<?xml version=\"1.0\"?><urlset>"),
+            Some(MediaType::Xml)
+        );
+        assert_eq!(sniff(b"%PDF-1.7\n"), Some(MediaType::Pdf));
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0"), Some(MediaType::Image));
+        // `<pre>` is not `<p>`
+        assert_eq!(sniff(b"<pre>"), None);
+        assert_eq!(sniff(b"Hello"), None);
+    }
+
+    #[test]
+    fn document_kind_tells_xhtml_and_xml_from_html() {
+        let xhtml = b"<?xml version=\"1.0\"?>\n<html xmlns=\"http://www.w3.org/1999/xhtml\">";
+
+        assert_eq!(document_kind(Some("text/html"), xhtml), DocumentKind::Xhtml);
+        assert_eq!(
+            document_kind(Some("application/xhtml+xml"), b"<html>"),
+            DocumentKind::Xhtml
+        );
+        assert_eq!(
+            document_kind(Some("text/html"), b"<!DOCTYPE html><html>"),
+            DocumentKind::Html
+        );
+        assert_eq!(
+            document_kind(Some("application/rss+xml"), b"<rss version=\"2.0\">"),
+            DocumentKind::Xml
+        );
+        assert_eq!(
+            document_kind(None, b"<?xml version=\"1.0\"?><feed>"),
+            DocumentKind::Xml
+        );
+        assert_eq!(
+            document_kind(Some("text/plain"), b"Hello"),
+            DocumentKind::Html
+        );
+    }
+
+    #[test]
+    fn type_mismatch_is_recorded_when_missing_or_wrong() {
+        assert_eq!(
+            TypeMismatch::new(Some("text/html; charset=utf-8"), Some(MediaType::Html)),
+            None
+        );
+        assert_eq!(TypeMismatch::new(Some("text/plain"), None), None);
+        assert_eq!(
+            TypeMismatch::new(Some("Text/HTML"), Some(MediaType::Pdf)),
+            Some(TypeMismatch {
+                declared: Some("text/html".to_owned()),
+                sniffed: MediaType::Pdf,
+            })
+        );
+        assert_eq!(
+            TypeMismatch::new(None, Some(MediaType::Html))
+                .unwrap()
+                .to_string(),
+            "no Content-Type, sniffed html"
+        );
+    }
+
+    #[test]
+    fn is_parseable_trusts_the_sniffed_type() {
+        assert!(is_parseable(Some("image/png"), Some(MediaType::Html)));
+        assert!(!is_parseable(Some("text/html"), Some(MediaType::Pdf)));
+        assert!(!is_parseable(Some("application/zip"), None));
+        assert!(is_parseable(Some("text/plain"), None));
+        assert!(is_parseable(None, None));
+    }
+
+    #[test]
+    fn is_streaming_recognises_event_streams() {
+        assert!(is_streaming("text/event-stream; charset=utf-8"));
+        assert!(is_streaming("multipart/x-mixed-replace; boundary=frame"));
+        assert!(!is_streaming("text/html"));
+    }
+
+    #[test]
+    fn has_binary_extension_looks_at_the_file_name_only() {
+        assert!(has_binary_extension("https://a.com/files/report.PDF?v=2"));
+        assert!(has_binary_extension("https://a.com/release-1.0.tar.gz"));
+        assert!(!has_binary_extension("https://a.com/zip/"));
+        assert!(!has_binary_extension("https://a.com/page.html#a.zip"));
+        assert!(!has_binary_extension("https://downloads.zip/"));
+    }
+}
@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+use crate::data_store::DataStore;
+
+/// Signs of whether a page is made for small screens, from its HTML
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct MobileSignals {
+    /// Content of the page's `<meta name="viewport">`
+    pub viewport: Option<String>,
+    /// Whether the page adapts its styles to the screen, with media queries in `<link media>`
+    /// or `<style>` elements
+    pub media_queries: bool,
+    /// Whether images come in several sizes, with `srcset` or `<picture>`
+    pub responsive_images: bool,
+}
+
+impl MobileSignals {
+    /// Value of a property of the viewport, i.e. `width`, lowercased
+    fn viewport_property(&self, name: &str) -> Option<String> {
+        self.viewport
+            .as_deref()?
+            .split([',', ';'])
+            .filter_map(|property| property.split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim().to_lowercase())
+    }
+
+    /// What keeps the page from being readable on a phone
+    pub fn issues(&self) -> Vec<MobileIssue> {
+        if self.viewport.is_none() {
+            return vec![MobileIssue::NoViewport];
+        }
+
+        let mut issues = vec![];
+        if let Some(width) = self
+            .viewport_property("width")
+            .filter(|width| width != "device-width")
+        {
+            issues.push(MobileIssue::FixedWidth(width));
+        }
+        let zoom_disabled = matches!(
+            self.viewport_property("user-scalable").as_deref(),
+            Some("no" | "0")
+        ) || self
+            .viewport_property("maximum-scale")
+            .and_then(|scale| scale.parse::<f64>().ok())
+            .is_some_and(|scale| scale <= 1.0);
+        if zoom_disabled {
+            issues.push(MobileIssue::ZoomDisabled);
+        }
+
+        issues
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum MobileIssue {
+    /// No `<meta name="viewport">`, so phones render the page at desktop width
+    NoViewport,
+    /// The viewport has a fixed width rather than the device's
+    FixedWidth(String),
+    /// The viewport keeps users from zooming in
+    ZoomDisabled,
+}
+
+impl fmt::Display for MobileIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MobileIssue::NoViewport => write!(f, "no viewport, rendered at desktop width"),
+            MobileIssue::FixedWidth(width) => {
+                write!(f, "viewport width is {}, not device-width", width)
+            }
+            MobileIssue::ZoomDisabled => write!(f, "viewport disables zooming"),
+        }
+    }
+}
+
+/// Mobile readiness of the crawled HTML pages, from the signals recorded for them
+#[derive(Debug, PartialEq, Default)]
+pub struct MobileAudit {
+    /// Issues by page, of the pages with any
+    pub issues: BTreeMap<String, Vec<MobileIssue>>,
+    /// HTML pages checked
+    pub pages: usize,
+    pub with_viewport: usize,
+    pub with_media_queries: usize,
+    pub with_responsive_images: usize,
+}
+
+impl MobileAudit {
+    pub fn from_store<T: DataStore>(store: &T) -> Self {
+        let mut audit = MobileAudit::default();
+
+        for (page, entry) in store.entries() {
+            let Some(mobile) = &entry.mobile else {
+                continue;
+            };
+            audit.pages += 1;
+            audit.with_viewport += mobile.viewport.is_some() as usize;
+            audit.with_media_queries += mobile.media_queries as usize;
+            audit.with_responsive_images += mobile.responsive_images as usize;
+
+            let issues = mobile.issues();
+            if !issues.is_empty() {
+                audit.issues.insert(page.clone(), issues);
+            }
+        }
+
+        audit
+    }
+}
+
+impl fmt::Display for MobileAudit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Mobile readiness of {} pages checked: {} with a viewport, {} with media queries, {} with responsive images, {} with issues:",
+            self.pages,
+            self.with_viewport,
+            self.with_media_queries,
+            self.with_responsive_images,
+            self.issues.len()
+        )?;
+        for (page, issues) in &self.issues {
+            writeln!(f, "  {}", page)?;
+            for issue in issues {
+                writeln!(f, "    {}", issue)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod mobile_tests {
+    use std::collections::BTreeMap;
+
+    use super::{MobileAudit, MobileIssue, MobileSignals};
+    use crate::data_store::{DataStore, Store};
+
+    fn signals(viewport: Option<&str>) -> MobileSignals {
+        MobileSignals {
+            viewport: viewport.map(str::to_owned),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn mobile_signals_issues_come_from_the_viewport() {
+        assert!(signals(Some("width=device-width, initial-scale=1"))
+            .issues()
+            .is_empty());
+        assert_eq!(signals(None).issues(), vec![MobileIssue::NoViewport]);
+        assert_eq!(
+            signals(Some("width=980; user-scalable=no")).issues(),
+            vec![
+                MobileIssue::FixedWidth("980".to_owned()),
+                MobileIssue::ZoomDisabled
+            ]
+        );
+        assert_eq!(
+            signals(Some("Width=Device-Width, maximum-scale=1.0")).issues(),
+            vec![MobileIssue::ZoomDisabled]
+        );
+    }
+
+    #[test]
+    fn mobile_audit_counts_signals_and_lists_pages_with_issues() {
+        let mut s = Store::new();
+        for (url, mobile) in [
+            (
+                "https://a.com/",
+                MobileSignals {
+                    media_queries: true,
+                    responsive_images: true,
+                    ..signals(Some("width=device-width"))
+                },
+            ),
+            ("https://a.com/old", signals(None)),
+        ] {
+            s.add(url.to_owned(), None);
+            s.visited(url);
+            s.set_mobile(url, mobile);
+        }
+        s.add("https://a.com/file.pdf".to_owned(), None);
+
+        let audit = MobileAudit::from_store(&s);
+
+        assert_eq!(
+            audit,
+            MobileAudit {
+                issues: BTreeMap::from([(
+                    "https://a.com/old".to_owned(),
+                    vec![MobileIssue::NoViewport]
+                )]),
+                pages: 2,
+                with_viewport: 1,
+                with_media_queries: 1,
+                with_responsive_images: 1,
+            }
+        );
+        assert!(audit
+            .to_string()
+            .contains("  https://a.com/old\n    no viewport, rendered at desktop width\n"));
+    }
+}
@@ -0,0 +1,178 @@
+use ::object_store::{
+    aws::{AmazonS3, AmazonS3Builder, AmazonS3ConfigKey},
+    gcp::{GoogleCloudStorage, GoogleCloudStorageBuilder, GoogleConfigKey},
+    path::Path,
+    ClientConfigKey, ObjectStore as _,
+};
+use std::io;
+
+/// How long an upload may take, the exports of large crawls take longer than the default 30s
+const UPLOAD_TIMEOUT: &str = "300s";
+
+/// An object in a bucket, `s3://bucket/key` or `gs://bucket/key`
+#[derive(Debug, PartialEq, Clone)]
+pub struct ObjectUrl {
+    pub provider: Provider,
+    pub bucket: String,
+    pub key: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Provider {
+    S3,
+    Gcs,
+}
+
+impl ObjectUrl {
+    /// The object a URL points to, none if it isn't an object storage URL
+    pub fn parse(url: &str) -> Option<Self> {
+        let (provider, rest) = match url.split_once("://")? {
+            ("s3", rest) => (Provider::S3, rest),
+            ("gs", rest) => (Provider::Gcs, rest),
+            _ => return None,
+        };
+        let (bucket, key) = rest.split_once('/')?;
+        if bucket.is_empty() || key.is_empty() {
+            return None;
+        }
+
+        Some(ObjectUrl {
+            provider,
+            bucket: bucket.to_owned(),
+            key: key.to_owned(),
+        })
+    }
+}
+
+/// The bucket of an object, in S3, an S3 compatible store or GCS
+#[derive(Debug)]
+pub enum ObjectStore {
+    S3(AmazonS3),
+    Gcs(GoogleCloudStorage),
+}
+
+impl ObjectStore {
+    /// The bucket of an object, configured from the environment, objects being written with
+    /// the given content type:
+    /// - S3: `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, `AWS_SESSION_TOKEN`, `AWS_REGION` and
+    ///   `AWS_ENDPOINT_URL`, for S3 compatible stores, or the credentials of the instance
+    /// - GCS: the service account key file in `GOOGLE_SERVICE_ACCOUNT` or
+    ///   `GOOGLE_APPLICATION_CREDENTIALS`, or the credentials of the instance
+    pub fn from_env(object: &ObjectUrl, content_type: &str) -> Result<Self, io::Error> {
+        // set one by one, so that the client options of the environment are kept
+        let options = [
+            (ClientConfigKey::DefaultContentType, content_type),
+            (ClientConfigKey::Timeout, UPLOAD_TIMEOUT),
+        ];
+
+        match object.provider {
+            Provider::S3 => options
+                .into_iter()
+                .fold(AmazonS3Builder::from_env(), |builder, (key, value)| {
+                    builder.with_config(AmazonS3ConfigKey::Client(key), value)
+                })
+                .with_bucket_name(&object.bucket)
+                .build()
+                .map(ObjectStore::S3),
+            Provider::Gcs => options
+                .into_iter()
+                .fold(
+                    GoogleCloudStorageBuilder::from_env(),
+                    |builder, (key, value)| {
+                        builder.with_config(GoogleConfigKey::Client(key), value)
+                    },
+                )
+                .with_bucket_name(&object.bucket)
+                .build()
+                .map(ObjectStore::Gcs),
+        }
+        .map_err(io::Error::other)
+    }
+
+    /// Fails if no credentials can be found for the bucket
+    pub async fn check_credentials(&self) -> Result<(), io::Error> {
+        match self {
+            ObjectStore::S3(s3) => s3.credentials().get_credential().await.map(drop),
+            ObjectStore::Gcs(gcs) => gcs.credentials().get_credential().await.map(drop),
+        }
+        .map_err(io::Error::other)
+    }
+
+    /// Writes the object, replacing it if it exists
+    pub async fn put(&self, key: &str, body: Vec<u8>) -> Result<(), io::Error> {
+        let location = Path::from(key);
+        match self {
+            ObjectStore::S3(s3) => s3.put(&location, body.into()).await,
+            ObjectStore::Gcs(gcs) => gcs.put(&location, body.into()).await,
+        }
+        .map(drop)
+        .map_err(io::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod object_store_tests {
+    use ::object_store::{aws::AmazonS3Builder, ClientOptions};
+    use wiremock::{
+        matchers::{body_string, header, header_regex, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::{ObjectStore, ObjectUrl, Provider};
+
+    #[test]
+    fn object_url_parses_s3_and_gcs_urls() {
+        assert_eq!(
+            ObjectUrl::parse("s3://bucket/crawls/pages.json"),
+            Some(ObjectUrl {
+                provider: Provider::S3,
+                bucket: "bucket".to_owned(),
+                key: "crawls/pages.json".to_owned(),
+            })
+        );
+        assert_eq!(
+            ObjectUrl::parse("gs://bucket/pages.json").map(|url| url.provider),
+            Some(Provider::Gcs)
+        );
+        assert_eq!(ObjectUrl::parse("out/pages.json"), None);
+        assert_eq!(ObjectUrl::parse("s3://bucket"), None);
+        assert_eq!(ObjectUrl::parse("https://a.com/pages.json"), None);
+    }
+
+    #[tokio::test]
+    async fn object_store_puts_signed_objects() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/bucket/crawls/pages.json"))
+            .and(header("Content-Type", "application/json"))
+            .and(body_string("[]"))
+            // wiremock splits the header on its commas, and matches each part
+            .and(header_regex(
+                "Authorization",
+                "^ ?(AWS4-HMAC-SHA256 Credential=AKID/[0-9]{8}/us-east-1/s3/aws4_request\
+                 |SignedHeaders=[a-z0-9;-]+\
+                 |Signature=[0-9a-f]{64})$",
+            ))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"1\""))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let s3 = AmazonS3Builder::new()
+            .with_client_options(ClientOptions::new().with_default_content_type("application/json"))
+            .with_endpoint(mock_server.uri())
+            .with_allow_http(true)
+            .with_bucket_name("bucket")
+            .with_region("us-east-1")
+            .with_access_key_id("AKID")
+            .with_secret_access_key("secret")
+            .build()
+            .unwrap();
+        let store = ObjectStore::S3(s3);
+
+        store.check_credentials().await.unwrap();
+        store
+            .put("crawls/pages.json", b"[]".to_vec())
+            .await
+            .unwrap();
+    }
+}
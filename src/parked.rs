@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+use url::Url;
+
+use crate::{data_store::DataStore, fetch::FetchResponse, link::url_parts};
+
+/// Domain parking and domain marketplace services expired or unused domains redirect to
+const PARKING_HOSTS: [&str; 12] = [
+    "sedoparking.com",
+    "sedo.com",
+    "bodis.com",
+    "parkingcrew.net",
+    "parklogic.com",
+    "above.com",
+    "dan.com",
+    "afternic.com",
+    "hugedomains.com",
+    "buydomains.com",
+    "undeveloped.com",
+    "domainmarket.com",
+];
+/// Start of the body searched for parking markers
+const MARKERS_SEARCH_BYTES: usize = 64 * 1024;
+/// Lowercase phrases of parking and expired domain pages
+const PARKED_MARKERS: [&str; 8] = [
+    "this domain is for sale",
+    "this domain may be for sale",
+    "buy this domain",
+    "domain is parked",
+    "this domain has expired",
+    "this domain name has expired",
+    "parked free, courtesy of godaddy",
+    "the domain owner has not yet",
+];
+
+/// What gave away that a link points at a parked or expired domain
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ParkedSign {
+    /// Redirects to a domain parking or marketplace service, with its host
+    ParkingService(String),
+    /// The page says the domain is for sale or has expired, with the phrase found
+    Marker(String),
+    /// A deep link redirecting to the homepage of another domain, which took over the old one
+    HomepageRedirect(String),
+}
+
+impl fmt::Display for ParkedSign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParkedSign::ParkingService(host) => write!(f, "redirects to parking service {}", host),
+            ParkedSign::Marker(marker) => write!(f, "page says \"{}\"", marker),
+            ParkedSign::HomepageRedirect(url) => write!(f, "redirects to homepage {}", url),
+        }
+    }
+}
+
+fn is_parking_host(host: &str) -> bool {
+    PARKING_HOSTS
+        .iter()
+        .any(|parking| host == *parking || host.ends_with(&format!(".{}", parking)))
+}
+
+fn root_domain(url: &Url) -> Option<String> {
+    Some(url_parts(url.as_str()).ok()?.root_domain().to_owned())
+}
+
+/// Whether the destination of a link looks like a parked or expired domain - it redirects to a
+/// parking service or to the homepage of another domain, or the page says the domain is for sale
+pub fn detect(url: &str, response: &FetchResponse) -> Option<ParkedSign> {
+    let parking_host = response
+        .redirects
+        .iter()
+        .filter_map(|redirect| Url::parse(&redirect.to).ok())
+        .filter_map(|to| to.host_str().map(str::to_lowercase))
+        .find(|host| is_parking_host(host));
+    if let Some(host) = parking_host {
+        return Some(ParkedSign::ParkingService(host));
+    }
+
+    let mut end = response.body.len().min(MARKERS_SEARCH_BYTES);
+    while !response.body.is_char_boundary(end) {
+        end -= 1;
+    }
+    let body = response.body[..end].to_lowercase();
+    if let Some(marker) = PARKED_MARKERS.iter().find(|marker| body.contains(*marker)) {
+        return Some(ParkedSign::Marker(marker.to_string()));
+    }
+
+    // moving to https:// or www. on the same domain is not a takeover
+    let (requested, served) = (Url::parse(url).ok()?, Url::parse(&response.url).ok()?);
+    let is_deep_link = requested.path() != "/" || requested.query().is_some();
+    let is_homepage = served.path() == "/" && served.query().is_none();
+    match is_deep_link && is_homepage && root_domain(&requested) != root_domain(&served) {
+        true => Some(ParkedSign::HomepageRedirect(response.url.clone())),
+        false => None,
+    }
+}
+
+/// Links pointing at parked or expired domains, with the pages linking to them
+#[derive(Debug, PartialEq, Default)]
+pub struct ParkedLinks(pub BTreeMap<String, (ParkedSign, Vec<String>)>);
+
+impl ParkedLinks {
+    pub fn from_store<T: DataStore>(store: &T) -> Self {
+        let entries = store.entries();
+        let mut links = entries
+            .iter()
+            .filter_map(|(url, entry)| Some(((*url).clone(), (entry.parked.clone()?, vec![]))))
+            .collect::<BTreeMap<_, _>>();
+
+        for (page, entry) in &entries {
+            for url in &entry.urls_found {
+                if let Some((_, pages)) = links.get_mut(url) {
+                    pages.push((*page).clone());
+                }
+            }
+        }
+        for (_, pages) in links.values_mut() {
+            pages.sort();
+        }
+
+        ParkedLinks(links)
+    }
+}
+
+impl fmt::Display for ParkedLinks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Links to parked or expired domains ({}):", self.0.len())?;
+        for (url, (sign, pages)) in &self.0 {
+            writeln!(f, "  {} ({})", url, sign)?;
+            for page in pages {
+                writeln!(f, "    linked from {}", page)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod parked_tests {
+    use std::collections::BTreeMap;
+
+    use super::{detect, ParkedLinks, ParkedSign};
+    use crate::{
+        data_store::{DataStore, Store},
+        fetch::{FetchResponse, Redirect},
+    };
+
+    fn response(url: &str, body: &str, redirects: Vec<Redirect>) -> FetchResponse {
+        FetchResponse {
+            status: 200,
+            body: body.to_owned(),
+            url: url.to_owned(),
+            redirects,
+            content_type: None,
+            sniffed_type: None,
+            links: vec![],
+            streaming: false,
+            partial: false,
+            size: None,
+        }
+    }
+
+    #[test]
+    fn detect_finds_parking_services_markers_and_homepage_takeovers() {
+        let redirect = |from: &str, to: &str| Redirect {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            status: 302,
+        };
+
+        assert_eq!(
+            detect(
+                "https://old.com/",
+                &response(
+                    "https://www.sedoparking.com/old.com",
+                    "",
+                    vec![redirect(
+                        "https://old.com/",
+                        "https://www.sedoparking.com/old.com"
+                    )]
+                )
+            ),
+            Some(ParkedSign::ParkingService("www.sedoparking.com".to_owned()))
+        );
+        assert_eq!(
+            detect(
+                "https://old.com/",
+                &response(
+                    "https://old.com/",
+                    "<h1>This domain is for sale!</h1>",
+                    vec![]
+                )
+            ),
+            Some(ParkedSign::Marker("this domain is for sale".to_owned()))
+        );
+        assert_eq!(
+            detect(
+                "https://old.com/products/1",
+                &response("https://casino.com/", "", vec![])
+            ),
+            Some(ParkedSign::HomepageRedirect(
+                "https://casino.com/".to_owned()
+            ))
+        );
+        // a site moving to https:// and www. is still the same business
+        assert_eq!(
+            detect(
+                "http://old.com/products/1",
+                &response("https://www.old.com/", "", vec![])
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn parked_links_lists_the_pages_linking_to_parked_domains() {
+        let mut s = Store::new();
+        s.add(
+            "https://a.com/".to_owned(),
+            Some("https://old.com/".to_owned()),
+        );
+        s.add(
+            "https://a.com/partners".to_owned(),
+            Some("https://old.com/".to_owned()),
+        );
+        s.add("https://old.com/".to_owned(), None);
+        s.set_parked(
+            "https://old.com/",
+            ParkedSign::Marker("buy this domain".to_owned()),
+        );
+
+        let actual = ParkedLinks::from_store(&s);
+
+        assert_eq!(
+            actual.0,
+            BTreeMap::from([(
+                "https://old.com/".to_owned(),
+                (
+                    ParkedSign::Marker("buy this domain".to_owned()),
+                    vec![
+                        "https://a.com/".to_owned(),
+                        "https://a.com/partners".to_owned()
+                    ]
+                )
+            )])
+        );
+        assert!(actual.to_string().contains(
+            "  https://old.com/ (page says \"buy this domain\")\n    linked from https://a.com/\n"
+        ));
+    }
+}
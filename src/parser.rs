@@ -1,26 +1,346 @@
 use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use url::Url;
+
+use crate::{mime::DocumentKind, mobile::MobileSignals, xml};
+
+/// Elements that link to other documents, and the attribute holding the URL
+const LINK_ATTRIBUTES: [(&str, &str); 5] = [
+    ("a", "href"),
+    ("frame", "src"),
+    ("iframe", "src"),
+    ("object", "data"),
+    ("embed", "src"),
+];
+
+/// Attributes commonly used by JS navigation to hold a URL
+const DATA_ATTRIBUTES: [&str; 2] = ["data-href", "data-url"];
+
+/// `<input>` types that are never part of a submitted form's query
+const UNSUBMITTED_INPUT_TYPES: [&str; 5] = ["submit", "button", "reset", "image", "file"];
+
+/// Element a link was found in
+#[derive(Debug, PartialEq, Clone)]
+pub struct LinkElement {
+    pub tag: String,
+    /// Tokens of the `rel` attribute, lowercased
+    pub rel: Vec<String>,
+}
+
+/// A `<form>` found on a page
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Form {
+    pub action: String,
+    /// Lowercase method, `get` unless the form declares another one
+    pub method: String,
+    /// Names of the fields that would be submitted
+    pub fields: Vec<String>,
+}
+
+impl Form {
+    pub fn is_get(&self) -> bool {
+        self.method == "get"
+    }
+
+    /// URL requested when a GET form is submitted with all of its fields left empty
+    pub fn empty_submission(&self) -> Option<String> {
+        if !self.is_get() {
+            return None;
+        }
+
+        let mut url = Url::parse(&self.action).ok()?;
+        if self.fields.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut()
+                .clear()
+                .extend_pairs(self.fields.iter().map(|field| (field, "")));
+        }
+
+        Some(url.to_string())
+    }
+}
+
+/// Extracts the URL assigned in `location.href = '...'`, `location = '...'`,
+/// `location.assign('...')` or `location.replace('...')` style handlers
+fn location_url(js: &str) -> Option<String> {
+    let (_, rest) = js.split_once("location")?;
+    let rest = [".href", ".assign", ".replace"]
+        .iter()
+        .find_map(|member| rest.strip_prefix(member))
+        .unwrap_or(rest)
+        .trim_start();
+    let rest = rest
+        .strip_prefix('=')
+        .or_else(|| rest.strip_prefix('('))?
+        .trim_start();
+
+    let quote = rest.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+    let (url, _) = rest[1..].split_once(quote)?;
+
+    Some(url.to_owned())
+}
 
 pub struct Parser {
     html_parsed: Html,
+    /// Links of an XML document that isn't XHTML, with the element each is found in
+    xml_links: Vec<(String, LinkElement)>,
 }
 
 impl Parser {
     pub fn new(html_doc_str: String) -> Self {
         Parser {
             html_parsed: Html::parse_document(&html_doc_str),
+            xml_links: vec![],
+        }
+    }
+
+    /// Parses a document as the kind it is: XHTML is read as XML before being parsed as HTML, so
+    /// that its CDATA sections, namespace prefixes and `<a/>` elements are understood, and other
+    /// XML documents are only read for the links of their elements
+    pub fn for_document(doc_str: String, kind: DocumentKind) -> Self {
+        match kind {
+            DocumentKind::Html => Parser::new(doc_str),
+            DocumentKind::Xhtml => Parser::new(xml::to_html(&doc_str)),
+            DocumentKind::Xml => Parser {
+                html_parsed: Html::new_document(),
+                xml_links: xml::links(&doc_str),
+            },
+        }
+    }
+
+    pub fn canonical(&self) -> Option<String> {
+        let selector = Selector::parse("link[rel=\"canonical\"]").unwrap();
+
+        self.html_parsed
+            .select(&selector)
+            .find_map(|element| element.value().attr("href"))
+            .map(|href| href.to_owned())
+    }
+
+    /// Href of every `<link>` with this relation type, i.e. `next` or `preload`, as written in the page
+    pub fn head_links(&self, rel: &str) -> Vec<String> {
+        let selector = Selector::parse(&format!("link[rel~=\"{}\" i][href]", rel)).unwrap();
+
+        self.html_parsed
+            .select(&selector)
+            .filter_map(|element| element.value().attr("href"))
+            .map(|href| href.to_owned())
+            .collect()
+    }
+
+    /// Language and href of every `<link rel="alternate" hreflang>`, as written in the page
+    pub fn alternates(&self) -> Vec<(String, String)> {
+        let selector = Selector::parse("link[rel~=\"alternate\" i][hreflang][href]").unwrap();
+
+        self.html_parsed
+            .select(&selector)
+            .filter_map(|element| {
+                let hreflang = element.value().attr("hreflang")?;
+                let href = element.value().attr("href")?;
+                Some((hreflang.to_owned(), href.to_owned()))
+            })
+            .collect()
+    }
+
+    /// Text of the page's `<title>`, with whitespace collapsed
+    pub fn title(&self) -> Option<String> {
+        let selector = Selector::parse("title").unwrap();
+
+        self.html_parsed
+            .select(&selector)
+            .next()
+            .map(|title| title.text().collect::<Vec<_>>().join(" "))
+            .map(|title| title.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|title| !title.is_empty())
+    }
+
+    /// Content of the page's `<meta name="description">`, with whitespace collapsed
+    pub fn meta_description(&self) -> Option<String> {
+        let selector = Selector::parse("meta[name=\"description\" i]").unwrap();
+
+        self.html_parsed
+            .select(&selector)
+            .find_map(|element| element.value().attr("content"))
+            .map(|content| content.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|content| !content.is_empty())
+    }
+
+    /// The viewport and the responsive design hints of the page
+    pub fn mobile_signals(&self) -> MobileSignals {
+        let viewport_selector = Selector::parse("meta[name=\"viewport\" i][content]").unwrap();
+        let media_selector = Selector::parse("link[rel~=\"stylesheet\" i][media], style").unwrap();
+        let image_selector = Selector::parse("img[srcset], picture").unwrap();
+
+        let viewport = self
+            .html_parsed
+            .select(&viewport_selector)
+            .find_map(|element| element.value().attr("content"))
+            .map(|content| content.split_whitespace().collect::<Vec<_>>().join(" "));
+        // media types alone, i.e. `print`, don't adapt to the screen size, media features do
+        let media_queries = self.html_parsed.select(&media_selector).any(|element| {
+            match element.value().attr("media") {
+                Some(media) => media.contains('('),
+                None => element.text().any(|css| css.contains("@media")),
+            }
+        });
+
+        MobileSignals {
+            viewport,
+            media_queries,
+            responsive_images: self.html_parsed.select(&image_selector).next().is_some(),
+        }
+    }
+
+    /// Text of every `<a href>` by href, whitespace collapsed, falling back to
+    /// the `alt` text of the images in the anchor
+    pub fn anchor_texts(&self) -> HashMap<String, Vec<String>> {
+        let selector = Selector::parse("a[href]").unwrap();
+        let image_selector = Selector::parse("img[alt]").unwrap();
+        let mut texts = HashMap::<String, Vec<String>>::new();
+
+        for anchor in self.html_parsed.select(&selector) {
+            let Some(href) = anchor.value().attr("href") else {
+                continue;
+            };
+            let mut text = anchor.text().collect::<Vec<_>>().join(" ");
+            if text.trim().is_empty() {
+                text = anchor
+                    .select(&image_selector)
+                    .filter_map(|image| image.value().attr("alt"))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+            }
+
+            texts
+                .entry(href.to_owned())
+                .or_default()
+                .push(text.split_whitespace().collect::<Vec<_>>().join(" "));
+        }
+
+        texts
+    }
+
+    /// The first element each link is found in, by URL as written in the page: the elements of
+    /// [`Parser::all_links`], `<link>` elements and the `data-href`/`data-url` attributes of any
+    /// element
+    pub fn link_elements(&self) -> HashMap<String, LinkElement> {
+        let link_attributes = LINK_ATTRIBUTES.iter().chain(&[("link", "href")]);
+        let selector = Selector::parse(
+            &link_attributes
+                .clone()
+                .map(|(name, _)| *name)
+                .chain(["[data-href]", "[data-url]"])
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+        .unwrap();
+        let mut elements = HashMap::new();
+
+        for element in self.html_parsed.select(&selector) {
+            let element = element.value();
+            let hrefs = link_attributes
+                .clone()
+                .filter(|(name, _)| *name == element.name())
+                .map(|(_, attribute)| *attribute)
+                .chain(DATA_ATTRIBUTES)
+                .filter_map(|attribute| element.attr(attribute));
+            for href in hrefs {
+                elements
+                    .entry(href.to_owned())
+                    .or_insert_with(|| LinkElement {
+                        tag: element.name().to_owned(),
+                        rel: element
+                            .attr("rel")
+                            .map(|rel| rel.split_whitespace().map(str::to_lowercase).collect())
+                            .unwrap_or_default(),
+                    });
+            }
         }
+        for (href, element) in &self.xml_links {
+            elements
+                .entry(href.clone())
+                .or_insert_with(|| element.clone());
+        }
+
+        elements
+    }
+
+    /// URLs hidden in `data-href`/`data-url` attributes and `onclick` location changes
+    pub fn script_links(&self) -> Vec<String> {
+        let selector = Selector::parse("[data-href], [data-url], [onclick]").unwrap();
+        let mut vec: Vec<String> = vec![];
+
+        for element in self.html_parsed.select(&selector) {
+            let element = element.value();
+            for attribute in DATA_ATTRIBUTES {
+                if let Some(url) = element.attr(attribute) {
+                    vec.push(url.to_owned());
+                }
+            }
+            if let Some(url) = element.attr("onclick").and_then(location_url) {
+                vec.push(url);
+            }
+        }
+
+        vec
+    }
+
+    pub fn forms(&self) -> Vec<Form> {
+        let form_selector = Selector::parse("form").unwrap();
+        let field_selector = Selector::parse("input[name], select[name], textarea[name]").unwrap();
+
+        self.html_parsed
+            .select(&form_selector)
+            .map(|form| {
+                let fields = form
+                    .select(&field_selector)
+                    .filter(|field| {
+                        let input_type = field.value().attr("type").unwrap_or_default();
+                        !UNSUBMITTED_INPUT_TYPES
+                            .iter()
+                            .any(|unsubmitted| unsubmitted.eq_ignore_ascii_case(input_type))
+                    })
+                    .filter_map(|field| field.value().attr("name"))
+                    .map(|name| name.to_owned())
+                    .collect();
+
+                Form {
+                    action: form.value().attr("action").unwrap_or_default().to_owned(),
+                    method: form
+                        .value()
+                        .attr("method")
+                        .unwrap_or("get")
+                        .to_ascii_lowercase(),
+                    fields,
+                }
+            })
+            .collect()
     }
 
     pub fn all_links(self) -> Vec<String> {
         let mut vec: Vec<String> = vec![];
-        let selector = Selector::parse("a").unwrap();
+        let selector = Selector::parse(
+            &LINK_ATTRIBUTES
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+        .unwrap();
 
+        // select() returns elements in document order, whatever the order of the selector list
         for element in self.html_parsed.select(&selector) {
-            let parsed = element.value().attr("href");
-            if let Some(el) = parsed {
+            let attribute = LINK_ATTRIBUTES
+                .iter()
+                .find(|(name, _)| *name == element.value().name())
+                .map(|(_, attribute)| *attribute);
+            if let Some(el) = attribute.and_then(|attribute| element.value().attr(attribute)) {
                 vec.push(el.to_owned());
             }
         }
+        vec.extend(self.xml_links.into_iter().map(|(href, _)| href));
 
         vec
     }
@@ -28,7 +348,8 @@ impl Parser {
 
 #[cfg(test)]
 mod parser_tests {
-    use super::Parser;
+    use super::{location_url, Form, LinkElement, Parser};
+    use crate::{mime::DocumentKind, mobile::MobileSignals};
 
     #[test]
     fn all_links_extracts_existing_links_from_html_string() {
@@ -43,4 +364,250 @@ mod parser_tests {
         assert_eq!(links.pop(), Some("/link".to_owned()));
         assert_eq!(links.pop(), None);
     }
+
+    #[test]
+    fn all_links_extracts_frame_object_and_embed_urls() {
+        let parser = Parser::new(
+            "<frameset><frame src=\"/menu\"><frame src=\"/main\"></frameset>".to_owned(),
+        );
+
+        assert_eq!(parser.all_links(), vec!["/menu", "/main"]);
+
+        let parser = Parser::new(
+            "<object data=\"/movie.swf\"></object><embed src=\"/clip.svg\"><iframe src=\"/map\"></iframe>"
+                .to_owned(),
+        );
+
+        assert_eq!(parser.all_links(), vec!["/movie.swf", "/clip.svg", "/map"]);
+    }
+
+    #[test]
+    fn script_links_extracts_data_attributes_and_onclick_locations() {
+        let parser = Parser::new(
+            "<li data-href=\"/menu/one\">One</li>\
+             <div data-url=\"/menu/two\">Two</div>\
+             <button onclick=\"location.href='/menu/three'\">Three</button>\
+             <button onclick=\"alert('hi')\">Four</button>"
+                .to_owned(),
+        );
+
+        assert_eq!(
+            parser.script_links(),
+            vec!["/menu/one", "/menu/two", "/menu/three"]
+        );
+    }
+
+    #[test]
+    fn link_elements_records_the_first_element_of_each_link() {
+        let parser = Parser::new(
+            "<link rel=\"Next\" href=\"/2\">\
+             <a href=\"/about\" rel=\"nofollow noopener\">About</a>\
+             <iframe src=\"/embed\"></iframe>\
+             <li data-href=\"/menu\">Menu</li>\
+             <a href=\"/about\">About again</a>"
+                .to_owned(),
+        );
+
+        let elements = parser.link_elements();
+
+        assert_eq!(elements.len(), 4);
+        assert_eq!(
+            elements["/about"],
+            LinkElement {
+                tag: "a".to_owned(),
+                rel: vec!["nofollow".to_owned(), "noopener".to_owned()],
+            }
+        );
+        assert_eq!(elements["/2"].rel, vec!["next"]);
+        assert_eq!(elements["/embed"].tag, "iframe");
+        assert_eq!(elements["/menu"].tag, "li");
+    }
+
+    #[test]
+    fn location_url_handles_assignment_and_method_calls() {
+        assert_eq!(
+            location_url("window.location = \"/a\"; return false;"),
+            Some("/a".to_owned())
+        );
+        assert_eq!(
+            location_url("document.location.assign('/b')"),
+            Some("/b".to_owned())
+        );
+        assert_eq!(location_url("location.href = url"), None);
+    }
+
+    #[test]
+    fn forms_extracts_action_method_and_submitted_fields() {
+        let parser = Parser::new(
+            "<form action=\"/search\"><input name=\"q\"><select name=\"sort\"></select>\
+             <input type=\"submit\" name=\"go\"></form>\
+             <form action=\"/login\" method=\"POST\"><input name=\"user\"></form>"
+                .to_owned(),
+        );
+
+        assert_eq!(
+            parser.forms(),
+            vec![
+                Form {
+                    action: "/search".to_owned(),
+                    method: "get".to_owned(),
+                    fields: vec!["q".to_owned(), "sort".to_owned()],
+                },
+                Form {
+                    action: "/login".to_owned(),
+                    method: "post".to_owned(),
+                    fields: vec!["user".to_owned()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_submission_is_only_built_for_get_forms() {
+        let form = |method: &str| Form {
+            action: "https://a.com/search?old=1".to_owned(),
+            method: method.to_owned(),
+            fields: vec!["q".to_owned(), "page".to_owned()],
+        };
+
+        assert_eq!(
+            form("get").empty_submission(),
+            Some("https://a.com/search?q=&page=".to_owned())
+        );
+        assert_eq!(form("post").empty_submission(), None);
+    }
+
+    #[test]
+    fn canonical_extracts_canonical_link_from_html_string() {
+        let parser = Parser::new(
+            "<head><link rel=\"canonical\" href=\"/page\"></head><a href=\"/link\">Link</a>"
+                .to_owned(),
+        );
+
+        assert_eq!(parser.canonical(), Some("/page".to_owned()));
+    }
+
+    #[test]
+    fn anchor_texts_collects_every_text_of_a_link() {
+        let parser = Parser::new(
+            "<a href=\"/docs\">Read the\n  docs</a><a href=\"/docs\"><img alt=\"Docs\"></a>\
+             <a href=\"/logout\">Log out</a><a name=\"top\">Top</a>"
+                .to_owned(),
+        );
+
+        let texts = parser.anchor_texts();
+
+        assert_eq!(texts.len(), 2);
+        assert_eq!(texts["/docs"], vec!["Read the docs", "Docs"]);
+        assert_eq!(texts["/logout"], vec!["Log out"]);
+    }
+
+    #[test]
+    fn head_links_extracts_links_of_a_relation_type() {
+        let parser = Parser::new(
+            "<link rel=\"prev\" href=\"/1\"><link rel=\"Next\" href=\"/3\">\
+             <link rel=\"preload\" href=\"/app.css\" as=\"style\"><link rel=\"next\">"
+                .to_owned(),
+        );
+
+        assert_eq!(parser.head_links("next"), vec!["/3".to_owned()]);
+        assert_eq!(parser.head_links("preload"), vec!["/app.css".to_owned()]);
+    }
+
+    #[test]
+    fn alternates_extracts_hreflang_links() {
+        let parser = Parser::new(
+            "<link rel=\"alternate\" hreflang=\"fr\" href=\"/fr\">\
+             <link rel=\"Alternate stylesheet\" hreflang=\"x-default\" href=\"/\">\
+             <link rel=\"alternate\" type=\"application/rss+xml\" href=\"/feed\">"
+                .to_owned(),
+        );
+
+        assert_eq!(
+            parser.alternates(),
+            vec![
+                ("fr".to_owned(), "/fr".to_owned()),
+                ("x-default".to_owned(), "/".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn title_collapses_whitespace() {
+        let parser = Parser::new("<head><title>\n  Example\n  Domain </title></head>".to_owned());
+
+        assert_eq!(parser.title(), Some("Example Domain".to_owned()));
+        assert_eq!(Parser::new("<p>One</p>".to_owned()).title(), None);
+    }
+
+    #[test]
+    fn meta_description_collapses_whitespace() {
+        let parser = Parser::new(
+            "<head><meta name=\"Description\" content=\" Our  products\n\"></head>".to_owned(),
+        );
+
+        assert_eq!(parser.meta_description(), Some("Our products".to_owned()));
+        assert_eq!(
+            Parser::new("<meta name=\"description\" content=\" \">".to_owned()).meta_description(),
+            None
+        );
+    }
+
+    #[test]
+    fn mobile_signals_finds_the_viewport_media_queries_and_responsive_images() {
+        let parser = Parser::new(
+            "<head><meta name=\"viewport\" content=\"width=device-width,\n initial-scale=1\">\
+             <style>@media (max-width: 600px) { nav { display: none } }</style></head>\
+             <body><picture><img src=\"/a.jpg\"></picture></body>"
+                .to_owned(),
+        );
+
+        assert_eq!(
+            parser.mobile_signals(),
+            MobileSignals {
+                viewport: Some("width=device-width, initial-scale=1".to_owned()),
+                media_queries: true,
+                responsive_images: true,
+            }
+        );
+        assert_eq!(
+            Parser::new("<link rel=\"stylesheet\" href=\"/p.css\" media=\"print\">".to_owned())
+                .mobile_signals(),
+            MobileSignals::default()
+        );
+    }
+
+    #[test]
+    fn for_document_reads_xhtml_and_feeds_as_xml() {
+        // as HTML, the empty anchor would wrap the next one and `]]>` would end up in the title
+        let xhtml = Parser::for_document(
+            "<?xml version=\"1.0\"?><html xmlns=\"http://www.w3.org/1999/xhtml\"><head>\
+             <title><![CDATA[Q&A]]></title></head><body><a href=\"/empty\"/>\
+             <a href=\"/faq\">FAQ</a></body></html>"
+                .to_owned(),
+            DocumentKind::Xhtml,
+        );
+        assert_eq!(xhtml.title().as_deref(), Some("Q&A"));
+        assert_eq!(xhtml.anchor_texts()["/faq"], vec!["FAQ".to_owned()]);
+        assert_eq!(xhtml.all_links(), vec!["/empty", "/faq"]);
+
+        let feed = Parser::for_document(
+            "<rss><channel><item><link>https://a.com/1</link></item></channel></rss>".to_owned(),
+            DocumentKind::Xml,
+        );
+        assert_eq!(feed.link_elements()["https://a.com/1"].tag, "link");
+        assert_eq!(feed.all_links(), vec!["https://a.com/1"]);
+        // as HTML, `<link>` is empty and its URL a stray text
+        let feed_as_html = Parser::new(
+            "<rss><channel><item><link>https://a.com/1</link></item></channel></rss>".to_owned(),
+        );
+        assert!(feed_as_html.all_links().is_empty());
+    }
+
+    #[test]
+    fn canonical_returns_none_when_no_canonical_link_exists() {
+        let parser = Parser::new("<p>One</p>".to_owned());
+
+        assert_eq!(parser.canonical(), None);
+    }
 }
@@ -0,0 +1,183 @@
+use regex::{Regex, RegexBuilder};
+use std::sync::Arc;
+
+use crate::link::{filter_url, Error, LinkDecision, Scope, UrlParts};
+
+/// Decides, before a link is enqueued, whether it is in scope of the crawl.
+/// Implement it to take full control of which links get crawled.
+pub trait UrlPolicy: Send + Sync {
+    /// What should happen to the (absolute) `url` found on `page_url`
+    fn decide(&self, url: &str, page_url: &str) -> LinkDecision;
+}
+
+/// The default policy: http(s) links on a seed's domain, or beyond it depending on the scope
+pub struct ScopePolicy {
+    seeds_url_parts: Vec<Arc<Result<UrlParts, Error>>>,
+    scope: Scope,
+}
+
+impl ScopePolicy {
+    pub fn new(seeds_url_parts: Vec<Arc<Result<UrlParts, Error>>>, scope: Scope) -> Self {
+        ScopePolicy {
+            seeds_url_parts,
+            scope,
+        }
+    }
+}
+
+impl UrlPolicy for ScopePolicy {
+    fn decide(&self, url: &str, _page_url: &str) -> LinkDecision {
+        // decisions are ordered, so an internal link to any of the seeds wins
+        let decision = self
+            .seeds_url_parts
+            .iter()
+            .map(|url_parts| filter_url(url.to_owned(), url_parts.clone()))
+            .min()
+            .unwrap_or(LinkDecision::SkipExternal);
+
+        match decision {
+            LinkDecision::SkipExternal if self.scope.follows(false) => LinkDecision::Enqueue,
+            decision => decision,
+        }
+    }
+}
+
+/// Policies consulted in order, a link is only enqueued if every one of them enqueues it
+pub struct PolicyChain(pub Vec<Arc<dyn UrlPolicy>>);
+
+impl UrlPolicy for PolicyChain {
+    fn decide(&self, url: &str, page_url: &str) -> LinkDecision {
+        self.0
+            .iter()
+            .map(|policy| policy.decide(url, page_url))
+            .find(|decision| *decision != LinkDecision::Enqueue)
+            .unwrap_or(LinkDecision::Enqueue)
+    }
+}
+
+/// Case insensitive pattern matched against the text of a link
+pub fn link_text_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+/// Rules on the anchor text of links, i.e. only follow links labeled `download` or `docs`,
+/// never follow links labeled `logout`
+#[derive(Debug, Clone, Default)]
+pub struct LinkTextRules {
+    /// When not empty, a link is only followed if one of its texts matches one of these
+    pub follow: Vec<Regex>,
+    /// A link is never followed if one of its texts matches one of these
+    pub skip: Vec<Regex>,
+}
+
+impl LinkTextRules {
+    pub fn is_empty(&self) -> bool {
+        self.follow.is_empty() && self.skip.is_empty()
+    }
+
+    /// Whether a link can be followed, given the texts of every anchor pointing to it on the page
+    pub fn allows(&self, texts: &[String]) -> bool {
+        let matches = |patterns: &[Regex]| {
+            texts
+                .iter()
+                .any(|text| patterns.iter().any(|pattern| pattern.is_match(text)))
+        };
+
+        !matches(&self.skip) && (self.follow.is_empty() || matches(&self.follow))
+    }
+}
+
+#[cfg(test)]
+mod policy_tests {
+    use std::sync::Arc;
+
+    use super::{link_text_pattern, LinkTextRules, PolicyChain, ScopePolicy, UrlPolicy};
+    use crate::link::{url_parts, LinkDecision, Scope};
+
+    struct NoQueryStrings;
+
+    impl UrlPolicy for NoQueryStrings {
+        fn decide(&self, url: &str, _page_url: &str) -> LinkDecision {
+            if url.contains('?') {
+                LinkDecision::SkipFiltered
+            } else {
+                LinkDecision::Enqueue
+            }
+        }
+    }
+
+    #[test]
+    fn scope_policy_follows_external_links_depending_on_scope() {
+        let seeds_url_parts = vec![Arc::new(url_parts("https://a.com"))];
+        let page = "https://a.com/";
+
+        let same_domain = ScopePolicy::new(seeds_url_parts.clone(), Scope::SameDomain);
+        assert_eq!(
+            same_domain.decide("https://a.com/1", page),
+            LinkDecision::Enqueue
+        );
+        assert_eq!(
+            same_domain.decide("https://b.com/", page),
+            LinkDecision::SkipExternal
+        );
+        assert_eq!(
+            same_domain.decide("mailto:me@a.com", page),
+            LinkDecision::SkipScheme
+        );
+
+        let external_once = ScopePolicy::new(seeds_url_parts, Scope::ExternalOnce);
+        assert_eq!(
+            external_once.decide("https://b.com/", page),
+            LinkDecision::Enqueue
+        );
+    }
+
+    #[test]
+    fn policy_chain_returns_first_decision_that_is_not_enqueue() {
+        let chain = PolicyChain(vec![
+            Arc::new(ScopePolicy::new(
+                vec![Arc::new(url_parts("https://a.com"))],
+                Scope::SameDomain,
+            )),
+            Arc::new(NoQueryStrings),
+        ]);
+        let page = "https://a.com/";
+
+        assert_eq!(chain.decide("https://a.com/1", page), LinkDecision::Enqueue);
+        assert_eq!(
+            chain.decide("https://a.com/1?page=2", page),
+            LinkDecision::SkipFiltered
+        );
+        assert_eq!(
+            chain.decide("https://b.com/?page=2", page),
+            LinkDecision::SkipExternal
+        );
+    }
+
+    #[test]
+    fn link_text_rules_apply_follow_and_skip_patterns() {
+        let texts = |texts: &[&str]| {
+            texts
+                .iter()
+                .map(|text| text.to_string())
+                .collect::<Vec<_>>()
+        };
+        let rules = LinkTextRules {
+            follow: vec![link_text_pattern("download|docs").unwrap()],
+            skip: vec![link_text_pattern("^logout$").unwrap()],
+        };
+
+        assert!(rules.allows(&texts(&["Read the Docs"])));
+        assert!(rules.allows(&texts(&["Home", "Downloads"])));
+        assert!(!rules.allows(&texts(&["Home"])));
+        assert!(!rules.allows(&texts(&["Docs", "Logout"])));
+
+        let skip_only = LinkTextRules {
+            skip: rules.skip,
+            ..Default::default()
+        };
+        assert!(skip_only.allows(&texts(&["Home"])));
+        assert!(!skip_only.allows(&texts(&["LOGOUT"])));
+        assert!(LinkTextRules::default().is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+use log::warn;
+use serde::Serialize;
+use std::{
+    fmt,
+    fs::File,
+    io,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Why a request waited
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WaitReason {
+    /// The politeness delay between two requests to a host, `--delay`
+    Delay,
+    /// The host's robots.txt `Crawl-delay`
+    CrawlDelay,
+    /// Outside of the crawl windows
+    CrawlWindow,
+    /// The host looked like it was blocking the crawler and requests to it are paused
+    Blocked,
+}
+
+impl fmt::Display for WaitReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaitReason::Delay => write!(f, "delay"),
+            WaitReason::CrawlDelay => write!(f, "crawl-delay"),
+            WaitReason::CrawlWindow => write!(f, "crawl-window"),
+            WaitReason::Blocked => write!(f, "blocked"),
+        }
+    }
+}
+
+/// A politeness decision taken before a request
+#[derive(Debug, PartialEq, Clone)]
+pub struct Politeness {
+    /// Host the request is made to, empty when the decision applies to every host
+    pub host: String,
+    /// How long the request waited
+    pub waited: Duration,
+    /// Delay applied between two requests to the host
+    pub delay: Duration,
+    pub reason: WaitReason,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    /// Milliseconds since the Unix epoch
+    timestamp_ms: u128,
+    host: &'a str,
+    url: &'a str,
+    waited_ms: u128,
+    delay_ms: u128,
+    reason: WaitReason,
+}
+
+/// A CSV log of every politeness decision, written as they are taken, to show site owners
+/// that the crawl stayed within the agreed limits
+pub struct PolitenessLog {
+    writer: Mutex<csv::Writer<Box<dyn io::Write + Send>>>,
+}
+
+impl PolitenessLog {
+    pub fn create(path: &Path) -> Result<Self, io::Error> {
+        Ok(Self::new(Box::new(File::create(path)?)))
+    }
+
+    pub fn new(writer: Box<dyn io::Write + Send>) -> Self {
+        PolitenessLog {
+            writer: Mutex::new(csv::Writer::from_writer(writer)),
+        }
+    }
+
+    /// Logs the decision taken before requesting `url`, or before pausing the crawl without a `url`
+    pub fn record(&self, url: Option<&str>, politeness: &Politeness) {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let record = Record {
+            timestamp_ms,
+            host: &politeness.host,
+            url: url.unwrap_or_default(),
+            waited_ms: politeness.waited.as_millis(),
+            delay_ms: politeness.delay.as_millis(),
+            reason: politeness.reason,
+        };
+
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        // flushed every time, so that the log is complete even if the crawl is killed
+        if let Err(e) = writer.serialize(record).and_then(|_| Ok(writer.flush()?)) {
+            warn!("Could not write the politeness log - {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod politeness_tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{Politeness, PolitenessLog, WaitReason};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn politeness_log_writes_a_csv_row_per_decision() {
+        let buffer = Buffer::default();
+        let log = PolitenessLog::new(Box::new(buffer.clone()));
+
+        log.record(
+            Some("https://a.com/1"),
+            &Politeness {
+                host: "a.com".to_owned(),
+                waited: Duration::from_millis(1500),
+                delay: Duration::from_secs(2),
+                reason: WaitReason::CrawlDelay,
+            },
+        );
+        log.record(
+            None,
+            &Politeness {
+                host: String::new(),
+                waited: Duration::from_secs(60),
+                delay: Duration::ZERO,
+                reason: WaitReason::CrawlWindow,
+            },
+        );
+
+        let csv = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "timestamp_ms,host,url,waited_ms,delay_ms,reason");
+        assert!(lines[1].ends_with(",a.com,https://a.com/1,1500,2000,crawl-delay"));
+        assert!(lines[2].ends_with(",,,60000,0,crawl-window"));
+    }
+}
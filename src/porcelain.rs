@@ -0,0 +1,104 @@
+use std::fmt::Write;
+
+use crate::{data_store::DataStore, summary::CrawlSummary};
+
+/// Version of the porcelain format, bumped on any change that could break a parser
+pub const PORCELAIN_VERSION: u32 = 1;
+
+/// Renders the crawl in the stable, line based porcelain format.
+///
+/// Every line is a record type followed by tab separated fields, records are sorted:
+///
+/// ```text
+/// version  <version>
+/// page     <status or ->  <url>
+/// link     <decision>     <page url>  <link url>
+/// redirect <status>       <from>      <to>
+/// summary  <pages visited> <pages failed> <links found>
+/// ```
+///
+/// New record types may be added within a version, so parsers should skip the ones they don't know.
+pub fn render<T: DataStore>(store: &T) -> String {
+    let mut entries = store.entries();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut out = String::new();
+    let _ = writeln!(out, "version\t{}", PORCELAIN_VERSION);
+
+    for (url, entry) in entries.iter().filter(|(_, entry)| entry.visited) {
+        let status = entry
+            .status
+            .map(|status| status.to_string())
+            .unwrap_or("-".to_owned());
+        let _ = writeln!(out, "page\t{}\t{}", status, url);
+    }
+    for (url, entry) in &entries {
+        for (link, decision) in &entry.link_decisions {
+            let _ = writeln!(out, "link\t{}\t{}\t{}", decision, url, link);
+        }
+    }
+    for (_, entry) in &entries {
+        for redirect in &entry.redirects {
+            let _ = writeln!(
+                out,
+                "redirect\t{}\t{}\t{}",
+                redirect.status, redirect.from, redirect.to
+            );
+        }
+    }
+
+    let summary = CrawlSummary::from_store("", store);
+    let _ = writeln!(
+        out,
+        "summary\t{}\t{}\t{}",
+        summary.pages_visited, summary.pages_failed, summary.links_found
+    );
+
+    out
+}
+
+#[cfg(test)]
+mod porcelain_tests {
+    use super::render;
+    use crate::{
+        data_store::{DataStore, Store},
+        link::LinkDecision,
+    };
+
+    #[test]
+    fn render_writes_sorted_tab_separated_records() {
+        let mut s = Store::new();
+        s.add(
+            "https://a.com/".to_owned(),
+            Some("https://a.com/1".to_owned()),
+        );
+        s.add(
+            "https://a.com/".to_owned(),
+            Some("https://b.com/".to_owned()),
+        );
+        s.visited("https://a.com/");
+        s.set_status("https://a.com/", 200);
+        s.set_link_decision(
+            "https://a.com/",
+            "https://b.com/".to_owned(),
+            LinkDecision::SkipExternal,
+        );
+        s.set_link_decision(
+            "https://a.com/",
+            "https://a.com/1".to_owned(),
+            LinkDecision::Enqueue,
+        );
+        s.add("https://a.com/1".to_owned(), None);
+        s.visited("https://a.com/1");
+
+        assert_eq!(
+            render(&s),
+            "version\t1\n\
+             page\t200\thttps://a.com/\n\
+             page\t-\thttps://a.com/1\n\
+             link\tenqueue\thttps://a.com/\thttps://a.com/1\n\
+             link\tskip-external\thttps://a.com/\thttps://b.com/\n\
+             summary\t2\t0\t2\n"
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    data_store::{DataStore, DataStoreEntry, Error},
+    link::{is_internal, url_parts},
+    sqlite_store::SqliteStore,
+};
+
+/// What to drop from a saved crawl to shrink it before sharing or archiving it
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Prune {
+    /// Links to other domains than the page's own
+    pub drop_external_edges: bool,
+    /// Page body samples
+    pub drop_bodies: bool,
+    /// Every page that didn't respond with a 4xx or 5xx status
+    pub errors_only: bool,
+}
+
+impl Prune {
+    /// Whether the page is kept at all
+    pub fn keeps(&self, entry: &DataStoreEntry) -> bool {
+        !self.errors_only || entry.status.is_some_and(|status| status >= 400)
+    }
+
+    /// Drops what isn't wanted from a kept page, returning the number of links dropped
+    pub fn apply(&self, url: &str, entry: &mut DataStoreEntry) -> usize {
+        if self.drop_bodies {
+            entry.body_sample = None;
+        }
+        if !self.drop_external_edges {
+            return 0;
+        }
+
+        let page_url_parts = [Arc::new(url_parts(url))];
+        let links_before = entry.urls_found.len();
+        entry
+            .urls_found
+            .retain(|link| is_internal(link, &page_url_parts));
+        entry
+            .link_counts
+            .retain(|link, _| is_internal(link, &page_url_parts));
+        entry
+            .link_decisions
+            .retain(|link, _| is_internal(link, &page_url_parts));
+        entry
+            .link_sources
+            .retain(|link, _| is_internal(link, &page_url_parts));
+
+        links_before - entry.urls_found.len()
+    }
+}
+
+/// What pruning a saved crawl removed
+#[derive(Debug, PartialEq, Default)]
+pub struct PruneReport {
+    pub pages_before: usize,
+    pub pages_after: usize,
+    pub links_dropped: usize,
+}
+
+impl fmt::Display for PruneReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Pruned {} of {} pages and {} links",
+            self.pages_before - self.pages_after,
+            self.pages_before,
+            self.links_dropped
+        )
+    }
+}
+
+/// Prunes the pages of a saved crawl and compacts the database
+pub fn prune(store: &mut SqliteStore, prune: Prune) -> Result<PruneReport, Error> {
+    let mut report = PruneReport {
+        pages_before: store.entries().len(),
+        ..Default::default()
+    };
+
+    store.retain(|url, entry| {
+        let keep = prune.keeps(entry);
+        if keep {
+            report.links_dropped += prune.apply(url, entry);
+        }
+        keep
+    })?;
+    store.flush()?;
+    store.compact()?;
+    report.pages_after = store.entries().len();
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod prune_tests {
+    use super::{prune, Prune, PruneReport};
+    use crate::{data_store::DataStore, sqlite_store::SqliteStore};
+
+    #[test]
+    fn prune_drops_external_edges_bodies_and_pages_without_errors() {
+        let path =
+            std::env::temp_dir().join(format!("url-crawler-prune-{}.db", std::process::id()));
+        let mut s = SqliteStore::open(&path, false).unwrap();
+        for link in ["https://a.com/missing", "https://b.com/"] {
+            s.add("https://a.com/".to_owned(), Some(link.to_owned()));
+        }
+        s.set_status("https://a.com/", 200);
+        s.add(
+            "https://a.com/missing".to_owned(),
+            Some("https://c.com/".to_owned()),
+        );
+        s.set_status("https://a.com/missing", 404);
+        s.set_body_sample("https://a.com/missing", "Not found".to_owned());
+
+        let report = prune(
+            &mut s,
+            Prune {
+                drop_external_edges: true,
+                drop_bodies: true,
+                errors_only: false,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            report,
+            PruneReport {
+                pages_before: 2,
+                pages_after: 2,
+                links_dropped: 2,
+            }
+        );
+        assert_eq!(
+            s.get("https://a.com/").unwrap().urls_found,
+            vec!["https://a.com/missing".to_owned()]
+        );
+        assert_eq!(s.get("https://a.com/missing").unwrap().body_sample, None);
+
+        let report = prune(
+            &mut s,
+            Prune {
+                errors_only: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(report.to_string(), "Pruned 1 of 2 pages and 0 links\n");
+        drop(s);
+
+        let pruned = SqliteStore::open(&path, true).unwrap();
+
+        assert!(pruned.get("https://a.com/").is_none());
+        assert_eq!(
+            pruned.get("https://a.com/missing").unwrap().urls_found,
+            Vec::<String>::new()
+        );
+        drop(pruned);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,287 @@
+use crossbeam_queue::SegQueue;
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::RandomState, BinaryHeap, HashMap, VecDeque},
+    hash::{BuildHasher, Hasher},
+};
+
+use crate::{link::host_with_port, url_frontier::QueuedUrl};
+
+/// Order in which the URL frontier hands out queued URLs
+#[derive(clap::ValueEnum, PartialEq, Debug, Clone, Copy, Default)]
+pub enum FrontierPolicy {
+    /// First in, first out (breadth first)
+    #[default]
+    Fifo,
+    /// Take turns between hosts, so a single host can't monopolise the crawl
+    RoundRobin,
+    /// Shallowest URLs first, in the order they were queued otherwise
+    Priority,
+    /// A random queued URL
+    Random,
+}
+
+impl FrontierPolicy {
+    pub fn queue(&self) -> Box<dyn Queue> {
+        match self {
+            FrontierPolicy::Fifo => Box::<FifoQueue>::default(),
+            FrontierPolicy::RoundRobin => Box::<RoundRobinQueue>::default(),
+            FrontierPolicy::Priority => Box::<PriorityQueue>::default(),
+            FrontierPolicy::Random => Box::<RandomQueue>::default(),
+        }
+    }
+
+    /// The queue of the policy, the random one popping in the order of `seed`
+    pub fn seeded_queue(&self, seed: u64) -> Box<dyn Queue> {
+        match self {
+            FrontierPolicy::Random => Box::new(RandomQueue::with_seed(seed)),
+            policy => policy.queue(),
+        }
+    }
+}
+
+/// Scheduling strategy of the URL frontier
+pub trait Queue: Send + Sync {
+    fn push(&mut self, value: QueuedUrl);
+    fn pop(&mut self) -> Option<QueuedUrl>;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FifoQueue(SegQueue<QueuedUrl>);
+
+impl Queue for FifoQueue {
+    fn push(&mut self, value: QueuedUrl) {
+        self.0.push(value);
+    }
+
+    fn pop(&mut self) -> Option<QueuedUrl> {
+        self.0.pop()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// A FIFO queue per host, popped from in turns
+#[derive(Debug, Default)]
+pub struct RoundRobinQueue {
+    hosts: HashMap<String, VecDeque<QueuedUrl>>,
+    /// Hosts with queued URLs, the next one to pop from first
+    turns: VecDeque<String>,
+    len: usize,
+}
+
+impl Queue for RoundRobinQueue {
+    fn push(&mut self, value: QueuedUrl) {
+        let host = host_with_port(&value.url).unwrap_or_default();
+        let queue = self.hosts.entry(host.clone()).or_default();
+        if queue.is_empty() {
+            self.turns.push_back(host);
+        }
+        queue.push_back(value);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<QueuedUrl> {
+        let host = self.turns.pop_front()?;
+        let queue = self.hosts.get_mut(&host)?;
+        let value = queue.pop_front();
+        if queue.is_empty() {
+            self.hosts.remove(&host);
+        } else {
+            self.turns.push_back(host);
+        }
+        self.len -= 1;
+
+        value
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// Shallowest URLs first, ties broken by the order they were queued in
+#[derive(Debug, Default)]
+pub struct PriorityQueue {
+    heap: BinaryHeap<(Reverse<usize>, Reverse<u64>, String)>,
+    pushed: u64,
+}
+
+impl Queue for PriorityQueue {
+    fn push(&mut self, value: QueuedUrl) {
+        self.heap
+            .push((Reverse(value.depth), Reverse(self.pushed), value.url));
+        self.pushed += 1;
+    }
+
+    fn pop(&mut self) -> Option<QueuedUrl> {
+        self.heap
+            .pop()
+            .map(|(Reverse(depth), _, url)| QueuedUrl::new(url, depth))
+    }
+
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+}
+
+/// Pops a random queued URL, using a xorshift generator
+#[derive(Debug)]
+pub struct RandomQueue {
+    values: Vec<QueuedUrl>,
+    state: u64,
+}
+
+impl RandomQueue {
+    /// A queue whose pop order only depends on the seed and the pushed URLs
+    pub fn with_seed(seed: u64) -> Self {
+        RandomQueue {
+            values: vec![],
+            // xorshift never leaves zero
+            state: seed.max(1),
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
+
+impl Default for RandomQueue {
+    fn default() -> Self {
+        RandomQueue::with_seed(RandomState::new().build_hasher().finish())
+    }
+}
+
+impl Queue for RandomQueue {
+    fn push(&mut self, value: QueuedUrl) {
+        self.values.push(value);
+    }
+
+    fn pop(&mut self) -> Option<QueuedUrl> {
+        if self.values.is_empty() {
+            return None;
+        }
+
+        let index = (self.next() % self.values.len() as u64) as usize;
+        Some(self.values.swap_remove(index))
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+}
+
+#[cfg(test)]
+mod queue_tests {
+    use super::{FifoQueue, PriorityQueue, Queue, RandomQueue, RoundRobinQueue};
+    use crate::url_frontier::QueuedUrl;
+
+    fn fill(queue: &mut dyn Queue, values: &[(&str, usize)]) {
+        for (url, depth) in values {
+            queue.push(QueuedUrl::new(url.to_string(), *depth));
+        }
+    }
+
+    fn drain(queue: &mut dyn Queue) -> Vec<String> {
+        std::iter::from_fn(|| queue.pop())
+            .map(|value| value.url)
+            .collect()
+    }
+
+    #[test]
+    fn fifo_queue_pops_in_push_order() {
+        let mut queue = FifoQueue::default();
+        fill(
+            &mut queue,
+            &[("https://a.com/1", 0), ("https://a.com/2", 1)],
+        );
+
+        assert_eq!(queue.len(), 2);
+        assert_eq!(
+            drain(&mut queue),
+            vec!["https://a.com/1", "https://a.com/2"]
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn round_robin_queue_takes_turns_between_hosts() {
+        let mut queue = RoundRobinQueue::default();
+        fill(
+            &mut queue,
+            &[
+                ("https://a.com/1", 0),
+                ("https://a.com/2", 0),
+                ("https://a.com/3", 0),
+                ("https://b.com/1", 0),
+                ("https://c.com/1", 0),
+            ],
+        );
+
+        assert_eq!(queue.len(), 5);
+        assert_eq!(
+            drain(&mut queue),
+            vec![
+                "https://a.com/1",
+                "https://b.com/1",
+                "https://c.com/1",
+                "https://a.com/2",
+                "https://a.com/3"
+            ]
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn priority_queue_pops_shallowest_first() {
+        let mut queue = PriorityQueue::default();
+        fill(
+            &mut queue,
+            &[("deep", 3), ("shallow", 1), ("seed", 0), ("shallow-2", 1)],
+        );
+
+        assert_eq!(
+            drain(&mut queue),
+            vec!["seed", "shallow", "shallow-2", "deep"]
+        );
+    }
+
+    #[test]
+    fn random_queue_pops_every_value_in_a_seeded_order() {
+        let values = (0..20)
+            .map(|i| (format!("https://a.com/{}", i), 0))
+            .collect::<Vec<_>>();
+        let values = values
+            .iter()
+            .map(|(url, depth)| (url.as_str(), *depth))
+            .collect::<Vec<_>>();
+
+        let mut first = RandomQueue::with_seed(42);
+        let mut second = RandomQueue::with_seed(42);
+        fill(&mut first, &values);
+        fill(&mut second, &values);
+        let order = drain(&mut first);
+
+        assert_eq!(order, drain(&mut second));
+        assert_ne!(
+            order,
+            values.iter().map(|(url, _)| *url).collect::<Vec<_>>()
+        );
+
+        let mut sorted = order.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), 20);
+    }
+}
@@ -0,0 +1,153 @@
+use std::{collections::HashSet, fmt, str::FromStr, sync::Mutex};
+use url::Url;
+
+use crate::robots::pattern_matches;
+
+/// How many URLs whose path matches a pattern are crawled at most, i.e. `/tag/*=100`
+#[derive(Debug, PartialEq, Clone)]
+pub struct PathQuota {
+    /// Path (and query) pattern, with robots.txt `*` wildcards and `$` end anchor
+    pub pattern: String,
+    pub max: usize,
+}
+
+impl PathQuota {
+    pub fn matches(&self, url: &str) -> bool {
+        let Ok(url) = Url::parse(url) else {
+            return false;
+        };
+
+        match url.query() {
+            Some(query) => pattern_matches(&self.pattern, &format!("{}?{}", url.path(), query)),
+            None => pattern_matches(&self.pattern, url.path()),
+        }
+    }
+}
+
+impl FromStr for PathQuota {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "`{}` is not a <path pattern>=<max URLs> quota, i.e. /tag/*=100",
+                s
+            )
+        };
+        let (pattern, max) = s.rsplit_once('=').ok_or_else(invalid)?;
+        if !pattern.starts_with('/') {
+            return Err(invalid());
+        }
+
+        Ok(PathQuota {
+            pattern: pattern.to_owned(),
+            max: max.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl fmt::Display for PathQuota {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.pattern, self.max)
+    }
+}
+
+/// Counts the URLs crawled under each quota, so that sections like tags or archives don't use up
+/// the page budget of a crawl
+#[derive(Debug)]
+pub struct PathQuotas {
+    quotas: Vec<PathQuota>,
+    /// URLs claimed so far, per quota
+    claimed: Vec<Mutex<HashSet<String>>>,
+}
+
+impl PathQuotas {
+    pub fn new(quotas: Vec<PathQuota>) -> Self {
+        PathQuotas {
+            claimed: quotas.iter().map(|_| Mutex::default()).collect(),
+            quotas,
+        }
+    }
+
+    /// Claims a URL against its quota, returning whether the quota allows it to be crawled. URLs
+    /// matching no quota are always allowed, and so are URLs claimed before, i.e. linked from
+    /// several pages.
+    pub fn claim(&self, url: &str) -> bool {
+        let Some(index) = self.index_for(url) else {
+            return true;
+        };
+
+        let mut claimed = self.claimed[index].lock().unwrap();
+        if claimed.contains(url) {
+            return true;
+        }
+        if claimed.len() >= self.quotas[index].max {
+            return false;
+        }
+        claimed.insert(url.to_owned())
+    }
+
+    /// The quota of a URL, the longest pattern its path matches as in robots.txt, so that the
+    /// order of the quotas doesn't matter
+    pub fn quota_for(&self, url: &str) -> Option<&PathQuota> {
+        self.index_for(url).map(|index| &self.quotas[index])
+    }
+
+    fn index_for(&self, url: &str) -> Option<usize> {
+        self.quotas
+            .iter()
+            .enumerate()
+            .filter(|(_, quota)| quota.matches(url))
+            .max_by_key(|(_, quota)| quota.pattern.len())
+            .map(|(index, _)| index)
+    }
+}
+
+#[cfg(test)]
+mod quota_tests {
+    use super::{PathQuota, PathQuotas};
+
+    #[test]
+    fn path_quota_parses_pattern_and_max() {
+        assert_eq!(
+            "/tag/*=100".parse::<PathQuota>(),
+            Ok(PathQuota {
+                pattern: "/tag/*".to_owned(),
+                max: 100,
+            })
+        );
+        assert_eq!(
+            "/search?q=*=5"
+                .parse::<PathQuota>()
+                .map(|quota| quota.to_string()),
+            Ok("/search?q=*=5".to_owned())
+        );
+        assert!("tag/*=100".parse::<PathQuota>().is_err());
+        assert!("/tag/*".parse::<PathQuota>().is_err());
+        assert!("/tag/*=many".parse::<PathQuota>().is_err());
+    }
+
+    #[test]
+    fn path_quotas_claim_up_to_the_longest_matching_quota() {
+        let quotas = PathQuotas::new(vec![
+            "/tag/*=1".parse().unwrap(),
+            "/tag/rust/*=3".parse().unwrap(),
+        ]);
+
+        assert!(quotas.claim("https://a.com/tag/news/1"));
+        // linked again, it doesn't take another URL of the quota
+        assert!(quotas.claim("https://a.com/tag/news/1"));
+        assert!(!quotas.claim("https://a.com/tag/news/2"));
+        for page in 1..=3 {
+            assert!(quotas.claim(&format!("https://a.com/tag/rust/{}", page)));
+        }
+        assert!(!quotas.claim("https://a.com/tag/rust/4"));
+        assert!(quotas.claim("https://a.com/blog/1"));
+        assert_eq!(
+            quotas
+                .quota_for("https://a.com/tag/go/1")
+                .map(|quota| quota.max),
+            Some(1)
+        );
+    }
+}
@@ -0,0 +1,441 @@
+use log::info;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use url::Url;
+
+use crate::fetch::{Fetch, DEFAULT_USER_AGENT};
+
+/// Longer crawl delays are capped, a host asking for more is crawled once an hour
+const MAX_CRAWL_DELAY_S: f64 = 3600.0;
+
+/// What to assume when a host's robots.txt cannot be fetched (network error or 5xx)
+#[derive(clap::ValueEnum, PartialEq, Debug, Clone, Copy, Default)]
+pub enum UnreachablePolicy {
+    /// Crawl the host as if it had no robots.txt
+    Allow,
+    /// Do not crawl the host at all (as recommended by RFC 9309)
+    #[default]
+    Deny,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct Group {
+    user_agents: Vec<String>,
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+}
+
+/// Product token of a user agent, the part robots.txt groups are matched against,
+/// i.e. `url-crawler` for `url-crawler/0.1.0 (+https://example.com)`
+fn product_token(user_agent: &str) -> String {
+    user_agent
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Parsed robots.txt rules of a single host
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct RobotsRules {
+    groups: Vec<Group>,
+    sitemaps: Vec<String>,
+    disallow_all: bool,
+}
+
+impl RobotsRules {
+    pub fn allow_all() -> Self {
+        RobotsRules::default()
+    }
+
+    pub fn disallow_all() -> Self {
+        RobotsRules {
+            disallow_all: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn parse(robots_txt: &str) -> Self {
+        let mut groups: Vec<Group> = vec![];
+        let mut sitemaps = vec![];
+        let mut in_user_agent_lines = false;
+
+        for line in robots_txt.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+
+            match key.trim().to_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_user_agent_lines {
+                        groups.push(Group::default());
+                    }
+                    in_user_agent_lines = true;
+                    if let Some(group) = groups.last_mut() {
+                        group.user_agents.push(value.to_lowercase());
+                    }
+                }
+                key @ ("allow" | "disallow") => {
+                    in_user_agent_lines = false;
+                    // an empty disallow rule allows everything
+                    if value.is_empty() {
+                        continue;
+                    }
+                    if let Some(group) = groups.last_mut() {
+                        group.rules.push(Rule {
+                            allow: key == "allow",
+                            pattern: value.to_owned(),
+                        });
+                    }
+                }
+                "crawl-delay" => {
+                    in_user_agent_lines = false;
+                    let crawl_delay = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|delay| *delay >= 0.0)
+                        .and_then(|delay| {
+                            Duration::try_from_secs_f64(delay.min(MAX_CRAWL_DELAY_S)).ok()
+                        });
+                    if let (Some(group), Some(crawl_delay)) = (groups.last_mut(), crawl_delay) {
+                        group.crawl_delay = Some(crawl_delay);
+                    }
+                }
+                // sitemaps are not tied to a group
+                "sitemap" => sitemaps.push(value.to_owned()),
+                _ => in_user_agent_lines = false,
+            }
+        }
+
+        RobotsRules {
+            groups,
+            sitemaps,
+            disallow_all: false,
+        }
+    }
+
+    /// Sitemap URLs listed in the robots.txt
+    pub fn sitemaps(&self) -> &[String] {
+        &self.sitemaps
+    }
+
+    /// Groups that apply to the user agent: the ones naming its product token or, if there are none, the `*` ones
+    fn groups(&self, user_agent: &str) -> Vec<&Group> {
+        let token = product_token(user_agent);
+        let named = |name: &str| {
+            self.groups
+                .iter()
+                .filter(|group| group.user_agents.iter().any(|agent| agent == name))
+                .collect::<Vec<_>>()
+        };
+
+        match named(&token) {
+            groups if groups.is_empty() => named("*"),
+            groups => groups,
+        }
+    }
+
+    /// Whether the path (and query) may be crawled by any user agent without a group of its own
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.is_allowed_for("*", path)
+    }
+
+    /// Whether the path (and query) may be crawled by the user agent, the longest matching rule wins
+    pub fn is_allowed_for(&self, user_agent: &str, path: &str) -> bool {
+        if self.disallow_all {
+            return false;
+        }
+
+        self.groups(user_agent)
+            .into_iter()
+            .flat_map(|group| &group.rules)
+            .filter(|rule| pattern_matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .map(|rule| rule.allow)
+            .unwrap_or(true)
+    }
+
+    /// How long the user agent should wait between requests to the host, if the robots.txt says so
+    pub fn crawl_delay_for(&self, user_agent: &str) -> Option<Duration> {
+        self.groups(user_agent)
+            .into_iter()
+            .find_map(|group| group.crawl_delay)
+    }
+}
+
+/// Matches a robots.txt path pattern, supporting `*` wildcards and the `$` end anchor
+pub fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*').collect::<Vec<_>>();
+    let Some(mut rest) = path.strip_prefix(parts[0]) else {
+        return false;
+    };
+    if parts.len() == 1 {
+        return !anchored || rest.is_empty();
+    }
+
+    let last = parts.pop().unwrap_or_default();
+    for part in &parts[1..] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    if anchored {
+        rest.ends_with(last)
+    } else {
+        rest.contains(last)
+    }
+}
+
+struct CachedRobots {
+    rules: RobotsRules,
+    fetched_at: Instant,
+}
+
+/// Per host robots.txt cache, entries are re-fetched once they are older than the TTL
+pub struct RobotsCache {
+    ttl: Duration,
+    unreachable_policy: UnreachablePolicy,
+    user_agent: String,
+    hosts: Mutex<HashMap<String, CachedRobots>>,
+}
+
+impl RobotsCache {
+    pub fn new(ttl: Duration, unreachable_policy: UnreachablePolicy) -> Self {
+        RobotsCache {
+            ttl,
+            unreachable_policy,
+            user_agent: DEFAULT_USER_AGENT.to_owned(),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Matches the robots.txt groups against this user agent rather than the default one
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_owned();
+        self
+    }
+
+    fn cached(&self, origin: &str) -> Option<RobotsRules> {
+        let hosts = self.hosts.lock().unwrap();
+        hosts
+            .get(origin)
+            .filter(|cached| cached.fetched_at.elapsed() < self.ttl)
+            .map(|cached| cached.rules.clone())
+    }
+
+    async fn fetch<F: Fetch>(&self, client: &F, origin: &str) -> RobotsRules {
+        let unreachable = match self.unreachable_policy {
+            UnreachablePolicy::Allow => RobotsRules::allow_all(),
+            UnreachablePolicy::Deny => RobotsRules::disallow_all(),
+        };
+
+        match client.fetch(&format!("{}/robots.txt", origin)).await {
+            Ok(response) if (200..300).contains(&response.status) => {
+                let rules = RobotsRules::parse(&response.body);
+                for sitemap in rules.sitemaps() {
+                    info!("Found sitemap in {}/robots.txt: {}", origin, sitemap);
+                }
+                rules
+            }
+            // a missing robots.txt means there are no restrictions
+            Ok(response) if (400..500).contains(&response.status) => RobotsRules::allow_all(),
+            _ => unreachable,
+        }
+    }
+
+    pub async fn rules<F: Fetch>(&self, client: &F, url: &Url) -> RobotsRules {
+        let origin = url.origin().ascii_serialization();
+
+        if let Some(rules) = self.cached(&origin) {
+            return rules;
+        }
+
+        let rules = self.fetch(client, &origin).await;
+        self.hosts.lock().unwrap().insert(
+            origin,
+            CachedRobots {
+                rules: rules.clone(),
+                fetched_at: Instant::now(),
+            },
+        );
+
+        rules
+    }
+
+    pub async fn is_allowed<F: Fetch>(&self, client: &F, url: &str) -> bool {
+        let Ok(url) = Url::parse(url) else {
+            return false;
+        };
+
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_owned(),
+        };
+
+        self.rules(client, &url)
+            .await
+            .is_allowed_for(&self.user_agent, &path)
+    }
+
+    /// The `Crawl-delay` of the URL's host, if its robots.txt sets one for the user agent
+    pub async fn crawl_delay<F: Fetch>(&self, client: &F, url: &str) -> Option<Duration> {
+        let url = Url::parse(url).ok()?;
+
+        self.rules(client, &url)
+            .await
+            .crawl_delay_for(&self.user_agent)
+    }
+}
+
+#[cfg(test)]
+mod robots_tests {
+    use std::time::Duration;
+    use wiremock::{matchers::path, Mock, MockServer, ResponseTemplate};
+
+    use super::{RobotsCache, RobotsRules, UnreachablePolicy};
+    use crate::fetch::{Fetch, HttpFetch};
+
+    #[test]
+    fn parse_applies_longest_matching_rule_of_wildcard_group() {
+        let rules = RobotsRules::parse(
+            "User-agent: other\nDisallow: /\n\n\
+             User-agent: *\nDisallow: /private # secret\nAllow: /private/public\nDisallow: /*.pdf$\n",
+        );
+
+        assert!(rules.is_allowed("/about"));
+        assert!(!rules.is_allowed("/private/page"));
+        assert!(rules.is_allowed("/private/public/page"));
+        assert!(!rules.is_allowed("/docs/file.pdf"));
+        assert!(rules.is_allowed("/docs/file.pdf?download=1"));
+    }
+
+    #[test]
+    fn parse_matches_groups_against_the_user_agent_product_token() {
+        let rules = RobotsRules::parse(
+            "User-agent: *\nDisallow: /\nCrawl-delay: 10\n\n\
+             User-agent: other-bot\nUser-agent: URL-Crawler\nDisallow: /private\nCrawl-delay: 2.5\n\n\
+             User-agent: url-crawler\nDisallow: /tmp\n",
+        );
+
+        assert!(!rules.is_allowed("/about"));
+        assert!(rules.is_allowed_for("url-crawler/0.1.0", "/about"));
+        assert!(!rules.is_allowed_for("url-crawler/0.1.0", "/private/page"));
+        assert!(!rules.is_allowed_for("url-crawler/0.1.0", "/tmp/page"));
+        assert_eq!(
+            rules.crawl_delay_for("url-crawler/0.1.0"),
+            Some(Duration::from_millis(2500))
+        );
+        assert_eq!(
+            rules.crawl_delay_for("another-bot"),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            RobotsRules::allow_all().crawl_delay_for("another-bot"),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_caps_huge_crawl_delays() {
+        for crawl_delay in ["inf", "1e20"] {
+            let rules =
+                RobotsRules::parse(&format!("User-agent: *\nCrawl-delay: {}\n", crawl_delay));
+            assert_eq!(
+                rules.crawl_delay_for("url-crawler"),
+                Some(Duration::from_secs(3600))
+            );
+        }
+        let rules = RobotsRules::parse("User-agent: *\nCrawl-delay: NaN\n");
+        assert_eq!(rules.crawl_delay_for("url-crawler"), None);
+    }
+
+    #[test]
+    fn parse_collects_sitemap_lines_outside_of_groups() {
+        let rules = RobotsRules::parse(
+            "Sitemap: https://a.com/sitemap.xml
+User-agent: *
+Disallow: /x
+Sitemap: https://a.com/news.xml",
+        );
+
+        assert_eq!(
+            rules.sitemaps(),
+            ["https://a.com/sitemap.xml", "https://a.com/news.xml"]
+        );
+        assert!(!rules.is_allowed("/x"));
+    }
+
+    #[test]
+    fn parse_allows_everything_for_empty_disallow() {
+        let rules = RobotsRules::parse("User-agent: *\nDisallow:\n");
+
+        assert!(rules.is_allowed("/anything"));
+    }
+
+    #[tokio::test]
+    async fn is_allowed_caches_robots_txt_until_ttl_expires() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/robots.txt"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string("User-agent: *\nDisallow: /no"),
+            )
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        let client: HttpFetch = Fetch::new();
+
+        let cache = RobotsCache::new(Duration::from_millis(200), UnreachablePolicy::Deny);
+        let uri = mock_server.uri();
+
+        assert!(!cache.is_allowed(&client, &format!("{}/no", uri)).await);
+        assert!(cache.is_allowed(&client, &format!("{}/yes", uri)).await);
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(cache.is_allowed(&client, &format!("{}/yes", uri)).await);
+    }
+
+    #[tokio::test]
+    async fn is_allowed_applies_unreachable_policy_on_server_errors() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/robots.txt"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&mock_server)
+            .await;
+        let client: HttpFetch = Fetch::new();
+        let url = format!("{}/page", mock_server.uri());
+
+        let deny = RobotsCache::new(Duration::from_secs(60), UnreachablePolicy::Deny);
+        let allow = RobotsCache::new(Duration::from_secs(60), UnreachablePolicy::Allow);
+
+        assert!(!deny.is_allowed(&client, &url).await);
+        assert!(allow.is_allowed(&client, &url).await);
+    }
+
+    #[tokio::test]
+    async fn is_allowed_allows_everything_when_robots_txt_is_missing() {
+        let mock_server = MockServer::start().await;
+        let client: HttpFetch = Fetch::new();
+
+        let cache = RobotsCache::new(Duration::from_secs(60), UnreachablePolicy::Deny);
+
+        assert!(cache.is_allowed(&client, &mock_server.uri()).await);
+    }
+}
@@ -0,0 +1,170 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    sync::Mutex,
+};
+use url::Url;
+
+use crate::link::host_with_port;
+
+/// URLs discovered and sampled in a stratum of a sampled crawl
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize)]
+pub struct StratumCounts {
+    pub discovered: usize,
+    pub sampled: usize,
+}
+
+/// What a sampled crawl discovered and crawled, to scale its findings up to the whole site
+#[derive(Debug, PartialEq, Clone, Default, Serialize)]
+pub struct SampleReport {
+    /// Fraction of the discovered URLs crawled
+    pub rate: f64,
+    pub discovered: usize,
+    pub sampled: usize,
+    /// Counts per host and first path segment, i.e. `a.com/blog`
+    pub strata: BTreeMap<String, StratumCounts>,
+}
+
+impl fmt::Display for SampleReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} discovered URLs crawled at a {} rate, in {} strata",
+            self.sampled,
+            self.discovered,
+            self.rate,
+            self.strata.len()
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct Stratum {
+    /// The first URL discovered, always crawled so that every section of a site is represented
+    first: String,
+    /// Hashes of the distinct URLs discovered
+    discovered: HashSet<u64>,
+    sampled: usize,
+}
+
+/// Crawls a sample of the discovered URLs, to estimate the health of sites too large to crawl
+/// in full. URLs are stratified by host and first path segment, the first URL of each stratum is
+/// crawled and the others are kept at the sample rate. Past the first URL of its stratum, whether
+/// a URL is kept depends on the URL alone, so rediscovering it gives the same answer. The strata
+/// only hold URLs discovered by this run: a resumed crawl starts them afresh, so it may crawl a
+/// different first URL per stratum and its report leaves out the URLs discovered before.
+#[derive(Debug)]
+pub struct Sampler {
+    rate: f64,
+    strata: Mutex<HashMap<String, Stratum>>,
+}
+
+impl Sampler {
+    pub fn new(rate: f64) -> Self {
+        Sampler {
+            rate,
+            strata: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether a discovered URL is part of the sample
+    pub fn sample(&self, url: &str) -> bool {
+        let digest = Sha256::digest(url.as_bytes());
+        let hash = u64::from_be_bytes(digest[..8].try_into().unwrap_or_default());
+
+        let mut strata = self.strata.lock().unwrap();
+        let stratum = strata.entry(stratum(url)).or_insert_with(|| Stratum {
+            first: url.to_owned(),
+            ..Default::default()
+        });
+        let sampled = stratum.first == url || (hash as f64 / u64::MAX as f64) < self.rate;
+        if stratum.discovered.insert(hash) && sampled {
+            stratum.sampled += 1;
+        }
+
+        sampled
+    }
+
+    pub fn report(&self) -> SampleReport {
+        let mut report = SampleReport {
+            rate: self.rate,
+            ..Default::default()
+        };
+        for (name, stratum) in self.strata.lock().unwrap().iter() {
+            let counts = StratumCounts {
+                discovered: stratum.discovered.len(),
+                sampled: stratum.sampled,
+            };
+            report.discovered += counts.discovered;
+            report.sampled += counts.sampled;
+            report.strata.insert(name.clone(), counts);
+        }
+
+        report
+    }
+}
+
+/// Host and first path segment of a URL
+fn stratum(url: &str) -> String {
+    let host = host_with_port(url).unwrap_or_default();
+    let segment = Url::parse(url)
+        .ok()
+        .and_then(|url| {
+            url.path_segments()
+                .and_then(|mut segments| segments.next().map(str::to_owned))
+        })
+        .unwrap_or_default();
+
+    format!("{}/{}", host, segment)
+}
+
+/// Parses a sample rate, a fraction of the discovered URLs above 0 and up to 1
+pub fn sample_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate <= 1.0 => Ok(rate),
+        _ => Err(format!("`{}` is not a fraction above 0 and up to 1", s)),
+    }
+}
+
+#[cfg(test)]
+mod sample_tests {
+    use super::{sample_rate, Sampler};
+
+    #[test]
+    fn sampler_keeps_the_first_url_of_each_stratum_and_a_fraction_of_the_rest() {
+        let sampler = Sampler::new(0.1);
+        let urls = (0..1000)
+            .map(|i| format!("https://a.com/blog/{}", i))
+            .collect::<Vec<_>>();
+
+        let sampled = urls.iter().filter(|url| sampler.sample(url)).count();
+        assert!((70..130).contains(&sampled), "{} sampled", sampled);
+        // the same URLs get the same answer, and aren't counted twice
+        assert_eq!(
+            urls.iter().filter(|url| sampler.sample(url)).count(),
+            sampled
+        );
+        assert!(sampler.sample("https://a.com/shop/1"));
+        assert!(sampler.sample("https://b.com/blog/1"));
+
+        let report = sampler.report();
+        assert_eq!(report.discovered, 1002);
+        assert_eq!(report.sampled, sampled + 2);
+        assert_eq!(
+            report.strata.keys().collect::<Vec<_>>(),
+            ["a.com/blog", "a.com/shop", "b.com/blog"]
+        );
+        assert_eq!(report.strata["a.com/blog"].discovered, 1000);
+    }
+
+    #[test]
+    fn sample_rate_must_be_a_fraction() {
+        assert_eq!(sample_rate("0.1"), Ok(0.1));
+        assert_eq!(sample_rate("1"), Ok(1.0));
+        assert!(sample_rate("0").is_err());
+        assert!(sample_rate("1.5").is_err());
+        assert!(sample_rate("10%").is_err());
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::{sleep_until, Instant};
+
+use crate::{
+    delay_formula::DelayFormula,
+    link::host_with_port,
+    politeness::{Politeness, WaitReason},
+    url_frontier::QueuedUrl,
+};
+
+/// Longest pause of a host, longer ones would hold it back for the rest of the crawl anyway
+pub const MAX_PAUSE: Duration = Duration::from_secs(30 * 24 * 3600);
+
+/// Time as the politeness delays see it
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+    /// Waits until `deadline`, returning straight away if it has passed
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Future<Output = ()> + Send + '_>>;
+}
+
+/// The clock of the tokio runtime
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        Box::pin(sleep_until(deadline))
+    }
+}
+
+/// A clock that only moves when told to, sleeping moves it to the deadline without waiting, so
+/// that politeness can be tested without real sleeps
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<Instant>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock {
+            now: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+impl ManualClock {
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        let mut now = self.now.lock().unwrap();
+        *now = (*now).max(deadline);
+
+        Box::pin(async {})
+    }
+}
+
+/// Politeness of a crawl: decides when each dequeued URL may be requested, spacing the requests to
+/// a host by its delay and holding back paused hosts
+pub struct Scheduler {
+    delay: Duration,
+    host_delays: HashMap<String, Duration>,
+    /// When the next request to each host may be made
+    next_slots: HashMap<String, Instant>,
+    /// Until when requests to each paused host are held back
+    paused_until: HashMap<String, Instant>,
+    delay_formula: Option<DelayFormula>,
+    /// URLs scheduled so far per host, for the delay formula
+    host_pages: HashMap<String, usize>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Scheduler {
+    /// Spaces requests to the same host by `delay`, on the system clock
+    pub fn new(delay: Duration) -> Self {
+        Scheduler {
+            delay,
+            host_delays: HashMap::new(),
+            next_slots: HashMap::new(),
+            paused_until: HashMap::new(),
+            delay_formula: None,
+            host_pages: HashMap::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Scales the politeness delay of each request with its depth or the requests made to its host
+    pub fn delay_formula(mut self, delay_formula: DelayFormula) -> Self {
+        self.delay_formula = Some(delay_formula);
+        self
+    }
+
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn clock_handle(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
+    }
+
+    /// Reserves the next request slot of the URL's host, returning when the URL may be requested
+    /// and the politeness decision taken
+    pub fn schedule(&mut self, url: &QueuedUrl) -> (Instant, Politeness) {
+        let now = self.clock.now();
+        let host = host_with_port(&url.url).unwrap_or_default();
+        let delay = self.delay_for(&url.url);
+        let pages = self.host_pages.entry(host.clone()).or_default();
+        let delay = match &self.delay_formula {
+            Some(delay_formula) => delay_formula.delay(delay, url.depth, *pages),
+            None => delay,
+        };
+        *pages += 1;
+        let is_paused = self
+            .paused_until
+            .get(&host)
+            .is_some_and(|until| *until > now);
+        let reason = match (is_paused, self.host_delays.contains_key(&host)) {
+            (true, _) => WaitReason::Blocked,
+            (false, true) => WaitReason::CrawlDelay,
+            (false, false) => WaitReason::Delay,
+        };
+        let slot = self.next_slots.entry(host.clone()).or_insert(now);
+        let ready_at = (*slot).max(now);
+        *slot = ready_at + delay;
+
+        (
+            ready_at,
+            Politeness {
+                host,
+                waited: ready_at - now,
+                delay,
+                reason,
+            },
+        )
+    }
+
+    /// Overrides the politeness delay for a host, i.e. with its robots.txt `Crawl-delay`
+    pub fn set_host_delay(&mut self, host: String, delay: Duration) {
+        self.host_delays.insert(host, delay);
+    }
+
+    /// Politeness delay between two requests to the URL's host
+    pub fn delay_for(&self, url: &str) -> Duration {
+        host_with_port(url)
+            .and_then(|host| self.host_delays.get(&host).copied())
+            .unwrap_or(self.delay)
+    }
+
+    /// Until when requests to a host are held back, if it is paused
+    pub fn paused_until(&self, host: &str) -> Option<Instant> {
+        let now = self.clock.now();
+        self.paused_until
+            .get(host)
+            .copied()
+            .filter(|until| *until > now)
+    }
+
+    /// Holds back requests to a host for `pause` from now, at most for `MAX_PAUSE`
+    pub fn pause_host(&mut self, host: String, pause: Duration) {
+        let until = self.clock.now() + pause.min(MAX_PAUSE);
+        let slot = self.next_slots.entry(host.clone()).or_insert(until);
+        *slot = (*slot).max(until);
+        self.paused_until.insert(host, until);
+    }
+}
+
+#[cfg(test)]
+mod scheduler_tests {
+    use std::{sync::Arc, time::Duration};
+
+    use super::{Clock, ManualClock, Scheduler, MAX_PAUSE};
+    use crate::{
+        politeness::{Politeness, WaitReason},
+        url_frontier::QueuedUrl,
+    };
+
+    fn url(url: &str) -> QueuedUrl {
+        QueuedUrl::new(url.to_owned(), 1)
+    }
+
+    #[test]
+    fn scheduler_spaces_requests_per_host_as_time_passes() {
+        let clock = Arc::new(ManualClock::default());
+        let mut scheduler = Scheduler::new(Duration::from_secs(2)).clock(clock.clone());
+        let start = clock.now();
+
+        assert_eq!(scheduler.schedule(&url("https://a.com/1")).0, start);
+        assert_eq!(
+            scheduler.schedule(&url("https://a.com/2")).0,
+            start + Duration::from_secs(2)
+        );
+
+        clock.advance(Duration::from_secs(3));
+        let (ready_at, politeness) = scheduler.schedule(&url("https://a.com/3"));
+        assert_eq!(ready_at, start + Duration::from_secs(4));
+        assert_eq!(politeness.waited, Duration::from_secs(1));
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(scheduler.schedule(&url("https://a.com/4")).0, clock.now());
+    }
+
+    #[test]
+    fn scheduler_holds_back_paused_hosts_until_the_pause_is_over() {
+        let clock = Arc::new(ManualClock::default());
+        let mut scheduler = Scheduler::new(Duration::from_secs(1)).clock(clock.clone());
+        let start = clock.now();
+
+        scheduler.pause_host("a.com".to_owned(), Duration::from_secs(60));
+        assert_eq!(
+            scheduler.schedule(&url("https://a.com/1")).1,
+            Politeness {
+                host: "a.com".to_owned(),
+                waited: Duration::from_secs(60),
+                delay: Duration::from_secs(1),
+                reason: WaitReason::Blocked,
+            }
+        );
+
+        clock.advance(Duration::from_secs(61));
+        let (ready_at, politeness) = scheduler.schedule(&url("https://a.com/2"));
+        assert_eq!(ready_at, start + Duration::from_secs(61));
+        assert_eq!(politeness.reason, WaitReason::Delay);
+
+        scheduler.pause_host("b.com".to_owned(), Duration::MAX);
+        assert_eq!(
+            scheduler.paused_until("b.com"),
+            Some(clock.now() + MAX_PAUSE)
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs, io,
+    path::Path,
+};
+use url::Url;
+
+use crate::{data_store::DataStore, link::LinkDecision, policy::UrlPolicy};
+
+/// Query parameters of internal search and filter pages
+const SEARCH_PARAMS: [&str; 12] = [
+    "q", "query", "s", "search", "keyword", "keywords", "term", "filter", "filters", "facet",
+    "sort", "orderby",
+];
+/// Path segments of internal search pages
+const SEARCH_SEGMENTS: [&str; 3] = ["search", "find", "results"];
+/// Phrases of pages telling a search or filter matched nothing, lowercase
+const NO_RESULTS_MARKERS: [&str; 6] = [
+    "no results",
+    "0 results",
+    "nothing found",
+    "no products found",
+    "did not match any",
+    "no matches",
+];
+
+fn is_search_param(name: &str) -> bool {
+    let name = name.to_lowercase();
+    // i.e. `filter[color]` or `facet.size`
+    let name = name.split(['[', '.']).next().unwrap_or_default();
+
+    SEARCH_PARAMS.contains(&name)
+}
+
+/// Whether the page body tells that a search or filter matched nothing
+pub fn has_no_results(body: &str) -> bool {
+    let body = body.to_lowercase();
+
+    NO_RESULTS_MARKERS
+        .iter()
+        .any(|marker| body.contains(marker))
+}
+
+/// Whether the page looks like an internal search or filter page - a search path or query parameter, or
+/// any query string on a page telling that nothing matched - and if so, whether it has results
+pub fn detect(url: &str, body: &str) -> Option<bool> {
+    let url = Url::parse(url).ok()?;
+    let is_search_path = url.path_segments().is_some_and(|mut segments| {
+        segments.any(|segment| SEARCH_SEGMENTS.contains(&segment.to_lowercase().as_str()))
+    });
+    let is_search = is_search_path || url.query_pairs().any(|(name, _)| is_search_param(&name));
+    if !is_search && url.query().is_none() {
+        return None;
+    }
+    let has_results = !has_no_results(body);
+
+    (is_search || !has_results).then_some(has_results)
+}
+
+/// What search pages have in common, the host, path and query parameter names, i.e.
+/// `a.com/search?q`, so that other searches can be recognised without fetching them
+pub fn pattern(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let params = url
+        .query_pairs()
+        .map(|(name, _)| name.into_owned())
+        .collect::<BTreeSet<_>>();
+    let mut pattern = format!("{}{}", url.host_str()?, url.path());
+    if !params.is_empty() {
+        pattern.push('?');
+        pattern.push_str(&params.into_iter().collect::<Vec<_>>().join("&"));
+    }
+
+    Some(pattern)
+}
+
+/// Internal search and filter pages that were crawled
+#[derive(Debug, PartialEq, Default)]
+pub struct SearchPages {
+    /// Whether each search page had results, by URL
+    pub pages: BTreeMap<String, bool>,
+}
+
+impl SearchPages {
+    pub fn from_store<T: DataStore>(store: &T) -> Self {
+        let pages = store
+            .entries()
+            .into_iter()
+            .filter_map(|(url, entry)| Some((url.clone(), entry.search_page?)))
+            .collect();
+
+        SearchPages { pages }
+    }
+
+    pub fn without_results(&self) -> usize {
+        self.pages
+            .values()
+            .filter(|has_results| !**has_results)
+            .count()
+    }
+
+    /// Patterns of every search page, to skip similar pages in future crawls
+    pub fn patterns(&self) -> BTreeSet<String> {
+        self.pages.keys().filter_map(|url| pattern(url)).collect()
+    }
+}
+
+impl fmt::Display for SearchPages {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Search pages ({}, {} without results):",
+            self.pages.len(),
+            self.without_results()
+        )?;
+        for (url, has_results) in &self.pages {
+            match has_results {
+                true => writeln!(f, "  {}", url)?,
+                false => writeln!(f, "  {} (no results)", url)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Skips links matching the patterns of search pages found in previous crawls, persisted one per line
+#[derive(Debug, PartialEq, Default)]
+pub struct SearchPagePolicy {
+    patterns: BTreeSet<String>,
+}
+
+impl SearchPagePolicy {
+    pub fn load(path: &Path) -> Result<Self, io::Error> {
+        let patterns = fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_owned)
+            .collect();
+
+        Ok(SearchPagePolicy { patterns })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), io::Error> {
+        let lines = self
+            .patterns
+            .iter()
+            .map(|pattern| format!("{}\n", pattern))
+            .collect::<String>();
+
+        fs::write(path, lines)
+    }
+
+    /// Adds the patterns of the search pages found in a crawl
+    pub fn learn(&mut self, search_pages: &SearchPages) {
+        self.patterns.extend(search_pages.patterns());
+    }
+}
+
+impl UrlPolicy for SearchPagePolicy {
+    fn decide(&self, url: &str, _page_url: &str) -> LinkDecision {
+        match pattern(url).is_some_and(|pattern| self.patterns.contains(&pattern)) {
+            true => LinkDecision::SkipFiltered,
+            false => LinkDecision::Enqueue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod search_tests {
+    use std::collections::BTreeMap;
+
+    use super::{detect, pattern, SearchPagePolicy, SearchPages};
+    use crate::{
+        data_store::{DataStore, Store},
+        link::LinkDecision,
+        policy::UrlPolicy,
+    };
+
+    #[test]
+    fn detect_finds_search_paths_params_and_empty_filter_pages() {
+        assert_eq!(
+            detect("https://a.com/search", "<p>3 products</p>"),
+            Some(true)
+        );
+        assert_eq!(detect("https://a.com/shop?Q=shoes", ""), Some(true));
+        assert_eq!(
+            detect("https://a.com/shop?filter[color]=red", "No results"),
+            Some(false)
+        );
+        assert_eq!(
+            detect(
+                "https://a.com/shop?color=purple",
+                "Sorry, no products found"
+            ),
+            Some(false)
+        );
+        assert_eq!(detect("https://a.com/shop?color=red", "3 products"), None);
+        // a page without a query string only tells about its own content
+        assert_eq!(detect("https://a.com/blog/no-results", "No results"), None);
+    }
+
+    #[test]
+    fn search_page_policy_skips_urls_matching_learned_patterns() {
+        let mut s = Store::new();
+        s.try_mark_visited("https://a.com/search?q=shoes&page=2");
+        s.set_search_page("https://a.com/search?q=shoes&page=2", false);
+        s.try_mark_visited("https://a.com/about");
+
+        let search_pages = SearchPages::from_store(&s);
+        let mut policy = SearchPagePolicy::default();
+        policy.learn(&search_pages);
+
+        assert_eq!(
+            search_pages.pages,
+            BTreeMap::from([("https://a.com/search?q=shoes&page=2".to_owned(), false)])
+        );
+        assert_eq!(
+            pattern("https://a.com/search?q=shoes&page=2"),
+            Some("a.com/search?page&q".to_owned())
+        );
+        assert_eq!(
+            policy.decide("https://a.com/search?page=1&q=hats", "https://a.com/"),
+            LinkDecision::SkipFiltered
+        );
+        assert_eq!(
+            policy.decide("https://a.com/search", "https://a.com/"),
+            LinkDecision::Enqueue
+        );
+
+        let path = std::env::temp_dir().join(format!(
+            "url-crawler-search-patterns-{}.txt",
+            std::process::id()
+        ));
+        policy.save(&path).unwrap();
+        assert_eq!(SearchPagePolicy::load(&path).unwrap(), policy);
+        let _ = std::fs::remove_file(&path);
+    }
+}
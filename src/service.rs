@@ -0,0 +1,159 @@
+use log::Level;
+use std::{env, io, process, time::Duration};
+
+/// Reports the state of the crawl to systemd through the `sd_notify` protocol, for
+/// `Type=notify` units. Does nothing when not started by systemd, i.e. without `NOTIFY_SOCKET`.
+#[derive(Debug, Default, Clone)]
+pub struct Notifier {
+    /// Address of systemd's notification socket, a path or `@` and an abstract name
+    socket: Option<String>,
+}
+
+impl Notifier {
+    pub fn new(socket: Option<String>) -> Self {
+        Notifier { socket }
+    }
+
+    /// The notifier of the unit that started the process
+    pub fn from_env() -> Self {
+        Notifier::new(
+            env::var("NOTIFY_SOCKET")
+                .ok()
+                .filter(|socket| !socket.is_empty()),
+        )
+    }
+
+    /// Sends newline separated `KEY=value` assignments, i.e. `READY=1`
+    pub fn notify(&self, state: &str) -> Result<(), io::Error> {
+        match &self.socket {
+            Some(socket) => send(socket, state),
+            None => Ok(()),
+        }
+    }
+
+    /// Tells systemd the crawl started, with a status line for `systemctl status`
+    pub fn ready(&self, status: &str) -> Result<(), io::Error> {
+        self.notify(&format!("READY=1\nSTATUS={}", status))
+    }
+
+    pub fn watchdog(&self) -> Result<(), io::Error> {
+        self.notify("WATCHDOG=1")
+    }
+
+    /// Tells systemd the crawl is over and the process about to exit
+    pub fn stopping(&self, status: &str) -> Result<(), io::Error> {
+        self.notify(&format!("STOPPING=1\nSTATUS={}", status))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn send(socket: &str, state: &str) -> Result<(), io::Error> {
+    use std::os::{
+        linux::net::SocketAddrExt,
+        unix::net::{SocketAddr, UnixDatagram},
+    };
+
+    let address = match socket.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(socket)?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &address)?;
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send(_socket: &str, _state: &str) -> Result<(), io::Error> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "sd_notify is only supported on Linux",
+    ))
+}
+
+/// How often to ping the watchdog of the unit, half its `WatchdogSec=`, none if it has no watchdog
+pub fn watchdog_interval() -> Option<Duration> {
+    parse_watchdog_interval(
+        env::var("WATCHDOG_USEC").ok().as_deref(),
+        env::var("WATCHDOG_PID").ok().as_deref(),
+        process::id(),
+    )
+}
+
+/// Half the watchdog timeout of `WATCHDOG_USEC`, if the watchdog is meant for this process
+fn parse_watchdog_interval(
+    usec: Option<&str>,
+    pid: Option<&str>,
+    own_pid: u32,
+) -> Option<Duration> {
+    // the watchdog of a unit whose main process is another, i.e. a wrapper script
+    if pid.is_some_and(|pid| pid.parse() != Ok(own_pid)) {
+        return None;
+    }
+
+    usec?
+        .parse()
+        .ok()
+        .filter(|usec| *usec > 0)
+        .map(|usec| Duration::from_micros(usec) / 2)
+}
+
+/// The `<N>` prefix journald reads the syslog priority of a line of stderr from
+pub fn journald_prefix(level: Level) -> &'static str {
+    match level {
+        Level::Error => "<3>",
+        Level::Warn => "<4>",
+        Level::Info => "<6>",
+        Level::Debug | Level::Trace => "<7>",
+    }
+}
+
+// the notifier only sends on Linux
+#[cfg(all(test, target_os = "linux"))]
+mod service_tests {
+    use log::Level;
+    use std::{fs, os::unix::net::UnixDatagram, time::Duration};
+
+    use super::{journald_prefix, parse_watchdog_interval, Notifier};
+
+    #[test]
+    fn notifier_sends_states_to_the_notify_socket() {
+        let path =
+            std::env::temp_dir().join(format!("url-crawler-notify-{}.sock", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let systemd = UnixDatagram::bind(&path).unwrap();
+        let notifier = Notifier::new(Some(path.to_str().unwrap().to_owned()));
+
+        notifier.ready("crawling https://a.com/").unwrap();
+        notifier.watchdog().unwrap();
+
+        let mut buf = [0; 256];
+        let len = systemd.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1\nSTATUS=crawling https://a.com/");
+        let len = systemd.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"WATCHDOG=1");
+        fs::remove_file(&path).unwrap();
+
+        // outside of systemd
+        assert!(Notifier::default().ready("crawling").is_ok());
+    }
+
+    #[test]
+    fn watchdog_interval_is_half_the_timeout_of_this_process() {
+        assert_eq!(
+            parse_watchdog_interval(Some("30000000"), None, 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            parse_watchdog_interval(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            parse_watchdog_interval(Some("30000000"), Some("7"), 42),
+            None
+        );
+        assert_eq!(parse_watchdog_interval(Some("0"), None, 42), None);
+        assert_eq!(parse_watchdog_interval(None, None, 42), None);
+
+        assert_eq!(journald_prefix(Level::Warn), "<4>");
+    }
+}
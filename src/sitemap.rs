@@ -0,0 +1,351 @@
+use log::{info, warn};
+use url::Url;
+
+use crate::{
+    alternates::{Alternate, AlternateSource},
+    fetch::Fetch,
+    robots::RobotsCache,
+    xml::{local_name, tokens, Token},
+};
+
+/// Sitemap indexes can point to further indexes, don't follow them forever
+const MAX_SITEMAP_DEPTH: usize = 3;
+
+/// A page listed by a sitemap
+#[derive(Debug, PartialEq)]
+pub struct SitemapUrl {
+    pub loc: String,
+    /// Language versions of the page, from its `<xhtml:link rel="alternate" hreflang>` elements
+    pub alternates: Vec<Alternate>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Sitemap {
+    /// A `<urlset>`, an RSS or Atom feed, or a text file listing pages
+    UrlSet(Vec<SitemapUrl>),
+    /// A `<sitemapindex>` listing further sitemap URLs
+    Index(Vec<String>),
+}
+
+/// An entry of a sitemap or feed: a `<url>`, `<sitemap>`, RSS `<item>` or Atom `<entry>`
+#[derive(Debug, Default)]
+struct Entry {
+    /// Text of its first `<loc>`, or `<link>` in RSS
+    text: Option<String>,
+    /// `href` of its first `<link>` to the entry itself, in Atom
+    href: Option<String>,
+    /// Language and URL of its `<xhtml:link rel="alternate" hreflang>` elements
+    alternates: Vec<(String, String)>,
+}
+
+/// Name of the root element and entries of a sitemap or feed
+fn entries(xml: &str) -> (Option<String>, Vec<Entry>) {
+    let mut root = None;
+    let mut entries = vec![];
+    let mut entry: Option<Entry> = None;
+    // text of the `<loc>` or `<link>` being read
+    let mut text: Option<String> = None;
+
+    for token in tokens(xml) {
+        match token {
+            Token::Start {
+                name,
+                attributes,
+                self_closing,
+            } => {
+                let name = local_name(name);
+                root.get_or_insert_with(|| name.to_owned());
+                let attribute = |attribute: &str| {
+                    attributes
+                        .iter()
+                        .find(|(name, _)| local_name(name) == attribute)
+                        .map(|(_, value)| value.clone())
+                };
+                match (name, &mut entry) {
+                    ("url" | "sitemap" | "item" | "entry", _) => entry = Some(Entry::default()),
+                    ("loc" | "link", Some(_)) if !self_closing => text = Some(String::new()),
+                    _ => {}
+                }
+                if let ("link", Some(entry)) = (name, &mut entry) {
+                    let rel = attribute("rel");
+                    if let (Some("alternate"), Some(hreflang), Some(href)) =
+                        (rel.as_deref(), attribute("hreflang"), attribute("href"))
+                    {
+                        entry.alternates.push((hreflang, href));
+                    }
+                    // other links of an Atom entry are to other kinds of resources, i.e. enclosures
+                    if matches!(rel.as_deref(), None | Some("alternate")) && entry.href.is_none() {
+                        entry.href = attribute("href");
+                    }
+                }
+            }
+            Token::Text(content) => {
+                if let Some(text) = &mut text {
+                    text.push_str(&content);
+                }
+            }
+            Token::End { name } => match (local_name(name), &mut entry) {
+                ("loc" | "link", Some(entry)) => {
+                    let text = text.take().map(|text| text.trim().to_owned());
+                    if entry.text.is_none() {
+                        entry.text = text.filter(|text| !text.is_empty());
+                    }
+                }
+                ("url" | "sitemap" | "item" | "entry", _) => entries.extend(entry.take()),
+                _ => {}
+            },
+        }
+    }
+
+    (root, entries)
+}
+
+/// Extracts the URL on each line of a text sitemap, other lines are skipped
+fn text_urls(text: &str) -> Vec<SitemapUrl> {
+    text.trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("http://") || line.starts_with("https://"))
+        .filter(|line| Url::parse(line).is_ok())
+        .map(|line| SitemapUrl {
+            loc: line.to_owned(),
+            alternates: vec![],
+        })
+        .collect()
+}
+
+/// Parses an XML sitemap or sitemap index, an RSS or Atom feed, or a text sitemap listing a URL
+/// per line, the formats search engines accept as sitemaps
+pub fn parse_sitemap(document: &str) -> Sitemap {
+    if !document
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .starts_with('<')
+    {
+        return Sitemap::UrlSet(text_urls(document));
+    }
+
+    let (root, entries) = entries(document);
+    match root.as_deref() {
+        Some("sitemapindex") => {
+            Sitemap::Index(entries.into_iter().filter_map(|entry| entry.text).collect())
+        }
+        // the link of an RSS item is its text, of an Atom entry its `href`
+        Some("rss" | "feed") => Sitemap::UrlSet(
+            entries
+                .into_iter()
+                .filter_map(|entry| entry.text.or(entry.href))
+                .map(|loc| SitemapUrl {
+                    loc,
+                    alternates: vec![],
+                })
+                .collect(),
+        ),
+        _ => Sitemap::UrlSet(
+            entries
+                .into_iter()
+                .filter_map(|entry| {
+                    let loc = entry.text?;
+                    let alternates = entry
+                        .alternates
+                        .iter()
+                        .filter_map(|(hreflang, href)| {
+                            Alternate::new(&loc, hreflang, href, AlternateSource::Sitemap)
+                        })
+                        .collect();
+                    Some(SitemapUrl { loc, alternates })
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Fetches a sitemap and returns the pages it lists, following sitemap indexes
+pub async fn sitemap_urls<F: Fetch>(client: &F, sitemap_url: &str) -> Vec<SitemapUrl> {
+    let mut urls = vec![];
+    let mut sitemaps = vec![(sitemap_url.to_owned(), 0)];
+
+    while let Some((sitemap_url, depth)) = sitemaps.pop() {
+        let response = match client.fetch(&sitemap_url).await {
+            Ok(response) if (200..300).contains(&response.status) => response,
+            Ok(response) => {
+                warn!("Sitemap {} responded with {}", sitemap_url, response.status);
+                continue;
+            }
+            Err(e) => {
+                warn!("Error requesting sitemap {} - {}", sitemap_url, e);
+                continue;
+            }
+        };
+
+        match parse_sitemap(&response.body) {
+            Sitemap::UrlSet(page_urls) => urls.extend(page_urls),
+            Sitemap::Index(_) if depth >= MAX_SITEMAP_DEPTH => {
+                warn!("Not following nested sitemap index {}", sitemap_url);
+            }
+            Sitemap::Index(sitemap_urls) => {
+                sitemaps.extend(sitemap_urls.into_iter().map(|url| (url, depth + 1)));
+            }
+        }
+    }
+
+    urls
+}
+
+/// Returns the pages of every sitemap listed in the seed host's robots.txt
+pub async fn discover<F: Fetch>(client: &F, robots: &RobotsCache, seed: &str) -> Vec<SitemapUrl> {
+    let Ok(seed) = Url::parse(seed) else {
+        return vec![];
+    };
+
+    let mut urls = vec![];
+    for sitemap_url in robots.rules(client, &seed).await.sitemaps() {
+        info!("Reading sitemap: {}", sitemap_url);
+        urls.extend(sitemap_urls(client, sitemap_url).await);
+    }
+
+    urls
+}
+
+#[cfg(test)]
+mod sitemap_tests {
+    use std::time::Duration;
+    use wiremock::{matchers::path, Mock, MockServer, ResponseTemplate};
+
+    use super::{discover, parse_sitemap, Sitemap, SitemapUrl};
+    use crate::{
+        alternates::{Alternate, AlternateSource},
+        fetch::{Fetch, HttpFetch},
+        robots::{RobotsCache, UnreachablePolicy},
+    };
+
+    #[test]
+    fn parse_sitemap_extracts_urls_from_urlset() {
+        let sitemap = parse_sitemap(
+            "<?xml version=\"1.0\"?><urlset><!-- <url><loc>https://a.com/old</loc></url> -->\
+             <url><loc> https://a.com/?a=1&amp;b=2 </loc></url>\
+             <url><loc><![CDATA[https://a.com/two]]></loc></url></urlset>",
+        );
+
+        assert_eq!(
+            sitemap,
+            Sitemap::UrlSet(vec![
+                SitemapUrl {
+                    loc: "https://a.com/?a=1&b=2".to_owned(),
+                    alternates: vec![]
+                },
+                SitemapUrl {
+                    loc: "https://a.com/two".to_owned(),
+                    alternates: vec![]
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_sitemap_extracts_the_alternates_of_each_url() {
+        let sitemap = parse_sitemap(
+            "<urlset xmlns:xhtml=\"http://www.w3.org/1999/xhtml\"><url><loc>https://a.com/en</loc>\
+             <xhtml:link rel=\"alternate\" hreflang=\"fr\" href=\"https://a.com/fr?a=1&amp;b=2\"/>\
+             <xhtml:link\n rel='alternate'\n hreflang='EN' href='https://a.com/en'/></url></urlset>",
+        );
+
+        let alternate = |hreflang: &str, url: &str| Alternate {
+            hreflang: hreflang.to_owned(),
+            url: url.to_owned(),
+            source: AlternateSource::Sitemap,
+        };
+        assert_eq!(
+            sitemap,
+            Sitemap::UrlSet(vec![SitemapUrl {
+                loc: "https://a.com/en".to_owned(),
+                alternates: vec![
+                    alternate("fr", "https://a.com/fr?a=1&b=2"),
+                    alternate("en", "https://a.com/en")
+                ]
+            }])
+        );
+    }
+
+    #[test]
+    fn parse_sitemap_reads_text_sitemaps_and_feeds() {
+        let locs = |document: &str| match parse_sitemap(document) {
+            Sitemap::UrlSet(urls) => urls.into_iter().map(|url| url.loc).collect::<Vec<_>>(),
+            Sitemap::Index(_) => panic!("not a list of pages"),
+        };
+
+        assert_eq!(
+            locs(
+                "\u{feff}https://a.com/one\r\n\n  https://a.com/two \n# three\nftp://a.com/four\n"
+            ),
+            vec!["https://a.com/one", "https://a.com/two"]
+        );
+        assert_eq!(
+            locs(
+                "<?xml version=\"1.0\"?><rss version=\"2.0\"><channel><link>https://a.com/</link>\
+                 <item><title>One</title><link>https://a.com/one?a=1&amp;b=2</link></item>\
+                 <item><link><![CDATA[https://a.com/two]]></link></item></channel></rss>"
+            ),
+            vec!["https://a.com/one?a=1&b=2", "https://a.com/two"]
+        );
+        assert_eq!(
+            locs(
+                "<feed xmlns=\"http://www.w3.org/2005/Atom\"><link href=\"https://a.com/\"/>\
+                 <entry><link rel=\"enclosure\" href=\"https://a.com/one.mp3\"/>\
+                 <link rel=\"alternate\" href=\"https://a.com/one\"/></entry>\
+                 <entry><link href=\"https://a.com/two\"/></entry></feed>"
+            ),
+            vec!["https://a.com/one", "https://a.com/two"]
+        );
+    }
+
+    #[test]
+    fn parse_sitemap_recognises_sitemap_index() {
+        let sitemap = parse_sitemap(
+            "<sitemapindex><sitemap><loc>https://a.com/pages.xml</loc></sitemap></sitemapindex>",
+        );
+
+        assert_eq!(
+            sitemap,
+            Sitemap::Index(vec!["https://a.com/pages.xml".to_owned()])
+        );
+    }
+
+    #[tokio::test]
+    async fn discover_reads_sitemaps_listed_in_robots_txt() {
+        let mock_server = MockServer::start().await;
+        let uri = mock_server.uri();
+
+        Mock::given(path("/robots.txt"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(format!("User-agent: *\nSitemap: {}/index.xml\n", uri)),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/index.xml"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(format!(
+                "<sitemapindex><sitemap><loc>{}/pages.xml</loc></sitemap></sitemapindex>",
+                uri
+            )))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/pages.xml"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(format!(
+                "<urlset><url><loc>{}/hidden</loc></url></urlset>",
+                uri
+            )))
+            .mount(&mock_server)
+            .await;
+
+        let client: HttpFetch = Fetch::new();
+        let robots = RobotsCache::new(Duration::from_secs(60), UnreachablePolicy::Allow);
+
+        let urls = discover(&client, &robots, &uri).await;
+
+        assert_eq!(
+            urls.into_iter().map(|url| url.loc).collect::<Vec<_>>(),
+            vec![format!("{}/hidden", uri)]
+        );
+    }
+}
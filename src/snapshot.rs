@@ -0,0 +1,197 @@
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt, sync::Arc};
+use tokio::{sync::Semaphore, task::JoinSet};
+use url::Url;
+
+use crate::{
+    data_store::DataStore, fetch::Fetch, link::LinkDecision, parser::Parser, robots::RobotsCache,
+};
+
+/// The root page of an external site linked to from the crawl, fetched once and not crawled any further
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct ExternalSnapshot {
+    pub host: String,
+    /// URL of the root page that was requested
+    pub url: String,
+    pub status: Option<u16>,
+    /// Where the root page redirects to, if it does
+    pub redirected_to: Option<String>,
+    pub title: Option<String>,
+    /// Why the root page could not be fetched
+    pub error: Option<String>,
+}
+
+impl fmt::Display for ExternalSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.status, &self.error) {
+            (Some(status), _) => write!(f, "{}", status)?,
+            (None, Some(error)) => write!(f, "{}", error)?,
+            (None, None) => write!(f, "-")?,
+        }
+        if let Some(redirected_to) = &self.redirected_to {
+            write!(f, " -> {}", redirected_to)?;
+        }
+        if let Some(title) = &self.title {
+            write!(f, " \"{}\"", title)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Root page of every external host linked to from a crawled page, by host
+pub fn external_roots<T: DataStore>(store: &T) -> BTreeMap<String, String> {
+    let mut roots = BTreeMap::new();
+
+    for (_, entry) in store.entries() {
+        for (link, decision) in &entry.link_decisions {
+            if *decision != LinkDecision::SkipExternal {
+                continue;
+            }
+            let Ok(url) = Url::parse(link) else {
+                continue;
+            };
+            if let Some(host) = url.host_str() {
+                roots
+                    .entry(host.to_owned())
+                    .or_insert_with(|| format!("{}/", url.origin().ascii_serialization()));
+            }
+        }
+    }
+
+    roots
+}
+
+/// Fetches the root page of every external host, at most `concurrency` at a time, recording its status, redirect and title
+pub async fn snapshot<F: Fetch + Send + Sync + 'static>(
+    client: Arc<F>,
+    robots: Option<Arc<RobotsCache>>,
+    roots: BTreeMap<String, String>,
+    concurrency: usize,
+) -> Vec<ExternalSnapshot> {
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+
+    for (host, url) in roots {
+        let client = client.clone();
+        let robots = robots.clone();
+        let permits = permits.clone();
+
+        tasks.spawn(async move {
+            let _permit = permits.acquire().await;
+            let mut snapshot = ExternalSnapshot {
+                host,
+                url,
+                status: None,
+                redirected_to: None,
+                title: None,
+                error: None,
+            };
+
+            if let Some(robots) = robots {
+                if !robots.is_allowed(client.as_ref(), &snapshot.url).await {
+                    snapshot.error = Some("disallowed by robots.txt".to_owned());
+                    return snapshot;
+                }
+            }
+
+            match client.fetch(&snapshot.url).await {
+                Ok(response) => {
+                    snapshot.status = Some(response.status);
+                    if !response.redirects.is_empty() {
+                        snapshot.redirected_to = Some(response.url);
+                    }
+                    snapshot.title = Parser::new(response.body).title();
+                }
+                Err(e) => snapshot.error = Some(e.to_string()),
+            }
+
+            snapshot
+        });
+    }
+
+    let mut snapshots = vec![];
+    while let Some(res) = tasks.join_next().await {
+        if let Ok(snapshot) = res {
+            snapshots.push(snapshot);
+        }
+    }
+    snapshots.sort_by(|a, b| a.host.cmp(&b.host));
+
+    snapshots
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use std::sync::Arc;
+    use wiremock::{
+        matchers::{any, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::{external_roots, snapshot, ExternalSnapshot};
+    use crate::{
+        data_store::{DataStore, Store},
+        fetch::{Fetch, HttpFetch},
+        link::LinkDecision,
+    };
+
+    #[test]
+    fn external_roots_keeps_one_root_per_external_host() {
+        let mut s = Store::new();
+        let page = "https://a.com";
+        s.add(page.to_owned(), None);
+        for (link, decision) in [
+            ("https://b.com/one", LinkDecision::SkipExternal),
+            ("https://b.com/two", LinkDecision::SkipExternal),
+            ("http://c.com:8080/x?y=1", LinkDecision::SkipExternal),
+            ("https://a.com/about", LinkDecision::Enqueue),
+        ] {
+            s.set_link_decision(page, link.to_owned(), decision);
+        }
+
+        let roots = external_roots(&s);
+
+        assert_eq!(
+            roots.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("b.com".to_owned(), "https://b.com/".to_owned()),
+                ("c.com".to_owned(), "http://c.com:8080/".to_owned()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn snapshot_records_status_redirect_and_title() {
+        let mock_server = MockServer::start().await;
+        let uri = mock_server.uri();
+
+        Mock::given(path("/"))
+            .respond_with(ResponseTemplate::new(301).insert_header("Location", "/home"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_string("<title>Home page</title>"))
+            .mount(&mock_server)
+            .await;
+
+        let client: HttpFetch = Fetch::new();
+        let roots = [("b.com".to_owned(), format!("{}/", uri))].into();
+
+        let snapshots = snapshot(Arc::new(client), None, roots, 4).await;
+
+        let expected = ExternalSnapshot {
+            host: "b.com".to_owned(),
+            url: format!("{}/", uri),
+            status: Some(200),
+            redirected_to: Some(format!("{}/home", uri)),
+            title: Some("Home page".to_owned()),
+            error: None,
+        };
+        assert_eq!(snapshots, vec![expected.clone()]);
+        assert_eq!(
+            expected.to_string(),
+            format!("200 -> {}/home \"Home page\"", uri)
+        );
+    }
+}
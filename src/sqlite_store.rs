@@ -0,0 +1,512 @@
+use log::info;
+use rusqlite::{params, Connection};
+use std::{
+    collections::HashSet,
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    alternates::Alternate,
+    data_store::{DataStore, DataStoreEntry, Error, LocaleResult, Store},
+    fetch::{PartialContent, Redirect},
+    link::{LinkDecision, LinkSource},
+    mime::TypeMismatch,
+    mobile::MobileSignals,
+    parked::ParkedSign,
+    parser::Form,
+    url_frontier::QueuedUrl,
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS pages (url TEXT PRIMARY KEY, entry TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS frontier (url TEXT PRIMARY KEY, depth INTEGER NOT NULL);
+    CREATE TABLE IF NOT EXISTS failures (
+        url TEXT PRIMARY KEY,
+        attempts INTEGER NOT NULL,
+        retry_at INTEGER NOT NULL
+    );
+";
+
+/// Failed fetches after which a URL is given up on, across resumed crawls
+pub const MAX_ATTEMPTS: u32 = 3;
+/// Backoff (in seconds) after the first failed fetch of a URL, doubled with each further failure
+pub const RETRY_BACKOFF_S: u64 = 60;
+
+/// Seconds since the epoch, the clock failed fetches are backed off from
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// A URL queued but not visited when a crawl stopped, with its failed fetches
+#[derive(Debug, PartialEq, Clone)]
+pub struct FrontierUrl {
+    pub queued: QueuedUrl,
+    /// Failed fetches of the URL so far
+    pub attempts: u32,
+    /// When the URL may be fetched again, in seconds since the epoch
+    pub retry_at: u64,
+}
+
+impl FrontierUrl {
+    /// Whether the URL failed too many times to be fetched again
+    pub fn is_given_up(&self) -> bool {
+        self.attempts >= MAX_ATTEMPTS
+    }
+
+    /// Whether the URL is still backing off from its last failed fetch at `now`
+    pub fn is_backing_off(&self, now: u64) -> bool {
+        self.retry_at > now
+    }
+}
+
+/// A data store persisted to an SQLite database, alongside the URLs queued in the frontier, so that
+/// a crawl can be resumed after the process stopped. Pages are kept in memory too and written on flush.
+pub struct SqliteStore {
+    store: Store,
+    // a connection can't be shared between threads, the store lock already serialises access to it
+    connection: Mutex<Connection>,
+    /// Pages changed since the last flush
+    dirty: HashSet<String>,
+    /// URLs queued since the last flush
+    queued: Vec<QueuedUrl>,
+    /// URLs that failed to be fetched since the last flush
+    failed: Vec<String>,
+}
+
+impl SqliteStore {
+    /// Opens the database at `path`, creating it if needed. Unless resuming, the pages and queued URLs
+    /// of a previous crawl are cleared.
+    pub fn open(path: &Path, resume: bool) -> Result<Self, Error> {
+        Self::with_connection(Connection::open(path)?, resume)
+    }
+
+    fn with_connection(connection: Connection, resume: bool) -> Result<Self, Error> {
+        connection.execute_batch(SCHEMA)?;
+        if !resume {
+            connection
+                .execute_batch("DELETE FROM pages; DELETE FROM frontier; DELETE FROM failures;")?;
+        }
+
+        let mut store = Store::new();
+        {
+            let mut statement = connection.prepare("SELECT url, entry FROM pages")?;
+            let mut rows = statement.query([])?;
+            while let Some(row) = rows.next()? {
+                let entry: String = row.get(1)?;
+                store.insert(row.get(0)?, serde_json::from_str(&entry)?);
+            }
+        }
+
+        Ok(SqliteStore {
+            store,
+            connection: Mutex::new(connection),
+            dirty: HashSet::new(),
+            queued: vec![],
+            failed: vec![],
+        })
+    }
+
+    /// URLs that were queued but not visited when the crawl stopped, in the order they were queued.
+    /// URLs that failed too many times, or whose backoff hasn't passed yet, are left out.
+    pub fn pending(&self) -> Result<Vec<QueuedUrl>, Error> {
+        self.pending_at(now())
+    }
+
+    fn pending_at(&self, now: u64) -> Result<Vec<QueuedUrl>, Error> {
+        let mut pending = vec![];
+        for url in self.frontier()? {
+            if url.is_given_up() {
+                info!(
+                    "Giving up on {} after {} failures",
+                    url.queued.url, url.attempts
+                );
+            } else if url.is_backing_off(now) {
+                info!(
+                    "Backing off from {} for another {}s after {} failures",
+                    url.queued.url,
+                    url.retry_at - now,
+                    url.attempts
+                );
+            } else {
+                pending.push(url.queued);
+            }
+        }
+
+        Ok(pending)
+    }
+
+    /// Every URL queued but not visited, in the order they were queued, including the ones a
+    /// resumed crawl gives up on or backs off from
+    pub fn frontier(&self) -> Result<Vec<FrontierUrl>, Error> {
+        let connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        let mut statement = connection.prepare(
+            "SELECT frontier.url, frontier.depth, failures.attempts, failures.retry_at
+            FROM frontier LEFT JOIN failures ON failures.url = frontier.url
+            ORDER BY frontier.rowid",
+        )?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok(FrontierUrl {
+                    queued: QueuedUrl::new(row.get(0)?, row.get(1)?),
+                    attempts: row.get::<_, Option<u32>>(2)?.unwrap_or_default(),
+                    retry_at: row.get::<_, Option<u64>>(3)?.unwrap_or_default(),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows
+            .into_iter()
+            .filter(|url| !self.store.has_visited(&url.queued.url))
+            .collect())
+    }
+
+    /// Keeps the pages for which `keep` returns true, `keep` may also change the pages it keeps.
+    /// Removed pages are deleted straight away, the kept ones are written on flush.
+    pub fn retain(
+        &mut self,
+        keep: impl FnMut(&str, &mut DataStoreEntry) -> bool,
+    ) -> Result<(), Error> {
+        let removed = self.store.retain(keep);
+        {
+            let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+            let transaction = connection.transaction()?;
+            for key in &removed {
+                transaction.execute("DELETE FROM pages WHERE url = ?1", params![key])?;
+            }
+            transaction.commit()?;
+        }
+
+        let kept = self
+            .store
+            .entries()
+            .into_iter()
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        self.dirty.extend(kept);
+
+        Ok(())
+    }
+
+    /// Adds the pages of another store, merging the ones both have, to be written on flush.
+    /// Returns the number of pages both had.
+    pub fn merge<T: DataStore>(&mut self, other: &T) -> usize {
+        let mut shared = 0;
+        for (key, entry) in other.entries() {
+            if self.store.exists(key) {
+                shared += 1;
+            }
+            self.changed(key);
+            self.store.merge(key.clone(), entry.clone());
+        }
+
+        shared
+    }
+
+    /// Rebuilds the database file so that the space of deleted data is given back
+    pub fn compact(&self) -> Result<(), Error> {
+        let connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+
+        Ok(connection.execute_batch("VACUUM")?)
+    }
+
+    fn changed(&mut self, key: &str) {
+        if !self.dirty.contains(key) {
+            self.dirty.insert(key.to_owned());
+        }
+    }
+}
+
+impl DataStore for SqliteStore {
+    /// A store backed by an in-memory database
+    fn new() -> Self {
+        let connection =
+            Connection::open_in_memory().expect("Could not open an in-memory database");
+
+        Self::with_connection(connection, false).expect("Could not create the database schema")
+    }
+
+    fn add(&mut self, key: String, value: Option<String>) {
+        self.changed(&key);
+        self.store.add(key, value)
+    }
+
+    fn visited(&mut self, key: &str) {
+        self.changed(key);
+        self.store.visited(key)
+    }
+
+    fn set_status(&mut self, key: &str, status: u16) {
+        self.changed(key);
+        self.store.set_status(key, status)
+    }
+
+    fn set_canonical(&mut self, key: &str, canonical: String) {
+        self.changed(key);
+        self.store.set_canonical(key, canonical)
+    }
+
+    fn set_redirects(&mut self, key: &str, redirects: Vec<Redirect>) {
+        self.changed(key);
+        self.store.set_redirects(key, redirects)
+    }
+
+    fn set_content_hash(&mut self, key: &str, content_hash: String) {
+        self.changed(key);
+        self.store.set_content_hash(key, content_hash)
+    }
+
+    fn set_body_sample(&mut self, key: &str, body_sample: String) {
+        self.changed(key);
+        self.store.set_body_sample(key, body_sample)
+    }
+
+    fn set_title(&mut self, key: &str, title: String) {
+        self.changed(key);
+        self.store.set_title(key, title)
+    }
+
+    fn set_description(&mut self, key: &str, description: String) {
+        self.changed(key);
+        self.store.set_description(key, description)
+    }
+
+    fn set_search_page(&mut self, key: &str, has_results: bool) {
+        self.changed(key);
+        self.store.set_search_page(key, has_results)
+    }
+
+    fn set_type_mismatch(&mut self, key: &str, type_mismatch: TypeMismatch) {
+        self.changed(key);
+        self.store.set_type_mismatch(key, type_mismatch)
+    }
+
+    fn set_parked(&mut self, key: &str, parked: ParkedSign) {
+        self.changed(key);
+        self.store.set_parked(key, parked)
+    }
+
+    fn set_alternates(&mut self, key: &str, alternates: Vec<Alternate>) {
+        self.changed(key);
+        self.store.set_alternates(key, alternates)
+    }
+
+    fn set_resources(&mut self, key: &str, resources: Vec<String>) {
+        self.changed(key);
+        self.store.set_resources(key, resources)
+    }
+
+    fn set_streaming(&mut self, key: &str) {
+        self.changed(key);
+        self.store.set_streaming(key)
+    }
+
+    fn set_partial_content(&mut self, key: &str, partial_content: PartialContent) {
+        self.changed(key);
+        self.store.set_partial_content(key, partial_content)
+    }
+
+    fn set_mobile(&mut self, key: &str, mobile: MobileSignals) {
+        self.changed(key);
+        self.store.set_mobile(key, mobile)
+    }
+
+    fn add_form(&mut self, key: &str, form: Form) {
+        self.changed(key);
+        self.store.add_form(key, form)
+    }
+
+    fn set_link_decision(&mut self, key: &str, url: String, decision: LinkDecision) {
+        self.changed(key);
+        self.store.set_link_decision(key, url, decision)
+    }
+
+    fn set_link_source(&mut self, key: &str, url: String, source: LinkSource) {
+        self.changed(key);
+        self.store.set_link_source(key, url, source)
+    }
+
+    fn set_locale_result(&mut self, key: &str, locale: String, result: LocaleResult) {
+        self.changed(key);
+        self.store.set_locale_result(key, locale, result)
+    }
+
+    fn has_visited(&self, key: &str) -> bool {
+        self.store.has_visited(key)
+    }
+
+    fn try_mark_visited(&mut self, key: &str) -> bool {
+        self.changed(key);
+        self.store.try_mark_visited(key)
+    }
+
+    fn try_claim(&mut self, key: &str) -> bool {
+        self.store.try_claim(key)
+    }
+
+    fn release_claim(&mut self, key: &str) {
+        self.store.release_claim(key)
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        self.store.exists(key)
+    }
+
+    fn get(&self, key: &str) -> Option<&DataStoreEntry> {
+        self.store.get(key)
+    }
+
+    fn entries(&self) -> Vec<(&String, &DataStoreEntry)> {
+        self.store.entries()
+    }
+
+    fn queued(&mut self, url: &QueuedUrl) {
+        self.queued.push(url.clone());
+    }
+
+    fn failed(&mut self, key: &str) {
+        self.failed.push(key.to_owned());
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        let transaction = connection.transaction()?;
+
+        for queued in &self.queued {
+            transaction.execute(
+                "INSERT OR IGNORE INTO frontier (url, depth) VALUES (?1, ?2)",
+                params![queued.url, queued.depth],
+            )?;
+        }
+        for url in &self.failed {
+            transaction.execute(
+                "INSERT INTO failures (url, attempts, retry_at) VALUES (?1, 1, ?2 + ?3)
+                ON CONFLICT (url) DO UPDATE SET
+                    attempts = attempts + 1,
+                    retry_at = ?2 + ?3 * (1 << attempts)",
+                params![url, now(), RETRY_BACKOFF_S],
+            )?;
+        }
+        for key in &self.dirty {
+            let Some(entry) = self.store.get(key) else {
+                continue;
+            };
+            transaction.execute(
+                "INSERT OR REPLACE INTO pages (url, entry) VALUES (?1, ?2)",
+                params![key, serde_json::to_string(entry)?],
+            )?;
+            // visited URLs are done with, a resumed crawl doesn't queue them again
+            if entry.visited {
+                transaction.execute("DELETE FROM frontier WHERE url = ?1", params![key])?;
+                transaction.execute("DELETE FROM failures WHERE url = ?1", params![key])?;
+            }
+        }
+        transaction.commit()?;
+
+        self.dirty.clear();
+        self.queued.clear();
+        self.failed.clear();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod sqlite_store_tests {
+    use rusqlite::Connection;
+
+    use super::{now, SqliteStore, MAX_ATTEMPTS, RETRY_BACKOFF_S};
+    use crate::{data_store::DataStore, link::LinkDecision, url_frontier::QueuedUrl};
+
+    #[test]
+    fn sqlite_store_resumes_pages_and_pending_urls() {
+        let path = std::env::temp_dir().join(format!(
+            "url-crawler-sqlite-store-resumes-{}.db",
+            std::process::id()
+        ));
+        let mut s = SqliteStore::open(&path, false).unwrap();
+
+        s.try_mark_visited("https://a.com");
+        s.set_status("https://a.com", 200);
+        for link in ["https://a.com/one", "https://a.com/two"] {
+            s.add("https://a.com".to_owned(), Some(link.to_owned()));
+            s.set_link_decision("https://a.com", link.to_owned(), LinkDecision::Enqueue);
+            s.queued(&QueuedUrl::new(link.to_owned(), 1));
+        }
+        s.flush().unwrap();
+        s.try_mark_visited("https://a.com/one");
+        s.flush().unwrap();
+        // not flushed, lost when the process stops
+        s.try_mark_visited("https://a.com/two");
+        drop(s);
+
+        let resumed = SqliteStore::open(&path, true).unwrap();
+
+        assert!(resumed.has_visited("https://a.com/one"));
+        assert!(!resumed.has_visited("https://a.com/two"));
+        assert_eq!(resumed.get("https://a.com").unwrap().status, Some(200));
+        assert_eq!(
+            resumed.pending().unwrap(),
+            vec![QueuedUrl::new("https://a.com/two".to_owned(), 1)]
+        );
+
+        let restarted = SqliteStore::open(&path, false).unwrap();
+
+        assert!(restarted.entries().is_empty());
+        assert!(restarted.pending().unwrap().is_empty());
+        drop(restarted);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn sqlite_store_only_writes_on_flush() {
+        let mut s =
+            SqliteStore::with_connection(Connection::open_in_memory().unwrap(), false).unwrap();
+        let count = |s: &SqliteStore| -> i64 {
+            s.connection
+                .lock()
+                .unwrap()
+                .query_row("SELECT COUNT(*) FROM pages", [], |row| row.get(0))
+                .unwrap()
+        };
+
+        s.add("https://a.com".to_owned(), None);
+        assert_eq!(count(&s), 0);
+
+        s.flush().unwrap();
+        assert_eq!(count(&s), 1);
+        assert!(s.dirty.is_empty());
+    }
+
+    #[test]
+    fn sqlite_store_keeps_failure_history_across_resumes() {
+        let mut s =
+            SqliteStore::with_connection(Connection::open_in_memory().unwrap(), false).unwrap();
+        for url in ["https://a.com/flaky", "https://a.com/down"] {
+            s.queued(&QueuedUrl::new(url.to_owned(), 1));
+        }
+        s.failed("https://a.com/flaky");
+        for _ in 0..MAX_ATTEMPTS {
+            s.failed("https://a.com/down");
+            s.flush().unwrap();
+        }
+        let now = now();
+
+        // backing off from the flaky URL, given up on the one that's down
+        assert!(s.pending_at(now).unwrap().is_empty());
+        assert_eq!(
+            s.pending_at(now + RETRY_BACKOFF_S).unwrap(),
+            vec![QueuedUrl::new("https://a.com/flaky".to_owned(), 1)]
+        );
+
+        s.failed("https://a.com/flaky");
+        s.flush().unwrap();
+
+        assert!(s.pending_at(now + RETRY_BACKOFF_S).unwrap().is_empty());
+        assert_eq!(s.pending_at(now + 3 * RETRY_BACKOFF_S).unwrap().len(), 1);
+    }
+}
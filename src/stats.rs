@@ -0,0 +1,419 @@
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use crate::link::host_with_port;
+
+/// Phases of a single worker iteration
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Phase {
+    /// Waiting for the URL frontier lock and politeness delay
+    DequeueWait,
+    Fetch,
+    Parse,
+    Store,
+    Enqueue,
+}
+
+const PHASES: [Phase; 5] = [
+    Phase::DequeueWait,
+    Phase::Fetch,
+    Phase::Parse,
+    Phase::Store,
+    Phase::Enqueue,
+];
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Phase::DequeueWait => "dequeue wait",
+            Phase::Fetch => "fetch",
+            Phase::Parse => "parse",
+            Phase::Store => "store",
+            Phase::Enqueue => "enqueue",
+        };
+        f.pad(name)
+    }
+}
+
+#[derive(Debug, Default)]
+struct PhaseTotals {
+    nanos: AtomicU64,
+    count: AtomicU64,
+}
+
+/// Time spent in each phase, summed across all workers
+#[derive(Debug, Default)]
+pub struct PhaseStats {
+    totals: [PhaseTotals; 5],
+}
+
+impl PhaseStats {
+    pub fn record(&self, phase: Phase, duration: Duration) {
+        let totals = &self.totals[phase as usize];
+        totals
+            .nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+        totals.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn total(&self, phase: Phase) -> Duration {
+        Duration::from_nanos(self.totals[phase as usize].nanos.load(Ordering::Relaxed))
+    }
+
+    pub fn count(&self, phase: Phase) -> u64 {
+        self.totals[phase as usize].count.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Display for PhaseStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let overall = PHASES
+            .iter()
+            .map(|phase| self.total(*phase))
+            .sum::<Duration>()
+            .as_secs_f64();
+
+        writeln!(
+            f,
+            "{:<14}{:>12}{:>8}{:>10}{:>14}",
+            "phase", "total (s)", "share", "count", "avg (ms)"
+        )?;
+        for phase in PHASES {
+            let total = self.total(phase).as_secs_f64();
+            let count = self.count(phase);
+            let share = if overall > 0.0 { total / overall } else { 0.0 };
+            let avg = if count > 0 {
+                total * 1000.0 / count as f64
+            } else {
+                0.0
+            };
+
+            writeln!(
+                f,
+                "{:<14}{:>12.3}{:>7.1}%{:>10}{:>14.3}",
+                phase,
+                total,
+                share * 100.0,
+                count,
+                avg
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Staggered start of the workers, ramping from one worker up to all of them over `period`
+#[derive(Debug, Default)]
+pub struct WorkerRamp {
+    workers_n: usize,
+    period: Duration,
+    started: AtomicUsize,
+}
+
+impl WorkerRamp {
+    pub fn new(workers_n: usize, period: Duration) -> Self {
+        WorkerRamp {
+            workers_n,
+            period,
+            started: AtomicUsize::new(0),
+        }
+    }
+
+    /// How long after the start of the crawl the worker starts, the first one starts straight away
+    pub fn delay(&self, worker: usize) -> Duration {
+        if self.workers_n <= 1 {
+            return Duration::ZERO;
+        }
+
+        self.period
+            .mul_f64(worker.min(self.workers_n - 1) as f64 / (self.workers_n - 1) as f64)
+    }
+
+    /// Records a started worker, returning the number of workers started so far
+    pub fn start(&self) -> usize {
+        self.started.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn started(&self) -> usize {
+        self.started.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Display for WorkerRamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "workers: {}/{} started, ramp-up over {:.1}s",
+            self.started(),
+            self.workers_n,
+            self.period.as_secs_f64()
+        )
+    }
+}
+
+/// How a single host behaved during the crawl
+#[derive(Debug, PartialEq, Clone, Default, Serialize)]
+pub struct HostMetrics {
+    /// Pages that responded, whatever their status
+    pub pages: usize,
+    /// Requests that failed or responded with a 4xx or 5xx status
+    pub errors: usize,
+    /// Size of the response bodies
+    pub bytes: usize,
+    pub avg_latency_ms: f64,
+    pub p50_latency_ms: f64,
+    pub p95_latency_ms: f64,
+    pub p99_latency_ms: f64,
+    pub error_rate: f64,
+    /// Politeness delay (in seconds) applied ahead of each request to the host
+    pub delay_s: f64,
+    #[serde(skip)]
+    requests: usize,
+    #[serde(skip)]
+    latency: Duration,
+    /// Counts of the latencies in milliseconds, rounded to 2 significant digits so that percentiles
+    /// are within 5% of the exact value whatever the number of requests
+    #[serde(skip)]
+    latencies: BTreeMap<u64, usize>,
+}
+
+/// Milliseconds of a latency rounded to 2 significant digits, i.e. 1234ms to 1200ms
+fn rounded_ms(latency: Duration) -> u64 {
+    let ms = latency.as_millis() as u64;
+    let mut scale = 1;
+    while ms / scale >= 100 {
+        scale *= 10;
+    }
+
+    (ms + scale / 2) / scale * scale
+}
+
+impl HostMetrics {
+    fn record(&mut self, response: Option<(u16, usize)>, latency: Duration, delay: Duration) {
+        self.requests += 1;
+        self.latency += latency;
+        *self.latencies.entry(rounded_ms(latency)).or_default() += 1;
+        match response {
+            Some((status, bytes)) => {
+                self.pages += 1;
+                self.bytes += bytes;
+                if status >= 400 {
+                    self.errors += 1;
+                }
+            }
+            None => self.errors += 1,
+        }
+        self.delay_s = delay.as_secs_f64();
+        self.update_averages();
+    }
+
+    /// Adds up the metrics of the same host in separate crawls
+    pub fn merge(&mut self, other: &HostMetrics) {
+        self.pages += other.pages;
+        self.errors += other.errors;
+        self.bytes += other.bytes;
+        self.requests += other.requests;
+        self.latency += other.latency;
+        for (ms, count) in &other.latencies {
+            *self.latencies.entry(*ms).or_default() += count;
+        }
+        self.delay_s = self.delay_s.max(other.delay_s);
+        self.update_averages();
+    }
+
+    fn update_averages(&mut self) {
+        if self.requests > 0 {
+            self.avg_latency_ms = self.latency.as_secs_f64() * 1000.0 / self.requests as f64;
+            self.error_rate = self.errors as f64 / self.requests as f64;
+        }
+        self.p50_latency_ms = self.percentile(0.5);
+        self.p95_latency_ms = self.percentile(0.95);
+        self.p99_latency_ms = self.percentile(0.99);
+    }
+
+    /// Latency (in milliseconds) the fraction `p` of the requests took at most, by nearest rank
+    fn percentile(&self, p: f64) -> f64 {
+        let rank = ((p * self.requests as f64).ceil() as usize).max(1);
+        let mut seen = 0;
+        for (ms, count) in &self.latencies {
+            seen += count;
+            if seen >= rank {
+                return *ms as f64;
+            }
+        }
+
+        0.0
+    }
+}
+
+impl fmt::Display for HostMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} pages, {} bytes, avg latency {:.0}ms (p50 {:.0}ms, p95 {:.0}ms, p99 {:.0}ms), {:.1}% errors, delay {:.1}s",
+            self.pages,
+            self.bytes,
+            self.avg_latency_ms,
+            self.p50_latency_ms,
+            self.p95_latency_ms,
+            self.p99_latency_ms,
+            self.error_rate * 100.0,
+            self.delay_s
+        )
+    }
+}
+
+/// Request metrics per host, so multi-domain crawls show how each origin behaved
+#[derive(Debug, Default)]
+pub struct HostStats {
+    hosts: Mutex<BTreeMap<String, HostMetrics>>,
+}
+
+impl HostStats {
+    /// Records a request to the URL's host, `response` being its status and body size if it didn't fail
+    pub fn record(
+        &self,
+        url: &str,
+        response: Option<(u16, usize)>,
+        latency: Duration,
+        delay: Duration,
+    ) {
+        let Some(host) = host_with_port(url) else {
+            return;
+        };
+
+        self.hosts
+            .lock()
+            .unwrap()
+            .entry(host)
+            .or_default()
+            .record(response, latency, delay);
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, HostMetrics> {
+        self.hosts.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use std::time::Duration;
+
+    use super::{HostStats, Phase, PhaseStats, WorkerRamp};
+
+    #[test]
+    fn host_stats_aggregates_requests_per_host() {
+        let stats = HostStats::default();
+        let delay = Duration::from_secs(2);
+
+        stats.record(
+            "https://a.com/",
+            Some((200, 100)),
+            Duration::from_millis(10),
+            delay,
+        );
+        stats.record(
+            "https://a.com/1",
+            Some((500, 20)),
+            Duration::from_millis(30),
+            delay,
+        );
+        stats.record("https://b.com:8080/", None, Duration::from_millis(5), delay);
+        stats.record("not a url", None, Duration::ZERO, delay);
+
+        let hosts = stats.snapshot();
+        let a = &hosts["a.com"];
+        assert_eq!(hosts.len(), 2);
+        assert_eq!((a.pages, a.errors, a.bytes), (2, 1, 120));
+        assert_eq!(a.avg_latency_ms, 20.0);
+        assert_eq!((a.p50_latency_ms, a.p99_latency_ms), (10.0, 30.0));
+        assert_eq!(a.error_rate, 0.5);
+        assert_eq!(a.delay_s, 2.0);
+        assert_eq!(hosts["b.com:8080"].pages, 0);
+        assert_eq!(hosts["b.com:8080"].error_rate, 1.0);
+        assert_eq!(
+            a.to_string(),
+            "2 pages, 120 bytes, avg latency 20ms (p50 10ms, p95 30ms, p99 30ms), 50.0% errors, delay 2.0s"
+        );
+    }
+
+    #[test]
+    fn host_metrics_percentiles_stay_close_on_many_requests() {
+        let stats = HostStats::default();
+        for ms in 1..=1000 {
+            stats.record(
+                "https://a.com/",
+                Some((200, 0)),
+                Duration::from_millis(ms),
+                Duration::ZERO,
+            );
+        }
+
+        let a = &stats.snapshot()["a.com"];
+        assert_eq!(
+            (a.p50_latency_ms, a.p95_latency_ms, a.p99_latency_ms),
+            (500.0, 950.0, 990.0)
+        );
+
+        let mut merged = a.clone();
+        merged.merge(a);
+        assert_eq!(merged.p95_latency_ms, 950.0);
+    }
+
+    #[test]
+    fn record_accumulates_time_and_count_per_phase() {
+        let stats = PhaseStats::default();
+
+        stats.record(Phase::Fetch, Duration::from_millis(10));
+        stats.record(Phase::Fetch, Duration::from_millis(30));
+        stats.record(Phase::Parse, Duration::from_millis(5));
+
+        assert_eq!(stats.total(Phase::Fetch), Duration::from_millis(40));
+        assert_eq!(stats.count(Phase::Fetch), 2);
+        assert_eq!(stats.total(Phase::Parse), Duration::from_millis(5));
+        assert_eq!(stats.count(Phase::Enqueue), 0);
+    }
+
+    #[test]
+    fn display_prints_share_of_each_phase() {
+        let stats = PhaseStats::default();
+
+        stats.record(Phase::Fetch, Duration::from_millis(75));
+        stats.record(Phase::DequeueWait, Duration::from_millis(25));
+
+        let breakdown = stats.to_string();
+
+        assert!(breakdown.contains("fetch"));
+        assert!(breakdown.contains("75.0%"));
+        assert!(breakdown.contains("25.0%"));
+    }
+
+    #[test]
+    fn worker_ramp_spreads_worker_starts_over_the_period() {
+        let ramp = WorkerRamp::new(4, Duration::from_secs(30));
+
+        assert_eq!(ramp.delay(0), Duration::ZERO);
+        assert_eq!(ramp.delay(1), Duration::from_secs(10));
+        assert_eq!(ramp.delay(3), Duration::from_secs(30));
+        assert_eq!(
+            WorkerRamp::new(1, Duration::from_secs(30)).delay(0),
+            Duration::ZERO
+        );
+
+        ramp.start();
+        assert_eq!(ramp.start(), 2);
+        assert_eq!(
+            ramp.to_string(),
+            "workers: 2/4 started, ramp-up over 30.0s\n"
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    data_store::DataStore, export::Tags, link::LinkDecision, sample::SampleReport,
+    snapshot::ExternalSnapshot, stats::HostMetrics, url_frontier::FrontierMetrics,
+};
+
+/// Headline numbers of a finished crawl
+#[derive(Debug, PartialEq, Clone, Default, Serialize)]
+pub struct CrawlSummary {
+    pub seed: String,
+    /// Why the crawl was aborted before it finished, if it was
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aborted: Option<String>,
+    pub pages_visited: usize,
+    /// Visited pages that responded with a 4xx or 5xx status
+    pub pages_failed: usize,
+    pub links_found: usize,
+    /// Crawled pages that look like internal search or filter pages
+    pub search_pages: usize,
+    /// Visited pages whose body is not of the kind their `Content-Type` declares, or that have none
+    pub type_mismatches: usize,
+    /// Visited pages skipped as streaming resources, endpoints that never stop sending
+    pub streaming_skipped: usize,
+    /// Number of discovered links per decision
+    pub link_decisions: BTreeMap<LinkDecision, usize>,
+    pub frontier: FrontierMetrics,
+    /// Request metrics per host
+    pub hosts: BTreeMap<String, HostMetrics>,
+    /// Diagnostic of each host that looked like it was blocking the crawler
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub blocked_hosts: BTreeMap<String, String>,
+    /// Root pages of the external sites linked to, when snapshotted
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub external_sites: Vec<ExternalSnapshot>,
+    /// What a sampled crawl discovered and crawled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample: Option<SampleReport>,
+    /// Metadata of the crawl, also attached to every exported record
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: Tags,
+}
+
+impl CrawlSummary {
+    pub fn from_store<T: DataStore>(seed: &str, store: &T) -> Self {
+        let mut summary = CrawlSummary {
+            seed: seed.to_owned(),
+            ..Default::default()
+        };
+
+        for (_, entry) in store.entries() {
+            if entry.visited {
+                summary.pages_visited += 1;
+            }
+            if entry.status.is_some_and(|status| status >= 400) {
+                summary.pages_failed += 1;
+            }
+            summary.links_found += entry.link_counts.values().sum::<usize>();
+            if entry.search_page.is_some() {
+                summary.search_pages += 1;
+            }
+            if entry.type_mismatch.is_some() {
+                summary.type_mismatches += 1;
+            }
+            if entry.streaming {
+                summary.streaming_skipped += 1;
+            }
+            for decision in entry.link_decisions.values() {
+                *summary.link_decisions.entry(*decision).or_default() += 1;
+            }
+        }
+
+        summary
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// The summary in the Prometheus text format, i.e. for the textfile collector of the node
+    /// exporter, with the response latency percentiles of each host as a summary metric
+    pub fn to_prometheus(&self) -> String {
+        let mut metrics = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: Vec<(String, f64)>| {
+            metrics.push_str(&format!("# HELP url_crawler_{} {}\n", name, help));
+            metrics.push_str(&format!("# TYPE url_crawler_{} {}\n", name, kind));
+            for (labels, value) in samples {
+                metrics.push_str(&format!("url_crawler_{}{} {}\n", name, labels, value));
+            }
+        };
+        let per_host = |value: fn(&HostMetrics) -> f64| {
+            self.hosts
+                .iter()
+                .map(|(host, metrics)| (format!("{{host=\"{}\"}}", host), value(metrics)))
+                .collect::<Vec<_>>()
+        };
+
+        for (name, help, value) in [
+            ("pages_visited", "Pages visited", self.pages_visited),
+            (
+                "pages_failed",
+                "Pages that responded with a 4xx or 5xx status",
+                self.pages_failed,
+            ),
+            (
+                "links_found",
+                "Links found on the visited pages",
+                self.links_found,
+            ),
+        ] {
+            metric(name, "gauge", help, vec![(String::new(), value as f64)]);
+        }
+        metric(
+            "host_pages",
+            "gauge",
+            "Pages that responded, per host",
+            per_host(|metrics| metrics.pages as f64),
+        );
+        metric(
+            "host_errors",
+            "gauge",
+            "Requests that failed or responded with a 4xx or 5xx status, per host",
+            per_host(|metrics| metrics.errors as f64),
+        );
+        metric(
+            "host_bytes",
+            "gauge",
+            "Size of the response bodies, per host",
+            per_host(|metrics| metrics.bytes as f64),
+        );
+        let latencies = self
+            .hosts
+            .iter()
+            .flat_map(|(host, metrics)| {
+                [
+                    ("0.5", metrics.p50_latency_ms),
+                    ("0.95", metrics.p95_latency_ms),
+                    ("0.99", metrics.p99_latency_ms),
+                ]
+                .map(|(quantile, ms)| {
+                    (
+                        format!("{{host=\"{}\",quantile=\"{}\"}}", host, quantile),
+                        ms / 1000.0,
+                    )
+                })
+            })
+            .collect();
+        metric(
+            "host_latency_seconds",
+            "summary",
+            "Response latency, per host",
+            latencies,
+        );
+
+        metrics
+    }
+}
+
+impl fmt::Display for CrawlSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} pages visited, {} failed, {} links found",
+            self.seed, self.pages_visited, self.pages_failed, self.links_found
+        )?;
+        if let Some(reason) = &self.aborted {
+            writeln!(f, "  aborted: {}", reason)?;
+        }
+        if !self.link_decisions.is_empty() {
+            let decisions = self
+                .link_decisions
+                .iter()
+                .map(|(decision, count)| format!("{} {}", decision, count))
+                .collect::<Vec<_>>();
+            writeln!(f, "  links: {}", decisions.join(", "))?;
+        }
+        if self.search_pages > 0 {
+            writeln!(f, "  search pages: {}", self.search_pages)?;
+        }
+        if self.type_mismatches > 0 {
+            writeln!(f, "  content type mismatches: {}", self.type_mismatches)?;
+        }
+        if self.streaming_skipped > 0 {
+            writeln!(
+                f,
+                "  streaming resources skipped: {}",
+                self.streaming_skipped
+            )?;
+        }
+        writeln!(f, "  frontier: {}", self.frontier)?;
+        if let Some(sample) = &self.sample {
+            // the sampled pages failing in the same proportion as the site's
+            let failing = match self.pages_visited {
+                0 => 0,
+                visited => self.pages_failed * sample.discovered / visited,
+            };
+            writeln!(
+                f,
+                "  sample: {}, an estimated {} failing pages",
+                sample, failing
+            )?;
+        }
+        for (host, metrics) in &self.hosts {
+            writeln!(f, "  host {}: {}", host, metrics)?;
+        }
+        for (host, diagnostic) in &self.blocked_hosts {
+            writeln!(f, "  blocked {}: {}", host, diagnostic)?;
+        }
+        for snapshot in &self.external_sites {
+            writeln!(f, "  external {}: {}", snapshot.host, snapshot)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Summaries of several isolated crawls, merged into a single report
+pub struct MergedSummary(pub Vec<CrawlSummary>);
+
+impl fmt::Display for MergedSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut total = CrawlSummary {
+            seed: "total".to_owned(),
+            ..Default::default()
+        };
+
+        for summary in &self.0 {
+            write!(f, "{}", summary)?;
+            total.pages_visited += summary.pages_visited;
+            total.pages_failed += summary.pages_failed;
+            total.links_found += summary.links_found;
+            total.search_pages += summary.search_pages;
+            total.type_mismatches += summary.type_mismatches;
+            total.streaming_skipped += summary.streaming_skipped;
+            for (decision, count) in &summary.link_decisions {
+                *total.link_decisions.entry(*decision).or_default() += count;
+            }
+            total.frontier.merge(&summary.frontier);
+            for (host, metrics) in &summary.hosts {
+                total.hosts.entry(host.clone()).or_default().merge(metrics);
+            }
+            total.blocked_hosts.extend(summary.blocked_hosts.clone());
+        }
+
+        write!(f, "{}", total)
+    }
+}
+
+#[cfg(test)]
+mod summary_tests {
+    use super::{CrawlSummary, MergedSummary};
+    use crate::{
+        data_store::{DataStore, Store},
+        link::LinkDecision,
+        stats::HostMetrics,
+    };
+
+    #[test]
+    fn from_store_counts_visited_failed_pages_and_links() {
+        let mut s = Store::new();
+        s.add(
+            "https://a.com".to_owned(),
+            Some("https://a.com/1".to_owned()),
+        );
+        s.add(
+            "https://a.com".to_owned(),
+            Some("https://a.com/2".to_owned()),
+        );
+        s.visited("https://a.com");
+        s.set_status("https://a.com", 200);
+        s.add("https://a.com/1".to_owned(), None);
+        s.visited("https://a.com/1");
+        s.set_status("https://a.com/1", 404);
+        s.set_search_page("https://a.com/1", false);
+
+        let actual = CrawlSummary::from_store("https://a.com", &s);
+
+        assert_eq!(
+            actual,
+            CrawlSummary {
+                seed: "https://a.com".to_owned(),
+                pages_visited: 2,
+                pages_failed: 1,
+                links_found: 2,
+                search_pages: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn to_prometheus_writes_gauges_and_latency_percentiles_per_host() {
+        let mut summary = CrawlSummary {
+            pages_visited: 2,
+            ..Default::default()
+        };
+        let mut metrics = HostMetrics::default();
+        (
+            metrics.pages,
+            metrics.p50_latency_ms,
+            metrics.p95_latency_ms,
+            metrics.p99_latency_ms,
+        ) = (2, 20.0, 250.0, 1500.0);
+        summary.hosts.insert("a.com".to_owned(), metrics);
+
+        let metrics = summary.to_prometheus();
+
+        assert!(metrics.starts_with(
+            "# HELP url_crawler_pages_visited Pages visited\n\
+             # TYPE url_crawler_pages_visited gauge\n\
+             url_crawler_pages_visited 2\n"
+        ));
+        assert!(metrics.contains("url_crawler_host_pages{host=\"a.com\"} 2\n"));
+        assert!(metrics.contains(
+            "# TYPE url_crawler_host_latency_seconds summary\n\
+             url_crawler_host_latency_seconds{host=\"a.com\",quantile=\"0.5\"} 0.02\n\
+             url_crawler_host_latency_seconds{host=\"a.com\",quantile=\"0.95\"} 0.25\n\
+             url_crawler_host_latency_seconds{host=\"a.com\",quantile=\"0.99\"} 1.5\n"
+        ));
+    }
+
+    #[test]
+    fn merged_summary_adds_a_total_line() {
+        let summary = |seed: &str, pages_visited| CrawlSummary {
+            seed: seed.to_owned(),
+            pages_visited,
+            ..Default::default()
+        };
+
+        let merged = MergedSummary(vec![summary("a", 2), summary("b", 3)]).to_string();
+
+        assert!(merged.contains("total: 5 pages visited, 0 failed, 0 links found\n"));
+    }
+
+    #[test]
+    fn to_json_includes_link_decisions_and_hosts() {
+        let mut summary = CrawlSummary {
+            seed: "https://a.com".to_owned(),
+            ..Default::default()
+        };
+        summary.link_decisions.insert(LinkDecision::SkipExternal, 2);
+        summary
+            .hosts
+            .insert("a.com".to_owned(), HostMetrics::default());
+
+        let json: serde_json::Value = serde_json::from_str(&summary.to_json().unwrap()).unwrap();
+
+        assert_eq!(json["link_decisions"]["skip-external"], 2);
+        assert_eq!(json["hosts"]["a.com"]["pages"], 0);
+        assert!(summary.to_string().contains("  host a.com: 0 pages"));
+    }
+}
@@ -1,37 +1,237 @@
-use crossbeam_queue::SegQueue;
-use std::{sync::Arc, time::Duration};
-use tokio::time::sleep;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{Notify, RwLock},
+    time::Instant,
+};
+
+use crate::{
+    delay_formula::DelayFormula,
+    handle::Control,
+    health::Health,
+    link::host_with_port,
+    politeness::Politeness,
+    queue::{FrontierPolicy, Queue},
+    scheduler::{Clock, Scheduler},
+};
+
+/// A URL waiting in the frontier, with the number of links followed from a seed to reach it
+#[derive(Debug, PartialEq, Clone)]
+pub struct QueuedUrl {
+    pub url: String,
+    pub depth: usize,
+}
+
+impl QueuedUrl {
+    pub fn new(url: String, depth: usize) -> Self {
+        QueuedUrl { url, depth }
+    }
+}
+
+/// Why a discovered URL was not added to the frontier
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DropReason {
+    /// Already visited
+    Duplicate,
+    /// Out of scope
+    Filtered,
+    /// Disallowed by robots.txt
+    Robots,
+    /// A crawl budget has been exhausted
+    Budget,
+}
+
+#[derive(Debug, PartialEq, Clone, Default, Serialize)]
+pub struct FrontierMetrics {
+    pub enqueued: usize,
+    pub peak_queue_len: usize,
+    pub max_depth: usize,
+    pub dropped_duplicate: usize,
+    pub dropped_filtered: usize,
+    pub dropped_robots: usize,
+    pub dropped_budget: usize,
+}
+
+impl FrontierMetrics {
+    pub fn dropped(&self) -> usize {
+        self.dropped_duplicate + self.dropped_filtered + self.dropped_robots + self.dropped_budget
+    }
+
+    /// Adds up the metrics of separate frontiers
+    pub fn merge(&mut self, other: &FrontierMetrics) {
+        self.enqueued += other.enqueued;
+        self.peak_queue_len = self.peak_queue_len.max(other.peak_queue_len);
+        self.max_depth = self.max_depth.max(other.max_depth);
+        self.dropped_duplicate += other.dropped_duplicate;
+        self.dropped_filtered += other.dropped_filtered;
+        self.dropped_robots += other.dropped_robots;
+        self.dropped_budget += other.dropped_budget;
+    }
+}
+
+impl fmt::Display for FrontierMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} enqueued, {} dropped (duplicate {}, filtered {}, robots {}, budget {}), peak queue length {}, max depth {}",
+            self.enqueued,
+            self.dropped(),
+            self.dropped_duplicate,
+            self.dropped_filtered,
+            self.dropped_robots,
+            self.dropped_budget,
+            self.peak_queue_len,
+            self.max_depth
+        )
+    }
+}
+
+/// Dequeued URLs still being crawled, workers wait on it while the queue is empty
+#[derive(Debug, Default)]
+pub struct Activity {
+    in_flight: AtomicUsize,
+    /// Whether a dequeue found the crawl over, no URL can be added from then on
+    done: AtomicBool,
+    changed: Notify,
+}
+
+impl Activity {
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// Wakes up the workers waiting for more URLs
+    pub fn notify(&self) {
+        self.changed.notify_waiters();
+    }
+}
+
+/// A dequeued URL being crawled, the frontier counts it as in flight until the lease is dropped
+#[derive(Debug)]
+pub struct Lease {
+    activity: Arc<Activity>,
+}
+
+impl Lease {
+    fn new(activity: Arc<Activity>) -> Self {
+        activity.in_flight.fetch_add(1, Ordering::AcqRel);
+        Lease { activity }
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        self.activity.in_flight.fetch_sub(1, Ordering::AcqRel);
+        self.activity.notify();
+    }
+}
+
+/// Result of a (non-blocking) dequeue
+#[derive(Debug)]
+pub enum Dequeued {
+    /// A URL to crawl once the politeness delay of its host has passed, at `ready_at`
+    Ready {
+        url: QueuedUrl,
+        ready_at: Instant,
+        politeness: Politeness,
+        lease: Lease,
+    },
+    /// Nothing queued, but URLs being crawled may still add more
+    Empty,
+    /// Nothing queued but URLs of paused hosts, the first pause ending at `until`
+    Held { until: Instant },
+    /// Nothing queued and nothing being crawled, the crawl is over
+    Done,
+}
 
 pub trait URLFrontierable {
-    fn enqueue(&mut self, value: String);
-    fn dequeue(&mut self) -> impl std::future::Future<Output = Option<String>> + Send;
+    fn enqueue(&mut self, value: QueuedUrl);
+    /// Takes the next URL, reserving the next request slot of its host so that
+    /// requests to the same host are spaced by its politeness delay
+    fn dequeue(&mut self) -> Dequeued;
+    fn drop_url(&mut self, reason: DropReason);
+    fn metrics(&self) -> FrontierMetrics;
+    /// URLs queued and not dequeued yet
+    fn queue_len(&self) -> usize;
+    /// Overrides the politeness delay for a host, i.e. with its robots.txt `Crawl-delay`
+    fn set_host_delay(&mut self, host: String, delay: Duration);
+    /// Politeness delay between two requests to the URL's host
+    fn delay_for(&self, url: &str) -> Duration;
+    /// Holds back requests to a host for `pause`, i.e. when it looks like it is blocking the crawler
+    fn pause_host(&mut self, host: String, pause: Duration);
+    fn activity(&self) -> Arc<Activity>;
+    /// Counts a URL being added from outside of the crawl, i.e. a seed added through a handle,
+    /// as in flight so that the crawl doesn't finish meanwhile. None once the crawl is over.
+    fn lease(&self) -> Option<Lease>;
+    /// The clock the politeness delays are measured on
+    fn clock(&self) -> Arc<dyn Clock>;
 }
 
-#[derive(Default)]
 pub struct URLFrontier {
-    queue: Arc<SegQueue<String>>,
-    delay_s: Option<u64>,
+    queue: Box<dyn Queue>,
+    scheduler: Scheduler,
+    /// URLs of paused hosts, set aside by host until their pause is over so that workers crawl
+    /// other hosts meanwhile
+    held_back: HashMap<String, Vec<QueuedUrl>>,
+    activity: Arc<Activity>,
+    metrics: FrontierMetrics,
 }
 
 #[derive(Default)]
 pub struct URLFrontierBuilder {
-    queue: Arc<SegQueue<String>>,
+    seeds: Vec<QueuedUrl>,
     delay_s: Option<u64>,
+    policy: FrontierPolicy,
+    delay_formula: Option<DelayFormula>,
+    clock: Option<Arc<dyn Clock>>,
+    random_seed: Option<u64>,
 }
 
 impl URLFrontierBuilder {
     pub fn new() -> URLFrontierBuilder {
         URLFrontierBuilder {
-            queue: Arc::new(SegQueue::new()),
+            seeds: vec![],
             delay_s: None,
+            policy: FrontierPolicy::Fifo,
+            delay_formula: None,
+            clock: None,
+            random_seed: None,
         }
     }
 
-    pub fn value(self, value: String) -> URLFrontierBuilder {
-        self.queue.push(value);
+    /// Adds a seed URL
+    pub fn value(mut self, value: String) -> URLFrontierBuilder {
+        self.seeds.push(QueuedUrl::new(value, 0));
+        self
+    }
+
+    /// Adds URLs that were still queued when a previous run of the crawl stopped
+    pub fn queued(mut self, values: Vec<QueuedUrl>) -> URLFrontierBuilder {
+        self.seeds.extend(values);
+        self
+    }
+
+    /// Order in which queued URLs are handed out, FIFO by default
+    pub fn policy(mut self, policy: FrontierPolicy) -> URLFrontierBuilder {
+        self.policy = policy;
+        self
+    }
+
+    /// Seeds the order of the random policy, so that it hands out URLs in the same order every run
+    pub fn random_seed(mut self, seed: u64) -> URLFrontierBuilder {
+        self.random_seed = Some(seed);
         self
     }
 
+    /// Politeness delay between two requests to the same host
     pub fn delay_s(mut self, delay_s: u64) -> URLFrontierBuilder {
         if delay_s > 0 {
             self.delay_s = Some(delay_s)
@@ -39,41 +239,244 @@ impl URLFrontierBuilder {
         self
     }
 
+    /// Scales the politeness delay of each request with its depth or the requests made to its host
+    pub fn delay_formula(mut self, delay_formula: DelayFormula) -> URLFrontierBuilder {
+        self.delay_formula = Some(delay_formula);
+        self
+    }
+
+    /// Measures the politeness delays on another clock than the system's, i.e. a manual one in tests
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> URLFrontierBuilder {
+        self.clock = Some(clock);
+        self
+    }
+
     pub fn build(self) -> URLFrontier {
+        let metrics = FrontierMetrics {
+            enqueued: self.seeds.len(),
+            peak_queue_len: self.seeds.len(),
+            ..Default::default()
+        };
+        let mut queue = match self.random_seed {
+            Some(seed) => self.policy.seeded_queue(seed),
+            None => self.policy.queue(),
+        };
+        for seed in self.seeds {
+            queue.push(seed);
+        }
+
+        let mut scheduler = Scheduler::new(Duration::from_secs(self.delay_s.unwrap_or_default()));
+        if let Some(delay_formula) = self.delay_formula {
+            scheduler = scheduler.delay_formula(delay_formula);
+        }
+        if let Some(clock) = self.clock {
+            scheduler = scheduler.clock(clock);
+        }
+
         URLFrontier {
-            queue: self.queue,
-            delay_s: self.delay_s,
+            queue,
+            scheduler,
+            held_back: HashMap::new(),
+            activity: Arc::new(Activity::default()),
+            metrics,
         }
     }
 }
 
 impl URLFrontierable for URLFrontier {
-    async fn dequeue(&mut self) -> Option<String> {
-        if let Some(delay_s) = self.delay_s {
-            sleep(Duration::from_secs(delay_s)).await;
+    fn dequeue(&mut self) -> Dequeued {
+        let released = self
+            .held_back
+            .keys()
+            .filter(|host| self.scheduler.paused_until(host).is_none())
+            .cloned()
+            .collect::<Vec<_>>();
+        for host in released {
+            for url in self.held_back.remove(&host).unwrap_or_default() {
+                self.queue.push(url);
+            }
         }
-        self.queue.pop()
+
+        let url = loop {
+            let Some(url) = self.queue.pop() else {
+                let until = self
+                    .held_back
+                    .keys()
+                    .filter_map(|host| self.scheduler.paused_until(host))
+                    .min();
+                return match (until, self.activity.in_flight()) {
+                    (Some(until), _) => Dequeued::Held { until },
+                    (None, 0) => {
+                        self.activity.done.store(true, Ordering::Release);
+                        Dequeued::Done
+                    }
+                    (None, _) => Dequeued::Empty,
+                };
+            };
+            let host = host_with_port(&url.url).unwrap_or_default();
+            match self.scheduler.paused_until(&host) {
+                Some(_) => self.held_back.entry(host).or_default().push(url),
+                None => break url,
+            }
+        };
+
+        let (ready_at, politeness) = self.scheduler.schedule(&url);
+
+        Dequeued::Ready {
+            url,
+            ready_at,
+            politeness,
+            lease: Lease::new(self.activity.clone()),
+        }
+    }
+
+    fn enqueue(&mut self, value: QueuedUrl) {
+        self.metrics.enqueued += 1;
+        self.metrics.max_depth = self.metrics.max_depth.max(value.depth);
+        self.queue.push(value);
+        self.metrics.peak_queue_len = self.metrics.peak_queue_len.max(self.queue.len());
+        self.activity.notify();
+    }
+
+    fn drop_url(&mut self, reason: DropReason) {
+        let dropped = match reason {
+            DropReason::Duplicate => &mut self.metrics.dropped_duplicate,
+            DropReason::Filtered => &mut self.metrics.dropped_filtered,
+            DropReason::Robots => &mut self.metrics.dropped_robots,
+            DropReason::Budget => &mut self.metrics.dropped_budget,
+        };
+        *dropped += 1;
+    }
+
+    fn metrics(&self) -> FrontierMetrics {
+        self.metrics.clone()
+    }
+
+    fn queue_len(&self) -> usize {
+        self.queue.len() + self.held_back.values().map(Vec::len).sum::<usize>()
+    }
+
+    fn set_host_delay(&mut self, host: String, delay: Duration) {
+        self.scheduler.set_host_delay(host, delay);
+    }
+
+    fn delay_for(&self, url: &str) -> Duration {
+        self.scheduler.delay_for(url)
+    }
+
+    fn pause_host(&mut self, host: String, pause: Duration) {
+        self.scheduler.pause_host(host, pause);
+    }
+
+    fn activity(&self) -> Arc<Activity> {
+        self.activity.clone()
+    }
+
+    fn lease(&self) -> Option<Lease> {
+        // the frontier is locked, so no dequeue finds the crawl over before the lease is counted
+        (!self.activity.done.load(Ordering::Acquire)).then(|| Lease::new(self.activity.clone()))
     }
 
-    fn enqueue(&mut self, value: String) {
-        self.queue.push(value)
+    fn clock(&self) -> Arc<dyn Clock> {
+        self.scheduler.clock_handle()
+    }
+}
+
+/// Waits for the next URL to crawl, only locking the frontier to dequeue, and for the politeness
+/// delay of its host, returning the URL with the politeness decision taken. Returns `None` once
+/// nothing is queued and no other worker is crawling, or the crawl is aborted; keep the lease until
+/// the links found on the URL have been enqueued. A URL ready while the crawl is paused waits for
+/// the resume. Waiting for paused hosts or the resume doesn't count as a stall of `health`.
+pub async fn next_url<T: URLFrontierable>(
+    frontier: &RwLock<T>,
+    control: &Control,
+    health: Option<&Health>,
+) -> Option<(QueuedUrl, Politeness, Lease)> {
+    let (activity, clock) = {
+        let frontier = frontier.read().await;
+        (frontier.activity(), frontier.clock())
+    };
+
+    loop {
+        // created before looking at the queue, so that no enqueue or completion is missed
+        let changed = activity.changed.notified();
+        if control.is_aborted() {
+            return None;
+        }
+        let dequeued = frontier.write().await.dequeue();
+
+        match dequeued {
+            Dequeued::Ready {
+                url,
+                ready_at,
+                politeness,
+                lease,
+            } => {
+                tokio::select! {
+                    _ = clock.sleep_until(ready_at) => {}
+                    _ = control.aborted() => return None,
+                }
+                // a URL dequeued before a pause is requested once the crawl is resumed
+                if control.is_paused() {
+                    let _waiting = health.map(Health::waiting);
+                    control.resumed().await;
+                }
+                if control.is_aborted() {
+                    return None;
+                }
+                return Some((url, politeness, lease));
+            }
+            Dequeued::Empty => {
+                tokio::select! {
+                    _ = changed => {}
+                    _ = control.aborted() => {}
+                }
+            }
+            Dequeued::Held { until } => {
+                // waiting out a block pause, the crawl isn't stalled
+                let _waiting = health.map(Health::waiting);
+                tokio::select! {
+                    _ = changed => {}
+                    _ = clock.sleep_until(until) => {}
+                    _ = control.aborted() => {}
+                }
+            }
+            Dequeued::Done => return None,
+        }
     }
 }
 
 #[cfg(test)]
 mod url_frontier_tests {
+    use std::{sync::Arc, time::Duration};
+    use tokio::{sync::RwLock, time::Instant};
+
     use super::URLFrontierBuilder;
     use super::URLFrontierable;
+    use super::{next_url, Dequeued, DropReason, QueuedUrl};
+    use crate::{
+        handle::Control,
+        politeness::{Politeness, WaitReason},
+        queue::FrontierPolicy,
+        scheduler::{Clock, ManualClock},
+    };
+
+    fn ready(dequeued: Dequeued) -> (String, Instant) {
+        match dequeued {
+            Dequeued::Ready { url, ready_at, .. } => (url.url, ready_at),
+            dequeued => panic!("expected a URL, got {:?}", dequeued),
+        }
+    }
 
     #[test]
     fn url_frontier_builder_builds_url_frontier() {
-        let url_frontier = URLFrontierBuilder::new()
+        let mut url_frontier = URLFrontierBuilder::new()
             .delay_s(1)
             .value("one".to_string())
             .build();
 
-        assert!(url_frontier.delay_s == Some(1));
-        assert!(url_frontier.queue.pop() == Some("one".to_owned()));
+        assert_eq!(url_frontier.delay_for("one"), Duration::from_secs(1));
+        assert!(url_frontier.queue.pop() == Some(QueuedUrl::new("one".to_owned(), 0)));
     }
 
     #[tokio::test]
@@ -83,28 +486,226 @@ mod url_frontier_tests {
             .value("one".to_string())
             .build();
 
-        let val = url_frontier.dequeue().await;
+        let (url, _) = ready(url_frontier.dequeue());
 
-        assert_eq!(val, Some("one".to_owned()));
+        assert_eq!(url, "one");
     }
 
     #[tokio::test]
-    async fn url_frontier_dequeues_none_if_there_are_no_values_in_the_queue() {
+    async fn url_frontier_is_done_if_there_are_no_values_in_the_queue() {
         let mut url_frontier = URLFrontierBuilder::new().delay_s(0).build();
 
-        let val = url_frontier.dequeue().await;
-
-        assert_eq!(val, None);
+        assert!(matches!(url_frontier.dequeue(), Dequeued::Done));
     }
 
     #[tokio::test]
     async fn url_frontier_enqueues_value() {
         let mut url_frontier = URLFrontierBuilder::new().delay_s(0).build();
 
-        url_frontier.enqueue("two".to_owned());
-        let val = url_frontier.dequeue().await;
+        url_frontier.enqueue(QueuedUrl::new("two".to_owned(), 1));
+        let dequeued = url_frontier.dequeue();
+
+        assert!(matches!(dequeued, Dequeued::Ready { ref url, .. } if url.depth == 1));
+        // the dequeued URL is still being crawled
+        assert!(matches!(url_frontier.dequeue(), Dequeued::Empty));
+        drop(dequeued);
+        assert!(matches!(url_frontier.dequeue(), Dequeued::Done));
+    }
+
+    #[tokio::test]
+    async fn url_frontier_spaces_requests_per_host() {
+        let clock = Arc::new(ManualClock::default());
+        let mut url_frontier = URLFrontierBuilder::new()
+            .clock(clock.clone())
+            .delay_s(2)
+            .value("https://a.com/1".to_owned())
+            .value("https://a.com/2".to_owned())
+            .value("https://b.com/1".to_owned())
+            .value("https://a.com/3".to_owned())
+            .build();
+        let now = clock.now();
+
+        assert_eq!(ready(url_frontier.dequeue()).1, now);
+        assert_eq!(
+            ready(url_frontier.dequeue()).1,
+            now + Duration::from_secs(2)
+        );
+        // another host doesn't wait for the first one
+        assert_eq!(ready(url_frontier.dequeue()).1, now);
+
+        url_frontier.set_host_delay("a.com".to_owned(), Duration::from_millis(500));
+        let Dequeued::Ready { politeness, .. } = url_frontier.dequeue() else {
+            panic!("expected a URL");
+        };
+        assert_eq!(
+            politeness,
+            Politeness {
+                host: "a.com".to_owned(),
+                waited: Duration::from_secs(4),
+                delay: Duration::from_millis(500),
+                reason: WaitReason::CrawlDelay,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn url_frontier_scales_delays_with_the_delay_formula() {
+        let clock = Arc::new(ManualClock::default());
+        let mut url_frontier = URLFrontierBuilder::new()
+            .clock(clock.clone())
+            .delay_s(2)
+            .delay_formula("delay * (1 + depth) + pages".parse().unwrap())
+            .value("https://a.com/".to_owned())
+            .build();
+        url_frontier.enqueue(QueuedUrl::new("https://a.com/1".to_owned(), 1));
+        url_frontier.enqueue(QueuedUrl::new("https://a.com/2".to_owned(), 1));
+        let now = clock.now();
+
+        assert_eq!(ready(url_frontier.dequeue()).1, now);
+        assert_eq!(
+            ready(url_frontier.dequeue()).1,
+            now + Duration::from_secs(2)
+        );
+        assert_eq!(
+            ready(url_frontier.dequeue()).1,
+            now + Duration::from_secs(2 + 5)
+        );
+    }
+
+    #[tokio::test]
+    async fn url_frontier_holds_back_paused_hosts() {
+        let clock = Arc::new(ManualClock::default());
+        let mut url_frontier = URLFrontierBuilder::new()
+            .clock(clock.clone())
+            .delay_s(1)
+            .value("https://a.com/1".to_owned())
+            .value("https://b.com/1".to_owned())
+            .build();
+        let now = clock.now();
+
+        url_frontier.pause_host("a.com".to_owned(), Duration::from_secs(60));
+        // the other host is crawled meanwhile
+        assert_eq!(
+            ready(url_frontier.dequeue()),
+            ("https://b.com/1".to_owned(), now)
+        );
+        assert_eq!(url_frontier.queue_len(), 1);
+        assert!(matches!(
+            url_frontier.dequeue(),
+            Dequeued::Held { until } if until == now + Duration::from_secs(60)
+        ));
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(
+            ready(url_frontier.dequeue()),
+            ("https://a.com/1".to_owned(), clock.now())
+        );
+        assert!(matches!(url_frontier.dequeue(), Dequeued::Done));
+    }
+
+    #[tokio::test]
+    async fn url_frontier_dequeues_in_policy_order() {
+        let mut url_frontier = URLFrontierBuilder::new()
+            .policy(FrontierPolicy::RoundRobin)
+            .value("https://a.com/".to_owned())
+            .build();
+
+        url_frontier.enqueue(QueuedUrl::new("https://a.com/1".to_owned(), 1));
+        url_frontier.enqueue(QueuedUrl::new("https://b.com/".to_owned(), 1));
+
+        assert_eq!(ready(url_frontier.dequeue()).0, "https://a.com/");
+        assert_eq!(ready(url_frontier.dequeue()).0, "https://b.com/");
+        assert_eq!(ready(url_frontier.dequeue()).0, "https://a.com/1");
+    }
+
+    #[tokio::test]
+    async fn next_url_waits_for_urls_enqueued_by_a_busy_worker() {
+        let url_frontier = RwLock::new(
+            URLFrontierBuilder::new()
+                .value("https://a.com/".to_owned())
+                .build(),
+        );
+        let control = Control::default();
+
+        let (seed, _, lease) = next_url(&url_frontier, &control, None).await.unwrap();
+        assert_eq!(seed.url, "https://a.com/");
+
+        let (next, _) = tokio::join!(next_url(&url_frontier, &control, None), async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            url_frontier
+                .write()
+                .await
+                .enqueue(QueuedUrl::new("https://a.com/1".to_owned(), 1));
+            drop(lease);
+        });
+        let (next, _, lease) = next.unwrap();
+        assert_eq!(next.url, "https://a.com/1");
+
+        drop(lease);
+        assert!(next_url(&url_frontier, &control, None).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn next_url_sleeps_on_the_frontier_clock() {
+        let clock = Arc::new(ManualClock::default());
+        let url_frontier = RwLock::new(
+            URLFrontierBuilder::new()
+                .delay_s(3600)
+                .clock(clock.clone())
+                .value("https://a.com/1".to_owned())
+                .value("https://a.com/2".to_owned())
+                .build(),
+        );
+        let start = clock.now();
+        let control = Control::default();
+
+        let (_, first, _lease) = next_url(&url_frontier, &control, None).await.unwrap();
+        let (_, second, _lease) = next_url(&url_frontier, &control, None).await.unwrap();
+
+        assert_eq!(first.waited, Duration::ZERO);
+        // an hour of politeness passes without the test waiting for it
+        assert_eq!(second.waited, Duration::from_secs(3600));
+        assert_eq!(clock.now(), start + Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn url_frontier_applies_host_delay_overrides() {
+        let mut url_frontier = URLFrontierBuilder::new().delay_s(2).build();
+
+        url_frontier.set_host_delay("a.com".to_owned(), Duration::from_millis(500));
+
+        assert_eq!(
+            url_frontier.delay_for("https://a.com/page"),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            url_frontier.delay_for("https://a.com:8080/page"),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            url_frontier.delay_for("https://b.com/"),
+            Duration::from_secs(2)
+        );
+    }
+
+    #[tokio::test]
+    async fn url_frontier_tracks_enqueue_and_drop_metrics() {
+        let mut url_frontier = URLFrontierBuilder::new().value("seed".to_owned()).build();
+
+        url_frontier.enqueue(QueuedUrl::new("one".to_owned(), 1));
+        url_frontier.enqueue(QueuedUrl::new("two".to_owned(), 2));
+        let _seed = url_frontier.dequeue();
+        url_frontier.enqueue(QueuedUrl::new("three".to_owned(), 1));
+        url_frontier.drop_url(DropReason::Duplicate);
+        url_frontier.drop_url(DropReason::Robots);
+
+        let metrics = url_frontier.metrics();
 
-        assert_eq!(val, Some("two".to_owned()));
-        assert_eq!(url_frontier.dequeue().await, None);
+        assert_eq!(metrics.enqueued, 4);
+        assert_eq!(metrics.peak_queue_len, 3);
+        assert_eq!(metrics.max_depth, 2);
+        assert_eq!(metrics.dropped_duplicate, 1);
+        assert_eq!(metrics.dropped_robots, 1);
+        assert_eq!(metrics.dropped(), 2);
     }
 }
@@ -0,0 +1,422 @@
+use tokio::sync::broadcast;
+
+use crate::{
+    alternates::Alternate,
+    data_store::{DataStore, DataStoreEntry, Error, LocaleResult},
+    fetch::{PartialContent, Redirect},
+    link::{LinkDecision, LinkSource},
+    mime::TypeMismatch,
+    mobile::MobileSignals,
+    parked::ParkedSign,
+    parser::Form,
+    url_frontier::QueuedUrl,
+};
+
+/// Changes kept for a subscriber that is behind, older ones are dropped
+const CHANGES_CAPACITY: usize = 4096;
+
+/// A mutation of a watched data store
+#[derive(Debug, PartialEq, Clone)]
+pub enum StoreChange {
+    /// A URL was stored for the first time
+    PageAdded { url: String },
+    /// A link was recorded on a page
+    LinkAdded { page: String, url: String },
+    /// A page was marked as visited
+    Visited { url: String },
+    /// A page's response status was recorded
+    Status { url: String, status: u16 },
+    /// A fetch of the URL failed
+    Failed { url: String },
+    /// Anything else about the page was recorded, i.e. its canonical URL or title
+    Updated { url: String },
+}
+
+/// A data store sending each of its mutations to a subscriber, so that a live view of a crawl can
+/// follow it without polling the store's lock. Reads and persistence go to the wrapped store.
+pub struct WatchedStore<T> {
+    store: T,
+    changes: Option<broadcast::Sender<StoreChange>>,
+}
+
+impl<T: DataStore> WatchedStore<T> {
+    pub fn watch(store: T) -> Self {
+        WatchedStore {
+            store,
+            changes: None,
+        }
+    }
+
+    /// Sends the changes from now on to the returned receiver, as well as to the previous
+    /// subscribers. The store never waits for a subscriber: one more than `CHANGES_CAPACITY`
+    /// changes behind misses the oldest ones, its next `recv` returning `RecvError::Lagged`. The
+    /// receiver is closed once the store is dropped.
+    pub fn subscribe(&mut self) -> broadcast::Receiver<StoreChange> {
+        match &self.changes {
+            Some(changes) => changes.subscribe(),
+            None => {
+                let (sender, receiver) = broadcast::channel(CHANGES_CAPACITY);
+                self.changes = Some(sender);
+                receiver
+            }
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.store
+    }
+
+    fn send(&self, change: StoreChange) {
+        if let Some(changes) = &self.changes {
+            // the subscribers may have stopped listening, the store is updated regardless
+            let _ = changes.send(change);
+        }
+    }
+
+    /// Sends an update of a page, stores only record what they know of the pages they hold
+    fn updated(&self, key: &str) {
+        if self.store.exists(key) {
+            self.send(StoreChange::Updated {
+                url: key.to_owned(),
+            });
+        }
+    }
+}
+
+impl<T: DataStore> DataStore for WatchedStore<T> {
+    fn new() -> Self {
+        WatchedStore::watch(T::new())
+    }
+
+    fn add(&mut self, key: String, value: Option<String>) {
+        if !self.store.exists(&key) {
+            self.send(StoreChange::PageAdded { url: key.clone() });
+        }
+        if let Some(url) = &value {
+            self.send(StoreChange::LinkAdded {
+                page: key.clone(),
+                url: url.clone(),
+            });
+        }
+        self.store.add(key, value)
+    }
+
+    fn visited(&mut self, key: &str) {
+        if self.store.exists(key) && !self.store.has_visited(key) {
+            self.send(StoreChange::Visited {
+                url: key.to_owned(),
+            });
+        }
+        self.store.visited(key)
+    }
+
+    fn set_status(&mut self, key: &str, status: u16) {
+        let changed = self
+            .store
+            .get(key)
+            .is_some_and(|entry| entry.status != Some(status));
+        if changed {
+            self.send(StoreChange::Status {
+                url: key.to_owned(),
+                status,
+            });
+        }
+        self.store.set_status(key, status)
+    }
+
+    fn set_canonical(&mut self, key: &str, canonical: String) {
+        self.updated(key);
+        self.store.set_canonical(key, canonical)
+    }
+
+    fn set_redirects(&mut self, key: &str, redirects: Vec<Redirect>) {
+        self.updated(key);
+        self.store.set_redirects(key, redirects)
+    }
+
+    fn set_content_hash(&mut self, key: &str, content_hash: String) {
+        self.updated(key);
+        self.store.set_content_hash(key, content_hash)
+    }
+
+    fn set_body_sample(&mut self, key: &str, body_sample: String) {
+        self.updated(key);
+        self.store.set_body_sample(key, body_sample)
+    }
+
+    fn set_title(&mut self, key: &str, title: String) {
+        self.updated(key);
+        self.store.set_title(key, title)
+    }
+
+    fn set_description(&mut self, key: &str, description: String) {
+        self.updated(key);
+        self.store.set_description(key, description)
+    }
+
+    fn set_search_page(&mut self, key: &str, has_results: bool) {
+        self.updated(key);
+        self.store.set_search_page(key, has_results)
+    }
+
+    fn set_type_mismatch(&mut self, key: &str, type_mismatch: TypeMismatch) {
+        self.updated(key);
+        self.store.set_type_mismatch(key, type_mismatch)
+    }
+
+    fn set_parked(&mut self, key: &str, parked: ParkedSign) {
+        self.updated(key);
+        self.store.set_parked(key, parked)
+    }
+
+    fn set_alternates(&mut self, key: &str, alternates: Vec<Alternate>) {
+        self.updated(key);
+        self.store.set_alternates(key, alternates)
+    }
+
+    fn set_resources(&mut self, key: &str, resources: Vec<String>) {
+        self.updated(key);
+        self.store.set_resources(key, resources)
+    }
+
+    fn set_streaming(&mut self, key: &str) {
+        self.updated(key);
+        self.store.set_streaming(key)
+    }
+
+    fn set_partial_content(&mut self, key: &str, partial_content: PartialContent) {
+        self.updated(key);
+        self.store.set_partial_content(key, partial_content)
+    }
+
+    fn set_mobile(&mut self, key: &str, mobile: MobileSignals) {
+        self.updated(key);
+        self.store.set_mobile(key, mobile)
+    }
+
+    fn add_form(&mut self, key: &str, form: Form) {
+        self.updated(key);
+        self.store.add_form(key, form)
+    }
+
+    fn set_link_decision(&mut self, key: &str, url: String, decision: LinkDecision) {
+        self.updated(key);
+        self.store.set_link_decision(key, url, decision)
+    }
+
+    fn set_link_source(&mut self, key: &str, url: String, source: LinkSource) {
+        self.updated(key);
+        self.store.set_link_source(key, url, source)
+    }
+
+    fn set_locale_result(&mut self, key: &str, locale: String, result: LocaleResult) {
+        self.updated(key);
+        self.store.set_locale_result(key, locale, result)
+    }
+
+    fn has_visited(&self, key: &str) -> bool {
+        self.store.has_visited(key)
+    }
+
+    fn try_mark_visited(&mut self, key: &str) -> bool {
+        let added = !self.store.exists(key);
+        let marked = self.store.try_mark_visited(key);
+        if added {
+            self.send(StoreChange::PageAdded {
+                url: key.to_owned(),
+            });
+        }
+        if marked {
+            self.send(StoreChange::Visited {
+                url: key.to_owned(),
+            });
+        }
+
+        marked
+    }
+
+    fn try_claim(&mut self, key: &str) -> bool {
+        self.store.try_claim(key)
+    }
+
+    fn release_claim(&mut self, key: &str) {
+        self.store.release_claim(key)
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        self.store.exists(key)
+    }
+
+    fn get(&self, key: &str) -> Option<&DataStoreEntry> {
+        self.store.get(key)
+    }
+
+    fn entries(&self) -> Vec<(&String, &DataStoreEntry)> {
+        self.store.entries()
+    }
+
+    fn queued(&mut self, url: &QueuedUrl) {
+        self.store.queued(url)
+    }
+
+    fn failed(&mut self, key: &str) {
+        self.send(StoreChange::Failed {
+            url: key.to_owned(),
+        });
+        self.store.failed(key)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.store.flush()
+    }
+}
+
+#[cfg(test)]
+mod watched_store_tests {
+    use wiremock::{matchers::path, Mock, MockServer, ResponseTemplate};
+
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    use super::{StoreChange, WatchedStore, CHANGES_CAPACITY};
+    use crate::{
+        crawler::CrawlerBuilder,
+        data_store::{DataStore, Store},
+    };
+
+    #[test]
+    fn watched_store_sends_its_changes() {
+        let mut s = WatchedStore::watch(Store::new());
+        let mut changes = s.subscribe();
+
+        s.add(
+            "https://a.com/".to_owned(),
+            Some("https://a.com/1".to_owned()),
+        );
+        s.try_mark_visited("https://a.com/");
+        s.try_mark_visited("https://a.com/");
+        s.set_status("https://a.com/", 200);
+        s.set_title("https://a.com/", "A".to_owned());
+        s.failed("https://a.com/1");
+
+        let url = |url: &str| url.to_owned();
+        let mut received = vec![];
+        while let Ok(change) = changes.try_recv() {
+            received.push(change);
+        }
+        assert_eq!(
+            received,
+            vec![
+                StoreChange::PageAdded {
+                    url: url("https://a.com/")
+                },
+                StoreChange::LinkAdded {
+                    page: url("https://a.com/"),
+                    url: url("https://a.com/1")
+                },
+                StoreChange::Visited {
+                    url: url("https://a.com/")
+                },
+                StoreChange::Status {
+                    url: url("https://a.com/"),
+                    status: 200
+                },
+                StoreChange::Updated {
+                    url: url("https://a.com/")
+                },
+                StoreChange::Failed {
+                    url: url("https://a.com/1")
+                },
+            ]
+        );
+        assert_eq!(
+            s.into_inner().get("https://a.com/").unwrap().status,
+            Some(200)
+        );
+    }
+
+    #[test]
+    fn watched_store_only_sends_what_changed() {
+        let mut s = WatchedStore::watch(Store::new());
+        let mut changes = s.subscribe();
+
+        s.visited("https://a.com/");
+        s.set_status("https://a.com/", 200);
+        s.set_title("https://a.com/", "A".to_owned());
+        s.add("https://a.com/".to_owned(), None);
+        s.visited("https://a.com/");
+        s.visited("https://a.com/");
+        s.set_status("https://a.com/", 200);
+        s.set_status("https://a.com/", 200);
+
+        let mut received = vec![];
+        while let Ok(change) = changes.try_recv() {
+            received.push(change);
+        }
+        let url = "https://a.com/".to_owned();
+        assert_eq!(
+            received,
+            vec![
+                StoreChange::PageAdded { url: url.clone() },
+                StoreChange::Visited { url: url.clone() },
+                StoreChange::Status { url, status: 200 },
+            ]
+        );
+    }
+
+    #[test]
+    fn watched_store_drops_the_oldest_changes_of_a_lagging_subscriber() {
+        let mut s = WatchedStore::watch(Store::new());
+        let mut changes = s.subscribe();
+
+        for page in 0..CHANGES_CAPACITY + 1 {
+            s.add(format!("https://a.com/{}", page), None);
+        }
+
+        assert!(matches!(changes.try_recv(), Err(TryRecvError::Lagged(1))));
+        assert_eq!(
+            changes.try_recv(),
+            Ok(StoreChange::PageAdded {
+                url: "https://a.com/1".to_owned()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn watched_store_follows_a_crawl() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw("<a href=\"/1\">1</a>", "text/html"),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/1"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+
+        let mut store = WatchedStore::watch(Store::new());
+        let mut changes = store.subscribe();
+        let mut crawler = CrawlerBuilder::new()
+            .seed(&mock_server.uri())
+            .delay_s(0)
+            .data_store(store)
+            .build();
+        crawler.run().await;
+
+        let mut statuses = vec![];
+        while let Ok(change) = changes.try_recv() {
+            if let StoreChange::Status { url, status } = change {
+                statuses.push((url, status));
+            }
+        }
+        statuses.sort();
+        assert_eq!(
+            statuses,
+            vec![
+                (mock_server.uri(), 200),
+                (format!("{}/1", mock_server.uri()), 404)
+            ]
+        );
+    }
+}
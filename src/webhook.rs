@@ -0,0 +1,107 @@
+use reqwest::{header::CONTENT_TYPE, Client};
+use serde::Serialize;
+use std::{io, path::Path, time::Duration};
+
+use crate::summary::CrawlSummary;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How a crawl that didn't run to completion ended
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum CrawlOutcome {
+    /// Stopped by the abort threshold, with partial results
+    Aborted,
+    /// Could not run, i.e. its store could not be opened
+    Failed,
+}
+
+/// What is posted to the webhook of a crawl that didn't run to completion, so that unattended
+/// crawls never fail silently
+#[derive(Serialize, Debug, PartialEq)]
+pub struct WebhookReport<'a> {
+    pub seed: &'a str,
+    pub outcome: CrawlOutcome,
+    pub reason: &'a str,
+    /// Summary of the partial results, none if the crawl didn't run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<&'a CrawlSummary>,
+    /// Where the partial export was written, with `--output-file`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub export: Option<&'a Path>,
+}
+
+impl WebhookReport<'_> {
+    /// Posts the report as JSON to the webhook
+    pub async fn post(&self, url: &str) -> Result<(), io::Error> {
+        let client = Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_string(self)?)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(io::Error::other)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod webhook_tests {
+    use std::path::Path;
+    use wiremock::{
+        matchers::{body_json, header, method},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::{CrawlOutcome, WebhookReport};
+    use crate::summary::CrawlSummary;
+
+    #[tokio::test]
+    async fn webhook_report_is_posted_as_json() {
+        let mock_server = MockServer::start().await;
+        let summary = CrawlSummary {
+            seed: "https://a.com/".to_owned(),
+            pages_visited: 20,
+            ..Default::default()
+        };
+        let report = WebhookReport {
+            seed: "https://a.com/",
+            outcome: CrawlOutcome::Aborted,
+            reason: "20 of 20 requests failed, over the 50% abort threshold",
+            summary: Some(&summary),
+            export: Some(Path::new("/data/crawl.json")),
+        };
+        Mock::given(method("POST"))
+            .and(header("Content-Type", "application/json"))
+            .and(body_json(serde_json::to_value(&report).unwrap()))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        report.post(&mock_server.uri()).await.unwrap();
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["outcome"], "aborted");
+        assert_eq!(json["summary"]["pages_visited"], 20);
+        assert_eq!(json["export"], "/data/crawl.json");
+
+        let failed = WebhookReport {
+            outcome: CrawlOutcome::Failed,
+            summary: None,
+            export: None,
+            ..report
+        };
+        assert!(failed
+            .post(&format!("{}/gone", mock_server.uri()))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+fn parse_time(time: &str) -> Result<u32, String> {
+    let (hours, minutes) = time
+        .trim()
+        .split_once(':')
+        .ok_or_else(|| format!("`{}` is not a HH:MM time", time))?;
+    let hours = hours.parse::<u32>().ok().filter(|hours| *hours <= 24);
+    let minutes = minutes.parse::<u32>().ok().filter(|minutes| *minutes < 60);
+
+    match (hours, minutes) {
+        (Some(hours), Some(minutes)) if hours * 60 + minutes <= MINUTES_PER_DAY => {
+            Ok(hours * 60 + minutes)
+        }
+        _ => Err(format!("`{}` is not a HH:MM time", time)),
+    }
+}
+
+/// Time of day during which crawling is allowed, i.e. `01:00-05:00`, can span midnight (`22:00-02:00`)
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CrawlWindow {
+    /// Minutes since midnight
+    start: u32,
+    end: u32,
+}
+
+impl CrawlWindow {
+    /// Whether the minute of the day falls within the window
+    fn contains(&self, minute: u32) -> bool {
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+impl FromStr for CrawlWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| format!("`{}` is not a HH:MM-HH:MM window", s))?;
+        let window = CrawlWindow {
+            start: parse_time(start)? % MINUTES_PER_DAY,
+            end: parse_time(end)? % MINUTES_PER_DAY,
+        };
+
+        match window.start == window.end {
+            true => Err(format!("`{}` is an empty window", s)),
+            false => Ok(window),
+        }
+    }
+}
+
+/// Offset of the crawled sites' local time from UTC, i.e. `+02:00`
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct UtcOffset {
+    minutes: i32,
+}
+
+impl FromStr for UtcOffset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (sign, offset) = match (s.strip_prefix('+'), s.strip_prefix('-')) {
+            (Some(offset), _) => (1, offset),
+            (_, Some(offset)) => (-1, offset),
+            _ => return Err(format!("`{}` is not a +HH:MM or -HH:MM offset", s)),
+        };
+        let minutes = parse_time(offset)
+            .ok()
+            .filter(|minutes| *minutes <= 14 * 60)
+            .ok_or_else(|| format!("`{}` is not a +HH:MM or -HH:MM offset", s))?;
+
+        Ok(UtcOffset {
+            minutes: sign * minutes as i32,
+        })
+    }
+}
+
+impl fmt::Display for UtcOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.minutes < 0 { '-' } else { '+' };
+        let minutes = self.minutes.unsigned_abs();
+
+        write!(f, "{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+    }
+}
+
+/// The times of day the crawl may run at, in the crawled sites' local time
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CrawlWindows {
+    pub windows: Vec<CrawlWindow>,
+    pub utc_offset: UtcOffset,
+}
+
+impl CrawlWindows {
+    /// How long to pause at `now` (seconds since the Unix epoch) until a window opens, zero within a window
+    pub fn wait_at(&self, now: u64) -> Duration {
+        if self.windows.is_empty() {
+            return Duration::ZERO;
+        }
+
+        let local = now as i64 + self.utc_offset.minutes as i64 * 60;
+        let second_of_day = local.rem_euclid(MINUTES_PER_DAY as i64 * 60) as u32;
+        let minute = second_of_day / 60;
+        if self.windows.iter().any(|window| window.contains(minute)) {
+            return Duration::ZERO;
+        }
+
+        let wait_minutes = self
+            .windows
+            .iter()
+            .map(|window| (window.start + MINUTES_PER_DAY - minute) % MINUTES_PER_DAY)
+            .min()
+            .unwrap_or_default();
+
+        Duration::from_secs((wait_minutes * 60 - second_of_day % 60) as u64)
+    }
+
+    /// How long to pause right now until a window opens, zero within a window
+    pub fn wait(&self) -> Duration {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        self.wait_at(now)
+    }
+}
+
+#[cfg(test)]
+mod window_tests {
+    use std::time::Duration;
+
+    use super::{CrawlWindow, CrawlWindows, UtcOffset};
+
+    const HOUR: u64 = 60 * 60;
+
+    #[test]
+    fn crawl_window_and_utc_offset_parse_and_validate() {
+        assert!("01:00-05:00".parse::<CrawlWindow>().is_ok());
+        assert!("22:00-24:00".parse::<CrawlWindow>().is_ok());
+        assert!("05:00-05:00".parse::<CrawlWindow>().is_err());
+        assert!("25:00-05:00".parse::<CrawlWindow>().is_err());
+        assert!("1am-5am".parse::<CrawlWindow>().is_err());
+
+        assert_eq!("-05:30".parse::<UtcOffset>().unwrap().to_string(), "-05:30");
+        assert_eq!(UtcOffset::default().to_string(), "+00:00");
+        assert!("02:00".parse::<UtcOffset>().is_err());
+    }
+
+    #[test]
+    fn wait_at_is_zero_within_a_window_and_until_the_next_one_outside() {
+        let windows = CrawlWindows {
+            windows: vec![
+                "01:00-05:00".parse().unwrap(),
+                "22:00-23:00".parse().unwrap(),
+            ],
+            utc_offset: "+02:00".parse().unwrap(),
+        };
+
+        // 00:30 UTC is 02:30 local
+        assert_eq!(windows.wait_at(HOUR / 2), Duration::ZERO);
+        // 03:00 UTC is 05:00 local, the next window opens at 22:00
+        assert_eq!(windows.wait_at(3 * HOUR), Duration::from_secs(17 * HOUR));
+        // 21:30:20 UTC is 23:30:20 local, the next window opens at 01:00
+        assert_eq!(
+            windows.wait_at(21 * HOUR + 30 * 60 + 20),
+            Duration::from_secs(HOUR + 30 * 60 - 20)
+        );
+        assert_eq!(CrawlWindows::default().wait_at(3 * HOUR), Duration::ZERO);
+    }
+
+    #[test]
+    fn windows_can_span_midnight() {
+        let windows = CrawlWindows {
+            windows: vec!["22:00-02:00".parse().unwrap()],
+            ..Default::default()
+        };
+
+        assert_eq!(windows.wait_at(23 * HOUR), Duration::ZERO);
+        assert_eq!(windows.wait_at(HOUR), Duration::ZERO);
+        assert_eq!(windows.wait_at(2 * HOUR), Duration::from_secs(20 * HOUR));
+    }
+}
@@ -0,0 +1,343 @@
+use crate::parser::LinkElement;
+
+/// HTML elements without content, that XHTML may write as `<br/>` as well as `<br></br>`
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// HTML elements whose text HTML parsers read as written, without entities
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+
+/// Attributes holding a URL in XML documents, by local name: Atom `<link href>`, XLink
+/// `xlink:href`, and `<enclosure url>` or `<media:content url>` in feeds
+const URL_ATTRIBUTES: [&str; 3] = ["href", "src", "url"];
+
+/// Elements whose text is a URL: RSS `<link>` and sitemap `<loc>`
+const URL_ELEMENTS: [&str; 2] = ["link", "loc"];
+
+/// A tag or the text between tags of an XML document
+#[derive(Debug, PartialEq)]
+pub enum Token<'a> {
+    Start {
+        name: &'a str,
+        attributes: Vec<(&'a str, String)>,
+        self_closing: bool,
+    },
+    End {
+        name: &'a str,
+    },
+    /// Text, unescaped, and the content of CDATA sections as written
+    Text(String),
+}
+
+/// Name without its namespace prefix, `atom:link` is `link`
+pub fn local_name(name: &str) -> &str {
+    name.rsplit_once(':').map_or(name, |(_, local)| local)
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let character = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match character {
+            Some(character) => {
+                unescaped.push(character);
+                rest = &rest[end + 1..];
+            }
+            // not an entity XML knows, kept as written
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+
+    unescaped
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Attributes of the inside of a start tag, after its name
+fn attributes(mut tag: &str) -> Vec<(&str, String)> {
+    let mut attributes = vec![];
+
+    loop {
+        tag = tag.trim_start();
+        let Some((name, value)) = tag.split_once('=') else {
+            break;
+        };
+        let value = value.trim_start();
+        let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            break;
+        };
+        let Some((value, rest)) = value[1..].split_once(quote) else {
+            break;
+        };
+        attributes.push((name.trim(), unescape(value)));
+        tag = rest;
+    }
+
+    attributes
+}
+
+/// Splits an XML document into tags and text, skipping comments, processing instructions and the
+/// doctype. Unlike an HTML parser, CDATA sections are text and `<x/>` closes the element.
+pub fn tokens(xml: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        if start > 0 {
+            tokens.push(Token::Text(unescape(&rest[..start])));
+        }
+        rest = &rest[start..];
+
+        let skipped = [
+            ("<![CDATA[", "]]>"),
+            ("<!--", "-->"),
+            ("<?", "?>"),
+            ("<!", ">"),
+        ]
+        .into_iter()
+        .find(|(open, _)| rest.starts_with(open));
+        if let Some((open, close)) = skipped {
+            let end = rest.find(close).unwrap_or(rest.len());
+            if open == "<![CDATA[" {
+                tokens.push(Token::Text(rest[open.len()..end].to_owned()));
+            }
+            rest = rest.get(end + close.len()..).unwrap_or_default();
+            continue;
+        }
+
+        // the end of the tag, outside of quoted attribute values
+        let mut quote = None;
+        let Some(end) = rest.char_indices().skip(1).find_map(|(index, c)| {
+            match (quote, c) {
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(open), c) if c == open => quote = None,
+                (None, '>') => return Some(index),
+                _ => {}
+            }
+            None
+        }) else {
+            break;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            tokens.push(Token::End { name: name.trim() });
+            continue;
+        }
+        let (tag, self_closing) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        tokens.push(Token::Start {
+            name: &tag[..name_end],
+            attributes: attributes(&tag[name_end..]),
+            self_closing,
+        });
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(unescape(rest)));
+    }
+
+    tokens
+}
+
+/// Rewrites an XHTML document as the HTML an HTML parser reads the same way: without namespace
+/// prefixes and declarations, with CDATA sections as escaped text and `<a/>` written `<a></a>`.
+/// The text of `<script>` and `<style>` is written unescaped, as HTML parsers read it as written.
+pub fn to_html(xhtml: &str) -> String {
+    let mut html = String::with_capacity(xhtml.len());
+    let mut in_raw_text = false;
+
+    for token in tokens(xhtml) {
+        match token {
+            Token::Start {
+                name,
+                attributes,
+                self_closing,
+            } => {
+                let name = local_name(name);
+                html.push('<');
+                html.push_str(name);
+                for (attribute, value) in attributes {
+                    if attribute == "xmlns" || attribute.starts_with("xmlns:") {
+                        continue;
+                    }
+                    html.push_str(&format!(
+                        " {}=\"{}\"",
+                        local_name(attribute),
+                        escape(&value)
+                    ));
+                }
+                html.push('>');
+                if self_closing && !VOID_ELEMENTS.contains(&name) {
+                    html.push_str(&format!("</{}>", name));
+                }
+                in_raw_text = !self_closing && RAW_TEXT_ELEMENTS.contains(&name);
+            }
+            Token::End { name } => {
+                html.push_str(&format!("</{}>", local_name(name)));
+                in_raw_text = false;
+            }
+            Token::Text(text) if in_raw_text => html.push_str(&text),
+            Token::Text(text) => html.push_str(&escape(&text)),
+        }
+    }
+
+    html
+}
+
+/// Links of an XML document that isn't XHTML, i.e. a feed, in document order with the element
+/// each is found in: the URL attributes of any element and the text of `<link>` and `<loc>`
+pub fn links(xml: &str) -> Vec<(String, LinkElement)> {
+    let mut links = vec![];
+    // open elements, with the text found so far of those whose text is a URL
+    let mut open: Vec<(&str, Option<(LinkElement, String)>)> = vec![];
+
+    for token in tokens(xml) {
+        match token {
+            Token::Start {
+                name,
+                attributes,
+                self_closing,
+            } => {
+                let tag = local_name(name);
+                let element = LinkElement {
+                    tag: tag.to_owned(),
+                    rel: attributes
+                        .iter()
+                        .find(|(attribute, _)| local_name(attribute) == "rel")
+                        .map(|(_, rel)| rel.split_whitespace().map(str::to_lowercase).collect())
+                        .unwrap_or_default(),
+                };
+                for (attribute, value) in &attributes {
+                    if URL_ATTRIBUTES.contains(&local_name(attribute)) && !value.trim().is_empty() {
+                        links.push((value.trim().to_owned(), element.clone()));
+                    }
+                }
+                if !self_closing {
+                    let text = URL_ELEMENTS
+                        .contains(&tag)
+                        .then(|| (element, String::new()));
+                    open.push((name, text));
+                }
+            }
+            Token::End { name } => {
+                // the innermost element of that name, unclosed elements in between end with it
+                let Some(index) = open.iter().rposition(|(open, _)| *open == name) else {
+                    continue;
+                };
+                if let Some((_, Some((element, text)))) = open.split_off(index).into_iter().next() {
+                    if !text.trim().is_empty() {
+                        links.push((text.trim().to_owned(), element));
+                    }
+                }
+            }
+            Token::Text(text) => {
+                if let Some((_, Some((_, open_text)))) = open.last_mut() {
+                    open_text.push_str(&text);
+                }
+            }
+        }
+    }
+
+    links
+}
+
+#[cfg(test)]
+mod xml_tests {
+    use super::{links, to_html, tokens, unescape, Token};
+    use crate::parser::LinkElement;
+
+    #[test]
+    fn tokens_read_cdata_as_text_and_skip_comments() {
+        assert_eq!(
+            tokens(
+                "<?xml version=\"1.0\"?><!-- a > b --><a:b c='1>2'/>x &amp; <![CDATA[<y>]]></a:b>"
+            ),
+            vec![
+                Token::Start {
+                    name: "a:b",
+                    attributes: vec![("c", "1>2".to_owned())],
+                    self_closing: true,
+                },
+                Token::Text("x & ".to_owned()),
+                Token::Text("<y>".to_owned()),
+                Token::End { name: "a:b" },
+            ]
+        );
+        assert_eq!(unescape("&#233;&#xE9; &nbsp; &amp"), "éé &nbsp; &amp");
+    }
+
+    #[test]
+    fn to_html_rewrites_xhtml_for_html_parsers() {
+        assert_eq!(
+            to_html(
+                "<?xml version=\"1.0\"?>\n<html xmlns=\"http://www.w3.org/1999/xhtml\" \
+                 xml:lang=\"en\"><body><a href=\"/a\"/><br/>\
+                 <script><![CDATA[if (a < b && c) {}]]></script>\
+                 <style>a > b {}</style><p>1 &lt; 2</p></body></html>"
+            ),
+            "\n<html lang=\"en\"><body><a href=\"/a\"></a><br>\
+             <script>if (a < b && c) {}</script>\
+             <style>a > b {}</style><p>1 &lt; 2</p></body></html>"
+        );
+    }
+
+    #[test]
+    fn links_reads_feeds_and_namespaced_elements() {
+        let rss = r#"<?xml version="1.0"?>
+            <rss xmlns:atom="http://www.w3.org/2005/Atom"><channel>
+              <atom:link href="https://a.com/feed" rel="self"/>
+              <item><title>One</title><link><![CDATA[https://a.com/1?a=1&b=2]]></link></item>
+              <item><link>https://a.com/2</link><enclosure url="https://a.com/2.mp3"/></item>
+            </channel></rss>"#;
+        let element = |tag: &str, rel: &[&str]| LinkElement {
+            tag: tag.to_owned(),
+            rel: rel.iter().map(|rel| rel.to_string()).collect(),
+        };
+
+        assert_eq!(
+            links(rss),
+            vec![
+                ("https://a.com/feed".to_owned(), element("link", &["self"])),
+                ("https://a.com/1?a=1&b=2".to_owned(), element("link", &[])),
+                ("https://a.com/2".to_owned(), element("link", &[])),
+                ("https://a.com/2.mp3".to_owned(), element("enclosure", &[])),
+            ]
+        );
+    }
+}